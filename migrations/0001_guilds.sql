@@ -0,0 +1,8 @@
+CREATE TABLE guilds (
+    guild_id BIGINT PRIMARY KEY
+);
+
+CREATE TABLE admin_bot_spam_channel (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT NOT NULL
+);
@@ -0,0 +1,12 @@
+CREATE TABLE purge_jobs (
+    job_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    user_id BIGINT,
+    cutoff TIMESTAMPTZ NOT NULL,
+    last_message_id BIGINT,
+    state TEXT NOT NULL DEFAULT 'running',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX purge_jobs_state ON purge_jobs (state);
@@ -0,0 +1,9 @@
+CREATE TABLE retention_policies (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    max_age_seconds BIGINT NOT NULL,
+    run_at TIME NOT NULL,
+    last_run TIMESTAMPTZ
+);
+
+CREATE INDEX retention_policies_guild ON retention_policies (guild_id);
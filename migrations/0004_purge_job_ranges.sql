@@ -0,0 +1 @@
+ALTER TABLE purge_jobs ADD COLUMN oldest_message_id BIGINT;
@@ -0,0 +1,7 @@
+ALTER TABLE purge_jobs ADD COLUMN filter JSONB NOT NULL DEFAULT '{}';
+
+UPDATE purge_jobs
+SET filter = jsonb_build_object('user_id', user_id::text)
+WHERE user_id IS NOT NULL;
+
+ALTER TABLE purge_jobs DROP COLUMN user_id;
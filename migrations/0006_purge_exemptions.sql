@@ -0,0 +1,6 @@
+CREATE TABLE purge_exemptions (
+    guild_id BIGINT NOT NULL,
+    exempt_id BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    PRIMARY KEY (guild_id, exempt_id)
+);
@@ -0,0 +1,14 @@
+CREATE TABLE purge_audit (
+    audit_id BIGSERIAL PRIMARY KEY,
+    job_id BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    invoker_id BIGINT,
+    filter JSONB NOT NULL,
+    deleted BIGINT NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    dry_run BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX purge_audit_guild ON purge_audit (guild_id, created_at);
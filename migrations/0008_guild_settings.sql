@@ -0,0 +1,4 @@
+CREATE TABLE guild_settings (
+    guild_id BIGINT PRIMARY KEY,
+    purge_rate INTEGER
+);
@@ -0,0 +1,12 @@
+CREATE TABLE scheduled_jobs (
+    schedule_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    scheduled_by BIGINT NOT NULL,
+    run_at TIMESTAMPTZ NOT NULL,
+    max_age_seconds BIGINT NOT NULL,
+    filter JSONB NOT NULL
+);
+
+CREATE INDEX scheduled_jobs_run_at ON scheduled_jobs (run_at);
+CREATE INDEX scheduled_jobs_guild ON scheduled_jobs (guild_id);
@@ -0,0 +1,18 @@
+CREATE TABLE archived_messages (
+    message_id BIGINT PRIMARY KEY,
+    job_id BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    author_name TEXT NOT NULL,
+    author_avatar TEXT NOT NULL,
+    content TEXT NOT NULL,
+    attachments TEXT[] NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX archived_messages_job ON archived_messages (job_id);
+CREATE INDEX archived_messages_channel ON archived_messages (channel_id, archived_at);
+CREATE INDEX archived_messages_expires ON archived_messages (expires_at);
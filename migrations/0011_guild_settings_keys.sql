@@ -0,0 +1,10 @@
+ALTER TABLE guild_settings
+    ADD COLUMN bot_spam_channel BIGINT,
+    ADD COLUMN locale TEXT,
+    ADD COLUMN timezone TEXT;
+
+INSERT INTO guild_settings (guild_id, bot_spam_channel)
+SELECT guild_id, channel_id FROM admin_bot_spam_channel
+ON CONFLICT (guild_id) DO UPDATE SET bot_spam_channel = EXCLUDED.bot_spam_channel;
+
+DROP TABLE admin_bot_spam_channel;
@@ -0,0 +1,12 @@
+CREATE TABLE channels (
+    guild_id BIGINT NOT NULL,
+    purpose TEXT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, purpose)
+);
+
+INSERT INTO channels (guild_id, purpose, channel_id)
+SELECT guild_id, 'status', bot_spam_channel FROM guild_settings
+WHERE bot_spam_channel IS NOT NULL;
+
+ALTER TABLE guild_settings DROP COLUMN bot_spam_channel;
@@ -0,0 +1,5 @@
+CREATE TABLE mod_roles (
+    guild_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, role_id)
+);
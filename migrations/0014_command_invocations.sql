@@ -0,0 +1,11 @@
+CREATE TABLE command_invocations (
+    invocation_id BIGSERIAL PRIMARY KEY,
+    command TEXT NOT NULL,
+    guild_id BIGINT,
+    user_id BIGINT NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    success BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX command_invocations_created_at ON command_invocations (created_at);
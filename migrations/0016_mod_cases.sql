@@ -0,0 +1,14 @@
+ALTER TABLE guilds ADD COLUMN last_case BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE mod_cases (
+    guild_id BIGINT NOT NULL,
+    case_id BIGINT NOT NULL,
+    action TEXT NOT NULL,
+    user_id BIGINT,
+    channel_id BIGINT,
+    moderator_id BIGINT,
+    reason TEXT,
+    details TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, case_id)
+);
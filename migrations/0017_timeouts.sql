@@ -0,0 +1,9 @@
+CREATE TABLE timeouts (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    case_id BIGINT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE INDEX timeouts_expires_at ON timeouts (expires_at);
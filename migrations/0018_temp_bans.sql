@@ -0,0 +1,9 @@
+CREATE TABLE temp_bans (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    case_id BIGINT NOT NULL,
+    unban_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE INDEX temp_bans_unban_at ON temp_bans (unban_at);
@@ -0,0 +1 @@
+ALTER TABLE guild_settings ADD COLUMN dm_members BOOLEAN;
@@ -0,0 +1,6 @@
+CREATE TABLE banned_terms (
+    guild_id BIGINT NOT NULL,
+    term TEXT NOT NULL,
+    is_regex BOOLEAN NOT NULL,
+    PRIMARY KEY (guild_id, term)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE guild_settings
+    ADD COLUMN block_invites BOOLEAN,
+    ADD COLUMN invite_channels BIGINT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN invite_roles BIGINT[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE warnings ALTER COLUMN moderator_id DROP NOT NULL;
@@ -0,0 +1,4 @@
+ALTER TABLE guild_settings
+    ADD COLUMN max_mentions INT,
+    ADD COLUMN block_everyone BOOLEAN,
+    ADD COLUMN mention_timeout_seconds BIGINT;
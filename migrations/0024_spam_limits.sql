@@ -0,0 +1,6 @@
+ALTER TABLE guild_settings
+    ADD COLUMN spam_messages INT,
+    ADD COLUMN spam_window_seconds INT,
+    ADD COLUMN spam_repeats INT,
+    ADD COLUMN spam_action TEXT,
+    ADD COLUMN spam_timeout_seconds BIGINT;
@@ -0,0 +1,12 @@
+ALTER TABLE guild_settings
+    ADD COLUMN raid_joins INT,
+    ADD COLUMN raid_window_seconds INT,
+    ADD COLUMN raid_min_age_seconds BIGINT,
+    ADD COLUMN raid_verification BOOLEAN;
+
+CREATE TABLE raid_modes (
+    guild_id BIGINT PRIMARY KEY,
+    started_by BIGINT,
+    previous_verification SMALLINT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,11 @@
+CREATE TABLE lockdowns (
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    locked_by BIGINT NOT NULL,
+    previous_allow BIGINT,
+    previous_deny BIGINT,
+    unlock_at TIMESTAMPTZ,
+    PRIMARY KEY (guild_id, channel_id)
+);
+
+CREATE INDEX lockdowns_unlock_at ON lockdowns (unlock_at);
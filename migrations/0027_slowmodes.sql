@@ -0,0 +1,10 @@
+CREATE TABLE slowmodes (
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    set_by BIGINT NOT NULL,
+    previous_seconds INT NOT NULL,
+    revert_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (guild_id, channel_id)
+);
+
+CREATE INDEX slowmodes_revert_at ON slowmodes (revert_at);
@@ -0,0 +1,3 @@
+ALTER TABLE guild_settings
+    ADD COLUMN min_account_age_seconds BIGINT,
+    ADD COLUMN quarantine_role BIGINT;
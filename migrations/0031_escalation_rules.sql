@@ -0,0 +1,8 @@
+CREATE TABLE escalation_rules (
+    guild_id BIGINT NOT NULL,
+    warnings INT NOT NULL,
+    window_days INT NOT NULL,
+    action TEXT NOT NULL,
+    duration_seconds BIGINT,
+    PRIMARY KEY (guild_id, warnings)
+);
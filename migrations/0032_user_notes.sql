@@ -0,0 +1,10 @@
+CREATE TABLE user_notes (
+    note_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX user_notes_guild_user ON user_notes (guild_id, user_id);
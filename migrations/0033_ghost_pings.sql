@@ -0,0 +1 @@
+ALTER TABLE message_cache ADD COLUMN mentions BIGINT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,12 @@
+CREATE TABLE reminders (
+    reminder_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    remind_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX reminders_remind_at ON reminders (remind_at);
+CREATE INDEX reminders_guild_user ON reminders (guild_id, user_id);
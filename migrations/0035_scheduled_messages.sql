@@ -0,0 +1,15 @@
+CREATE TABLE scheduled_messages (
+    announcement_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    scheduled_by BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    embed_title TEXT,
+    embed_description TEXT,
+    send_at TIMESTAMPTZ NOT NULL,
+    every_seconds BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX scheduled_messages_send_at ON scheduled_messages (send_at);
+CREATE INDEX scheduled_messages_guild ON scheduled_messages (guild_id);
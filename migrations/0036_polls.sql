@@ -0,0 +1,24 @@
+CREATE TABLE polls (
+    poll_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT,
+    created_by BIGINT NOT NULL,
+    question TEXT NOT NULL,
+    options TEXT[] NOT NULL,
+    closes_at TIMESTAMPTZ NOT NULL,
+    closed BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX polls_open_closes_at ON polls (closes_at) WHERE NOT closed;
+
+CREATE TABLE poll_votes (
+    poll_id BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    option_index INT NOT NULL,
+    PRIMARY KEY (poll_id, user_id)
+);
+
+CREATE INDEX poll_votes_guild ON poll_votes (guild_id);
@@ -0,0 +1,12 @@
+CREATE TABLE role_menus (
+    menu_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT,
+    title TEXT NOT NULL,
+    role_ids BIGINT[] NOT NULL,
+    created_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX role_menus_guild ON role_menus (guild_id);
@@ -0,0 +1,16 @@
+CREATE TABLE autoroles (
+    guild_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+CREATE TABLE pending_autoroles (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    assign_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE INDEX pending_autoroles_assign_at ON pending_autoroles (assign_at);
+
+ALTER TABLE guild_settings ADD COLUMN autorole_delay_seconds BIGINT;
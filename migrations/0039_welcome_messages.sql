@@ -0,0 +1,6 @@
+CREATE TABLE welcome_messages (
+    guild_id BIGINT PRIMARY KEY,
+    channel_id BIGINT,
+    join_message TEXT,
+    leave_message TEXT
+);
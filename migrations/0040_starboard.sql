@@ -0,0 +1,13 @@
+CREATE TABLE starboard_entries (
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT PRIMARY KEY,
+    starboard_channel_id BIGINT NOT NULL,
+    starboard_message_id BIGINT,
+    stars INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX starboard_entries_guild ON starboard_entries (guild_id);
+
+ALTER TABLE guild_settings ADD COLUMN starboard_stars INTEGER;
@@ -0,0 +1,10 @@
+CREATE TABLE sticky_messages (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    message_id BIGINT,
+    created_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX sticky_messages_guild ON sticky_messages (guild_id);
@@ -0,0 +1,18 @@
+CREATE TABLE event_roles (
+    guild_id BIGINT NOT NULL,
+    role_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+CREATE TABLE event_reminders (
+    guild_id BIGINT NOT NULL,
+    event_id BIGINT NOT NULL,
+    lead_seconds BIGINT NOT NULL,
+    remind_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (event_id, lead_seconds)
+);
+
+CREATE INDEX event_reminders_remind_at ON event_reminders (remind_at);
+
+ALTER TABLE guild_settings
+    ADD COLUMN event_reminder_seconds BIGINT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,15 @@
+CREATE TABLE recurring_events (
+    recurring_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT,
+    channel_id BIGINT,
+    location TEXT,
+    starts_at TIMESTAMPTZ NOT NULL,
+    duration_seconds BIGINT NOT NULL,
+    every_seconds BIGINT NOT NULL,
+    event_id BIGINT,
+    created_by BIGINT NOT NULL
+);
+
+CREATE INDEX recurring_events_guild ON recurring_events (guild_id);
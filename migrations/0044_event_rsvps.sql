@@ -0,0 +1,9 @@
+CREATE TABLE event_rsvps (
+    guild_id BIGINT NOT NULL,
+    event_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (event_id, user_id)
+);
+
+CREATE INDEX event_rsvps_guild_user ON event_rsvps (guild_id, user_id);
@@ -0,0 +1,22 @@
+CREATE TABLE suggestions (
+    suggestion_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT,
+    user_id BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'open',
+    reason TEXT,
+    decided_by BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX suggestions_guild ON suggestions (guild_id);
+
+CREATE TABLE suggestion_votes (
+    suggestion_id BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    upvote BOOLEAN NOT NULL,
+    PRIMARY KEY (suggestion_id, user_id)
+);
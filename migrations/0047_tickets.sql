@@ -0,0 +1,12 @@
+CREATE TABLE tickets (
+    ticket_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    thread_id BIGINT UNIQUE,
+    user_id BIGINT NOT NULL,
+    opened_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    closed_at TIMESTAMPTZ,
+    closed_by BIGINT
+);
+
+CREATE UNIQUE INDEX tickets_open ON tickets (guild_id, user_id) WHERE closed_at IS NULL;
@@ -0,0 +1,24 @@
+CREATE TABLE giveaways (
+    giveaway_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT,
+    created_by BIGINT NOT NULL,
+    prize TEXT NOT NULL,
+    winner_count INT NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    ended BOOLEAN NOT NULL DEFAULT false,
+    winners BIGINT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX giveaways_open_ends_at ON giveaways (ends_at) WHERE NOT ended;
+
+CREATE TABLE giveaway_entries (
+    giveaway_id BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    PRIMARY KEY (giveaway_id, user_id)
+);
+
+CREATE INDEX giveaway_entries_guild ON giveaway_entries (guild_id);
@@ -0,0 +1,18 @@
+CREATE TABLE member_xp (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    xp BIGINT NOT NULL DEFAULT 0,
+    last_awarded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE INDEX member_xp_guild_xp ON member_xp (guild_id, xp DESC);
+
+CREATE TABLE level_roles (
+    guild_id BIGINT NOT NULL,
+    level INTEGER NOT NULL,
+    role_id BIGINT NOT NULL,
+    PRIMARY KEY (guild_id, level)
+);
+
+ALTER TABLE guild_settings ADD COLUMN leveling BOOLEAN;
@@ -0,0 +1,10 @@
+CREATE TABLE birthdays (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    month INTEGER NOT NULL,
+    day INTEGER NOT NULL,
+    announced_on DATE,
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE INDEX birthdays_date ON birthdays ((month * 100 + day));
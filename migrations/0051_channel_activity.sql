@@ -0,0 +1,23 @@
+CREATE TABLE channel_activity (
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    day DATE NOT NULL,
+    hour SMALLINT NOT NULL,
+    messages INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (channel_id, day, hour)
+);
+
+CREATE INDEX channel_activity_guild ON channel_activity (guild_id);
+CREATE INDEX channel_activity_day ON channel_activity (day);
+
+CREATE TABLE channel_posters (
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    day DATE NOT NULL,
+    user_id BIGINT NOT NULL,
+    messages INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (channel_id, day, user_id)
+);
+
+CREATE INDEX channel_posters_guild ON channel_posters (guild_id);
+CREATE INDEX channel_posters_day ON channel_posters (day);
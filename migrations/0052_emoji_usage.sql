@@ -0,0 +1,7 @@
+CREATE TABLE emoji_usage (
+    guild_id BIGINT NOT NULL,
+    emoji_id BIGINT NOT NULL,
+    uses BIGINT NOT NULL DEFAULT 0,
+    last_used_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, emoji_id)
+);
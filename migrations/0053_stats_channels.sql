@@ -0,0 +1,11 @@
+CREATE TABLE stats_channels (
+    guild_id BIGINT NOT NULL,
+    counter TEXT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    category_id BIGINT,
+    shown_name TEXT,
+    refreshed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, counter)
+);
+
+CREATE INDEX stats_channels_refreshed_at ON stats_channels (refreshed_at);
@@ -0,0 +1,11 @@
+CREATE TABLE pin_archive (
+    message_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    archive_channel_id BIGINT NOT NULL,
+    archive_message_id BIGINT,
+    archived_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX pin_archive_guild_id ON pin_archive (guild_id);
@@ -0,0 +1,13 @@
+ALTER TABLE guild_settings ADD COLUMN bookmarks BOOLEAN;
+ALTER TABLE guild_settings ADD COLUMN bookmark_emoji TEXT;
+
+CREATE TABLE bookmarks (
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    bookmarked_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (guild_id, user_id, message_id)
+);
@@ -0,0 +1,10 @@
+CREATE TABLE thread_policies (
+    channel_id BIGINT PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    auto_archive_minutes INTEGER,
+    close_after_seconds BIGINT,
+    join_role_id BIGINT,
+    checked_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX thread_policies_guild_id ON thread_policies (guild_id);
@@ -0,0 +1,12 @@
+CREATE TABLE quotes (
+    quote_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    said_at TIMESTAMPTZ NOT NULL,
+    quoted_by BIGINT NOT NULL,
+    quoted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (guild_id, message_id)
+);
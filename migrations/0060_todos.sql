@@ -0,0 +1,12 @@
+CREATE TABLE todos (
+    todo_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    content TEXT NOT NULL,
+    due_at TIMESTAMPTZ,
+    reminder_id BIGINT,
+    done_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX todos_guild_id_user_id ON todos (guild_id, user_id);
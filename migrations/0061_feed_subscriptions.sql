@@ -0,0 +1,15 @@
+CREATE TABLE feed_subscriptions (
+    subscription_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    url TEXT NOT NULL,
+    title TEXT,
+    keywords TEXT[] NOT NULL DEFAULT '{}',
+    exclude TEXT[] NOT NULL DEFAULT '{}',
+    seen_ids TEXT[] NOT NULL DEFAULT '{}',
+    added_by BIGINT NOT NULL,
+    checked_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (channel_id, url)
+);
+
+CREATE INDEX feed_subscriptions_guild_id ON feed_subscriptions (guild_id);
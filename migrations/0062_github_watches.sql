@@ -0,0 +1,16 @@
+CREATE TABLE github_watches (
+    watch_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    repo TEXT NOT NULL,
+    releases BOOLEAN NOT NULL,
+    issues BOOLEAN NOT NULL,
+    pulls BOOLEAN NOT NULL,
+    last_release_id BIGINT NOT NULL,
+    last_issue_number BIGINT NOT NULL,
+    added_by BIGINT NOT NULL,
+    checked_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (channel_id, repo)
+);
+
+CREATE INDEX github_watches_guild_id ON github_watches (guild_id);
@@ -0,0 +1,16 @@
+CREATE TABLE twitch_watches (
+    watch_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    twitch_user_id TEXT NOT NULL,
+    login TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    stream_id TEXT,
+    stream_title TEXT,
+    live_since TIMESTAMPTZ,
+    message_id BIGINT,
+    added_by BIGINT NOT NULL,
+    UNIQUE (channel_id, twitch_user_id)
+);
+
+CREATE INDEX twitch_watches_guild_id ON twitch_watches (guild_id);
@@ -0,0 +1,23 @@
+CREATE TABLE youtube_watches (
+    watch_id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    channel_id BIGINT NOT NULL,
+    youtube_channel_id TEXT NOT NULL,
+    channel_name TEXT NOT NULL,
+    template TEXT,
+    added_by BIGINT NOT NULL,
+    checked_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (channel_id, youtube_channel_id)
+);
+
+CREATE INDEX youtube_watches_guild_id ON youtube_watches (guild_id);
+
+CREATE TABLE youtube_videos (
+    watch_id BIGINT NOT NULL,
+    guild_id BIGINT NOT NULL,
+    video_id TEXT NOT NULL,
+    seen_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (watch_id, video_id)
+);
+
+CREATE INDEX youtube_videos_guild_id ON youtube_videos (guild_id);
@@ -0,0 +1,605 @@
+use poise::serenity_prelude::*;
+
+use crate::{
+    audit,
+    i18n::{self, Msg},
+    replies::reply,
+    settings::{parse_channel, ChannelPurpose, Setting},
+    Context, SlimeError,
+};
+
+/// How many settings one page of `/config list` shows.
+const PAGE_LEN: usize = 12;
+
+/// Manages the channel the bot posts status updates to
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("spam_channel_set", "spam_channel_show", "spam_channel_clear")
+)]
+pub async fn admin_bot_spam_channel(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Sets the channel the bot posts status updates to
+#[poise::command(slash_command, guild_only, rename = "set")]
+async fn spam_channel_set(
+    ctx: Context<'_>,
+    #[description = "Channel for the bot's status updates"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .settings
+        .update(guild_id, |settings| {
+            settings.channels.insert(ChannelPurpose::Status, channel.id)
+        })
+        .await?;
+
+    reply(
+        ctx,
+        format!("I'll post status updates in {}.", channel.mention()),
+    )
+    .await
+}
+
+/// Shows where the bot posts status updates
+#[poise::command(slash_command, guild_only, rename = "show")]
+async fn spam_channel_show(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = ctx.data().settings.get(guild_id).await?;
+
+    let content = match settings.channels.get(&ChannelPurpose::Status) {
+        Some(channel_id) => format!("I post status updates in {}.", channel_id.mention()),
+        None => "There is no bot spam channel, so status updates aren't posted.".to_string(),
+    };
+    reply(ctx, content).await
+}
+
+/// Stops posting status updates
+#[poise::command(slash_command, guild_only, rename = "clear")]
+async fn spam_channel_clear(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let previous = ctx
+        .data()
+        .settings
+        .update(guild_id, |settings| {
+            settings.channels.remove(&ChannelPurpose::Status)
+        })
+        .await?;
+
+    let content = match previous {
+        Some(channel_id) => format!(
+            "I won't post status updates in {} anymore.",
+            channel_id.mention()
+        ),
+        None => "There was no bot spam channel to clear.".to_string(),
+    };
+    reply(ctx, content).await
+}
+
+/// Changes how the bot behaves in this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands(
+        "get",
+        "set",
+        "list",
+        "reset",
+        "modrole",
+        "logignore",
+        "autorole",
+        "eventrole",
+        "levelrole"
+    )
+)]
+pub async fn config(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+async fn autocomplete_key<'a>(
+    _ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    Setting::ALL
+        .into_iter()
+        .map(Setting::key)
+        .filter(move |key| key.starts_with(partial))
+        .map(str::to_string)
+}
+
+/// Shows the current value of a setting
+#[poise::command(slash_command, guild_only)]
+async fn get(
+    ctx: Context<'_>,
+    #[description = "Setting to show"]
+    #[autocomplete = "autocomplete_key"]
+    key: Setting,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = ctx.data().settings.get(guild_id).await?;
+
+    let content = match settings.display(key) {
+        Some(value) => format!("`{}` is {value}.", key.key()),
+        None => format!("`{}` isn't set, so the default applies.", key.key()),
+    };
+    reply(ctx, content).await
+}
+
+/// Changes a setting
+#[poise::command(slash_command, guild_only)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Setting to change"]
+    #[autocomplete = "autocomplete_key"]
+    key: Setting,
+    #[description = "New value, see /config list"] value: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    if let Setting::Channel(_) = key {
+        if let Some(channel_id) = parse_channel(value.trim()) {
+            let in_guild = match channel_id.to_channel(ctx).await {
+                Ok(channel) => channel
+                    .guild()
+                    .is_some_and(|channel| channel.guild_id == guild_id),
+                Err(_) => false,
+            };
+            if !in_guild {
+                return reply(ctx, "That channel isn't in this server.".to_string()).await;
+            }
+        }
+    }
+
+    let settings = &ctx.data().settings;
+    let content = match settings
+        .update(guild_id, |settings| {
+            settings
+                .set(key, &value)
+                .map(|()| settings.display(key).unwrap_or_default())
+        })
+        .await?
+    {
+        Ok(value) => format!("`{}` is now {value}.", key.key()),
+        Err(e) => format!("Couldn't change `{}`: {e}.", key.key()),
+    };
+    reply(ctx, content).await
+}
+
+/// Lists every setting and its current value
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = ctx.data().settings.get(guild_id).await?;
+
+    let lines = Setting::ALL.into_iter().map(|setting| {
+        let value = settings
+            .display(setting)
+            .unwrap_or_else(|| "not set".to_string());
+        format!("`{}`: {value} ({})", setting.key(), setting.help())
+    });
+    audit::paginate(ctx, "Settings", &audit::pages(lines, PAGE_LEN)).await
+}
+
+/// Puts a setting back to its default
+#[poise::command(slash_command, guild_only)]
+async fn reset(
+    ctx: Context<'_>,
+    #[description = "Setting to reset"]
+    #[autocomplete = "autocomplete_key"]
+    key: Setting,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .settings
+        .update(guild_id, |settings| settings.reset(key))
+        .await?;
+
+    reply(ctx, format!("`{}` is back to its default.", key.key())).await
+}
+
+/// Manages the roles that may run purges
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("modrole_add", "modrole_remove", "modrole_list")
+)]
+async fn modrole(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Lets members with a role run purges
+#[poise::command(slash_command, guild_only, rename = "add")]
+async fn modrole_add(
+    ctx: Context<'_>,
+    #[description = "Role of your moderators"] role: Role,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .settings
+        .update(guild_id, |settings| settings.mod_roles.insert(role.id))
+        .await?;
+
+    reply(
+        ctx,
+        format!("Members with {} can run purges now.", role.mention()),
+    )
+    .await
+}
+
+/// Stops letting members with a role run purges
+#[poise::command(slash_command, guild_only, rename = "remove")]
+async fn modrole_remove(
+    ctx: Context<'_>,
+    #[description = "Role to take purges away from"] role: Role,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = ctx
+        .data()
+        .settings
+        .update(guild_id, |settings| settings.mod_roles.remove(&role.id))
+        .await?;
+
+    let content = if removed {
+        format!("Members with {} can't run purges anymore.", role.mention())
+    } else {
+        format!("{} isn't a moderator role.", role.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the roles that may run purges
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn modrole_list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = ctx.data().settings.get(guild_id).await?;
+
+    let content = if settings.mod_roles.is_empty() {
+        "Only administrators can run purges.".to_string()
+    } else {
+        settings
+            .mod_roles
+            .iter()
+            .map(|role_id| format!("- {}", role_id.mention()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    reply(ctx, content).await
+}
+
+/// Manages the channels whose edited and deleted messages aren't logged
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("logignore_add", "logignore_remove", "logignore_list")
+)]
+async fn logignore(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Stops logging edited and deleted messages in a channel
+#[poise::command(slash_command, guild_only, rename = "add")]
+async fn logignore_add(
+    ctx: Context<'_>,
+    #[description = "Channel to stop logging"] channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .settings
+        .update(guild_id, |settings| settings.log_ignored.insert(channel.id))
+        .await?;
+
+    reply(
+        ctx,
+        format!("Messages in {} won't be logged anymore.", channel.mention()),
+    )
+    .await
+}
+
+/// Logs edited and deleted messages in a channel again
+#[poise::command(slash_command, guild_only, rename = "remove")]
+async fn logignore_remove(
+    ctx: Context<'_>,
+    #[description = "Channel to log again"] channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = ctx
+        .data()
+        .settings
+        .update(guild_id, |settings| {
+            settings.log_ignored.remove(&channel.id)
+        })
+        .await?;
+
+    let content = if removed {
+        format!("Messages in {} are logged again.", channel.mention())
+    } else {
+        format!("{} isn't ignored.", channel.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the channels whose messages aren't logged
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn logignore_list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = ctx.data().settings.get(guild_id).await?;
+
+    let content = if settings.log_ignored.is_empty() {
+        "Messages in every channel are logged.".to_string()
+    } else {
+        settings
+            .log_ignored
+            .iter()
+            .map(|channel_id| format!("- {}", channel_id.mention()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    reply(ctx, content).await
+}
+
+/// Categories of commands moderators may run, see `command_check`.
+const MOD_CATEGORIES: [&str; 2] = ["Delete", "Moderation"];
+
+/// Lets administrators and members with a moderator role run the commands in
+/// `MOD_CATEGORIES`, which Discord shows to everyone.
+pub async fn command_check(ctx: Context<'_>) -> Result<bool, SlimeError> {
+    let category = ctx.command().category.as_deref();
+    if !category.is_some_and(|category| MOD_CATEGORIES.contains(&category)) {
+        return Ok(true);
+    }
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(true);
+    };
+    let Some(member) = ctx.author_member().await else {
+        return Ok(false);
+    };
+
+    let admin = member
+        .permissions
+        .is_some_and(|permissions| permissions.administrator());
+    let allowed = admin || {
+        let settings = ctx.data().settings.get(guild_id).await?;
+        member
+            .roles
+            .iter()
+            .any(|role| settings.mod_roles.contains(role))
+    };
+    if !allowed {
+        let locale = i18n::locale(ctx).await?;
+        reply(
+            ctx,
+            i18n::tr(locale.as_deref(), Msg::NotAllowed).to_string(),
+        )
+        .await?;
+    }
+
+    Ok(allowed)
+}
+
+/// Manages the roles members get when they join
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("autorole_add", "autorole_remove", "autorole_list")
+)]
+async fn autorole(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Gives a role to every member who joins from now on
+#[poise::command(slash_command, guild_only, rename = "add")]
+async fn autorole_add(
+    ctx: Context<'_>,
+    #[description = "Role for new members"] role: Role,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    if role.id == guild_id.everyone_role() || role.managed {
+        return reply(ctx, format!("Nobody can be given {}.", role.mention())).await;
+    }
+    ctx.data()
+        .settings
+        .update(guild_id, |settings| settings.autoroles.insert(role.id))
+        .await?;
+
+    reply(ctx, format!("Members who join get {} now.", role.mention())).await
+}
+
+/// Stops giving a role to members who join
+#[poise::command(slash_command, guild_only, rename = "remove")]
+async fn autorole_remove(
+    ctx: Context<'_>,
+    #[description = "Role to stop giving"] role: Role,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = ctx
+        .data()
+        .settings
+        .update(guild_id, |settings| settings.autoroles.remove(&role.id))
+        .await?;
+
+    let content = if removed {
+        format!("Members who join don't get {} anymore.", role.mention())
+    } else {
+        format!("{} isn't an autorole.", role.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the roles members get when they join
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn autorole_list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = ctx.data().settings.get(guild_id).await?;
+
+    let content = if settings.autoroles.is_empty() {
+        "Members get no roles when they join.".to_string()
+    } else {
+        let roles = settings
+            .autoroles
+            .iter()
+            .map(|role_id| format!("- {}", role_id.mention()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        match settings.autorole_delay {
+            Some(delay) => format!("Members get these roles {delay} after they join:\n{roles}"),
+            None => format!("Members get these roles when they join:\n{roles}"),
+        }
+    };
+    reply(ctx, content).await
+}
+
+/// Manages the roles pinged by server event reminders
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("eventrole_add", "eventrole_remove", "eventrole_list")
+)]
+async fn eventrole(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Pings a role when a server event is about to start
+#[poise::command(slash_command, guild_only, rename = "add")]
+async fn eventrole_add(
+    ctx: Context<'_>,
+    #[description = "Role to remind of events"] role: Role,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .settings
+        .update(guild_id, |settings| settings.event_roles.insert(role.id))
+        .await?;
+
+    reply(ctx, format!("Event reminders ping {} now.", role.mention())).await
+}
+
+/// Stops pinging a role when a server event is about to start
+#[poise::command(slash_command, guild_only, rename = "remove")]
+async fn eventrole_remove(
+    ctx: Context<'_>,
+    #[description = "Role to stop reminding"] role: Role,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = ctx
+        .data()
+        .settings
+        .update(guild_id, |settings| settings.event_roles.remove(&role.id))
+        .await?;
+
+    let content = if removed {
+        format!("Event reminders don't ping {} anymore.", role.mention())
+    } else {
+        format!("{} isn't pinged by event reminders.", role.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the roles pinged by server event reminders
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn eventrole_list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = ctx.data().settings.get(guild_id).await?;
+
+    let content = if settings.event_roles.is_empty() {
+        "Event reminders ping nobody.".to_string()
+    } else {
+        settings
+            .event_roles
+            .iter()
+            .map(|role_id| format!("- {}", role_id.mention()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    reply(ctx, content).await
+}
+
+/// Manages the roles members get for reaching a level
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("levelrole_add", "levelrole_remove", "levelrole_list")
+)]
+async fn levelrole(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Gives a role to members who reach a level, replacing the level's role if it has one
+#[poise::command(slash_command, guild_only, rename = "add")]
+async fn levelrole_add(
+    ctx: Context<'_>,
+    #[description = "Level members need"]
+    #[min = 1]
+    #[max = 1000]
+    level: u32,
+    #[description = "Role they get for it"] role: Role,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    if role.id == guild_id.everyone_role() || role.managed {
+        return reply(ctx, format!("Nobody can be given {}.", role.mention())).await;
+    }
+    ctx.data()
+        .settings
+        .update(guild_id, |settings| {
+            settings.level_roles.insert(level, role.id)
+        })
+        .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Members who reach level {level} get {} now.",
+            role.mention()
+        ),
+    )
+    .await
+}
+
+/// Stops giving a role to members who reach a level
+#[poise::command(slash_command, guild_only, rename = "remove")]
+async fn levelrole_remove(
+    ctx: Context<'_>,
+    #[description = "Level whose role to stop giving"]
+    #[min = 1]
+    #[max = 1000]
+    level: u32,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = ctx
+        .data()
+        .settings
+        .update(guild_id, |settings| settings.level_roles.remove(&level))
+        .await?;
+
+    let content = match removed {
+        Some(role_id) => format!(
+            "Members who reach level {level} don't get {} anymore.",
+            role_id.mention()
+        ),
+        None => format!("Level {level} has no role."),
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the roles members get for reaching levels
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn levelrole_list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = ctx.data().settings.get(guild_id).await?;
+
+    let content = if settings.level_roles.is_empty() {
+        "No level comes with a role.".to_string()
+    } else {
+        settings
+            .level_roles
+            .iter()
+            .map(|(level, role_id)| format!("- Level {level}: {}", role_id.mention()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    reply(ctx, content).await
+}
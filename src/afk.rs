@@ -0,0 +1,117 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::{
+    serenity_prelude::{self as serenity, *},
+    CreateReply,
+};
+
+use crate::{db, duration::HumanDuration, Context, Data, SlimeError};
+
+/// How long someone has been away, to the minute once it's been a while.
+fn away_for(since: DateTime<Utc>) -> HumanDuration {
+    let seconds = (Utc::now() - since).num_seconds().max(0);
+    let seconds = if seconds < 60 {
+        seconds
+    } else {
+        seconds - seconds % 60
+    };
+    HumanDuration(Duration::seconds(seconds))
+}
+
+/// Welcomes back the author of `message` if they were AFK, and tells them
+/// who of the members they mentioned is AFK. Neither reply pings anyone.
+pub async fn message_sent(
+    ctx: &serenity::Context,
+    data: &Data,
+    message: &Message,
+) -> Result<(), SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    if message.author.bot {
+        return Ok(());
+    }
+
+    let mut lines = Vec::new();
+    let back: Option<DateTime<Utc>> =
+        sqlx::query_scalar("DELETE FROM afk WHERE guild_id = $1 AND user_id = $2 RETURNING since")
+            .bind(i64::from(guild_id))
+            .bind(i64::from(message.author.id))
+            .fetch_optional(&data.pool)
+            .await?;
+    if let Some(since) = back {
+        lines.push(format!(
+            "Welcome back {}, you were AFK for {}.",
+            message.author.mention(),
+            away_for(since)
+        ));
+    }
+
+    let mentioned: Vec<i64> = message
+        .mentions
+        .iter()
+        .filter(|user| user.id != message.author.id && !user.bot)
+        .map(|user| i64::from(user.id))
+        .collect();
+    if !mentioned.is_empty() {
+        let away: Vec<(i64, Option<String>, DateTime<Utc>)> = sqlx::query_as(
+            "SELECT user_id, reason, since FROM afk WHERE guild_id = $1 AND user_id = ANY($2)",
+        )
+        .bind(i64::from(guild_id))
+        .bind(&mentioned)
+        .fetch_all(&data.pool)
+        .await?;
+        for (user_id, reason, since) in away {
+            let user = UserId::new(user_id as u64).mention();
+            let line = match reason {
+                Some(reason) => format!("{user} is AFK ({}): {reason}", away_for(since)),
+                None => format!("{user} is AFK ({}).", away_for(since)),
+            };
+            lines.push(line);
+        }
+    }
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    let reply = CreateMessage::new()
+        .content(lines.join("\n"))
+        .reference_message(message)
+        .allowed_mentions(CreateAllowedMentions::new());
+    message.channel_id.send_message(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Marks you as away, until you next say something
+#[poise::command(slash_command, guild_only)]
+pub async fn afk(
+    ctx: Context<'_>,
+    #[description = "What to tell members who mention you"]
+    #[max_length = 200]
+    reason: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let reason = reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    sqlx::query(
+        "INSERT INTO afk (guild_id, user_id, reason) VALUES ($1, $2, $3)
+        ON CONFLICT (guild_id, user_id) DO UPDATE SET reason = EXCLUDED.reason, since = now()",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .bind(&reason)
+    .execute(&data.pool)
+    .await?;
+
+    let content = match reason {
+        Some(reason) => format!("You're AFK now: {reason}"),
+        None => "You're AFK now.".to_string(),
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
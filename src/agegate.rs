@@ -0,0 +1,66 @@
+use chrono::{Duration, Utc};
+use poise::serenity_prelude::{self as serenity, Member, Mentionable, UserId};
+
+use crate::{
+    cases::{self, Action, NewCase},
+    duration::HumanDuration,
+    Data, SlimeError,
+};
+
+/// How long ago the account of `user_id` was created.
+pub fn account_age(user_id: UserId) -> Duration {
+    Duration::seconds(Utc::now().timestamp() - user_id.created_at().unix_timestamp())
+}
+
+/// Keeps accounts younger than the guild's `min_account_age` out: they get
+/// its `quarantine_role` if it has one and are kicked otherwise. Returns
+/// whether `member` was kept out.
+pub async fn check_member(
+    ctx: &serenity::Context,
+    data: &Data,
+    member: &Member,
+) -> Result<bool, SlimeError> {
+    if member.user.bot {
+        return Ok(false);
+    }
+    let guild_id = member.guild_id;
+    let settings = data.settings.get(guild_id).await?;
+    let Some(min_age) = settings.min_account_age else {
+        return Ok(false);
+    };
+    let age = account_age(member.user.id);
+    if age >= min_age.as_duration() {
+        return Ok(false);
+    }
+
+    let (action, what) = match settings.quarantine_role {
+        Some(role_id) => {
+            member.add_role(ctx, role_id).await?;
+            (Action::Quarantine, format!("gave {} to", role_id.mention()))
+        }
+        None => {
+            guild_id
+                .kick_with_reason(ctx, member.user.id, "The account is too new")
+                .await?;
+            (Action::Kick, "kicked".to_string())
+        }
+    };
+
+    let age = HumanDuration(age);
+    let case = NewCase {
+        action,
+        user_id: Some(member.user.id),
+        channel_id: None,
+        moderator_id: None,
+        reason: Some("account too new"),
+        details: Some(format!("The account was {age} old")),
+    };
+    let case_id = cases::file(&data.pool, guild_id, case).await?;
+    let content = format!(
+        "Case {case_id}: the account age gate {what} {}, their account is only {age} old.",
+        member.user.mention()
+    );
+    cases::announce(&ctx.http, data, guild_id, &content).await;
+
+    Ok(true)
+}
@@ -0,0 +1,365 @@
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use poise::{serenity_prelude::*, Modal};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    audit, confirm::confirm, db, duration::HumanDuration, localtime, replies::reply,
+    schedule::RunAt, ApplicationContext, Context, Data, SlimeError,
+};
+
+/// How many announcements a guild can have scheduled at once.
+const MAX_ANNOUNCEMENTS: i64 = 25;
+
+/// How many announcements one page of `/announce list` shows.
+const PAGE_LEN: usize = 10;
+
+/// How much of an announcement `/announce list` shows.
+const PREVIEW_LEN: usize = 50;
+
+/// How often a scheduled announcement is posted again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Repeat {
+    Daily,
+    Weekly,
+}
+
+impl Repeat {
+    pub fn interval(self) -> Duration {
+        match self {
+            Repeat::Daily => Duration::days(1),
+            Repeat::Weekly => Duration::weeks(1),
+        }
+    }
+}
+
+#[derive(Modal)]
+#[name = "Announcement embed"]
+struct EmbedModal {
+    #[name = "Title"]
+    #[max_length = 256]
+    title: Option<String>,
+    #[name = "Text"]
+    #[paragraph]
+    #[max_length = 4000]
+    description: String,
+}
+
+/// "Post `content` in `channel_id` at `send_at`, and every `every_seconds`
+/// after that", from the `scheduled_messages` table.
+#[derive(sqlx::FromRow)]
+struct Announcement {
+    announcement_id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    scheduled_by: i64,
+    content: String,
+    embed_title: Option<String>,
+    embed_description: Option<String>,
+    send_at: DateTime<Utc>,
+    every_seconds: Option<i64>,
+}
+
+impl Announcement {
+    fn message(&self) -> CreateMessage {
+        let message = CreateMessage::new().content(&self.content);
+        let Some(description) = &self.embed_description else {
+            return message;
+        };
+        let mut embed = CreateEmbed::new().description(description);
+        if let Some(title) = &self.embed_title {
+            embed = embed.title(title);
+        }
+        message.embed(embed)
+    }
+
+    fn line(&self, timezone: Option<FixedOffset>) -> String {
+        let every = self.every_seconds.map_or(String::new(), |seconds| {
+            format!(", every {}", HumanDuration(Duration::seconds(seconds)))
+        });
+        let embed = if self.embed_description.is_some() {
+            " (with an embed)"
+        } else {
+            ""
+        };
+        let mut preview: String = self.content.chars().take(PREVIEW_LEN).collect();
+        if preview.len() < self.content.len() {
+            preview.push('…');
+        }
+        format!(
+            "`{}` {} in {}{every}, by {}: {preview}{embed}",
+            self.announcement_id,
+            localtime::format_datetime(self.send_at, timezone),
+            ChannelId::new(self.channel_id as u64).mention(),
+            UserId::new(self.scheduled_by as u64).mention()
+        )
+    }
+}
+
+async fn due_announcements(pool: &PgPool) -> sqlx::Result<Vec<Announcement>> {
+    sqlx::query_as(
+        "SELECT announcement_id, guild_id, channel_id, scheduled_by, content, embed_title,
+        embed_description, send_at, every_seconds
+        FROM scheduled_messages WHERE send_at <= now()",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Moves a repeating announcement on to its next time, which skips the
+/// times missed while the bot was down, and removes any other.
+async fn advance(pool: &PgPool, announcement: &Announcement) -> sqlx::Result<()> {
+    let Some(every) = announcement.every_seconds else {
+        sqlx::query("DELETE FROM scheduled_messages WHERE announcement_id = $1")
+            .bind(announcement.announcement_id)
+            .execute(pool)
+            .await?;
+        return Ok(());
+    };
+
+    let missed = (Utc::now() - announcement.send_at).num_seconds() / every;
+    let next = announcement.send_at + Duration::seconds(every * (missed + 1));
+    sqlx::query("UPDATE scheduled_messages SET send_at = $2 WHERE announcement_id = $1")
+        .bind(announcement.announcement_id)
+        .bind(next)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Posts every announcement that is due, the scheduler calls this. Each is
+/// moved on before it's posted, so it's only ever posted once per time.
+pub async fn send_due(http: &Http, data: &Data) {
+    let due = match due_announcements(&data.pool).await {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to load due announcements: {}", e);
+            return;
+        }
+    };
+
+    for announcement in due {
+        if let Err(e) = advance(&data.pool, &announcement).await {
+            error!(
+                "failed to reschedule announcement {}: {}",
+                announcement.announcement_id, e
+            );
+            continue;
+        }
+        let channel_id = ChannelId::new(announcement.channel_id as u64);
+        if let Err(e) = channel_id.send_message(http, announcement.message()).await {
+            error!(
+                "failed to post announcement {} in guild {}: {}",
+                announcement.announcement_id, announcement.guild_id, e
+            );
+        }
+    }
+}
+
+/// Manages messages posted at a later time
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("schedule", "list", "cancel", "publish")
+)]
+pub async fn announce(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Schedules a message for a later time, once or repeating
+#[poise::command(slash_command, guild_only)]
+async fn schedule(
+    ctx: ApplicationContext<'_>,
+    #[description = "Channel to post in"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+    #[description = "When to post, e.g. 09:00, 2024-05-01 09:00 or 2h"] when: RunAt,
+    #[description = "What to post"]
+    #[max_length = 2000]
+    message: String,
+    #[description = "Post it again every day or week"] repeat: Option<Repeat>,
+    #[description = "Add an embed below the message, you write it next"] embed: Option<bool>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let now = Utc::now();
+    let send_at = when.resolve(timezone, now);
+    if send_at <= now {
+        return reply(ctx.into(), "That time has already passed.".to_string()).await;
+    }
+
+    let scheduled: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM scheduled_messages WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .fetch_one(&data.pool)
+            .await?;
+    if scheduled >= MAX_ANNOUNCEMENTS {
+        let content = format!(
+            "There are {MAX_ANNOUNCEMENTS} announcements scheduled already, cancel one first."
+        );
+        return reply(ctx.into(), content).await;
+    }
+
+    // The modal has to be the first response, so nothing can be deferred.
+    let (embed_title, embed_description) = if embed.unwrap_or(false) {
+        let Some(EmbedModal { title, description }) = EmbedModal::execute(ctx).await? else {
+            return Ok(());
+        };
+        (title, Some(description))
+    } else {
+        (None, None)
+    };
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let announcement_id: i64 = sqlx::query_scalar(
+        "INSERT INTO scheduled_messages
+        (guild_id, channel_id, scheduled_by, content, embed_title, embed_description, send_at,
+        every_seconds)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING announcement_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(i64::from(ctx.author().id))
+    .bind(&message)
+    .bind(&embed_title)
+    .bind(&embed_description)
+    .bind(send_at)
+    .bind(repeat.map(|repeat| repeat.interval().num_seconds()))
+    .fetch_one(&data.pool)
+    .await?;
+
+    let every = match repeat {
+        Some(Repeat::Daily) => ", and every day after that",
+        Some(Repeat::Weekly) => ", and every week after that",
+        None => "",
+    };
+    reply(
+        ctx.into(),
+        format!(
+            "Announcement `{announcement_id}`: I'll post it in {} at {}{every}.",
+            channel.mention(),
+            localtime::format_datetime(send_at, timezone)
+        ),
+    )
+    .await
+}
+
+/// Lists the announcements scheduled in this server
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let announcements: Vec<Announcement> = sqlx::query_as(
+        "SELECT announcement_id, guild_id, channel_id, scheduled_by, content, embed_title,
+        embed_description, send_at, every_seconds
+        FROM scheduled_messages WHERE guild_id = $1 ORDER BY send_at",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_all(&data.pool)
+    .await?;
+
+    if announcements.is_empty() {
+        return reply(ctx, "No announcements are scheduled.".to_string()).await;
+    }
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let lines = announcements
+        .iter()
+        .map(|announcement| announcement.line(timezone));
+    audit::paginate(
+        ctx,
+        "Scheduled announcements",
+        &audit::pages(lines, PAGE_LEN),
+    )
+    .await
+}
+
+/// Cancels a scheduled announcement, repeating ones for good
+#[poise::command(slash_command, guild_only)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "Announcement to cancel, see /announce list"] id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed =
+        sqlx::query("DELETE FROM scheduled_messages WHERE guild_id = $1 AND announcement_id = $2")
+            .bind(i64::from(guild_id))
+            .bind(id)
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Announcement `{id}` won't be posted.")
+    } else {
+        format!("There is no scheduled announcement `{id}`.")
+    };
+    reply(ctx, content).await
+}
+
+/// Copies a message into an announcement channel and publishes it to the servers following it
+#[poise::command(slash_command, guild_only)]
+async fn publish(
+    ctx: Context<'_>,
+    #[description = "Announcement channel to post in"]
+    #[channel_types("News")]
+    channel: GuildChannel,
+    #[description = "Link or id of the message to copy"] message: Message,
+) -> Result<(), SlimeError> {
+    if message.guild_id != ctx.guild_id() {
+        let content = "That message is in another server.".to_string();
+        return reply(ctx, content).await;
+    }
+    // Link previews come back by themselves from the copied text.
+    let embeds: Vec<CreateEmbed> = message
+        .embeds
+        .iter()
+        .filter(|embed| embed.kind.as_deref() == Some("rich"))
+        .cloned()
+        .map(CreateEmbed::from)
+        .collect();
+    if message.content.is_empty() && embeds.is_empty() && message.attachments.is_empty() {
+        return reply(ctx, "There's nothing in that message to copy.".to_string()).await;
+    }
+
+    let prompt = format!(
+        "Post a copy of {} in {} and publish it to every server following the channel? \
+        Published messages can't be taken back from those servers.",
+        message.link(),
+        channel.mention()
+    );
+    let Some(interactions) = confirm(ctx, prompt).await? else {
+        return Ok(());
+    };
+
+    let mut copy = CreateMessage::new()
+        .content(&message.content)
+        .embeds(embeds)
+        .allowed_mentions(CreateAllowedMentions::new());
+    for attachment in &message.attachments {
+        copy = copy.add_file(CreateAttachment::url(ctx, &attachment.url).await?);
+    }
+    let posted = channel.send_message(ctx, copy).await?;
+    let content = match posted.crosspost(ctx).await {
+        Ok(_) => format!("Published {}.", posted.link()),
+        Err(e) => {
+            error!(
+                "failed to publish message {} in guild {}: {}",
+                posted.id, channel.guild_id, e
+            );
+            format!(
+                "Posted {}, but Discord didn't let me publish it, try Publish on the message.",
+                posted.link()
+            )
+        }
+    };
+    let followup = CreateInteractionResponseFollowup::new()
+        .content(content)
+        .ephemeral(true);
+    interactions.create_followup(ctx, followup).await?;
+    Ok(())
+}
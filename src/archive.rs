@@ -0,0 +1,266 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude::*;
+use sqlx::{PgPool, QueryBuilder};
+use tracing::error;
+
+use crate::{jobs::JobId, msglog, replies::reply, Context, SlimeError};
+
+/// How many hours purged messages can be restored for.
+const ARCHIVE_TTL_HOURS: i64 = 48;
+
+/// How often expired messages are dropped from the archive.
+const EXPIRE_INTERVAL: StdDuration = StdDuration::from_secs(3600);
+
+/// Discord allows at most this many embeds per message.
+const EMBEDS_PER_MESSAGE: usize = 10;
+
+/// Discord allows at most this many characters in all embeds of a message
+/// together.
+const EMBED_CHARS_PER_MESSAGE: usize = 6000;
+
+const FOOTER: &str = "Restored after a purge";
+const ATTACHMENTS_FIELD: &str = "Attachments (not restored)";
+
+/// A purged message as kept in the `archived_messages` table.
+#[derive(sqlx::FromRow)]
+struct ArchivedMessage {
+    message_id: i64,
+    channel_id: i64,
+    author_name: String,
+    author_avatar: String,
+    content: String,
+    /// File names only, the files themselves are gone with the message.
+    attachments: Vec<String>,
+    sent_at: DateTime<Utc>,
+}
+
+impl ArchivedMessage {
+    /// Messages without text, like ones that only had attachments, still
+    /// need a description.
+    fn description(&self) -> &str {
+        if self.content.is_empty() {
+            "*no text*"
+        } else {
+            &self.content
+        }
+    }
+
+    fn attachments_text(&self) -> String {
+        msglog::field_text(&self.attachments.join("\n"))
+    }
+
+    /// How many characters its embed has, as Discord counts them.
+    fn embed_len(&self) -> usize {
+        let attachments = if self.attachments.is_empty() {
+            0
+        } else {
+            ATTACHMENTS_FIELD.len() + self.attachments_text().chars().count()
+        };
+        self.author_name.chars().count()
+            + self.description().chars().count()
+            + FOOTER.len()
+            + attachments
+    }
+
+    fn embed(&self) -> CreateEmbed {
+        let mut embed = CreateEmbed::new()
+            .author(CreateEmbedAuthor::new(&self.author_name).icon_url(&self.author_avatar))
+            .description(self.description())
+            .footer(CreateEmbedFooter::new(FOOTER));
+        if let Ok(sent_at) = Timestamp::from_unix_timestamp(self.sent_at.timestamp()) {
+            embed = embed.timestamp(sent_at);
+        }
+        if !self.attachments.is_empty() {
+            embed = embed.field(ATTACHMENTS_FIELD, self.attachments_text(), false);
+        }
+
+        embed
+    }
+}
+
+/// Splits `messages` into runs that fit in one message each, by embed count
+/// and by their embeds' length.
+fn batches(messages: &[ArchivedMessage]) -> Vec<&[ArchivedMessage]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut chars = 0;
+    for (i, message) in messages.iter().enumerate() {
+        let len = message.embed_len();
+        if i > start && (i - start == EMBEDS_PER_MESSAGE || chars + len > EMBED_CHARS_PER_MESSAGE) {
+            batches.push(&messages[start..i]);
+            start = i;
+            chars = 0;
+        }
+        chars += len;
+    }
+    if start < messages.len() {
+        batches.push(&messages[start..]);
+    }
+    batches
+}
+
+/// Keeps a copy of `messages` for `ARCHIVE_TTL_HOURS` before `job_id` deletes them.
+pub async fn store(
+    pool: &PgPool,
+    job_id: JobId,
+    guild_id: GuildId,
+    messages: &[Message],
+) -> sqlx::Result<()> {
+    if messages.is_empty() {
+        return Ok(());
+    }
+    let expires_at = Utc::now() + Duration::hours(ARCHIVE_TTL_HOURS);
+
+    let mut query = QueryBuilder::new(
+        "INSERT INTO archived_messages
+        (message_id, job_id, guild_id, channel_id, author_id, author_name, author_avatar,
+        content, attachments, sent_at, expires_at) ",
+    );
+    query.push_values(messages, |mut row, message| {
+        let attachments: Vec<_> = message
+            .attachments
+            .iter()
+            .map(|attachment| attachment.filename.clone())
+            .collect();
+        let sent_at = DateTime::from_timestamp(message.timestamp.unix_timestamp(), 0)
+            .unwrap_or_else(Utc::now);
+        row.push_bind(i64::from(message.id))
+            .push_bind(job_id.0 as i64)
+            .push_bind(i64::from(guild_id))
+            .push_bind(i64::from(message.channel_id))
+            .push_bind(i64::from(message.author.id))
+            .push_bind(message.author.name.clone())
+            .push_bind(message.author.face())
+            .push_bind(message.content.clone())
+            .push_bind(attachments)
+            .push_bind(sent_at)
+            .push_bind(expires_at);
+    });
+    // A resumed job may archive the same messages again.
+    query.push(" ON CONFLICT (message_id) DO NOTHING");
+    query.build().execute(pool).await?;
+
+    Ok(())
+}
+
+/// Forgets archived messages that weren't deleted after all, e.g. because
+/// the purge was cancelled.
+pub async fn discard(
+    pool: &PgPool,
+    message_ids: impl IntoIterator<Item = MessageId>,
+) -> sqlx::Result<()> {
+    let message_ids: Vec<i64> = message_ids.into_iter().map(i64::from).collect();
+    sqlx::query("DELETE FROM archived_messages WHERE message_id = ANY($1)")
+        .bind(message_ids)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Drops archived messages once they can't be restored anymore.
+pub async fn expire_archive(pool: PgPool) {
+    let mut interval = tokio::time::interval(EXPIRE_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = sqlx::query("DELETE FROM archived_messages WHERE expires_at <= now()")
+            .execute(&pool)
+            .await
+        {
+            error!("failed to expire archived messages: {}", e);
+        }
+    }
+}
+
+/// The latest purge in `channel_id` that can still be restored.
+async fn latest_job(pool: &PgPool, channel_id: ChannelId) -> sqlx::Result<Option<JobId>> {
+    let job_id: Option<i64> = sqlx::query_scalar(
+        "SELECT job_id FROM archived_messages WHERE channel_id = $1 AND expires_at > now()
+        ORDER BY archived_at DESC LIMIT 1",
+    )
+    .bind(i64::from(channel_id))
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(job_id.map(|job_id| JobId(job_id as u64)))
+}
+
+async fn job_messages(
+    pool: &PgPool,
+    guild_id: GuildId,
+    job_id: JobId,
+) -> sqlx::Result<Vec<ArchivedMessage>> {
+    sqlx::query_as(
+        "SELECT message_id, channel_id, author_name, author_avatar, content, attachments, sent_at
+        FROM archived_messages WHERE guild_id = $1 AND job_id = $2 AND expires_at > now()
+        ORDER BY message_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(job_id.0 as i64)
+    .fetch_all(pool)
+    .await
+}
+
+/// Re-posts the messages of a recent purge
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_restore(
+    ctx: Context<'_>,
+    #[description = "Job id from the purge report, defaults to the latest purge in this channel"]
+    job: Option<JobId>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let pool = &ctx.data().pool;
+
+    ctx.defer_ephemeral().await?;
+
+    let job = match job {
+        Some(job) => Some(job),
+        None => latest_job(pool, ctx.channel_id()).await?,
+    };
+    let messages = match job {
+        Some(job) => job_messages(pool, guild_id, job).await?,
+        None => Vec::new(),
+    };
+    if messages.is_empty() {
+        return reply(
+            ctx,
+            format!(
+                "There is nothing to restore, purged messages are only kept for {ARCHIVE_TTL_HOURS} hours."
+            ),
+        )
+        .await;
+    }
+
+    reply(ctx, format!("Restoring {} messages.", messages.len())).await?;
+
+    // A job only ever purges one channel.
+    let channel_id = ChannelId::new(messages[0].channel_id as u64);
+    for batch in batches(&messages) {
+        let embeds = batch.iter().map(ArchivedMessage::embed).collect();
+        channel_id
+            .send_message(ctx, CreateMessage::new().embeds(embeds))
+            .await?;
+        // Restored messages are gone from the archive, so running this
+        // again doesn't post them twice.
+        discard(
+            pool,
+            batch
+                .iter()
+                .map(|message| MessageId::new(message.message_id as u64)),
+        )
+        .await?;
+    }
+
+    reply(
+        ctx,
+        format!(
+            "Restored {} messages in {}.",
+            messages.len(),
+            channel_id.mention()
+        ),
+    )
+    .await
+}
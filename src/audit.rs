@@ -0,0 +1,244 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::{types::Json, PgPool};
+
+use crate::{
+    duration::HumanDuration, filter::MessageFilter, jobs::StoredJob, localtime, replies::shorten,
+    Context, SlimeError,
+};
+
+/// How many purges one page of `purge_history` shows.
+const PAGE_LEN: usize = 10;
+
+/// How long a page made by `pages` can be, embed descriptions take 4096
+/// characters at most.
+const PAGE_CHARS: usize = 4000;
+
+/// A purge that ran, as stored in the `purge_audit` table.
+struct AuditEntry {
+    channel_id: ChannelId,
+    invoker_id: Option<UserId>,
+    filter: MessageFilter,
+    deleted: i64,
+    duration: Duration,
+    dry_run: bool,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct AuditRow {
+    channel_id: i64,
+    invoker_id: Option<i64>,
+    filter: Json<MessageFilter>,
+    deleted: i64,
+    duration_ms: i64,
+    dry_run: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl From<AuditRow> for AuditEntry {
+    fn from(row: AuditRow) -> Self {
+        AuditEntry {
+            channel_id: ChannelId::new(row.channel_id as u64),
+            invoker_id: row.invoker_id.map(|id| UserId::new(id as u64)),
+            filter: row.filter.0,
+            deleted: row.deleted,
+            duration: Duration::milliseconds(row.duration_ms),
+            dry_run: row.dry_run,
+            created_at: row.created_at,
+        }
+    }
+}
+
+impl AuditEntry {
+    fn line(&self, timezone: Option<FixedOffset>) -> String {
+        let invoker = match self.invoker_id {
+            Some(invoker_id) => format!(" by {}", invoker_id.mention()),
+            None => " automatically".to_string(),
+        };
+        let filter = self.filter.description();
+        let filter = if filter.is_empty() {
+            String::new()
+        } else {
+            format!(" ({filter})")
+        };
+        let dry_run = if self.dry_run { " [dry run]" } else { "" };
+
+        format!(
+            "`{}` {}: {} messages in {}{invoker}{filter}{dry_run}",
+            localtime::format_datetime(self.created_at, timezone),
+            self.channel_id.mention(),
+            self.deleted,
+            HumanDuration(self.duration),
+        )
+    }
+}
+
+/// Records that `stored` ran and deleted `deleted` messages in `took`, or
+/// would have for a dry run. `invoker` is whoever started it, or `None` for
+/// automatic purges.
+pub async fn record(
+    pool: &PgPool,
+    stored: &StoredJob,
+    invoker: Option<UserId>,
+    deleted: usize,
+    took: StdDuration,
+    dry_run: bool,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO purge_audit
+        (job_id, guild_id, channel_id, invoker_id, filter, deleted, duration_ms, dry_run)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(stored.id.0 as i64)
+    .bind(i64::from(stored.guild_id))
+    .bind(i64::from(stored.channel_id))
+    .bind(invoker.map(i64::from))
+    .bind(Json(&stored.filter))
+    .bind(deleted as i64)
+    .bind(took.as_millis() as i64)
+    .bind(dry_run)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+async fn recent(pool: &PgPool, guild_id: GuildId, limit: u32) -> sqlx::Result<Vec<AuditEntry>> {
+    let rows: Vec<AuditRow> = sqlx::query_as(
+        "SELECT channel_id, invoker_id, filter, deleted, duration_ms, dry_run, created_at
+        FROM purge_audit WHERE guild_id = $1 ORDER BY created_at DESC LIMIT $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(limit))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(AuditEntry::from).collect())
+}
+
+fn make_page_buttons(prev_uuid: &str, next_uuid: &str) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(prev_uuid)
+            .label("previous")
+            .style(ButtonStyle::Secondary),
+        CreateButton::new(next_uuid)
+            .label("next")
+            .style(ButtonStyle::Secondary),
+    ])
+}
+
+fn make_page(title: &str, pages: &[String], page: usize) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(title)
+        .description(&pages[page])
+        .footer(CreateEmbedFooter::new(format!(
+            "Page {} of {}",
+            page + 1,
+            pages.len()
+        )))
+}
+
+/// Packs `lines` into pages for `paginate`, `per_page` lines each at most
+/// and never more than fit in an embed.
+pub fn pages(lines: impl IntoIterator<Item = String>, per_page: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    let mut page_lines = 0;
+    for line in lines {
+        let line = shorten(&line, PAGE_CHARS);
+        let len = page.chars().count() + 1 + line.chars().count();
+        if page_lines > 0 && (page_lines == per_page || len > PAGE_CHARS) {
+            pages.push(std::mem::take(&mut page));
+            page_lines = 0;
+        }
+        if page_lines > 0 {
+            page.push('\n');
+        }
+        page.push_str(&line);
+        page_lines += 1;
+    }
+    if page_lines > 0 {
+        pages.push(page);
+    }
+    pages
+}
+
+/// Shows `pages` in an ephemeral embed with buttons to flip through them.
+pub async fn paginate(ctx: Context<'_>, title: &str, pages: &[String]) -> Result<(), SlimeError> {
+    let id = ctx.id();
+    let prev_uuid = format!("{id}-prev");
+    let next_uuid = format!("{id}-next");
+
+    let mut reply = CreateReply::default()
+        .embed(make_page(title, pages, 0))
+        .ephemeral(true);
+    if pages.len() > 1 {
+        reply = reply.components(vec![make_page_buttons(&prev_uuid, &next_uuid)]);
+    }
+    ctx.send(reply).await?;
+    if pages.len() <= 1 {
+        return Ok(());
+    }
+
+    let mut page = 0;
+    while let Some(press) = ComponentInteractionCollector::new(ctx.serenity_context())
+        .timeout(StdDuration::from_secs(300))
+        .custom_ids(vec![prev_uuid.clone(), next_uuid.clone()])
+        .await
+    {
+        page = if press.data.custom_id == next_uuid {
+            (page + 1) % pages.len()
+        } else {
+            page.checked_sub(1).unwrap_or(pages.len() - 1)
+        };
+
+        let message = CreateInteractionResponseMessage::new().embed(make_page(title, pages, page));
+        press
+            .create_response(ctx, CreateInteractionResponse::UpdateMessage(message))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Shows the latest purges in this server
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+pub async fn purge_history(
+    ctx: Context<'_>,
+    #[description = "How many purges to show (default 20)"]
+    #[min = 1]
+    #[max = 100]
+    count: Option<u32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let entries = recent(&ctx.data().pool, guild_id, count.unwrap_or(20)).await?;
+    let timezone = ctx.data().settings.get(guild_id).await?.timezone;
+
+    if entries.is_empty() {
+        let reply = CreateReply::default()
+            .content("Nothing was purged yet.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let pages: Vec<String> = entries
+        .chunks(PAGE_LEN)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|entry| entry.line(timezone))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect();
+
+    paginate(ctx, "Purge history", &pages).await
+}
@@ -0,0 +1,584 @@
+use std::{collections::HashMap, sync::Arc};
+
+use dashmap::DashMap;
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    audit,
+    cases::{self, Action, NewCase},
+    duration::HumanDuration,
+    filter::Pattern,
+    replies::reply,
+    settings::GuildSettings,
+    spam::{Burst, SpamAction, SpamTracker},
+    timeouts, warnings, Context, Data, SlimeError,
+};
+
+/// The most banned terms a guild can have, each one is matched against
+/// every message.
+const MAX_TERMS: usize = 100;
+
+/// How many banned terms one page of `/automod words list` shows.
+const PAGE_LEN: usize = 20;
+
+/// A word, phrase or regex messages may not contain.
+struct BannedTerm {
+    term: String,
+    is_regex: bool,
+    pattern: Pattern,
+}
+
+impl BannedTerm {
+    /// Literal terms match regardless of case, regexes decide for themselves.
+    fn new(term: String, is_regex: bool) -> Result<Self, regex::Error> {
+        let pattern = if is_regex {
+            term.parse()?
+        } else {
+            format!("(?i){}", regex::escape(&term)).parse()?
+        };
+        Ok(BannedTerm {
+            term,
+            is_regex,
+            pattern,
+        })
+    }
+
+    fn display(&self) -> String {
+        if self.is_regex {
+            format!("`{}` (regex)", self.term)
+        } else {
+            format!("`{}`", self.term)
+        }
+    }
+}
+
+/// The banned terms of every guild that got a message since the bot
+/// started, kept in memory because every message is checked against them.
+pub struct AutomodCache {
+    pool: PgPool,
+    guilds: DashMap<GuildId, Arc<Vec<BannedTerm>>>,
+}
+
+impl AutomodCache {
+    pub fn new(pool: PgPool) -> Self {
+        AutomodCache {
+            pool,
+            guilds: DashMap::new(),
+        }
+    }
+
+    /// Drops the cached terms of a guild, after they changed or the bot left.
+    pub fn forget(&self, guild_id: GuildId) {
+        self.guilds.remove(&guild_id);
+    }
+
+    async fn terms(&self, guild_id: GuildId) -> sqlx::Result<Arc<Vec<BannedTerm>>> {
+        if let Some(terms) = self.guilds.get(&guild_id) {
+            return Ok(Arc::clone(&terms));
+        }
+
+        let rows: Vec<(String, bool)> = sqlx::query_as(
+            "SELECT term, is_regex FROM banned_terms WHERE guild_id = $1 ORDER BY term",
+        )
+        .bind(i64::from(guild_id))
+        .fetch_all(&self.pool)
+        .await?;
+        // Every term compiled when it was added, so this only skips terms a
+        // newer regex version might reject.
+        let terms: Arc<Vec<_>> = Arc::new(
+            rows.into_iter()
+                .filter_map(|(term, is_regex)| BannedTerm::new(term, is_regex).ok())
+                .collect(),
+        );
+        self.guilds.insert(guild_id, Arc::clone(&terms));
+
+        Ok(terms)
+    }
+}
+
+/// How invite links to other servers start.
+const INVITE_PREFIXES: [&str; 3] = [
+    "discord.gg/",
+    "discord.com/invite/",
+    "discordapp.com/invite/",
+];
+
+fn has_invite(content: &str) -> bool {
+    let content = content.to_ascii_lowercase();
+    INVITE_PREFIXES
+        .iter()
+        .any(|prefix| content.contains(prefix))
+}
+
+/// Whether the guild's invite blocker lets `message` through.
+fn invite_allowed(settings: &GuildSettings, message: &Message) -> bool {
+    !settings.block_invites.unwrap_or(false)
+        || settings.invite_channels.contains(&message.channel_id)
+        || message.member.as_ref().is_some_and(|member| {
+            member
+                .roles
+                .iter()
+                .any(|role| settings.invite_roles.contains(role))
+        })
+}
+
+/// Whether `message` tries to ping everyone. Discord only lets that through
+/// for members allowed to, for everyone else the mention does nothing.
+fn everyone_attempt(message: &Message) -> bool {
+    !message.mention_everyone
+        && (message.content.contains("@everyone") || message.content.contains("@here"))
+}
+
+/// Why automod deleted a message.
+enum Violation {
+    /// It matched this banned term, see `BannedTerm::display`.
+    Term(String),
+    Invite,
+    /// It mentioned this many members and roles, more than allowed.
+    MassMention(usize),
+    Everyone,
+    /// Its author went over the guild's spam limits with these messages.
+    Spam(Burst),
+}
+
+impl Violation {
+    fn reason(&self) -> &'static str {
+        match self {
+            Violation::Term(_) => "banned term",
+            Violation::Invite => "invite link",
+            Violation::MassMention(_) => "mass mention",
+            Violation::Everyone => "unauthorized @everyone",
+            Violation::Spam(_) => "spam",
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            Violation::Term(term) => format!("matching {term}"),
+            Violation::Invite => "with an invite link".to_string(),
+            Violation::MassMention(count) => format!("with {count} mentions"),
+            Violation::Everyone => "trying to ping everyone".to_string(),
+            Violation::Spam(burst) => burst.description.clone(),
+        }
+    }
+
+    /// Whether the guild's `mention_timeout` applies.
+    fn times_out(&self) -> bool {
+        matches!(self, Violation::MassMention(_) | Violation::Everyone)
+    }
+}
+
+/// Finds the first of the guild's rules `message` breaks, if any. Messages
+/// that break none count towards the author's spam limits.
+fn violation(
+    settings: &GuildSettings,
+    terms: &[BannedTerm],
+    spam: &SpamTracker,
+    message: &Message,
+) -> Option<Violation> {
+    if let Some(term) = terms
+        .iter()
+        .find(|term| term.pattern.is_match(&message.content))
+    {
+        return Some(Violation::Term(term.display()));
+    }
+    if has_invite(&message.content) && !invite_allowed(settings, message) {
+        return Some(Violation::Invite);
+    }
+    let mentions = message.mentions.len() + message.mention_roles.len();
+    if settings
+        .max_mentions
+        .is_some_and(|max| mentions > max as usize)
+    {
+        return Some(Violation::MassMention(mentions));
+    }
+    if settings.block_everyone.unwrap_or(false) && everyone_attempt(message) {
+        return Some(Violation::Everyone);
+    }
+    let limits = settings.spam_limits();
+    if limits.rate.is_some() || limits.repeats.is_some() {
+        let guild_id = message.guild_id.unwrap();
+        return spam.record(guild_id, message, &limits).map(Violation::Spam);
+    }
+
+    None
+}
+
+/// Times out the author of `message` for `timeout`. The message is gone
+/// either way, so failures are only logged.
+async fn time_out_author(
+    ctx: &serenity::Context,
+    data: &Data,
+    message: &Message,
+    violation: &Violation,
+    timeout: HumanDuration,
+) {
+    let guild_id = message.guild_id.unwrap();
+    let result = timeouts::apply(
+        &ctx.http,
+        &data.pool,
+        guild_id,
+        message.author.id,
+        timeout.as_duration(),
+        None,
+        violation.reason(),
+    )
+    .await;
+    match result {
+        Ok((case_id, _)) => {
+            let content = format!(
+                "Case {case_id}: automod timed out {} for {timeout}.",
+                message.author.mention()
+            );
+            cases::announce(&ctx.http, data, guild_id, &content).await;
+        }
+        Err(e) => error!("failed to time out {}: {}", message.author.id, e),
+    }
+}
+
+/// Warns the author of `message` for `reason` and tells them off in the
+/// channel, saying that `rule`.
+async fn warn_author(
+    ctx: &serenity::Context,
+    data: &Data,
+    message: &Message,
+    reason: &str,
+    rule: &str,
+) -> Result<(), SlimeError> {
+    let guild_id = message.guild_id.unwrap();
+    let author = message.author.id;
+    let count = warnings::add(&data.pool, guild_id, author, None, reason).await?;
+
+    let content = format!(
+        "{}, {rule}. You have {count} warnings now.",
+        author.mention()
+    );
+    if let Err(e) = message.channel_id.say(ctx, content).await {
+        error!("failed to warn {} about {}: {}", author, reason, e);
+    }
+    Ok(())
+}
+
+/// Deletes the messages of a spam burst, in bulk where there are several in
+/// one channel. Their author may have deleted some already, so failures are
+/// only logged.
+async fn delete_burst(ctx: &serenity::Context, burst: &Burst) {
+    let mut channels: HashMap<ChannelId, Vec<MessageId>> = HashMap::new();
+    for &(channel_id, message_id) in &burst.messages {
+        channels.entry(channel_id).or_default().push(message_id);
+    }
+
+    for (channel_id, message_ids) in channels {
+        let result = match message_ids[..] {
+            [message_id] => channel_id.delete_message(ctx, message_id).await,
+            _ => channel_id.delete_messages(ctx, &message_ids).await,
+        };
+        if let Err(e) = result {
+            error!("failed to delete spam in {}: {}", channel_id, e);
+        }
+    }
+}
+
+/// Deletes messages that break the guild's automod rules and logs that.
+/// Bots and members with a moderator role aren't checked. Returns whether
+/// the message was deleted.
+pub async fn check_message(
+    ctx: &serenity::Context,
+    data: &Data,
+    message: &Message,
+) -> Result<bool, SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(false);
+    };
+    if message.author.bot || message.webhook_id.is_some() {
+        return Ok(false);
+    }
+    let settings = data.settings.get(guild_id).await?;
+    if message.member.as_ref().is_some_and(|member| {
+        member
+            .roles
+            .iter()
+            .any(|role| settings.mod_roles.contains(role))
+    }) {
+        return Ok(false);
+    }
+
+    let terms = data.automod.terms(guild_id).await?;
+    let Some(violation) = violation(&settings, &terms, &data.spam, message) else {
+        return Ok(false);
+    };
+
+    let details = match &violation {
+        Violation::Spam(burst) => {
+            delete_burst(ctx, burst).await;
+            format!(
+                "Deleted {} messages of a member who {}",
+                burst.messages.len(),
+                violation.description()
+            )
+        }
+        _ => {
+            message.delete(ctx).await?;
+            format!(
+                "Deleted a message in {} {}",
+                message.channel_id.mention(),
+                violation.description()
+            )
+        }
+    };
+    let spam_action = settings.spam_action.unwrap_or_default();
+    match violation {
+        Violation::Invite => {
+            let rule = "invite links to other servers aren't allowed here";
+            warn_author(ctx, data, message, "Posted an invite link", rule).await?;
+        }
+        Violation::Spam(_) if spam_action == SpamAction::Warn => {
+            warn_author(ctx, data, message, "Spamming", "please don't spam").await?;
+        }
+        _ => {}
+    }
+    let case = NewCase {
+        action: Action::Automod,
+        user_id: Some(message.author.id),
+        channel_id: Some(message.channel_id),
+        moderator_id: None,
+        reason: Some(violation.reason()),
+        details: Some(details.clone()),
+    };
+    let case_id = cases::file(&data.pool, guild_id, case).await?;
+    let content = format!(
+        "Case {case_id}: {details}, sent by {}.",
+        message.author.mention()
+    );
+    cases::announce(&ctx.http, data, guild_id, &content).await;
+    let timeout = match violation {
+        Violation::Spam(_) if spam_action == SpamAction::Timeout => Some(settings.spam_timeout()),
+        _ if violation.times_out() => settings.mention_timeout,
+        _ => None,
+    };
+    if let Some(timeout) = timeout {
+        time_out_author(ctx, data, message, &violation, timeout).await;
+    }
+
+    Ok(true)
+}
+
+/// Configures what messages the bot deletes by itself
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("words", "invites")
+)]
+pub async fn automod(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Manages the words and phrases messages may not contain
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("words_add", "words_remove", "words_list")
+)]
+async fn words(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Deletes messages containing a word, phrase or regex from now on
+#[poise::command(slash_command, guild_only, rename = "add")]
+async fn words_add(
+    ctx: Context<'_>,
+    #[description = "Word or phrase, matched regardless of case"]
+    #[max_length = 200]
+    term: String,
+    #[description = "Treat the term as a regex (default false)"] regex: Option<bool>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let term = BannedTerm::new(term.trim().to_string(), regex.unwrap_or(false));
+    let term = match term {
+        Ok(term) if !term.term.is_empty() => term,
+        Ok(_) => return reply(ctx, "The term can't be empty.".to_string()).await,
+        Err(e) => return reply(ctx, format!("That regex doesn't work: {e}")).await,
+    };
+    if data.automod.terms(guild_id).await?.len() >= MAX_TERMS {
+        let content = format!("There are {MAX_TERMS} banned terms already, remove one first.");
+        return reply(ctx, content).await;
+    }
+
+    sqlx::query(
+        "INSERT INTO banned_terms (guild_id, term, is_regex) VALUES ($1, $2, $3)
+        ON CONFLICT (guild_id, term) DO UPDATE SET is_regex = EXCLUDED.is_regex",
+    )
+    .bind(i64::from(guild_id))
+    .bind(&term.term)
+    .bind(term.is_regex)
+    .execute(&data.pool)
+    .await?;
+    data.automod.forget(guild_id);
+
+    reply(
+        ctx,
+        format!("Messages matching {} will be deleted.", term.display()),
+    )
+    .await
+}
+
+/// Allows a word, phrase or regex again
+#[poise::command(slash_command, guild_only, rename = "remove")]
+async fn words_remove(
+    ctx: Context<'_>,
+    #[description = "The term, as shown by /automod words list"] term: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let term = term.trim();
+    let removed = sqlx::query("DELETE FROM banned_terms WHERE guild_id = $1 AND term = $2")
+        .bind(i64::from(guild_id))
+        .bind(term)
+        .execute(&data.pool)
+        .await?
+        .rows_affected();
+    data.automod.forget(guild_id);
+
+    let content = if removed > 0 {
+        format!("`{term}` is allowed again.")
+    } else {
+        format!("`{term}` isn't banned.")
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the banned words, phrases and regexes
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn words_list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let terms = ctx.data().automod.terms(guild_id).await?;
+
+    if terms.is_empty() {
+        return reply(ctx, "No terms are banned.".to_string()).await;
+    }
+
+    let lines = terms.iter().map(|term| format!("- {}", term.display()));
+    audit::paginate(ctx, "Banned terms", &audit::pages(lines, PAGE_LEN)).await
+}
+
+/// A channel or role the invite blocker lets through.
+#[derive(Clone, Copy)]
+enum InviteAllowed {
+    Channel(ChannelId),
+    Role(RoleId),
+}
+
+impl InviteAllowed {
+    /// Picks the one target of an `allow` or `revoke`, if exactly one was given.
+    fn from_args(channel: Option<GuildChannel>, role: Option<Role>) -> Option<Self> {
+        match (channel, role) {
+            (Some(channel), None) => Some(InviteAllowed::Channel(channel.id)),
+            (None, Some(role)) => Some(InviteAllowed::Role(role.id)),
+            _ => None,
+        }
+    }
+
+    fn mention(self) -> Mention {
+        match self {
+            InviteAllowed::Channel(id) => id.mention(),
+            InviteAllowed::Role(id) => id.mention(),
+        }
+    }
+}
+
+/// Manages where invite links are allowed, see the `block_invites` setting
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("invites_allow", "invites_revoke", "invites_list")
+)]
+async fn invites(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Allows invite links in a channel or for a role
+#[poise::command(slash_command, guild_only, rename = "allow")]
+async fn invites_allow(
+    ctx: Context<'_>,
+    #[description = "Channel to allow invites in"] channel: Option<GuildChannel>,
+    #[description = "Role to allow posting invites"] role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let Some(allowed) = InviteAllowed::from_args(channel, role) else {
+        return reply(ctx, "Pick either a channel or a role.".to_string()).await;
+    };
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .settings
+        .update(guild_id, |settings| match allowed {
+            InviteAllowed::Channel(id) => settings.invite_channels.insert(id),
+            InviteAllowed::Role(id) => settings.invite_roles.insert(id),
+        })
+        .await?;
+
+    reply(
+        ctx,
+        format!("Invite links are allowed for {}.", allowed.mention()),
+    )
+    .await
+}
+
+/// Stops allowing invite links in a channel or for a role
+#[poise::command(slash_command, guild_only, rename = "revoke")]
+async fn invites_revoke(
+    ctx: Context<'_>,
+    #[description = "Channel to block invites in again"] channel: Option<GuildChannel>,
+    #[description = "Role to block invites from again"] role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let Some(allowed) = InviteAllowed::from_args(channel, role) else {
+        return reply(ctx, "Pick either a channel or a role.".to_string()).await;
+    };
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = ctx
+        .data()
+        .settings
+        .update(guild_id, |settings| match allowed {
+            InviteAllowed::Channel(id) => settings.invite_channels.remove(&id),
+            InviteAllowed::Role(id) => settings.invite_roles.remove(&id),
+        })
+        .await?;
+
+    let content = if removed {
+        format!(
+            "Invite links aren't allowed for {} anymore.",
+            allowed.mention()
+        )
+    } else {
+        format!("Invite links weren't allowed for {}.", allowed.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Lists where invite links are allowed
+#[poise::command(slash_command, guild_only, rename = "list")]
+async fn invites_list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = ctx.data().settings.get(guild_id).await?;
+
+    let status = if settings.block_invites.unwrap_or(false) {
+        "Invite links are blocked."
+    } else {
+        "Invite links aren't blocked, see `/config set block_invites`."
+    };
+    let allowed: Vec<_> = settings
+        .invite_channels
+        .iter()
+        .map(|id| id.mention())
+        .chain(settings.invite_roles.iter().map(|id| id.mention()))
+        .map(|mention| format!("- {mention}"))
+        .collect();
+    let content = if allowed.is_empty() {
+        status.to_string()
+    } else {
+        format!("{status} They are allowed for:\n{}", allowed.join("\n"))
+    };
+    reply(ctx, content).await
+}
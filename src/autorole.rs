@@ -0,0 +1,108 @@
+use chrono::Utc;
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{settings::GuildSettings, Data, SlimeError};
+
+/// A member waiting for their autoroles, from the `pending_autoroles` table.
+#[derive(sqlx::FromRow)]
+struct PendingAutorole {
+    guild_id: i64,
+    user_id: i64,
+}
+
+/// Gives `user_id` every autorole of `settings`. A role that's gone or a
+/// member who already left is skipped rather than failing the rest.
+async fn assign(
+    http: &Http,
+    guild_id: GuildId,
+    user_id: UserId,
+    settings: &GuildSettings,
+) -> Result<(), SlimeError> {
+    for role_id in &settings.autoroles {
+        match http
+            .add_member_role(guild_id, user_id, *role_id, Some("Autorole"))
+            .await
+        {
+            Ok(()) => {}
+            Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {
+                warn!(
+                    "couldn't give autorole {} to {} in guild {}: {}",
+                    role_id, user_id, guild_id, e
+                );
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Gives a member who just joined the guild's autoroles, or queues them for
+/// the scheduler if the guild has an `autorole_delay`.
+pub async fn member_joined(
+    ctx: &serenity::Context,
+    data: &Data,
+    member: &Member,
+) -> Result<(), SlimeError> {
+    if member.user.bot {
+        return Ok(());
+    }
+    let guild_id = member.guild_id;
+    let settings = data.settings.get(guild_id).await?;
+    if settings.autoroles.is_empty() {
+        return Ok(());
+    }
+
+    let Some(delay) = settings.autorole_delay else {
+        return assign(&ctx.http, guild_id, member.user.id, &settings).await;
+    };
+    sqlx::query(
+        "INSERT INTO pending_autoroles (guild_id, user_id, assign_at) VALUES ($1, $2, $3)
+        ON CONFLICT (guild_id, user_id) DO UPDATE SET assign_at = EXCLUDED.assign_at",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(member.user.id))
+    .bind(Utc::now() + delay.as_duration())
+    .execute(&data.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Removes the members whose delay is over and returns them, so each one
+/// only ever gets their autoroles once.
+async fn take_due(pool: &PgPool) -> sqlx::Result<Vec<PendingAutorole>> {
+    sqlx::query_as(
+        "DELETE FROM pending_autoroles WHERE assign_at <= now() RETURNING guild_id, user_id",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Gives the autoroles to every member whose delay is over, the scheduler
+/// calls this. They get the roles configured now, not when they joined.
+pub async fn assign_due(http: &Http, data: &Data) {
+    let due = match take_due(&data.pool).await {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to load due autoroles: {}", e);
+            return;
+        }
+    };
+
+    for pending in due {
+        let guild_id = GuildId::new(pending.guild_id as u64);
+        let user_id = UserId::new(pending.user_id as u64);
+        let result = match data.settings.get(guild_id).await {
+            Ok(settings) => assign(http, guild_id, user_id, &settings).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            error!(
+                "failed to give autoroles to {} in guild {}: {}",
+                user_id, guild_id, e
+            );
+        }
+    }
+}
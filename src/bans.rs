@@ -0,0 +1,348 @@
+use std::{sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::{
+    cases::{self, Action, NewCase},
+    duration::HumanDuration,
+    hierarchy::target_problem,
+    localtime, preflight, purge,
+    replies::reply,
+    Context, Data, SlimeError,
+};
+
+/// The error code Discord answers with when unbanning someone who isn't
+/// banned, e.g. because a moderator already lifted the ban.
+const UNKNOWN_BAN: isize = 10026;
+
+/// How far back `softban` deletes messages at most, as far as bans can.
+const MAX_SOFTBAN_DAYS: i64 = 7;
+
+/// How often the background task looks for temporary bans that ran out.
+const UNBAN_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+/// A temporary ban that ran out, from the `temp_bans` table.
+#[derive(sqlx::FromRow)]
+struct DueUnban {
+    guild_id: i64,
+    user_id: i64,
+    case_id: i64,
+}
+
+async fn due_unbans(pool: &PgPool) -> sqlx::Result<Vec<DueUnban>> {
+    sqlx::query_as("SELECT guild_id, user_id, case_id FROM temp_bans WHERE unban_at <= now()")
+        .fetch_all(pool)
+        .await
+}
+
+async fn remove_temp_ban(pool: &PgPool, guild_id: GuildId, user_id: UserId) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM temp_bans WHERE guild_id = $1 AND user_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(i64::from(user_id))
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Lifts the ban and files a case for it. A ban somebody already lifted
+/// counts as done.
+async fn unban(http: &Http, data: &Data, due: &DueUnban) -> Result<(), SlimeError> {
+    let guild_id = GuildId::new(due.guild_id as u64);
+    let user_id = UserId::new(due.user_id as u64);
+    match guild_id.unban(http, user_id).await {
+        Ok(()) => {}
+        Err(Error::Http(HttpError::UnsuccessfulRequest(response)))
+            if response.error.code == UNKNOWN_BAN =>
+        {
+            return Ok(remove_temp_ban(&data.pool, guild_id, user_id).await?);
+        }
+        Err(e) => return Err(e.into()),
+    }
+    remove_temp_ban(&data.pool, guild_id, user_id).await?;
+
+    let case = NewCase {
+        action: Action::Unban,
+        user_id: Some(user_id),
+        channel_id: None,
+        moderator_id: None,
+        reason: None,
+        details: Some(format!("The tempban from case {} ran out", due.case_id)),
+    };
+    let case_id = cases::file(&data.pool, guild_id, case).await?;
+    let content = format!(
+        "Case {case_id}: {} was unbanned, the tempban from case {} ran out.",
+        user_id.mention(),
+        due.case_id
+    );
+    cases::announce(http, data, guild_id, &content).await;
+
+    Ok(())
+}
+
+/// Lifts temporary bans once they run out. The bans are kept in the
+/// database until then, so bans that ran out while the bot was down are
+/// lifted on the first check after it starts.
+pub async fn run_unbans(http: Arc<Http>, data: Data) {
+    let mut interval = tokio::time::interval(UNBAN_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let due = match due_unbans(&data.pool).await {
+            Ok(due) => due,
+            Err(e) => {
+                error!("failed to load due unbans: {}", e);
+                continue;
+            }
+        };
+
+        // A failed unban stays in the table and is tried again next time.
+        for due in due {
+            if let Err(e) = unban(&http, &data, &due).await {
+                error!(
+                    "failed to unban user {} in guild {}: {}",
+                    due.user_id, due.guild_id, e
+                );
+            }
+        }
+    }
+}
+
+/// Tells `user` by DM what happened to them and why, unless the guild
+/// turned that off. It has to happen before they leave the server, and
+/// users who don't accept DMs simply aren't told.
+async fn notify(
+    ctx: Context<'_>,
+    guild: &PartialGuild,
+    user: &User,
+    what: &str,
+    reason: &str,
+) -> Result<(), SlimeError> {
+    if !ctx.data().settings.get(guild.id).await?.dm_members() {
+        return Ok(());
+    }
+
+    let content = format!("You were {what} from {}: {reason}", guild.name);
+    if let Err(e) = user
+        .direct_message(ctx, CreateMessage::new().content(content))
+        .await
+    {
+        info!("couldn't tell user {} they were {}: {}", user.id, what, e);
+    }
+    Ok(())
+}
+
+/// Removes a member from the server
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn kick(
+    ctx: Context<'_>,
+    #[description = "Member to kick"] user: User,
+    #[description = "Why they are kicked"]
+    #[max_length = 512]
+    reason: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let guild = guild_id.to_partial_guild(ctx).await?;
+    if let Some(problem) = target_problem(ctx, &guild, user.id, true).await? {
+        return reply(ctx, problem).await;
+    }
+
+    notify(ctx, &guild, &user, "kicked", &reason).await?;
+    guild_id.kick_with_reason(ctx, user.id, &reason).await?;
+
+    let case = NewCase {
+        action: Action::Kick,
+        user_id: Some(user.id),
+        channel_id: None,
+        moderator_id: Some(ctx.author().id),
+        reason: Some(&reason),
+        details: None,
+    };
+    let case_id = cases::file(&data.pool, guild_id, case).await?;
+    let content = format!(
+        "Case {case_id}: {} kicked {}: {reason}",
+        ctx.author().mention(),
+        user.mention()
+    );
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
+
+/// Bans a user from the server
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn ban(
+    ctx: Context<'_>,
+    #[description = "User to ban"] user: User,
+    #[description = "Why they are banned"]
+    #[max_length = 512]
+    reason: String,
+    #[description = "Delete their messages from this many days back (default none)"]
+    #[max = 7]
+    delete_message_days: Option<u8>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let guild = guild_id.to_partial_guild(ctx).await?;
+    if let Some(problem) = target_problem(ctx, &guild, user.id, false).await? {
+        return reply(ctx, problem).await;
+    }
+
+    let delete_message_days = delete_message_days.unwrap_or(0);
+    notify(ctx, &guild, &user, "banned", &reason).await?;
+    guild_id
+        .ban_with_reason(ctx, user.id, delete_message_days, &reason)
+        .await?;
+
+    let details = (delete_message_days > 0)
+        .then(|| format!("Deleted their messages from the last {delete_message_days} days"));
+    let case = NewCase {
+        action: Action::Ban,
+        user_id: Some(user.id),
+        channel_id: None,
+        moderator_id: Some(ctx.author().id),
+        reason: Some(&reason),
+        details,
+    };
+    let case_id = cases::file(&data.pool, guild_id, case).await?;
+    let content = format!(
+        "Case {case_id}: {} banned {}: {reason}",
+        ctx.author().mention(),
+        user.mention()
+    );
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
+
+/// Bans a member for a while
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn tempban(
+    ctx: Context<'_>,
+    #[description = "Member to ban"] user: User,
+    #[description = "How long, e.g. 12h, 3d or 2w"] duration: HumanDuration,
+    #[description = "Why they are banned"]
+    #[max_length = 512]
+    reason: String,
+) -> Result<(), SlimeError> {
+    let duration = duration.as_duration();
+    if duration <= Duration::zero() {
+        return reply(ctx, "A tempban has to last at least 1s.".to_string()).await;
+    }
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let guild = guild_id.to_partial_guild(ctx).await?;
+    if let Some(problem) = target_problem(ctx, &guild, user.id, false).await? {
+        return reply(ctx, problem).await;
+    }
+
+    let what = format!("banned for {}", HumanDuration(duration));
+    notify(ctx, &guild, &user, &what, &reason).await?;
+    guild_id.ban_with_reason(ctx, user.id, 0, &reason).await?;
+
+    let case = NewCase {
+        action: Action::Tempban,
+        user_id: Some(user.id),
+        channel_id: None,
+        moderator_id: Some(ctx.author().id),
+        reason: Some(&reason),
+        details: Some(format!("For {}", HumanDuration(duration))),
+    };
+    let case_id = cases::file(&data.pool, guild_id, case).await?;
+    let unban_at: DateTime<Utc> = Utc::now() + duration;
+    sqlx::query(
+        "INSERT INTO temp_bans (guild_id, user_id, case_id, unban_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (guild_id, user_id)
+        DO UPDATE SET case_id = EXCLUDED.case_id, unban_at = EXCLUDED.unban_at",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user.id))
+    .bind(case_id)
+    .bind(unban_at)
+    .execute(&data.pool)
+    .await?;
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let content = format!(
+        "Case {case_id}: {} banned {} until {}: {reason}",
+        ctx.author().mention(),
+        user.mention(),
+        localtime::format_datetime(unban_at, timezone)
+    );
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
+
+/// Kicks a member and deletes their recent messages
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn softban(
+    ctx: Context<'_>,
+    #[description = "Member to softban"] user: User,
+    #[description = "Why they are softbanned"]
+    #[max_length = 512]
+    reason: Option<String>,
+    #[description = "Delete their messages from this far back, e.g. 12h or 3d (default 1d)"]
+    since: Option<HumanDuration>,
+) -> Result<(), SlimeError> {
+    let since = since.unwrap_or(HumanDuration(Duration::days(1)));
+    if since.as_duration() <= Duration::zero()
+        || since.as_duration() > Duration::days(MAX_SOFTBAN_DAYS)
+    {
+        let content = format!("Pick how far back to delete, at most {MAX_SOFTBAN_DAYS}d.");
+        return reply(ctx, content).await;
+    }
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+
+    ctx.defer_ephemeral().await?;
+    let guild = guild_id.to_partial_guild(ctx).await?;
+    if let Some(problem) = target_problem(ctx, &guild, user.id, true).await? {
+        return reply(ctx, problem).await;
+    }
+    let channel_ids = preflight::purgeable_channels(ctx).await?;
+
+    let reason_text = reason.as_deref().unwrap_or("no reason given");
+    notify(ctx, &guild, &user, "kicked", reason_text).await?;
+    // Banning without deleting anything and unbanning right away is a kick.
+    // The messages are deleted by regular purges, which are paced and reported.
+    guild_id
+        .ban_with_reason(ctx, user.id, 0, reason_text)
+        .await?;
+    guild_id.unban(ctx, user.id).await?;
+
+    let case = NewCase {
+        action: Action::Softban,
+        user_id: Some(user.id),
+        channel_id: None,
+        moderator_id: Some(ctx.author().id),
+        reason: reason.as_deref(),
+        details: Some(format!(
+            "Deleting their messages from the last {since} in {} channels",
+            channel_ids.len()
+        )),
+    };
+    let case_id = cases::file(&data.pool, guild_id, case).await?;
+    let content = format!(
+        "Case {case_id}: {} softbanned {}: {reason_text}. Their messages from the last {since} are being deleted.",
+        ctx.author().mention(),
+        user.mention()
+    );
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    tokio::spawn(purge::wipe_user(
+        Arc::clone(&ctx.serenity_context().http),
+        data.clone(),
+        guild_id,
+        channel_ids,
+        user.id,
+        Utc::now() - since.as_duration(),
+        ctx.author().id,
+    ));
+
+    reply(ctx, content).await
+}
@@ -0,0 +1,278 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use poise::serenity_prelude::*;
+use thiserror::Error;
+use tracing::error;
+
+use crate::{db, localtime, replies::reply, settings::ChannelPurpose, Context, Data, SlimeError};
+
+/// How many birthdays `/birthday upcoming` lists.
+const UPCOMING_COUNT: usize = 10;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("expected a date like 05-31 (month first) or 1990-05-31")]
+pub struct ParseBirthdayError;
+
+/// A day of the year, written as `MM-DD`. A year in front is fine too, but
+/// only the day is kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BirthdayDate {
+    month: u32,
+    day: u32,
+}
+
+impl FromStr for BirthdayDate {
+    type Err = ParseBirthdayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let month_day = match s.matches('-').count() {
+            1 => s,
+            2 => s.split_once('-').map_or(s, |(_, month_day)| month_day),
+            _ => return Err(ParseBirthdayError),
+        };
+        let (month, day) = month_day.split_once('-').ok_or(ParseBirthdayError)?;
+        let month = month.parse().map_err(|_| ParseBirthdayError)?;
+        let day = day.parse().map_err(|_| ParseBirthdayError)?;
+        // A leap year, so February 29th is a day too.
+        NaiveDate::from_ymd_opt(2000, month, day).ok_or(ParseBirthdayError)?;
+
+        Ok(BirthdayDate { month, day })
+    }
+}
+
+impl fmt::Display for BirthdayDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.on(2000).format("%B %-d"))
+    }
+}
+
+impl BirthdayDate {
+    /// The day it falls on in `year`. February 29th falls on the 28th in
+    /// years that don't have one.
+    fn on(self, year: i32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, self.month, self.day)
+            .or_else(|| NaiveDate::from_ymd_opt(year, self.month, self.day - 1))
+            .unwrap_or_default()
+    }
+
+    /// The next day it falls on, `today` included.
+    fn next(self, today: NaiveDate) -> NaiveDate {
+        let this_year = self.on(today.year());
+        if this_year >= today {
+            this_year
+        } else {
+            self.on(today.year() + 1)
+        }
+    }
+
+    /// How it's stored, e.g. 531 for May 31st.
+    fn key(self) -> i32 {
+        (self.month * 100 + self.day) as i32
+    }
+}
+
+/// A birthday from the `birthdays` table.
+#[derive(sqlx::FromRow)]
+struct Birthday {
+    guild_id: i64,
+    user_id: i64,
+    month: i32,
+    day: i32,
+}
+
+impl Birthday {
+    fn date(&self) -> BirthdayDate {
+        BirthdayDate {
+            month: self.month as u32,
+            day: self.day as u32,
+        }
+    }
+}
+
+/// Wishes every member whose birthday it is in their guild's timezone a
+/// happy birthday, the scheduler calls this. Each birthday is marked as
+/// wished for before the message is sent, so it's only sent once a year.
+pub async fn announce_due(http: &Http, data: &Data) {
+    let now = Utc::now();
+    // Whatever the timezone, today is one of these days somewhere.
+    let mut keys = Vec::new();
+    for offset in -1..=1 {
+        let date = now.date_naive() + Duration::days(offset);
+        let date = BirthdayDate {
+            month: date.month(),
+            day: date.day(),
+        };
+        keys.push(date.key());
+        if date.key() == 228 {
+            keys.push(229);
+        }
+    }
+
+    let due: Vec<Birthday> = match sqlx::query_as(
+        "SELECT guild_id, user_id, month, day FROM birthdays
+        WHERE month * 100 + day = ANY($1) AND (announced_on IS NULL OR announced_on < $2)",
+    )
+    .bind(&keys)
+    .bind(now.date_naive() - Duration::days(1))
+    .fetch_all(&data.pool)
+    .await
+    {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to load due birthdays: {}", e);
+            return;
+        }
+    };
+
+    for birthday in due {
+        if let Err(e) = announce(http, data, &birthday).await {
+            error!(
+                "failed to wish {} a happy birthday in guild {}: {}",
+                birthday.user_id, birthday.guild_id, e
+            );
+        }
+    }
+}
+
+async fn announce(http: &Http, data: &Data, birthday: &Birthday) -> Result<(), SlimeError> {
+    let guild_id = GuildId::new(birthday.guild_id as u64);
+    let settings = data.settings.get(guild_id).await?;
+    let Some(channel) = settings.channel(ChannelPurpose::Birthdays) else {
+        return Ok(());
+    };
+    let today = localtime::to_local(Utc::now(), settings.timezone).date();
+    if birthday.date().on(today.year()) != today {
+        return Ok(());
+    }
+
+    let claimed = sqlx::query(
+        "UPDATE birthdays SET announced_on = $3
+        WHERE guild_id = $1 AND user_id = $2 AND announced_on IS DISTINCT FROM $3",
+    )
+    .bind(birthday.guild_id)
+    .bind(birthday.user_id)
+    .bind(today)
+    .execute(&data.pool)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return Ok(());
+    }
+
+    let user_id = UserId::new(birthday.user_id as u64);
+    let message = CreateMessage::new()
+        .content(format!("Happy birthday, {}! 🎂", user_id.mention()))
+        .allowed_mentions(CreateAllowedMentions::new().users([user_id]));
+    channel.send_message(http, message).await?;
+
+    Ok(())
+}
+
+/// Forgets the birthday of a member who left, so they aren't wished a happy
+/// one in a server they're not in.
+pub async fn member_left(data: &Data, guild_id: GuildId, user: &User) -> Result<(), SlimeError> {
+    sqlx::query("DELETE FROM birthdays WHERE guild_id = $1 AND user_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(i64::from(user.id))
+        .execute(&data.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Tells the server when your birthday is
+#[poise::command(slash_command, guild_only, subcommands("set", "remove", "upcoming"))]
+pub async fn birthday(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Sets your birthday, so the server can wish you a happy one
+#[poise::command(slash_command, guild_only)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Your birthday, month first, e.g. 05-31"] date: BirthdayDate,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    db::ensure_guild(&data.pool, guild_id).await?;
+    // A new date might not have come around yet this year, the same date
+    // isn't wished for twice.
+    sqlx::query(
+        "INSERT INTO birthdays (guild_id, user_id, month, day) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (guild_id, user_id) DO UPDATE
+        SET month = EXCLUDED.month, day = EXCLUDED.day, announced_on = CASE
+            WHEN birthdays.month = EXCLUDED.month AND birthdays.day = EXCLUDED.day
+            THEN birthdays.announced_on
+        END",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .bind(date.month as i32)
+    .bind(date.day as i32)
+    .execute(&data.pool)
+    .await?;
+
+    reply(ctx, format!("Your birthday is on {date} now.")).await
+}
+
+/// Forgets your birthday
+#[poise::command(slash_command, guild_only)]
+async fn remove(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = sqlx::query("DELETE FROM birthdays WHERE guild_id = $1 AND user_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(i64::from(ctx.author().id))
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        "Your birthday is forgotten."
+    } else {
+        "You didn't set a birthday."
+    };
+    reply(ctx, content.to_string()).await
+}
+
+/// Lists the next birthdays in the server
+#[poise::command(slash_command, guild_only)]
+async fn upcoming(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let birthdays: Vec<Birthday> =
+        sqlx::query_as("SELECT guild_id, user_id, month, day FROM birthdays WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .fetch_all(&data.pool)
+            .await?;
+    if birthdays.is_empty() {
+        return reply(ctx, "Nobody set a birthday yet.".to_string()).await;
+    }
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let today = localtime::to_local(Utc::now(), timezone).date();
+    let mut birthdays: Vec<_> = birthdays
+        .into_iter()
+        .map(|birthday| (birthday.date().next(today), birthday))
+        .collect();
+    birthdays.sort_by_key(|(next, _)| *next);
+
+    let content = birthdays
+        .iter()
+        .take(UPCOMING_COUNT)
+        .map(|(next, birthday)| {
+            let when = if *next == today {
+                "today".to_string()
+            } else {
+                birthday.date().to_string()
+            };
+            format!(
+                "- {when}: {}",
+                UserId::new(birthday.user_id as u64).mention()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    reply(ctx, content).await
+}
@@ -0,0 +1,209 @@
+use poise::{
+    serenity_prelude::{self as serenity, *},
+    CreateReply,
+};
+use tracing::warn;
+
+use crate::{db, msglog::field_text, replies::reply, Context, Data, SlimeError};
+
+/// How many bookmarks `/bookmarks list` shows.
+const LIST_SIZE: i64 = 15;
+
+/// How much of a bookmarked message `/bookmarks list` shows.
+const PREVIEW_LEN: usize = 80;
+
+/// Whether `reaction` is the guild's bookmark emoji. Custom emojis are told
+/// apart by their id only, since they can be renamed.
+fn is_bookmark(reaction: &ReactionType, emoji: &ReactionType) -> bool {
+    match (reaction, emoji) {
+        (ReactionType::Custom { id, .. }, ReactionType::Custom { id: emoji_id, .. }) => {
+            id == emoji_id
+        }
+        (ReactionType::Unicode(reaction), ReactionType::Unicode(emoji)) => {
+            // Some clients add a variation selector, others don't.
+            reaction.trim_end_matches('\u{fe0f}') == emoji.trim_end_matches('\u{fe0f}')
+        }
+        _ => false,
+    }
+}
+
+/// The DM copy of a bookmarked message.
+fn copy(guild_id: GuildId, message: &Message) -> (String, CreateEmbed) {
+    let content = format!("🔖 Bookmarked from {}", message.channel_id.mention());
+    let mut embed = CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(message.author.tag()).icon_url(message.author.face()))
+        .colour(Colour::BLURPLE)
+        .field(
+            "Source",
+            message.id.link(message.channel_id, Some(guild_id)),
+            false,
+        )
+        .timestamp(message.timestamp);
+    if !message.content.is_empty() {
+        embed = embed.description(&message.content);
+    }
+    let image = message.attachments.iter().find(|attachment| {
+        attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|content_type| content_type.starts_with("image/"))
+    });
+    if let Some(image) = image {
+        embed = embed.image(&image.url);
+    }
+    (content, embed)
+}
+
+/// Bookmarks a message someone reacted to with the bookmark emoji and DMs
+/// them a copy, if the guild has bookmarks on. Reacting again doesn't send
+/// another one.
+pub async fn reaction_added(
+    ctx: &serenity::Context,
+    data: &Data,
+    reaction: &Reaction,
+) -> Result<(), SlimeError> {
+    let (Some(guild_id), Some(user_id)) = (reaction.guild_id, reaction.user_id) else {
+        return Ok(());
+    };
+    if reaction
+        .member
+        .as_ref()
+        .is_some_and(|member| member.user.bot)
+    {
+        return Ok(());
+    }
+    let settings = data.settings.get(guild_id).await?;
+    if !settings.bookmarks() || !is_bookmark(&reaction.emoji, &settings.bookmark_emoji()) {
+        return Ok(());
+    }
+
+    let message = match reaction.channel_id.message(ctx, reaction.message_id).await {
+        Ok(message) => message,
+        Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let added = sqlx::query(
+        "INSERT INTO bookmarks (guild_id, user_id, channel_id, message_id, author_id, content)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (guild_id, user_id, message_id) DO NOTHING",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user_id))
+    .bind(i64::from(message.channel_id))
+    .bind(i64::from(message.id))
+    .bind(i64::from(message.author.id))
+    .bind(&message.content)
+    .execute(&data.pool)
+    .await?
+    .rows_affected();
+    if added == 0 {
+        return Ok(());
+    }
+
+    // The bookmark is kept for `/bookmarks list` even if their DMs are
+    // closed.
+    let (content, embed) = copy(guild_id, &message);
+    let dm = match user_id.create_dm_channel(ctx).await {
+        Ok(dm) => dm
+            .send_message(ctx, CreateMessage::new().content(content).embed(embed))
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = dm {
+        warn!(
+            "failed to DM {} a bookmark in guild {}: {}",
+            user_id, guild_id, e
+        );
+    }
+
+    Ok(())
+}
+
+/// Shows the messages you bookmarked
+#[poise::command(slash_command, guild_only, subcommands("list", "remove"))]
+pub async fn bookmarks(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Lists the messages you bookmarked in this server, newest first
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let bookmarks: Vec<(i64, i64, i64, String)> = sqlx::query_as(
+        "SELECT channel_id, message_id, author_id, content FROM bookmarks
+        WHERE guild_id = $1 AND user_id = $2
+        ORDER BY bookmarked_at DESC LIMIT $3",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .bind(LIST_SIZE)
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if bookmarks.is_empty() {
+        let emoji = ctx.data().settings.get(guild_id).await?.bookmark_emoji();
+        let content = format!("You have no bookmarks, react to a message with {emoji} to add one.");
+        return reply(ctx, content).await;
+    }
+
+    let list = bookmarks
+        .into_iter()
+        .map(|(channel_id, message_id, author_id, content)| {
+            let mut preview: String = content.chars().take(PREVIEW_LEN).collect();
+            if content.chars().count() > PREVIEW_LEN {
+                preview.push('…');
+            }
+            format!(
+                "- {} by {}: {}",
+                MessageId::new(message_id as u64)
+                    .link(ChannelId::new(channel_id as u64), Some(guild_id)),
+                UserId::new(author_id as u64).mention(),
+                field_text(&preview).replace('\n', " ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let embed = CreateEmbed::new()
+        .title("Your bookmarks")
+        .colour(Colour::BLURPLE)
+        .description(list);
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Forgets a bookmark
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Link or id of the bookmarked message"] message: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let message_id = message
+        .trim()
+        .rsplit('/')
+        .next()
+        .and_then(|id| id.parse::<u64>().ok())
+        .filter(|&id| id != 0);
+    let Some(message_id) = message_id else {
+        return reply(ctx, format!("`{message}` is not a message link or id.")).await;
+    };
+
+    let removed = sqlx::query(
+        "DELETE FROM bookmarks WHERE guild_id = $1 AND user_id = $2 AND message_id = $3",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .bind(message_id as i64)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    let content = if removed > 0 {
+        "The bookmark is forgotten."
+    } else {
+        "You didn't bookmark that message."
+    };
+    reply(ctx, content.to_string()).await
+}
@@ -0,0 +1,248 @@
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{localtime, replies::reply, settings::ChannelPurpose, Context, Data, SlimeError};
+
+/// What a moderator did, as stored in the `action` column of `mod_cases`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Warn,
+    Purge,
+    Timeout,
+    Kick,
+    Ban,
+    Softban,
+    Tempban,
+    Unban,
+    Automod,
+    Quarantine,
+}
+
+impl Action {
+    fn as_str(self) -> &'static str {
+        match self {
+            Action::Warn => "warn",
+            Action::Purge => "purge",
+            Action::Timeout => "timeout",
+            Action::Kick => "kick",
+            Action::Ban => "ban",
+            Action::Softban => "softban",
+            Action::Tempban => "tempban",
+            Action::Unban => "unban",
+            Action::Automod => "automod",
+            Action::Quarantine => "quarantine",
+        }
+    }
+}
+
+/// A moderation action about to be filed, see `file`.
+pub struct NewCase<'a> {
+    pub action: Action,
+    /// The member the action was aimed at, if any.
+    pub user_id: Option<UserId>,
+    /// The channel the action happened in, if any.
+    pub channel_id: Option<ChannelId>,
+    pub moderator_id: Option<UserId>,
+    pub reason: Option<&'a str>,
+    /// What exactly happened, e.g. how many messages a purge deleted.
+    pub details: Option<String>,
+}
+
+/// Files `case` under the guild's next case number and returns that.
+pub async fn file(pool: &PgPool, guild_id: GuildId, case: NewCase<'_>) -> sqlx::Result<i64> {
+    let mut transaction = pool.begin().await?;
+    // The row lock on the guild keeps concurrent cases from sharing a number.
+    let case_id: i64 = sqlx::query_scalar(
+        "INSERT INTO guilds (guild_id, last_case) VALUES ($1, 1)
+        ON CONFLICT (guild_id) DO UPDATE SET last_case = guilds.last_case + 1
+        RETURNING last_case",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_one(&mut *transaction)
+    .await?;
+    sqlx::query(
+        "INSERT INTO mod_cases
+        (guild_id, case_id, action, user_id, channel_id, moderator_id, reason, details)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+    )
+    .bind(i64::from(guild_id))
+    .bind(case_id)
+    .bind(case.action.as_str())
+    .bind(case.user_id.map(i64::from))
+    .bind(case.channel_id.map(i64::from))
+    .bind(case.moderator_id.map(i64::from))
+    .bind(case.reason)
+    .bind(case.details)
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    Ok(case_id)
+}
+
+/// Posts `content` to the guild's log channel, if it has one. The action
+/// happened either way, so failures are only logged.
+pub async fn announce(http: &Http, data: &Data, guild_id: GuildId, content: &str) {
+    let channel = match data.settings.get(guild_id).await {
+        Ok(settings) => settings.channel(ChannelPurpose::Logs),
+        Err(e) => {
+            error!("failed to look up the log channel: {}", e);
+            return;
+        }
+    };
+    if let Some(channel) = channel {
+        if let Err(e) = channel.say(http, content).await {
+            error!("failed to post to the log channel: {}", e);
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct CaseRow {
+    action: String,
+    user_id: Option<i64>,
+    channel_id: Option<i64>,
+    moderator_id: Option<i64>,
+    reason: Option<String>,
+    details: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+async fn find(pool: &PgPool, guild_id: GuildId, case_id: i64) -> sqlx::Result<Option<CaseRow>> {
+    sqlx::query_as(
+        "SELECT action, user_id, channel_id, moderator_id, reason, details, created_at
+        FROM mod_cases WHERE guild_id = $1 AND case_id = $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(case_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// A case filed against a member, see `recent`.
+#[derive(sqlx::FromRow)]
+pub struct UserCase {
+    pub case_id: i64,
+    pub action: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The latest `limit` cases filed against `user_id`, newest first.
+pub async fn recent(
+    pool: &PgPool,
+    guild_id: GuildId,
+    user_id: UserId,
+    limit: i64,
+) -> sqlx::Result<Vec<UserCase>> {
+    sqlx::query_as(
+        "SELECT case_id, action, reason, created_at FROM mod_cases
+        WHERE guild_id = $1 AND user_id = $2 ORDER BY case_id DESC LIMIT $3",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user_id))
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Looks up and amends moderation cases
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("view", "edit_reason")
+)]
+pub async fn case(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Shows what happened in a case
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn view(
+    ctx: Context<'_>,
+    #[description = "Case number"]
+    #[min = 1]
+    id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some(case) = find(&ctx.data().pool, guild_id, id).await? else {
+        return reply(ctx, format!("There is no case {id}.")).await;
+    };
+    let timezone = ctx.data().settings.get(guild_id).await?.timezone;
+
+    let moderator = match case.moderator_id {
+        Some(id) => UserId::new(id as u64).mention().to_string(),
+        None => "automatic".to_string(),
+    };
+    let mut embed = CreateEmbed::new()
+        .title(format!("Case {id}: {}", case.action))
+        .field("Moderator", moderator, true);
+    if let Some(user_id) = case.user_id {
+        embed = embed.field(
+            "Member",
+            UserId::new(user_id as u64).mention().to_string(),
+            true,
+        );
+    }
+    if let Some(channel_id) = case.channel_id {
+        embed = embed.field(
+            "Channel",
+            ChannelId::new(channel_id as u64).mention().to_string(),
+            true,
+        );
+    }
+    if let Some(details) = case.details {
+        embed = embed.field("Details", details, false);
+    }
+    embed = embed
+        .field(
+            "Reason",
+            case.reason.as_deref().unwrap_or("none given"),
+            false,
+        )
+        .footer(CreateEmbedFooter::new(localtime::format_datetime(
+            case.created_at,
+            timezone,
+        )));
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Changes the reason given for a case
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    rename = "edit-reason"
+)]
+async fn edit_reason(
+    ctx: Context<'_>,
+    #[description = "Case number"]
+    #[min = 1]
+    id: i64,
+    #[description = "The new reason"]
+    #[max_length = 1000]
+    reason: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let updated =
+        sqlx::query("UPDATE mod_cases SET reason = $3 WHERE guild_id = $1 AND case_id = $2")
+            .bind(i64::from(guild_id))
+            .bind(id)
+            .bind(&reason)
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+
+    let content = if updated > 0 {
+        format!("Updated the reason of case {id}.")
+    } else {
+        format!("There is no case {id}.")
+    };
+    reply(ctx, content).await
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use poise::{serenity_prelude::*, CreateReply};
+use tracing::error;
+
+use crate::{
+    i18n::{self, Msg},
+    Context, SlimeError,
+};
+
+/// How long a confirmation prompt waits for an answer.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(120);
+
+fn make_uuid_buttons(
+    locale: Option<&str>,
+    yes_uuid: &str,
+    no_uuid: &str,
+    disabled: bool,
+) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(yes_uuid)
+            .label(i18n::tr(locale, Msg::Yes))
+            .style(ButtonStyle::Danger)
+            .disabled(disabled),
+        CreateButton::new(no_uuid)
+            .label(i18n::tr(locale, Msg::No))
+            .style(ButtonStyle::Secondary)
+            .disabled(disabled),
+    ])
+}
+
+fn make_disabled_buttons(locale: Option<&str>) -> CreateActionRow {
+    make_uuid_buttons(locale, "yes_disabled", "no_disabled", true)
+}
+
+/// Asks whether to go ahead with what `prompt` describes. Returns the button
+/// press that confirmed it, so the caller can follow up on it, or `None` if
+/// "no" was pressed or nobody answered in time. Either way the buttons are
+/// disabled afterwards.
+pub async fn confirm(
+    ctx: Context<'_>,
+    prompt: String,
+) -> Result<Option<ComponentInteraction>, SlimeError> {
+    let locale = i18n::locale(ctx).await?;
+    let locale = locale.as_deref();
+    let id = ctx.id();
+    let yes_uuid: String = format!("{id}-yes");
+    let no_uuid: String = format!("{id}-no");
+
+    let buttons = make_uuid_buttons(locale, &yes_uuid, &no_uuid, false);
+    let reply = CreateReply::default()
+        .content(&prompt)
+        .components(vec![buttons])
+        .ephemeral(true);
+
+    let handle = ctx.send(reply).await?;
+
+    let Some(interactions) = ComponentInteractionCollector::new(ctx.serenity_context())
+        .timeout(CONFIRM_TIMEOUT)
+        .custom_ids(vec![yes_uuid.clone(), no_uuid.clone()])
+        .await
+    else {
+        let timed_out = CreateReply::default()
+            .content(format!("{prompt}\n\n{}", i18n::tr(locale, Msg::NoAnswer)))
+            .components(vec![make_disabled_buttons(locale)]);
+        handle.edit(ctx, timed_out).await?;
+        return Ok(None);
+    };
+
+    let message = CreateInteractionResponseMessage::new()
+        .components(vec![make_disabled_buttons(locale)])
+        .content(&interactions.message.content);
+
+    let disable_buttons = CreateInteractionResponse::UpdateMessage(message);
+    interactions
+        .create_response(ctx, disable_buttons)
+        .await
+        .inspect_err(|e| error!("{}", e))?;
+
+    if interactions.data.custom_id != yes_uuid {
+        let followup = CreateInteractionResponseFollowup::new()
+            .content(i18n::tr(locale, Msg::Declined))
+            .ephemeral(true);
+        interactions
+            .create_followup(ctx, followup)
+            .await
+            .inspect_err(|e| error!("{}", e))?;
+        return Ok(None);
+    }
+
+    Ok(Some(interactions))
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{db, replies::reply, Context, Data, SlimeError};
+
+/// How often a counter channel is renamed at most. Discord only allows two
+/// renames of a channel every ten minutes.
+const REFRESH_MINUTES: f64 = 10.0;
+
+/// What a counter channel counts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Counter {
+    Members,
+    Online,
+    Boosts,
+}
+
+impl Counter {
+    const ALL: [Counter; 3] = [Counter::Members, Counter::Online, Counter::Boosts];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Counter::Members => "members",
+            Counter::Online => "online",
+            Counter::Boosts => "boosts",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        Counter::ALL
+            .into_iter()
+            .find(|counter| counter.as_str() == s)
+    }
+
+    /// What the counter's channel is called now. Discord doesn't always
+    /// return the counts, in which case they're shown as "?".
+    fn name(self, guild: &PartialGuild) -> String {
+        let count = |count: Option<u64>| count.map_or("?".to_string(), |count| count.to_string());
+        match self {
+            Counter::Members => format!("Members: {}", count(guild.approximate_member_count)),
+            Counter::Online => format!("Online: {}", count(guild.approximate_presence_count)),
+            Counter::Boosts => format!(
+                "Boosts: {} (level {})",
+                count(guild.premium_subscription_count),
+                u8::from(guild.premium_tier)
+            ),
+        }
+    }
+}
+
+/// A counter channel from the `stats_channels` table.
+#[derive(sqlx::FromRow)]
+struct CounterChannel {
+    guild_id: i64,
+    counter: String,
+    channel_id: i64,
+    shown_name: Option<String>,
+}
+
+/// Renames the counter channels of every guild whose counts weren't
+/// refreshed for `REFRESH_MINUTES`, the scheduler calls this. Channels are
+/// only renamed if their count changed.
+pub async fn refresh_due(http: &Http, data: &Data) {
+    let due: Vec<CounterChannel> = match sqlx::query_as(
+        "UPDATE stats_channels SET refreshed_at = now()
+        WHERE refreshed_at <= now() - make_interval(mins => $1)
+        RETURNING guild_id, counter, channel_id, shown_name",
+    )
+    .bind(REFRESH_MINUTES)
+    .fetch_all(&data.pool)
+    .await
+    {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to load due counter channels: {}", e);
+            return;
+        }
+    };
+
+    let mut guilds: HashMap<i64, Vec<CounterChannel>> = HashMap::new();
+    for channel in due {
+        guilds.entry(channel.guild_id).or_default().push(channel);
+    }
+    for (guild_id, channels) in guilds {
+        let guild_id = GuildId::new(guild_id as u64);
+        if let Err(e) = refresh(http, &data.pool, guild_id, &channels).await {
+            error!(
+                "failed to refresh the counter channels of guild {}: {}",
+                guild_id, e
+            );
+        }
+    }
+}
+
+async fn refresh(
+    http: &Http,
+    pool: &PgPool,
+    guild_id: GuildId,
+    channels: &[CounterChannel],
+) -> Result<(), SlimeError> {
+    let guild = guild_id.to_partial_guild_with_counts(http).await?;
+    for channel in channels {
+        let Some(counter) = Counter::from_str(&channel.counter) else {
+            continue;
+        };
+        let name = counter.name(&guild);
+        if channel.shown_name.as_deref() == Some(name.as_str()) {
+            continue;
+        }
+
+        let channel_id = ChannelId::new(channel.channel_id as u64);
+        let renamed = channel_id
+            .edit(
+                http,
+                EditChannel::new()
+                    .name(&name)
+                    .audit_log_reason("Stats channel"),
+            )
+            .await;
+        match renamed {
+            Ok(_) => {
+                sqlx::query(
+                    "UPDATE stats_channels SET shown_name = $3 WHERE guild_id = $1 AND counter = $2",
+                )
+                .bind(i64::from(guild_id))
+                .bind(&channel.counter)
+                .bind(&name)
+                .execute(pool)
+                .await?;
+            }
+            Err(e) => warn!(
+                "failed to rename the {} channel of guild {}: {}",
+                channel.counter, guild_id, e
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Manages voice channels whose names show server stats
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("setup", "remove")
+)]
+pub async fn channels(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Creates locked voice channels showing the member, online and boost counts
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn setup(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let set_up: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM stats_channels WHERE guild_id = $1)")
+            .bind(i64::from(guild_id))
+            .fetch_one(&data.pool)
+            .await?;
+    if set_up {
+        let content = "The stats channels are set up already, `/stats channels remove` them first.";
+        return reply(ctx, content.to_string()).await;
+    }
+    ctx.defer_ephemeral().await?;
+
+    // Nobody can join the channels, they're only there to be read.
+    let locked = PermissionOverwrite {
+        allow: Permissions::VIEW_CHANNEL,
+        deny: Permissions::CONNECT,
+        kind: PermissionOverwriteType::Role(guild_id.everyone_role()),
+    };
+    let guild = guild_id.to_partial_guild_with_counts(ctx).await?;
+    let category = CreateChannel::new("Server stats")
+        .kind(ChannelType::Category)
+        .position(0)
+        .permissions([locked.clone()])
+        .audit_log_reason("Stats channels");
+    let category = guild_id.create_channel(ctx, category).await?;
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    for counter in Counter::ALL {
+        let name = counter.name(&guild);
+        let channel = CreateChannel::new(&name)
+            .kind(ChannelType::Voice)
+            .category(category.id)
+            .permissions([locked.clone()])
+            .audit_log_reason("Stats channels");
+        let channel = guild_id.create_channel(ctx, channel).await?;
+        sqlx::query(
+            "INSERT INTO stats_channels (guild_id, counter, channel_id, category_id, shown_name)
+            VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(i64::from(guild_id))
+        .bind(counter.as_str())
+        .bind(i64::from(channel.id))
+        .bind(i64::from(category.id))
+        .bind(&name)
+        .execute(&data.pool)
+        .await?;
+    }
+
+    reply(
+        ctx,
+        format!(
+            "The stats channels are up, their counts are refreshed every {REFRESH_MINUTES} minutes."
+        ),
+    )
+    .await
+}
+
+/// Deletes the stats channels
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn remove(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed: Vec<(i64, Option<i64>)> = sqlx::query_as(
+        "DELETE FROM stats_channels WHERE guild_id = $1 RETURNING channel_id, category_id",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if removed.is_empty() {
+        return reply(ctx, "There are no stats channels.".to_string()).await;
+    }
+
+    let mut channel_ids: Vec<i64> = removed.iter().map(|&(channel_id, _)| channel_id).collect();
+    let mut categories: Vec<i64> = removed
+        .iter()
+        .filter_map(|&(_, category)| category)
+        .collect();
+    categories.dedup();
+    // The category goes last, so its channels don't end up without one.
+    channel_ids.extend(categories);
+    for channel_id in channel_ids {
+        let channel_id = ChannelId::new(channel_id as u64);
+        match channel_id.delete(ctx).await {
+            Ok(_) => {}
+            Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    reply(ctx, "The stats channels are gone.".to_string()).await
+}
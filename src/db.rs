@@ -0,0 +1,101 @@
+use poise::serenity_prelude::GuildId;
+use sqlx::{migrate::MigrateError, PgPool};
+
+/// Brings the database schema up to date with the `migrations` directory.
+pub async fn migrate(pool: &PgPool) -> Result<(), MigrateError> {
+    sqlx::migrate!().run(pool).await
+}
+
+/// Makes sure the guild has a row in `guilds`. Returns whether it didn't
+/// have one before.
+pub async fn ensure_guild(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<bool> {
+    let inserted = sqlx::query("INSERT INTO guilds (guild_id) VALUES ($1) ON CONFLICT DO NOTHING")
+        .bind(i64::from(guild_id))
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+    Ok(inserted > 0)
+}
+
+/// Tables with a `guild_id` column, which `forget_guild` cleans up.
+const GUILD_TABLES: [&str; 64] = [
+    "purge_jobs",
+    "retention_policies",
+    "purge_exemptions",
+    "purge_audit",
+    "guild_settings",
+    "scheduled_jobs",
+    "archived_messages",
+    "channels",
+    "mod_roles",
+    "command_invocations",
+    "warnings",
+    "mod_cases",
+    "timeouts",
+    "temp_bans",
+    "message_cache",
+    "message_log_ignored",
+    "banned_terms",
+    "raid_modes",
+    "lockdowns",
+    "slowmodes",
+    "verification_config",
+    "reports",
+    "escalation_rules",
+    "user_notes",
+    "reminders",
+    "scheduled_messages",
+    "polls",
+    "poll_votes",
+    "role_menus",
+    "autoroles",
+    "pending_autoroles",
+    "welcome_messages",
+    "starboard_entries",
+    "sticky_messages",
+    "event_roles",
+    "event_reminders",
+    "recurring_events",
+    "event_rsvps",
+    "tags",
+    "suggestions",
+    "suggestion_votes",
+    "tickets",
+    "giveaways",
+    "giveaway_entries",
+    "member_xp",
+    "level_roles",
+    "birthdays",
+    "channel_activity",
+    "channel_posters",
+    "emoji_usage",
+    "stats_channels",
+    "pin_archive",
+    "bookmarks",
+    "afk",
+    "thread_policies",
+    "quotes",
+    "todos",
+    "feed_subscriptions",
+    "github_watches",
+    "twitch_watches",
+    "youtube_watches",
+    "youtube_videos",
+    "webhook_relays",
+    "guilds",
+];
+
+/// Deletes everything stored about the guild.
+pub async fn forget_guild(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<()> {
+    let mut transaction = pool.begin().await?;
+    for table in GUILD_TABLES {
+        sqlx::query(&format!("DELETE FROM {table} WHERE guild_id = $1"))
+            .bind(i64::from(guild_id))
+            .execute(&mut *transaction)
+            .await?;
+    }
+    transaction.commit().await?;
+
+    Ok(())
+}
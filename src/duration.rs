@@ -0,0 +1,103 @@
+use std::{fmt, str::FromStr};
+
+use chrono::Duration;
+use thiserror::Error;
+
+/// Anything longer than this can't be subtracted from "now" anyway.
+const MAX_SECONDS: i64 = 100 * 365 * 86_400;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ParseDurationError {
+    #[error("a duration can't be empty")]
+    Empty,
+    #[error("expected a number before '{0}'")]
+    MissingNumber(char),
+    #[error("'{0}' is missing a unit, try something like '{0}d'")]
+    MissingUnit(String),
+    #[error("'{0}' is not a unit, expected one of s, m, h, d or w")]
+    UnknownUnit(char),
+    #[error("that duration is too long")]
+    Overflow,
+}
+
+/// A duration written the way people type them in chat, e.g. `3d`, `12h`,
+/// `2w` or combinations like `1d12h`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HumanDuration(pub Duration);
+
+impl HumanDuration {
+    pub fn as_duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = ParseDurationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseDurationError::Empty);
+        }
+
+        let mut seconds: i64 = 0;
+        let mut number = String::new();
+
+        for c in s.chars().filter(|c| !c.is_whitespace()) {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+
+            if number.is_empty() {
+                return Err(ParseDurationError::MissingNumber(c));
+            }
+            let amount: i64 = number.parse().map_err(|_| ParseDurationError::Overflow)?;
+            number.clear();
+
+            let unit_seconds = match c.to_ascii_lowercase() {
+                's' => 1,
+                'm' => 60,
+                'h' => 3_600,
+                'd' => 86_400,
+                'w' => 604_800,
+                other => return Err(ParseDurationError::UnknownUnit(other)),
+            };
+            seconds = amount
+                .checked_mul(unit_seconds)
+                .and_then(|part| seconds.checked_add(part))
+                .filter(|&seconds| seconds <= MAX_SECONDS)
+                .ok_or(ParseDurationError::Overflow)?;
+        }
+
+        if !number.is_empty() {
+            return Err(ParseDurationError::MissingUnit(number));
+        }
+
+        Ok(HumanDuration(Duration::seconds(seconds)))
+    }
+}
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut remaining = self.0.num_seconds();
+        if remaining == 0 {
+            return write!(f, "0s");
+        }
+
+        for (unit, seconds) in [
+            ('w', 604_800),
+            ('d', 86_400),
+            ('h', 3_600),
+            ('m', 60),
+            ('s', 1),
+        ] {
+            if remaining >= seconds {
+                write!(f, "{}{unit}", remaining / seconds)?;
+                remaining %= seconds;
+            }
+        }
+
+        Ok(())
+    }
+}
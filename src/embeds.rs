@@ -0,0 +1,199 @@
+use poise::{serenity_prelude::*, Modal};
+
+use crate::{replies::reply, ApplicationContext, Context, SlimeError};
+
+/// How many fields Discord allows in an embed.
+const MAX_FIELDS: usize = 25;
+
+/// How long Discord allows an embed field's name and value to be.
+const MAX_FIELD_NAME: usize = 256;
+const MAX_FIELD_VALUE: usize = 1024;
+
+#[derive(Modal)]
+#[name = "Embed"]
+struct EmbedModal {
+    #[name = "Title"]
+    #[max_length = 256]
+    title: Option<String>,
+    #[name = "Description"]
+    #[paragraph]
+    #[max_length = 4000]
+    description: Option<String>,
+    #[name = "Colour"]
+    #[placeholder = "#5865F2"]
+    #[max_length = 7]
+    colour: Option<String>,
+    #[name = "Fields"]
+    #[placeholder = "One per line, name | value"]
+    #[paragraph]
+    #[max_length = 4000]
+    fields: Option<String>,
+}
+
+impl EmbedModal {
+    /// What the modal shows to start with when editing `embed`.
+    fn from_embed(embed: &Embed) -> Self {
+        let fields = embed
+            .fields
+            .iter()
+            .map(|field| format!("{} | {}", field.name, field.value))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self {
+            title: embed.title.clone(),
+            description: embed.description.clone(),
+            colour: embed.colour.map(|colour| format!("#{}", colour.hex())),
+            fields: Some(fields).filter(|fields| !fields.is_empty()),
+        }
+    }
+
+    /// The embed the modal describes, or what's wrong with it.
+    fn embed(&self) -> Result<CreateEmbed, String> {
+        let text = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let mut embed = CreateEmbed::new();
+        let title = text(&self.title);
+        let description = text(&self.description);
+        if let Some(title) = &title {
+            embed = embed.title(title);
+        }
+        if let Some(description) = &description {
+            embed = embed.description(description);
+        }
+        if let Some(colour) = text(&self.colour) {
+            embed = embed.colour(parse_colour(&colour)?);
+        }
+
+        let fields = text(&self.fields).map_or(Ok(Vec::new()), |fields| parse_fields(&fields))?;
+        if title.is_none() && description.is_none() && fields.is_empty() {
+            return Err("An embed needs a title, a description or a field.".to_string());
+        }
+        Ok(embed.fields(fields))
+    }
+}
+
+/// Parses a colour written like `#5865F2`, with or without the `#`.
+fn parse_colour(colour: &str) -> Result<Colour, String> {
+    let hex = colour.strip_prefix('#').unwrap_or(colour);
+    if hex.len() != 6 {
+        return Err(format!("`{colour}` is not a colour like #5865F2."));
+    }
+    u32::from_str_radix(hex, 16)
+        .map(Colour::new)
+        .map_err(|_| format!("`{colour}` is not a colour like #5865F2."))
+}
+
+/// Parses embed fields written one per line as `name | value`.
+fn parse_fields(fields: &str) -> Result<Vec<(String, String, bool)>, String> {
+    let fields: Vec<(String, String, bool)> = fields
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (name, value) = line
+                .split_once('|')
+                .map(|(name, value)| (name.trim(), value.trim()))
+                .filter(|(name, value)| !name.is_empty() && !value.is_empty())
+                .ok_or_else(|| format!("`{line}` is not a field like `name | value`."))?;
+            if name.chars().count() > MAX_FIELD_NAME || value.chars().count() > MAX_FIELD_VALUE {
+                return Err(format!(
+                    "Field names can be {MAX_FIELD_NAME} characters long and values \
+                    {MAX_FIELD_VALUE}, `{name}` is longer."
+                ));
+            }
+            Ok((name.to_string(), value.to_string(), false))
+        })
+        .collect::<Result<_, _>>()?;
+    if fields.len() > MAX_FIELDS {
+        return Err(format!("An embed can have {MAX_FIELDS} fields at most."));
+    }
+    Ok(fields)
+}
+
+/// Builds embeds and posts them
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("create", "edit")
+)]
+pub async fn embed(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Writes an embed and posts it in a channel
+#[poise::command(slash_command, guild_only)]
+async fn create(
+    ctx: ApplicationContext<'_>,
+    #[description = "Channel to post in"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let Some(modal) = EmbedModal::execute(ctx).await? else {
+        return Ok(());
+    };
+    let embed = match modal.embed() {
+        Ok(embed) => embed,
+        Err(problem) => return reply(ctx.into(), problem).await,
+    };
+
+    let message = channel
+        .send_message(ctx.http(), CreateMessage::new().embed(embed))
+        .await?;
+    let content = format!(
+        "Posted it, {}. Change it with `/embed edit`.",
+        message.link()
+    );
+    reply(ctx.into(), content).await
+}
+
+/// Changes an embed posted with /embed create
+#[poise::command(slash_command, guild_only)]
+async fn edit(
+    ctx: ApplicationContext<'_>,
+    #[description = "Link to the message with the embed"] message: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let Some((link_guild_id, channel_id, message_id)) = parse_message_url(message.trim()) else {
+        return reply(ctx.into(), format!("`{message}` is not a message link.")).await;
+    };
+    if link_guild_id != guild_id {
+        let content = "That message is in another server.".to_string();
+        return reply(ctx.into(), content).await;
+    }
+    let mut message = match channel_id.message(ctx.http(), message_id).await {
+        Ok(message) => message,
+        Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {
+            return reply(ctx.into(), "That message doesn't exist.".to_string()).await;
+        }
+        Err(e) => return Err(e.into()),
+    };
+    // That's how /embed create posts them, other messages of the bot's are
+    // better left alone.
+    if message.author.id != ctx.framework().bot_id
+        || !message.content.is_empty()
+        || message.embeds.len() != 1
+    {
+        let content = "Only embeds posted with `/embed create` can be edited.".to_string();
+        return reply(ctx.into(), content).await;
+    }
+
+    let defaults = EmbedModal::from_embed(&message.embeds[0]);
+    let Some(modal) = EmbedModal::execute_with_defaults(ctx, defaults).await? else {
+        return Ok(());
+    };
+    let embed = match modal.embed() {
+        Ok(embed) => embed,
+        Err(problem) => return reply(ctx.into(), problem).await,
+    };
+
+    message
+        .edit(ctx.http(), EditMessage::new().embed(embed))
+        .await?;
+    reply(ctx.into(), format!("Edited {}.", message.link())).await
+}
@@ -0,0 +1,95 @@
+use poise::{
+    serenity_prelude::{
+        Colour, CreateEmbed, CreateMessage, Error, HttpError, Mentionable, StatusCode,
+    },
+    CreateReply, FrameworkError,
+};
+use tracing::error;
+
+use crate::{
+    i18n::{self, Msg},
+    settings::ChannelPurpose,
+    usage, Context, Data, SlimeError,
+};
+
+/// Discord's error codes for requests the bot isn't allowed to make.
+const MISSING_ACCESS: isize = 50001;
+const MISSING_PERMISSIONS: isize = 50013;
+
+/// Replaces poise's default error output for failed commands with a reply
+/// people can act on, and leaves everything else to poise.
+pub async fn on_error(error: FrameworkError<'_, Data, SlimeError>) {
+    match error {
+        FrameworkError::Command { error, ctx, .. } => command_failed(ctx, &error).await,
+        error => {
+            if let Err(e) = poise::builtins::on_error(error).await {
+                error!("failed to handle a framework error: {}", e);
+            }
+        }
+    }
+}
+
+/// What went wrong, in words for whoever ran the command.
+fn explain(error: &SlimeError, locale: Option<&str>, channel: &str) -> String {
+    let msg = match error {
+        SlimeError::SerenityError(Error::Http(HttpError::UnsuccessfulRequest(response))) => {
+            match response.error.code {
+                MISSING_ACCESS => Msg::MissingAccess,
+                MISSING_PERMISSIONS => Msg::MissingPermissions,
+                _ if response.status_code == StatusCode::TOO_MANY_REQUESTS => Msg::RateLimited,
+                _ => Msg::DiscordFailed,
+            }
+        }
+        SlimeError::SerenityError(_) => Msg::DiscordFailed,
+        SlimeError::DatabaseError(_) => Msg::DatabaseFailed,
+    };
+    i18n::tr_args(locale, msg, &[("channel", channel)])
+}
+
+async fn command_failed(ctx: Context<'_>, error: &SlimeError) {
+    let command = ctx.command().qualified_name.clone();
+    error!("/{} failed: {}", command, error);
+    usage::record(ctx, false).await;
+
+    let locale = match i18n::locale(ctx).await {
+        Ok(locale) => locale,
+        Err(e) => {
+            error!("failed to look up the locale: {}", e);
+            None
+        }
+    };
+    let channel = ctx.channel_id().mention().to_string();
+    let reply = CreateReply::default()
+        .content(explain(error, locale.as_deref(), &channel))
+        .ephemeral(true);
+    if let Err(e) = ctx.send(reply).await {
+        error!("failed to report the error of /{}: {}", command, e);
+    }
+
+    let Some(guild_id) = ctx.guild_id() else {
+        return;
+    };
+    let error_channel = match ctx.data().settings.get(guild_id).await {
+        Ok(settings) => settings.channel(ChannelPurpose::Errors),
+        Err(e) => {
+            error!("failed to look up the error channel: {}", e);
+            return;
+        }
+    };
+    let Some(error_channel) = error_channel else {
+        return;
+    };
+
+    let embed = CreateEmbed::new()
+        .title(format!("/{command} failed"))
+        .colour(Colour::RED)
+        .field("Run by", ctx.author().mention().to_string(), true)
+        .field("Channel", channel, true)
+        .field("Error", error.to_string(), false);
+    if let Err(e) = error_channel
+        .send_message(ctx, CreateMessage::new().embed(embed))
+        .await
+    {
+        error!("failed to post the error of /{}: {}", command, e);
+    }
+}
@@ -0,0 +1,296 @@
+use chrono::Duration;
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+
+use crate::{
+    cases::{self, Action, NewCase},
+    db,
+    duration::HumanDuration,
+    replies::reply,
+    timeouts, Context, Data, SlimeError,
+};
+
+/// The most escalation rules a guild can have.
+const MAX_RULES: usize = 10;
+
+/// What an escalation rule does to a member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Punishment {
+    Timeout,
+    Kick,
+    Ban,
+}
+
+impl Punishment {
+    fn as_str(self) -> &'static str {
+        match self {
+            Punishment::Timeout => "timeout",
+            Punishment::Kick => "kick",
+            Punishment::Ban => "ban",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "timeout" => Some(Punishment::Timeout),
+            "kick" => Some(Punishment::Kick),
+            "ban" => Some(Punishment::Ban),
+            _ => None,
+        }
+    }
+}
+
+/// "Members with `warnings` warnings within `window_days` days get
+/// `punishment`." Only timeouts have a `duration`.
+pub struct Rule {
+    warnings: i32,
+    window_days: i32,
+    punishment: Punishment,
+    duration: Option<HumanDuration>,
+}
+
+impl Rule {
+    /// What sets the rule off, e.g. "3 warnings within 30 days".
+    pub fn trigger(&self) -> String {
+        format!(
+            "{} warnings within {} days",
+            self.warnings, self.window_days
+        )
+    }
+
+    /// What the rule does, e.g. "a 1h timeout".
+    pub fn action(&self) -> String {
+        match (self.punishment, self.duration) {
+            (Punishment::Timeout, Some(duration)) => format!("a {duration} timeout"),
+            (Punishment::Timeout, None) => "a timeout".to_string(),
+            (Punishment::Kick, _) => "a kick".to_string(),
+            (Punishment::Ban, _) => "a ban".to_string(),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct RuleRow {
+    warnings: i32,
+    window_days: i32,
+    action: String,
+    duration_seconds: Option<i64>,
+}
+
+async fn guild_rules(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<Vec<Rule>> {
+    let rows: Vec<RuleRow> = sqlx::query_as(
+        "SELECT warnings, window_days, action, duration_seconds FROM escalation_rules
+        WHERE guild_id = $1 ORDER BY warnings",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(Rule {
+                warnings: row.warnings,
+                window_days: row.window_days,
+                punishment: Punishment::from_str(&row.action)?,
+                duration: row
+                    .duration_seconds
+                    .map(|seconds| HumanDuration(Duration::seconds(seconds))),
+            })
+        })
+        .collect())
+}
+
+/// The rule the latest warning of `user_id` sets off, if any. That's the
+/// strictest rule whose number of warnings they have within its window.
+pub async fn triggered(
+    pool: &PgPool,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> sqlx::Result<Option<Rule>> {
+    for rule in guild_rules(pool, guild_id).await?.into_iter().rev() {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT count(*) FROM warnings WHERE guild_id = $1 AND user_id = $2
+            AND created_at > now() - make_interval(days => $3)",
+        )
+        .bind(i64::from(guild_id))
+        .bind(i64::from(user_id))
+        .bind(rule.window_days)
+        .fetch_one(pool)
+        .await?;
+        if count >= i64::from(rule.warnings) {
+            return Ok(Some(rule));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Gives `user_id` what `rule` says and files a case for it, pointing back
+/// to `warn_case`, the warning that set it off. Returns the case number.
+pub async fn apply(
+    http: &Http,
+    data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+    rule: &Rule,
+    warn_case: i64,
+) -> Result<i64, SlimeError> {
+    let reason = format!("{} (escalation of case {warn_case})", rule.trigger());
+    let action = match rule.punishment {
+        Punishment::Timeout => {
+            // Rules can't be added without a duration, this is only a fallback.
+            let duration = rule
+                .duration
+                .map_or(Duration::hours(1), |duration| duration.as_duration());
+            let (case_id, _) =
+                timeouts::apply(http, &data.pool, guild_id, user_id, duration, None, &reason)
+                    .await?;
+            return Ok(case_id);
+        }
+        Punishment::Kick => {
+            guild_id.kick_with_reason(http, user_id, &reason).await?;
+            Action::Kick
+        }
+        Punishment::Ban => {
+            guild_id.ban_with_reason(http, user_id, 0, &reason).await?;
+            Action::Ban
+        }
+    };
+
+    let case = NewCase {
+        action,
+        user_id: Some(user_id),
+        channel_id: None,
+        moderator_id: None,
+        reason: Some(&reason),
+        details: None,
+    };
+    Ok(cases::file(&data.pool, guild_id, case).await?)
+}
+
+/// Manages what happens to members who keep getting warned
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("add", "remove", "list")
+)]
+pub async fn escalation(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Punishes members who reach a number of warnings, replacing any rule for that number
+#[poise::command(slash_command, guild_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Number of warnings that sets the rule off"]
+    #[min = 2]
+    #[max = 50]
+    warnings: i32,
+    #[description = "Only count warnings from this many days back"]
+    #[min = 1]
+    #[max = 365]
+    within_days: i32,
+    #[description = "What happens to the member"] punishment: Punishment,
+    #[description = "How long timeouts last, e.g. 1h"] duration: Option<HumanDuration>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let duration = match (punishment, duration) {
+        (Punishment::Timeout, Some(duration))
+            if duration.as_duration() > Duration::zero()
+                && duration.as_duration() <= Duration::days(timeouts::MAX_TIMEOUT_DAYS) =>
+        {
+            Some(duration)
+        }
+        (Punishment::Timeout, _) => {
+            let content = format!(
+                "Timeouts need a duration of at most {} days.",
+                timeouts::MAX_TIMEOUT_DAYS
+            );
+            return reply(ctx, content).await;
+        }
+        _ => None,
+    };
+    let rules = guild_rules(&data.pool, guild_id).await?;
+    if rules.len() >= MAX_RULES && !rules.iter().any(|rule| rule.warnings == warnings) {
+        let content = format!("There are {MAX_RULES} rules already, remove one first.");
+        return reply(ctx, content).await;
+    }
+
+    let rule = Rule {
+        warnings,
+        window_days: within_days,
+        punishment,
+        duration,
+    };
+    db::ensure_guild(&data.pool, guild_id).await?;
+    sqlx::query(
+        "INSERT INTO escalation_rules (guild_id, warnings, window_days, action, duration_seconds)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (guild_id, warnings) DO UPDATE
+        SET window_days = EXCLUDED.window_days, action = EXCLUDED.action,
+        duration_seconds = EXCLUDED.duration_seconds",
+    )
+    .bind(i64::from(guild_id))
+    .bind(rule.warnings)
+    .bind(rule.window_days)
+    .bind(rule.punishment.as_str())
+    .bind(
+        rule.duration
+            .map(|duration| duration.as_duration().num_seconds()),
+    )
+    .execute(&data.pool)
+    .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Members with {} get {} from now on.",
+            rule.trigger(),
+            rule.action()
+        ),
+    )
+    .await
+}
+
+/// Removes the rule for a number of warnings
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Number of warnings of the rule, see /escalation list"] warnings: i32,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = sqlx::query("DELETE FROM escalation_rules WHERE guild_id = $1 AND warnings = $2")
+        .bind(i64::from(guild_id))
+        .bind(warnings)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("{warnings} warnings don't escalate anymore.")
+    } else {
+        format!("There is no rule for {warnings} warnings.")
+    };
+    reply(ctx, content).await
+}
+
+/// Lists what happens to members who keep getting warned
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let rules = guild_rules(&ctx.data().pool, guild_id).await?;
+
+    let content = if rules.is_empty() {
+        "Warnings never escalate.".to_string()
+    } else {
+        rules
+            .iter()
+            .map(|rule| format!("- {}: {}", rule.trigger(), rule.action()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    reply(ctx, content).await
+}
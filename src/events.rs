@@ -0,0 +1,710 @@
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use poise::{
+    serenity_prelude::{self as serenity, *},
+    CreateReply,
+};
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{
+    announcements::Repeat,
+    audit, db,
+    duration::HumanDuration,
+    ics::{self, CalendarEvent},
+    localtime, msglog,
+    replies::reply,
+    schedule::RunAt,
+    settings::{ChannelPurpose, GuildSettings},
+    Context, Data, SlimeError,
+};
+
+/// How many recurring events a guild can have at once.
+const MAX_RECURRING_EVENTS: i64 = 25;
+
+/// How many recurring events one page of `/event recurring list` shows.
+const PAGE_LEN: usize = 10;
+
+/// How many members `/event attendance` lists at most.
+const MAX_ATTENDEES: i64 = 50;
+
+/// How long events last unless they're given a length.
+const DEFAULT_EVENT_HOURS: i64 = 2;
+
+/// "Remind of `event_id` `lead_seconds` before it starts", from the
+/// `event_reminders` table.
+#[derive(sqlx::FromRow)]
+struct EventReminder {
+    guild_id: i64,
+    event_id: i64,
+    lead_seconds: i64,
+}
+
+/// "Hold `name` every `every_seconds`", from the `recurring_events` table.
+/// `starts_at` is when the latest occurrence starts, the table keeps the
+/// Discord event for it too.
+#[derive(sqlx::FromRow)]
+struct RecurringEvent {
+    recurring_id: i64,
+    guild_id: i64,
+    name: String,
+    description: Option<String>,
+    channel_id: Option<i64>,
+    location: Option<String>,
+    starts_at: DateTime<Utc>,
+    duration_seconds: i64,
+    every_seconds: i64,
+}
+
+impl RecurringEvent {
+    fn line(&self, timezone: Option<FixedOffset>) -> String {
+        let place = match (&self.location, self.channel_id) {
+            (Some(location), _) => location.clone(),
+            (None, Some(channel_id)) => ChannelId::new(channel_id as u64).mention().to_string(),
+            (None, None) => "somewhere".to_string(),
+        };
+        format!(
+            "`{}` **{}** every {}, next at {} in {place}",
+            self.recurring_id,
+            self.name,
+            HumanDuration(Duration::seconds(self.every_seconds)),
+            localtime::format_datetime(self.starts_at, timezone)
+        )
+    }
+
+    /// Creates the Discord event for the occurrence starting at `starts_at`.
+    async fn create_occurrence(&self, http: &Http) -> Result<ScheduledEvent, SlimeError> {
+        let ends_at = self.starts_at + Duration::seconds(self.duration_seconds);
+        let start = localtime::to_timestamp(self.starts_at);
+        let mut event = match (&self.location, self.channel_id) {
+            (Some(location), _) => {
+                CreateScheduledEvent::new(ScheduledEventType::External, &self.name, start)
+                    .location(location)
+            }
+            (None, channel_id) => {
+                let channel_id = ChannelId::new(channel_id.unwrap_or_default() as u64);
+                let kind = match channel_id.to_channel(http).await?.guild() {
+                    Some(channel) if channel.kind == ChannelType::Stage => {
+                        ScheduledEventType::StageInstance
+                    }
+                    _ => ScheduledEventType::Voice,
+                };
+                CreateScheduledEvent::new(kind, &self.name, start).channel_id(channel_id)
+            }
+        };
+        event = event
+            .end_time(localtime::to_timestamp(ends_at))
+            .audit_log_reason("Recurring event");
+        if let Some(description) = &self.description {
+            event = event.description(description);
+        }
+        let guild_id = GuildId::new(self.guild_id as u64);
+        Ok(guild_id.create_scheduled_event(http, event).await?)
+    }
+}
+
+fn link(guild_id: GuildId, event_id: ScheduledEventId) -> String {
+    format!("https://discord.com/events/{guild_id}/{event_id}")
+}
+
+/// Where `event` takes place, a channel or whatever location it was given.
+fn location(event: &ScheduledEvent) -> String {
+    let location = event
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.location.clone());
+    match (location, event.channel_id) {
+        (Some(location), _) => location,
+        (None, Some(channel_id)) => channel_id.mention().to_string(),
+        (None, None) => "somewhere".to_string(),
+    }
+}
+
+/// Replaces the reminders of `event` with one per lead time of the guild,
+/// leaving out those whose time has passed. Events that are no longer
+/// scheduled lose their reminders.
+async fn plan_reminders(
+    pool: &PgPool,
+    settings: &GuildSettings,
+    event: &ScheduledEvent,
+) -> sqlx::Result<()> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query("DELETE FROM event_reminders WHERE event_id = $1")
+        .bind(i64::from(event.id))
+        .execute(&mut *transaction)
+        .await?;
+
+    if event.status == ScheduledEventStatus::Scheduled {
+        let starts_at = localtime::from_timestamp(event.start_time);
+        let now = Utc::now();
+        for lead in &settings.event_reminders {
+            let remind_at = starts_at - lead.as_duration();
+            if remind_at <= now {
+                continue;
+            }
+            sqlx::query(
+                "INSERT INTO event_reminders (guild_id, event_id, lead_seconds, remind_at)
+                VALUES ($1, $2, $3, $4)",
+            )
+            .bind(i64::from(event.guild_id))
+            .bind(i64::from(event.id))
+            .bind(lead.as_duration().num_seconds())
+            .bind(remind_at)
+            .execute(&mut *transaction)
+            .await?;
+        }
+    }
+
+    transaction.commit().await
+}
+
+/// Announces a new server event in the status channel and plans its
+/// reminders.
+pub async fn event_created(
+    ctx: &serenity::Context,
+    data: &Data,
+    event: &ScheduledEvent,
+) -> Result<(), SlimeError> {
+    let settings = data.settings.get(event.guild_id).await?;
+    plan_reminders(&data.pool, &settings, event).await?;
+
+    let Some(channel) = settings.channel(ChannelPurpose::Status) else {
+        return Ok(());
+    };
+    let starts_at = localtime::from_timestamp(event.start_time);
+    let mut embed = CreateEmbed::new()
+        .title(format!("New event: {}", event.name))
+        .url(link(event.guild_id, event.id))
+        .colour(Colour::BLUE)
+        .field(
+            "Starts",
+            localtime::format_datetime(starts_at, settings.timezone),
+            true,
+        )
+        .field("Where", location(event), true);
+    if let Some(description) = &event.description {
+        embed = embed.description(description);
+    }
+    if let Err(e) = channel
+        .send_message(ctx, CreateMessage::new().embed(embed))
+        .await
+    {
+        warn!(
+            "failed to announce event {} in guild {}: {}",
+            event.id, event.guild_id, e
+        );
+    }
+
+    Ok(())
+}
+
+/// Forgets who was interested in an event that never happened, so it
+/// doesn't count towards their attendance.
+async fn forget_rsvps(pool: &PgPool, event_id: ScheduledEventId) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM event_rsvps WHERE event_id = $1")
+        .bind(i64::from(event_id))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Plans the reminders of a server event again, since its start may have
+/// moved or it may have started or been cancelled.
+pub async fn event_updated(data: &Data, event: &ScheduledEvent) -> Result<(), SlimeError> {
+    let settings = data.settings.get(event.guild_id).await?;
+    plan_reminders(&data.pool, &settings, event).await?;
+    if event.status == ScheduledEventStatus::Canceled {
+        forget_rsvps(&data.pool, event.id).await?;
+    }
+    Ok(())
+}
+
+/// Forgets the reminders and RSVPs of a server event that was deleted.
+pub async fn event_deleted(data: &Data, event: &ScheduledEvent) -> Result<(), SlimeError> {
+    sqlx::query("DELETE FROM event_reminders WHERE event_id = $1")
+        .bind(i64::from(event.id))
+        .execute(&data.pool)
+        .await?;
+    forget_rsvps(&data.pool, event.id).await?;
+    Ok(())
+}
+
+/// Remembers that a member is interested in a server event.
+pub async fn rsvp_added(
+    data: &Data,
+    rsvp: &GuildScheduledEventUserAddEvent,
+) -> Result<(), SlimeError> {
+    sqlx::query(
+        "INSERT INTO event_rsvps (guild_id, event_id, user_id) VALUES ($1, $2, $3)
+        ON CONFLICT (event_id, user_id) DO NOTHING",
+    )
+    .bind(i64::from(rsvp.guild_id))
+    .bind(i64::from(rsvp.scheduled_event_id))
+    .bind(i64::from(rsvp.user_id))
+    .execute(&data.pool)
+    .await?;
+    Ok(())
+}
+
+/// Forgets that a member was interested in a server event.
+pub async fn rsvp_removed(
+    data: &Data,
+    rsvp: &GuildScheduledEventUserRemoveEvent,
+) -> Result<(), SlimeError> {
+    sqlx::query("DELETE FROM event_rsvps WHERE event_id = $1 AND user_id = $2")
+        .bind(i64::from(rsvp.scheduled_event_id))
+        .bind(i64::from(rsvp.user_id))
+        .execute(&data.pool)
+        .await?;
+    Ok(())
+}
+
+/// Removes the reminders that are due and returns them, so each one is
+/// only ever sent once.
+async fn take_due(pool: &PgPool) -> sqlx::Result<Vec<EventReminder>> {
+    sqlx::query_as(
+        "DELETE FROM event_reminders WHERE remind_at <= now()
+        RETURNING guild_id, event_id, lead_seconds",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Pings the guild's event roles in its status channel about an event that
+/// is about to start, unless it was cancelled or started early.
+async fn send(http: &Http, data: &Data, reminder: &EventReminder) -> Result<(), SlimeError> {
+    let guild_id = GuildId::new(reminder.guild_id as u64);
+    let event_id = ScheduledEventId::new(reminder.event_id as u64);
+    let event = match guild_id.scheduled_event(http, event_id, false).await {
+        Ok(event) => event,
+        Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    if event.status != ScheduledEventStatus::Scheduled {
+        return Ok(());
+    }
+    let settings = data.settings.get(guild_id).await?;
+    let Some(channel) = settings.channel(ChannelPurpose::Status) else {
+        return Ok(());
+    };
+
+    let roles = settings
+        .event_roles
+        .iter()
+        .map(|role_id| role_id.mention().to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let content = format!(
+        "{roles} **{}** starts in {}, {}: {}",
+        event.name,
+        HumanDuration(Duration::seconds(reminder.lead_seconds)),
+        location(&event),
+        link(guild_id, event_id)
+    );
+    let message = CreateMessage::new()
+        .content(content.trim_start())
+        .allowed_mentions(CreateAllowedMentions::new().roles(settings.event_roles.clone()));
+    channel.send_message(http, message).await?;
+
+    Ok(())
+}
+
+/// Sends every event reminder that is due, the scheduler calls this.
+pub async fn remind_due(http: &Http, data: &Data) {
+    let due = match take_due(&data.pool).await {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to load due event reminders: {}", e);
+            return;
+        }
+    };
+
+    for reminder in due {
+        if let Err(e) = send(http, data, &reminder).await {
+            error!(
+                "failed to remind of event {} in guild {}: {}",
+                reminder.event_id, reminder.guild_id, e
+            );
+        }
+    }
+}
+
+/// Moves a recurring event on to its next occurrence, skipping the ones
+/// missed while the bot was down, and returns it the way it is now.
+async fn advance(pool: &PgPool, recurring: RecurringEvent) -> sqlx::Result<RecurringEvent> {
+    let missed = (Utc::now() - recurring.starts_at).num_seconds() / recurring.every_seconds;
+    let next = recurring.starts_at + Duration::seconds(recurring.every_seconds * (missed + 1));
+    sqlx::query(
+        "UPDATE recurring_events SET starts_at = $2, event_id = NULL WHERE recurring_id = $1",
+    )
+    .bind(recurring.recurring_id)
+    .bind(next)
+    .execute(pool)
+    .await?;
+
+    Ok(RecurringEvent {
+        starts_at: next,
+        ..recurring
+    })
+}
+
+/// Creates the next occurrence of every recurring event whose latest one
+/// ended, the scheduler calls this. Each is moved on before its Discord
+/// event is created, so an occurrence is only ever created once.
+pub async fn recur_due(http: &Http, data: &Data) {
+    let due: Vec<RecurringEvent> = match sqlx::query_as(
+        "SELECT recurring_id, guild_id, name, description, channel_id, location, starts_at,
+        duration_seconds, every_seconds
+        FROM recurring_events WHERE starts_at + duration_seconds * interval '1 second' <= now()",
+    )
+    .fetch_all(&data.pool)
+    .await
+    {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to load due recurring events: {}", e);
+            return;
+        }
+    };
+
+    for recurring in due {
+        let recurring_id = recurring.recurring_id;
+        let guild_id = recurring.guild_id;
+        let recurring = match advance(&data.pool, recurring).await {
+            Ok(recurring) => recurring,
+            Err(e) => {
+                error!("failed to move recurring event {} on: {}", recurring_id, e);
+                continue;
+            }
+        };
+        let created = match recurring.create_occurrence(http).await {
+            Ok(event) => {
+                sqlx::query("UPDATE recurring_events SET event_id = $2 WHERE recurring_id = $1")
+                    .bind(recurring_id)
+                    .bind(i64::from(event.id))
+                    .execute(&data.pool)
+                    .await
+                    .map(|_| ())
+                    .map_err(SlimeError::from)
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = created {
+            error!(
+                "failed to create the next occurrence of recurring event {} in guild {}: {}",
+                recurring_id, guild_id, e
+            );
+        }
+    }
+}
+
+/// Manages the server's events
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("recurring", "attendance", "export")
+)]
+pub async fn event(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Manages events that are held again and again
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("recurring_create", "recurring_list", "recurring_delete")
+)]
+async fn recurring(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Creates an event that's held every day or week, the next one is created after each ends
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command, guild_only, category = "Moderation", rename = "create")]
+async fn recurring_create(
+    ctx: Context<'_>,
+    #[description = "What the event is called"]
+    #[max_length = 100]
+    name: String,
+    #[description = "When the first one starts, e.g. 20:00 or 2024-05-01 20:00"] when: RunAt,
+    #[description = "Hold it every day or week"] repeat: Repeat,
+    #[description = "Voice or stage channel it's held in"]
+    #[channel_types("Voice", "Stage")]
+    channel: Option<GuildChannel>,
+    #[description = "Where it's held if not in a channel"]
+    #[max_length = 100]
+    location: Option<String>,
+    #[description = "How long it lasts, e.g. 90m (default 2h)"] length: Option<HumanDuration>,
+    #[description = "What the event is about"]
+    #[max_length = 1000]
+    description: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    if channel.is_some() == location.is_some() {
+        let content = "Give the event either a channel or a location.".to_string();
+        return reply(ctx, content).await;
+    }
+    let every = repeat.interval();
+    let length = length.map_or(Duration::hours(DEFAULT_EVENT_HOURS), |length| {
+        length.as_duration()
+    });
+    if length <= Duration::zero() || length > every {
+        let content = format!(
+            "The event has to end before the next one starts, so within {}.",
+            HumanDuration(every)
+        );
+        return reply(ctx, content).await;
+    }
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let now = Utc::now();
+    let starts_at = when.resolve(timezone, now);
+    if starts_at <= now {
+        return reply(ctx, "That time has already passed.".to_string()).await;
+    }
+
+    let recurring: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM recurring_events WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .fetch_one(&data.pool)
+            .await?;
+    if recurring >= MAX_RECURRING_EVENTS {
+        let content =
+            format!("There are {MAX_RECURRING_EVENTS} recurring events already, delete one first.");
+        return reply(ctx, content).await;
+    }
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let recurring: RecurringEvent = sqlx::query_as(
+        "INSERT INTO recurring_events
+        (guild_id, name, description, channel_id, location, starts_at, duration_seconds,
+        every_seconds, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING recurring_id, guild_id, name, description, channel_id, location, starts_at,
+        duration_seconds, every_seconds",
+    )
+    .bind(i64::from(guild_id))
+    .bind(&name)
+    .bind(&description)
+    .bind(channel.as_ref().map(|channel| i64::from(channel.id)))
+    .bind(&location)
+    .bind(starts_at)
+    .bind(length.num_seconds())
+    .bind(every.num_seconds())
+    .bind(i64::from(ctx.author().id))
+    .fetch_one(&data.pool)
+    .await?;
+
+    let event = match recurring.create_occurrence(ctx.http()).await {
+        Ok(event) => event,
+        Err(e) => {
+            sqlx::query("DELETE FROM recurring_events WHERE recurring_id = $1")
+                .bind(recurring.recurring_id)
+                .execute(&data.pool)
+                .await?;
+            return Err(e);
+        }
+    };
+    sqlx::query("UPDATE recurring_events SET event_id = $2 WHERE recurring_id = $1")
+        .bind(recurring.recurring_id)
+        .bind(i64::from(event.id))
+        .execute(&data.pool)
+        .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Recurring event `{}`: the first one is at {}, the next is created after each ends.",
+            recurring.recurring_id,
+            localtime::format_datetime(starts_at, timezone)
+        ),
+    )
+    .await
+}
+
+/// Lists the recurring events of this server
+#[poise::command(slash_command, guild_only, category = "Moderation", rename = "list")]
+async fn recurring_list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let recurring: Vec<RecurringEvent> = sqlx::query_as(
+        "SELECT recurring_id, guild_id, name, description, channel_id, location, starts_at,
+        duration_seconds, every_seconds
+        FROM recurring_events WHERE guild_id = $1 ORDER BY starts_at",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_all(&data.pool)
+    .await?;
+
+    if recurring.is_empty() {
+        return reply(ctx, "There are no recurring events.".to_string()).await;
+    }
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let lines = recurring.iter().map(|recurring| recurring.line(timezone));
+    audit::paginate(ctx, "Recurring events", &audit::pages(lines, PAGE_LEN)).await
+}
+
+/// Stops holding a recurring event, the next one already created stays
+#[poise::command(slash_command, guild_only, category = "Moderation", rename = "delete")]
+async fn recurring_delete(
+    ctx: Context<'_>,
+    #[description = "Recurring event to stop, see /event recurring list"] id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed =
+        sqlx::query("DELETE FROM recurring_events WHERE guild_id = $1 AND recurring_id = $2")
+            .bind(i64::from(guild_id))
+            .bind(id)
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Recurring event `{id}` won't be held again.")
+    } else {
+        format!("There is no recurring event `{id}`.")
+    };
+    reply(ctx, content).await
+}
+
+async fn autocomplete_event(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Vec::new();
+    };
+    let Ok(events) = guild_id.scheduled_events(ctx, false).await else {
+        return Vec::new();
+    };
+    let partial = partial.to_lowercase();
+    events
+        .into_iter()
+        .filter(|event| event.name.to_lowercase().contains(&partial))
+        .map(|event| AutocompleteChoice::new(event.name, event.id.to_string()))
+        .collect()
+}
+
+/// Reads an event id, or the id at the end of an event link.
+fn parse_event(value: &str) -> Option<ScheduledEventId> {
+    let id = value.trim().rsplit('/').next()?;
+    id.parse()
+        .ok()
+        .filter(|&id| id != 0)
+        .map(ScheduledEventId::new)
+}
+
+/// Shows who's interested in an event and how many events each of them wanted to go to
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn attendance(
+    ctx: Context<'_>,
+    #[description = "Event to look at, or a link to it"]
+    #[autocomplete = "autocomplete_event"]
+    event: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(event_id) = parse_event(&event) else {
+        return reply(ctx, format!("'{event}' is not an event.")).await;
+    };
+    let name = match guild_id.scheduled_event(ctx, event_id, false).await {
+        Ok(event) => event.name,
+        Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => {
+            format!("Event {event_id}")
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT count(*) FROM event_rsvps WHERE guild_id = $1 AND event_id = $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(event_id))
+    .fetch_one(&data.pool)
+    .await?;
+    let attendees: Vec<(i64, i64)> = sqlx::query_as(
+        "SELECT rsvp.user_id, count(*) FROM event_rsvps rsvp
+        JOIN event_rsvps history
+        ON history.guild_id = rsvp.guild_id AND history.user_id = rsvp.user_id
+        WHERE rsvp.guild_id = $1 AND rsvp.event_id = $2
+        GROUP BY rsvp.user_id ORDER BY count(*) DESC, rsvp.user_id LIMIT $3",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(event_id))
+    .bind(MAX_ATTENDEES)
+    .fetch_all(&data.pool)
+    .await?;
+
+    let interested = if attendees.is_empty() {
+        "nobody yet".to_string()
+    } else {
+        let lines = attendees
+            .iter()
+            .map(|&(user_id, events)| {
+                format!("{}: {events} events", UserId::new(user_id as u64).mention())
+            })
+            .collect::<Vec<_>>();
+        msglog::field_text(&lines.join("\n"))
+    };
+    let embed = CreateEmbed::new()
+        .title(name)
+        .url(link(guild_id, event_id))
+        .field(format!("Interested ({total})"), interested, false)
+        .footer(CreateEmbedFooter::new(
+            "Events count every event they were interested in that wasn't cancelled",
+        ));
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Sends the server's upcoming events as a file calendar apps can import
+#[poise::command(slash_command, guild_only)]
+async fn export(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let events = guild_id.scheduled_events(ctx, false).await?;
+    if events.is_empty() {
+        return reply(ctx, "There are no upcoming events.".to_string()).await;
+    }
+    let guild = guild_id.to_partial_guild(ctx).await?;
+    let channels = guild_id.channels(ctx).await?;
+
+    let events: Vec<CalendarEvent> = events
+        .iter()
+        .map(|event| {
+            let starts_at = localtime::from_timestamp(event.start_time);
+            let ends_at = event.end_time.map_or(
+                starts_at + Duration::hours(DEFAULT_EVENT_HOURS),
+                localtime::from_timestamp,
+            );
+            let location = event
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.location.clone())
+                .or_else(|| {
+                    let channel = channels.get(&event.channel_id?)?;
+                    Some(format!("#{}", channel.name))
+                });
+            CalendarEvent {
+                uid: format!("{}@discord.com", event.id),
+                summary: event.name.clone(),
+                description: event.description.clone(),
+                location,
+                url: link(guild_id, event.id),
+                starts_at,
+                ends_at,
+            }
+        })
+        .collect();
+    let calendar = ics::calendar(&guild.name, &events);
+
+    let attachment = CreateAttachment::bytes(calendar.into_bytes(), "events.ics");
+    let reply = CreateReply::default()
+        .content(format!(
+            "{} upcoming events, open the file to add them to your calendar.",
+            events.len()
+        ))
+        .attachment(attachment)
+        .ephemeral(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use std::collections::HashSet;
+
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+
+use crate::{audit, db, members::MemberCache, replies::reply, Context, SlimeError};
+
+/// How many exemptions one page of `/purge_exempt list` shows.
+const PAGE_LEN: usize = 20;
+
+/// A user or role whose messages are never purged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Exempt {
+    User(UserId),
+    Role(RoleId),
+}
+
+impl Exempt {
+    /// Picks the one target of an `add` or `remove`, if exactly one was given.
+    fn from_args(user: Option<User>, role: Option<Role>) -> Option<Self> {
+        match (user, role) {
+            (Some(user), None) => Some(Exempt::User(user.id)),
+            (None, Some(role)) => Some(Exempt::Role(role.id)),
+            _ => None,
+        }
+    }
+
+    fn kind(self) -> &'static str {
+        match self {
+            Exempt::User(_) => "user",
+            Exempt::Role(_) => "role",
+        }
+    }
+
+    fn id(self) -> i64 {
+        match self {
+            Exempt::User(id) => i64::from(id),
+            Exempt::Role(id) => i64::from(id),
+        }
+    }
+
+    fn mention(self) -> Mention {
+        match self {
+            Exempt::User(id) => id.mention(),
+            Exempt::Role(id) => id.mention(),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct ExemptRow {
+    exempt_id: i64,
+    kind: String,
+}
+
+impl From<ExemptRow> for Exempt {
+    fn from(row: ExemptRow) -> Self {
+        match row.kind.as_str() {
+            "role" => Exempt::Role(RoleId::new(row.exempt_id as u64)),
+            _ => Exempt::User(UserId::new(row.exempt_id as u64)),
+        }
+    }
+}
+
+async fn guild_exemptions(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<Vec<Exempt>> {
+    let rows: Vec<ExemptRow> = sqlx::query_as(
+        "SELECT exempt_id, kind FROM purge_exemptions WHERE guild_id = $1 ORDER BY kind, exempt_id",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(Exempt::from).collect())
+}
+
+/// The exemptions of one guild, loaded once per purge.
+pub struct Exemptions {
+    users: HashSet<UserId>,
+    roles: HashSet<RoleId>,
+    members: MemberCache,
+}
+
+impl Exemptions {
+    pub async fn load(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<Self> {
+        let mut users = HashSet::new();
+        let mut roles = HashSet::new();
+        for exempt in guild_exemptions(pool, guild_id).await? {
+            match exempt {
+                Exempt::User(id) => users.insert(id),
+                Exempt::Role(id) => roles.insert(id),
+            };
+        }
+
+        Ok(Exemptions {
+            users,
+            roles,
+            members: MemberCache::new(guild_id),
+        })
+    }
+
+    /// Whether `message` was sent by an exempt user or a member with an
+    /// exempt role.
+    pub async fn covers(&self, http: &Http, message: &Message) -> Result<bool, SlimeError> {
+        let author = message.author.id;
+        if self.users.contains(&author) {
+            return Ok(true);
+        }
+        // Webhooks aren't members, so they can't have roles.
+        if self.roles.is_empty() || message.webhook_id.is_some() {
+            return Ok(false);
+        }
+
+        let roles = self.members.roles(http, author).await?;
+        Ok(roles.is_some_and(|roles| roles.iter().any(|role| self.roles.contains(role))))
+    }
+
+    /// The guild's members, shared with checks that need them too.
+    pub fn members(&self) -> &MemberCache {
+        &self.members
+    }
+}
+
+/// Manages the users and roles whose messages are never purged
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("add", "remove", "list")
+)]
+pub async fn purge_exempt(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Protects a user's or role's messages from purges
+#[poise::command(slash_command, guild_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "User to exempt"] user: Option<User>,
+    #[description = "Role to exempt"] role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let Some(exempt) = Exempt::from_args(user, role) else {
+        return reply(ctx, "Pick either a user or a role.".to_string()).await;
+    };
+    let guild_id = ctx.guild_id().unwrap();
+    let pool = &ctx.data().pool;
+
+    db::ensure_guild(pool, guild_id).await?;
+    sqlx::query(
+        "INSERT INTO purge_exemptions (guild_id, exempt_id, kind) VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(i64::from(guild_id))
+    .bind(exempt.id())
+    .bind(exempt.kind())
+    .execute(pool)
+    .await?;
+
+    reply(
+        ctx,
+        format!("Messages from {} won't be purged.", exempt.mention()),
+    )
+    .await
+}
+
+/// Lets purges delete a user's or role's messages again
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "User to stop exempting"] user: Option<User>,
+    #[description = "Role to stop exempting"] role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let Some(exempt) = Exempt::from_args(user, role) else {
+        return reply(ctx, "Pick either a user or a role.".to_string()).await;
+    };
+    let guild_id = ctx.guild_id().unwrap();
+
+    let removed =
+        sqlx::query("DELETE FROM purge_exemptions WHERE guild_id = $1 AND exempt_id = $2")
+            .bind(i64::from(guild_id))
+            .bind(exempt.id())
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Messages from {} can be purged again.", exempt.mention())
+    } else {
+        format!("{} isn't exempt.", exempt.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the users and roles exempt from purges
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let exemptions = guild_exemptions(&ctx.data().pool, guild_id).await?;
+
+    if exemptions.is_empty() {
+        return reply(ctx, "Nobody is exempt from purges.".to_string()).await;
+    }
+
+    let lines = exemptions
+        .iter()
+        .map(|exempt| format!("- {} ({})", exempt.mention(), exempt.kind()));
+    audit::paginate(ctx, "Exempt from purges", &audit::pages(lines, PAGE_LEN)).await
+}
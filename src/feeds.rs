@@ -0,0 +1,537 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use hyper::client::connect::dns::Name;
+
+use poise::serenity_prelude::*;
+use reqwest::{
+    dns::{Addrs, Resolve, Resolving},
+    redirect,
+};
+use sqlx::PgPool;
+use thiserror::Error;
+use tracing::{error, warn};
+
+use crate::{
+    audit, db, localtime,
+    replies::{reply, shorten},
+    syndication::{self, Feed, Item, ParseFeedError},
+    Context, Data, SlimeError,
+};
+
+/// How often the background task checks for feeds that are due.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often each subscription's feed is fetched, at most.
+const POLL_MINUTES: f64 = 15.0;
+
+/// How long fetching a feed can take before it's given up on.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// How big a feed can be, in bytes.
+const MAX_FEED_BYTES: usize = 2 * 1024 * 1024;
+
+/// How many feeds a guild can subscribe to at once.
+const MAX_SUBSCRIPTIONS: i64 = 20;
+
+/// How many new items of a feed are posted at once, the newest ones. More
+/// than that is a feed that got rewritten rather than one with news.
+const MAX_POSTS: usize = 5;
+
+/// How many item ids each subscription remembers having seen.
+const SEEN_LIMIT: usize = 200;
+
+/// How much of an item's summary its embed shows.
+const SUMMARY_LEN: usize = 350;
+
+/// How many keywords a subscription's filters can have.
+const MAX_KEYWORDS: usize = 20;
+
+/// How many redirects are followed when fetching something.
+const MAX_REDIRECTS: usize = 5;
+
+/// How many subscriptions one page of `/feed list` shows.
+const PAGE_LEN: usize = 10;
+
+#[derive(Error, Debug)]
+pub enum FetchError {
+    #[error("I couldn't fetch it: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("the server answered with {0}")]
+    Status(reqwest::StatusCode),
+    #[error("it's bigger than {} MB", MAX_FEED_BYTES / 1024 / 1024)]
+    TooLarge,
+    #[error("{0}")]
+    Parse(#[from] ParseFeedError),
+    #[error("it's not a web address I can fetch")]
+    Blocked,
+}
+
+impl FetchError {
+    /// What to tell whoever asked for the fetch. What the server answered
+    /// stays in the logs, so that it can't be used to find out what's on
+    /// the bot's network.
+    pub fn summary(&self) -> String {
+        match self {
+            FetchError::Http(_) | FetchError::Status(_) => "I couldn't fetch it".to_string(),
+            e => e.to_string(),
+        }
+    }
+}
+
+/// Whether `ip` is an address on the internet, rather than one of the
+/// machine the bot runs on or its network.
+fn public_ip(ip: IpAddr) -> bool {
+    let v4 = |ip: Ipv4Addr| {
+        let [a, b, ..] = ip.octets();
+        !(ip.is_loopback()
+            || ip.is_private()
+            || ip.is_link_local()
+            || ip.is_unspecified()
+            || ip.is_broadcast()
+            || a == 0
+            // Carrier-grade NAT, 100.64.0.0/10.
+            || (a == 100 && b & 0xc0 == 64))
+    };
+    match ip {
+        IpAddr::V4(ip) => v4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return v4(ip);
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                // Unique local, fc00::/7, and link local, fe80::/10.
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// Resolves host names to their public addresses only, so that a name
+/// pointing into the bot's network can't be fetched either.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// The HTTP client feeds are fetched with. It only connects to public
+/// addresses, redirects included.
+pub fn client() -> reqwest::Client {
+    let redirects = redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if !fetchable(attempt.url().as_str()) {
+            attempt.error("redirected to an address that can't be fetched")
+        } else {
+            attempt.follow()
+        }
+    });
+    reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(redirects)
+        .dns_resolver(Arc::new(PublicResolver))
+        .user_agent(concat!("pond-slime/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Err creating the HTTP client")
+}
+
+pub async fn fetch(client: &reqwest::Client, url: &str) -> Result<Feed, FetchError> {
+    if !fetchable(url) {
+        return Err(FetchError::Blocked);
+    }
+    let mut response = client.get(url).send().await?;
+    if !response.status().is_success() {
+        return Err(FetchError::Status(response.status()));
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        body.extend_from_slice(&chunk);
+        if body.len() > MAX_FEED_BYTES {
+            return Err(FetchError::TooLarge);
+        }
+    }
+    Ok(syndication::parse(&String::from_utf8_lossy(&body))?)
+}
+
+/// Whether `url` is a web address the bot may fetch. Addresses of the
+/// machine the bot runs on and its network are left alone, host names are
+/// checked once resolved by [`PublicResolver`].
+fn fetchable(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    if host.eq_ignore_ascii_case("localhost") {
+        return false;
+    }
+    match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => public_ip(ip),
+        Err(_) => true,
+    }
+}
+
+/// Reads a comma separated list of keywords, lowercased.
+fn parse_keywords(keywords: Option<&str>) -> Vec<String> {
+    keywords
+        .unwrap_or_default()
+        .split(',')
+        .map(|keyword| keyword.trim().to_lowercase())
+        .filter(|keyword| !keyword.is_empty())
+        .collect()
+}
+
+/// "Post new items of the feed at `url` in `channel_id`", from the
+/// `feed_subscriptions` table.
+#[derive(sqlx::FromRow)]
+struct Subscription {
+    subscription_id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    url: String,
+    title: Option<String>,
+    /// Only items mentioning one of these are posted, if there are any.
+    keywords: Vec<String>,
+    /// Items mentioning one of these aren't posted.
+    exclude: Vec<String>,
+    seen_ids: Vec<String>,
+}
+
+impl Subscription {
+    fn wants(&self, item: &Item) -> bool {
+        let text = format!(
+            "{} {}",
+            item.title.as_deref().unwrap_or_default(),
+            item.summary.as_deref().unwrap_or_default()
+        )
+        .to_lowercase();
+        let mentions = |keyword: &String| text.contains(keyword.as_str());
+        (self.keywords.is_empty() || self.keywords.iter().any(mentions))
+            && !self.exclude.iter().any(mentions)
+    }
+
+    fn line(&self) -> String {
+        let mut line = format!(
+            "`{}` {} in {}",
+            self.subscription_id,
+            self.title.as_deref().unwrap_or(&self.url),
+            ChannelId::new(self.channel_id as u64).mention()
+        );
+        if !self.keywords.is_empty() {
+            line.push_str(&format!(", about {}", self.keywords.join(", ")));
+        }
+        if !self.exclude.is_empty() {
+            line.push_str(&format!(", not about {}", self.exclude.join(", ")));
+        }
+        line
+    }
+}
+
+fn embed(feed: &Feed, item: &Item) -> CreateEmbed {
+    let mut embed = CreateEmbed::new().colour(Colour::ORANGE);
+    if let Some(title) = &item.title {
+        embed = embed.title(shorten(&syndication::plain_text(title), 256));
+    }
+    if let Some(link) = item.link.as_deref().filter(|link| fetchable(link)) {
+        embed = embed.url(link);
+    }
+    if let Some(summary) = &item.summary {
+        let summary = syndication::plain_text(summary);
+        if !summary.is_empty() {
+            embed = embed.description(shorten(&summary, SUMMARY_LEN));
+        }
+    }
+    if let Some(title) = &feed.title {
+        embed = embed.footer(CreateEmbedFooter::new(shorten(title, 256)));
+    }
+    if let Some(published) = item.published {
+        embed = embed.timestamp(localtime::to_timestamp(published));
+    }
+    embed
+}
+
+/// The ids `subscription` has seen once it's seen `feed`: the feed's own,
+/// then the ones from before, up to `SEEN_LIMIT`.
+fn seen_after(subscription: &Subscription, feed: &Feed) -> Vec<String> {
+    let mut seen: Vec<String> = feed.items.iter().map(|item| item.id.clone()).collect();
+    for id in &subscription.seen_ids {
+        if seen.len() >= SEEN_LIMIT {
+            break;
+        }
+        if !seen.contains(id) {
+            seen.push(id.clone());
+        }
+    }
+    seen.truncate(SEEN_LIMIT);
+    seen
+}
+
+/// Posts the items of `feed` that `subscription` hasn't seen and wants.
+/// They are marked seen first, so a failed post is never posted twice.
+async fn deliver(
+    http: &Http,
+    pool: &PgPool,
+    subscription: &Subscription,
+    feed: &Feed,
+) -> Result<(), SlimeError> {
+    let new: Vec<&Item> = feed
+        .items
+        .iter()
+        .filter(|item| !subscription.seen_ids.contains(&item.id))
+        .collect();
+    if new.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        "UPDATE feed_subscriptions SET seen_ids = $2, title = coalesce($3, title)
+        WHERE subscription_id = $1",
+    )
+    .bind(subscription.subscription_id)
+    .bind(seen_after(subscription, feed))
+    .bind(&feed.title)
+    .execute(pool)
+    .await?;
+
+    let channel_id = ChannelId::new(subscription.channel_id as u64);
+    for item in new.into_iter().take(MAX_POSTS).rev() {
+        if !subscription.wants(item) {
+            continue;
+        }
+        channel_id
+            .send_message(http, CreateMessage::new().embed(embed(feed, item)))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Fetches the feeds that are due every `POLL_MINUTES` and posts their new
+/// items. Each feed is fetched once however many channels subscribe to it.
+pub async fn poll_feeds(http: Arc<Http>, data: Data) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let due: Vec<Subscription> = match sqlx::query_as(
+            "UPDATE feed_subscriptions SET checked_at = now()
+            WHERE checked_at <= now() - make_interval(mins => $1)
+            RETURNING subscription_id, guild_id, channel_id, url, title, keywords, exclude,
+            seen_ids",
+        )
+        .bind(POLL_MINUTES)
+        .fetch_all(&data.pool)
+        .await
+        {
+            Ok(due) => due,
+            Err(e) => {
+                error!("failed to load due feed subscriptions: {}", e);
+                continue;
+            }
+        };
+
+        let mut feeds: HashMap<String, Vec<Subscription>> = HashMap::new();
+        for subscription in due {
+            feeds
+                .entry(subscription.url.clone())
+                .or_default()
+                .push(subscription);
+        }
+        for (url, subscriptions) in feeds {
+            // It's tried again next time.
+            let feed = match fetch(&data.web, &url).await {
+                Ok(feed) => feed,
+                Err(e) => {
+                    warn!("failed to fetch feed {}: {}", url, e);
+                    continue;
+                }
+            };
+            for subscription in subscriptions {
+                if let Err(e) = deliver(&http, &data.pool, &subscription, &feed).await {
+                    error!(
+                        "failed to post feed {} in guild {}: {}",
+                        subscription.subscription_id, subscription.guild_id, e
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Posts new items of RSS and Atom feeds in channels
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("subscribe", "list", "unsubscribe")
+)]
+pub async fn feed(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Posts new items of a feed in a channel, or changes what gets posted
+#[poise::command(slash_command, guild_only)]
+async fn subscribe(
+    ctx: Context<'_>,
+    #[description = "Address of the RSS or Atom feed"]
+    #[max_length = 500]
+    url: String,
+    #[description = "Channel to post in"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+    #[description = "Only post items mentioning one of these, comma separated"]
+    #[max_length = 500]
+    keywords: Option<String>,
+    #[description = "Don't post items mentioning one of these, comma separated"]
+    #[max_length = 500]
+    exclude: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let url = url.trim().to_string();
+    if !fetchable(&url) {
+        return reply(ctx, format!("`{url}` is not a web address I can fetch.")).await;
+    }
+    let keywords = parse_keywords(keywords.as_deref());
+    let exclude = parse_keywords(exclude.as_deref());
+    if keywords.len() > MAX_KEYWORDS || exclude.len() > MAX_KEYWORDS {
+        let content = format!("A feed can be filtered by {MAX_KEYWORDS} keywords at most.");
+        return reply(ctx, content).await;
+    }
+
+    let subscriptions: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM feed_subscriptions WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .fetch_one(&data.pool)
+            .await?;
+    let subscribed: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM feed_subscriptions WHERE channel_id = $1 AND url = $2)",
+    )
+    .bind(i64::from(channel.id))
+    .bind(&url)
+    .fetch_one(&data.pool)
+    .await?;
+    if subscriptions >= MAX_SUBSCRIPTIONS && !subscribed {
+        let content = format!(
+            "This server subscribes to {MAX_SUBSCRIPTIONS} feeds already, unsubscribe from one \
+            first."
+        );
+        return reply(ctx, content).await;
+    }
+
+    // Fetching the feed can take a while.
+    ctx.defer_ephemeral().await?;
+    let feed = match fetch(&data.web, &url).await {
+        Ok(feed) => feed,
+        Err(e) => {
+            warn!("failed to fetch feed {} to subscribe to it: {}", url, e);
+            return reply(ctx, format!("That feed didn't work, {}.", e.summary())).await;
+        }
+    };
+
+    // What's in the feed already counts as seen, only what comes after it
+    // is posted.
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let seen: Vec<String> = feed
+        .items
+        .iter()
+        .take(SEEN_LIMIT)
+        .map(|item| item.id.clone())
+        .collect();
+    let subscription: Subscription = sqlx::query_as(
+        "INSERT INTO feed_subscriptions
+        (guild_id, channel_id, url, title, keywords, exclude, seen_ids, added_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (channel_id, url) DO UPDATE SET
+        title = EXCLUDED.title, keywords = EXCLUDED.keywords, exclude = EXCLUDED.exclude
+        RETURNING subscription_id, guild_id, channel_id, url, title, keywords, exclude, seen_ids",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(&url)
+    .bind(&feed.title)
+    .bind(&keywords)
+    .bind(&exclude)
+    .bind(&seen)
+    .bind(i64::from(ctx.author().id))
+    .fetch_one(&data.pool)
+    .await?;
+
+    let verb = if subscribed {
+        "Updated"
+    } else {
+        "Subscribed to"
+    };
+    reply(
+        ctx,
+        format!(
+            "{verb} {}, new items are posted as they come.",
+            subscription.line()
+        ),
+    )
+    .await
+}
+
+/// Lists the feeds this server subscribes to
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let subscriptions: Vec<Subscription> = sqlx::query_as(
+        "SELECT subscription_id, guild_id, channel_id, url, title, keywords, exclude, seen_ids
+        FROM feed_subscriptions WHERE guild_id = $1 ORDER BY subscription_id",
+    )
+    .bind(i64::from(ctx.guild_id().unwrap()))
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if subscriptions.is_empty() {
+        let content = "This server subscribes to no feeds, add one with `/feed subscribe`.";
+        return reply(ctx, content.to_string()).await;
+    }
+
+    let lines = subscriptions
+        .iter()
+        .map(|subscription| format!("- {}", subscription.line()));
+    audit::paginate(ctx, "Feed subscriptions", &audit::pages(lines, PAGE_LEN)).await
+}
+
+/// Stops posting a feed's new items
+#[poise::command(slash_command, guild_only)]
+async fn unsubscribe(
+    ctx: Context<'_>,
+    #[description = "Feed to unsubscribe from, see /feed list"] id: i64,
+) -> Result<(), SlimeError> {
+    let removed =
+        sqlx::query("DELETE FROM feed_subscriptions WHERE guild_id = $1 AND subscription_id = $2")
+            .bind(i64::from(ctx.guild_id().unwrap()))
+            .bind(id)
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Feed `{id}` won't be posted anymore.")
+    } else {
+        format!("There is no feed `{id}`.")
+    };
+    reply(ctx, content).await
+}
@@ -0,0 +1,165 @@
+use std::{fmt, str::FromStr};
+
+use poise::serenity_prelude::{Mentionable, Message, UserId};
+use regex::{Regex, RegexBuilder};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A regex typed in by a moderator. Compiled with a size limit so a
+/// pathological pattern can't eat the bot's memory.
+#[derive(Clone, Debug)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.0.is_match(haystack)
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RegexBuilder::new(s)
+            .size_limit(1 << 20)
+            .build()
+            .map(Pattern)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.as_str().fmt(f)
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        pattern.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Which of the collected messages a purge actually deletes. It is stored
+/// with the job, so a resumed purge applies the same rules.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageFilter {
+    /// Only delete messages from this user.
+    pub user_id: Option<UserId>,
+    /// Keep pinned messages.
+    pub exclude_pinned: bool,
+    /// Only delete messages whose content matches.
+    pub pattern: Option<Pattern>,
+    /// Only delete messages sent by bots and webhooks.
+    pub bots_only: bool,
+    /// Only delete messages with one of the selected kinds of content. If
+    /// none is selected every message qualifies.
+    pub has_attachment: bool,
+    pub has_embed: bool,
+    pub has_link: bool,
+    /// Only delete messages from users who left the server. Checking that
+    /// takes a request per author, so `purge::candidate_messages` does it
+    /// rather than `skip_reason`.
+    pub departed_only: bool,
+}
+
+/// Why a message was kept out of a purge.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Skip {
+    Pinned,
+    Filtered,
+}
+
+impl MessageFilter {
+    /// Returns why `message` shouldn't be deleted, or `None` if it should.
+    pub fn skip_reason(&self, message: &Message) -> Option<Skip> {
+        if self
+            .user_id
+            .is_some_and(|user_id| message.author.id != user_id)
+        {
+            return Some(Skip::Filtered);
+        }
+        if self.bots_only && !message.author.bot && message.webhook_id.is_none() {
+            return Some(Skip::Filtered);
+        }
+        if self
+            .pattern
+            .as_ref()
+            .is_some_and(|pattern| !pattern.is_match(&message.content))
+        {
+            return Some(Skip::Filtered);
+        }
+        if self.filters_content() && !self.has_selected_content(message) {
+            return Some(Skip::Filtered);
+        }
+        if self.exclude_pinned && message.pinned {
+            return Some(Skip::Pinned);
+        }
+
+        None
+    }
+
+    fn filters_content(&self) -> bool {
+        self.has_attachment || self.has_embed || self.has_link
+    }
+
+    fn has_selected_content(&self, message: &Message) -> bool {
+        (self.has_attachment && !message.attachments.is_empty())
+            || (self.has_embed && !message.embeds.is_empty())
+            || (self.has_link && has_link(&message.content))
+    }
+
+    /// Describes the content filters for a prompt, e.g. "with attachments or links".
+    pub fn content_description(&self) -> Option<String> {
+        let kinds: Vec<_> = [
+            (self.has_attachment, "attachments"),
+            (self.has_embed, "embeds"),
+            (self.has_link, "links"),
+        ]
+        .into_iter()
+        .filter_map(|(selected, kind)| selected.then_some(kind))
+        .collect();
+
+        (!kinds.is_empty()).then(|| format!("with {}", kinds.join(" or ")))
+    }
+
+    /// Lists the filters in use, e.g. "from @user, bots only", or an empty
+    /// string if every message qualifies.
+    pub fn description(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(user_id) = self.user_id {
+            parts.push(format!("from {}", user_id.mention()));
+        }
+        if self.bots_only {
+            parts.push("bots only".to_string());
+        }
+        if self.departed_only {
+            parts.push("departed members only".to_string());
+        }
+        if let Some(pattern) = &self.pattern {
+            parts.push(format!("matching `{pattern}`"));
+        }
+        if let Some(content) = self.content_description() {
+            parts.push(content);
+        }
+        if self.exclude_pinned {
+            parts.push("pinned kept".to_string());
+        }
+
+        parts.join(", ")
+    }
+
+    pub fn deletes(&self, message: &Message) -> bool {
+        self.skip_reason(message).is_none()
+    }
+}
+
+fn has_link(content: &str) -> bool {
+    content.contains("https://") || content.contains("http://")
+}
@@ -0,0 +1,567 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serde::Deserialize;
+use sqlx::PgPool;
+use thiserror::Error;
+use tracing::{error, warn};
+
+use crate::{
+    db, localtime,
+    replies::{reply, shorten},
+    Context, Data, SlimeError,
+};
+
+/// How often the background task checks for repositories that are due.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often each watched repository is checked, at most.
+const POLL_MINUTES: f64 = 15.0;
+
+/// How many requests checking a repository takes.
+const REQUESTS_PER_CHECK: f64 = 2.0;
+
+/// How many requests an hour checks can take without a token. GitHub allows
+/// 60, the rest is left for `/github watch`.
+const ANONYMOUS_REQUESTS_PER_HOUR: f64 = 50.0;
+
+/// How many repositories a guild can watch at once.
+const MAX_WATCHES: i64 = 10;
+
+/// How many releases, and issues and pull requests, a check looks at.
+const PAGE_LEN: usize = 20;
+
+/// How many new releases, issues or pull requests are posted at once.
+const MAX_POSTS: usize = 5;
+
+/// How much of a release's notes or an issue's text its embed shows.
+const BODY_LEN: usize = 400;
+
+const API: &str = "https://api.github.com";
+
+#[derive(Error, Debug)]
+enum ApiError {
+    #[error("I couldn't reach GitHub: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("there's no such repository, or it's private")]
+    NotFound,
+    #[error("GitHub answered with {0}")]
+    Status(reqwest::StatusCode),
+}
+
+#[derive(Deserialize)]
+struct User {
+    login: String,
+    avatar_url: String,
+    html_url: String,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    id: i64,
+    tag_name: String,
+    name: Option<String>,
+    html_url: String,
+    body: Option<String>,
+    draft: bool,
+    prerelease: bool,
+    published_at: Option<String>,
+    author: Option<User>,
+}
+
+/// An issue or, if `pull_request` is there, a pull request: GitHub lists
+/// both as issues, numbered together.
+#[derive(Deserialize)]
+struct Issue {
+    number: i64,
+    title: String,
+    html_url: String,
+    body: Option<String>,
+    user: Option<User>,
+    created_at: String,
+    pull_request: Option<serde::de::IgnoredAny>,
+}
+
+/// What's new in a repository: its latest releases and issues, newest
+/// first.
+struct Activity {
+    releases: Vec<Release>,
+    issues: Vec<Issue>,
+}
+
+async fn get<T: for<'de> Deserialize<'de>>(
+    web: &reqwest::Client,
+    token: Option<&str>,
+    path: &str,
+) -> Result<T, ApiError> {
+    let mut request = web
+        .get(format!("{API}{path}"))
+        .header("Accept", "application/vnd.github+json")
+        .header("X-GitHub-Api-Version", "2022-11-28");
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await?;
+    match response.status() {
+        status if status.is_success() => Ok(response.json().await?),
+        reqwest::StatusCode::NOT_FOUND => Err(ApiError::NotFound),
+        status => Err(ApiError::Status(status)),
+    }
+}
+
+async fn fetch(
+    web: &reqwest::Client,
+    token: Option<&str>,
+    repo: &str,
+) -> Result<Activity, ApiError> {
+    let releases = get(
+        web,
+        token,
+        &format!("/repos/{repo}/releases?per_page={PAGE_LEN}"),
+    )
+    .await?;
+    let issues = get(
+        web,
+        token,
+        &format!("/repos/{repo}/issues?state=all&sort=created&direction=desc&per_page={PAGE_LEN}"),
+    )
+    .await?;
+    Ok(Activity { releases, issues })
+}
+
+/// Reads `owner/repo`, or a link to the repository, lowercased.
+fn parse_repo(repo: &str) -> Option<String> {
+    let repo = repo.trim().trim_end_matches('/');
+    let repo = repo
+        .strip_prefix("https://github.com/")
+        .or(repo.strip_prefix("github.com/"))
+        .unwrap_or(repo);
+    let (owner, name) = repo.split_once('/')?;
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part.len() <= 100
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    if !valid(owner) || !valid(name) {
+        return None;
+    }
+    Some(format!("{owner}/{name}").to_lowercase())
+}
+
+/// What of a repository's activity gets posted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Events {
+    All,
+    Releases,
+    #[name = "Issues and pull requests"]
+    IssuesAndPulls,
+    Issues,
+    #[name = "Pull requests"]
+    Pulls,
+}
+
+impl Events {
+    /// Whether releases, issues and pull requests are posted.
+    fn flags(self) -> (bool, bool, bool) {
+        match self {
+            Events::All => (true, true, true),
+            Events::Releases => (true, false, false),
+            Events::IssuesAndPulls => (false, true, true),
+            Events::Issues => (false, true, false),
+            Events::Pulls => (false, false, true),
+        }
+    }
+}
+
+/// "Post `repo`'s new activity in `channel_id`", from the `github_watches`
+/// table. The last release and issue number seen are what's not new.
+#[derive(sqlx::FromRow)]
+struct Watch {
+    watch_id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    repo: String,
+    releases: bool,
+    issues: bool,
+    pulls: bool,
+    last_release_id: i64,
+    last_issue_number: i64,
+}
+
+impl Watch {
+    fn line(&self) -> String {
+        let mut events = Vec::new();
+        if self.releases {
+            events.push("releases");
+        }
+        if self.issues {
+            events.push("issues");
+        }
+        if self.pulls {
+            events.push("pull requests");
+        }
+        format!(
+            "`{}` {} in {}: {}",
+            self.watch_id,
+            self.repo,
+            ChannelId::new(self.channel_id as u64).mention(),
+            events.join(", ")
+        )
+    }
+}
+
+fn author(user: &User) -> CreateEmbedAuthor {
+    CreateEmbedAuthor::new(&user.login)
+        .icon_url(&user.avatar_url)
+        .url(&user.html_url)
+}
+
+fn release_embed(repo: &str, release: &Release) -> CreateEmbed {
+    let name = release
+        .name
+        .as_deref()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or(&release.tag_name);
+    let kind = if release.prerelease {
+        "Pre-release"
+    } else {
+        "Release"
+    };
+    let mut embed = CreateEmbed::new()
+        .title(shorten(&format!("{repo} {name}"), 256))
+        .url(&release.html_url)
+        .colour(Colour::DARK_GREEN)
+        .footer(CreateEmbedFooter::new(format!(
+            "{kind} {}",
+            release.tag_name
+        )));
+    if let Some(body) = release
+        .body
+        .as_deref()
+        .filter(|body| !body.trim().is_empty())
+    {
+        embed = embed.description(shorten(body.trim(), BODY_LEN));
+    }
+    if let Some(user) = &release.author {
+        embed = embed.author(author(user));
+    }
+    let published = release
+        .published_at
+        .as_deref()
+        .and_then(|at| DateTime::parse_from_rfc3339(at).ok());
+    if let Some(published) = published {
+        embed = embed.timestamp(localtime::to_timestamp(published.with_timezone(&Utc)));
+    }
+    embed
+}
+
+fn issue_embed(repo: &str, issue: &Issue) -> CreateEmbed {
+    let (kind, colour) = if issue.pull_request.is_some() {
+        ("Pull request", Colour::PURPLE)
+    } else {
+        ("Issue", Colour::DARK_GREEN)
+    };
+    let mut embed = CreateEmbed::new()
+        .title(shorten(&format!("#{} {}", issue.number, issue.title), 256))
+        .url(&issue.html_url)
+        .colour(colour)
+        .footer(CreateEmbedFooter::new(format!("{kind} opened in {repo}")));
+    if let Some(body) = issue.body.as_deref().filter(|body| !body.trim().is_empty()) {
+        embed = embed.description(shorten(body.trim(), BODY_LEN));
+    }
+    if let Some(user) = &issue.user {
+        embed = embed.author(author(user));
+    }
+    if let Ok(created) = DateTime::parse_from_rfc3339(&issue.created_at) {
+        embed = embed.timestamp(localtime::to_timestamp(created.with_timezone(&Utc)));
+    }
+    embed
+}
+
+/// The newest release id and issue number in `activity`, or what was seen
+/// before if there's nothing newer. Drafts don't count, they are only
+/// posted once they're published.
+fn latest(activity: &Activity, release_id: i64, issue_number: i64) -> (i64, i64) {
+    let release_id = activity
+        .releases
+        .iter()
+        .filter(|release| !release.draft)
+        .map(|release| release.id)
+        .fold(release_id, i64::max);
+    let issue_number = activity
+        .issues
+        .iter()
+        .map(|issue| issue.number)
+        .fold(issue_number, i64::max);
+    (release_id, issue_number)
+}
+
+/// Posts what's new in `activity` that `watch` wants. It's marked seen
+/// first, so a failed post is never posted twice.
+async fn deliver(
+    http: &Http,
+    pool: &PgPool,
+    watch: &Watch,
+    activity: &Activity,
+) -> Result<(), SlimeError> {
+    let (release_id, issue_number) =
+        latest(activity, watch.last_release_id, watch.last_issue_number);
+    if release_id == watch.last_release_id && issue_number == watch.last_issue_number {
+        return Ok(());
+    }
+    sqlx::query(
+        "UPDATE github_watches SET last_release_id = $2, last_issue_number = $3
+        WHERE watch_id = $1",
+    )
+    .bind(watch.watch_id)
+    .bind(release_id)
+    .bind(issue_number)
+    .execute(pool)
+    .await?;
+
+    let mut embeds = Vec::new();
+    if watch.releases {
+        let releases = activity
+            .releases
+            .iter()
+            .filter(|release| release.id > watch.last_release_id && !release.draft);
+        embeds.extend(
+            releases
+                .take(MAX_POSTS)
+                .map(|release| release_embed(&watch.repo, release)),
+        );
+    }
+    let issues = activity.issues.iter().filter(|issue| {
+        let wanted = if issue.pull_request.is_some() {
+            watch.pulls
+        } else {
+            watch.issues
+        };
+        wanted && issue.number > watch.last_issue_number
+    });
+    embeds.extend(
+        issues
+            .take(MAX_POSTS)
+            .map(|issue| issue_embed(&watch.repo, issue)),
+    );
+
+    let channel_id = ChannelId::new(watch.channel_id as u64);
+    for embed in embeds.into_iter().rev() {
+        channel_id
+            .send_message(http, CreateMessage::new().embed(embed))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// How many minutes apart each repository is checked. Without a token the
+/// checks of every watched repository have to fit in GitHub's anonymous
+/// rate limit, so the more there are the less often each one is checked.
+async fn poll_minutes(data: &Data) -> sqlx::Result<f64> {
+    if data.github_token.is_some() {
+        return Ok(POLL_MINUTES);
+    }
+    let repos: i64 = sqlx::query_scalar("SELECT count(DISTINCT repo) FROM github_watches")
+        .fetch_one(&data.pool)
+        .await?;
+    let minutes = repos as f64 * REQUESTS_PER_CHECK * 60.0 / ANONYMOUS_REQUESTS_PER_HOUR;
+    Ok(minutes.max(POLL_MINUTES))
+}
+
+/// Checks the watched repositories that are due every `poll_minutes` and
+/// posts their new releases, issues and pull requests. Each repository is
+/// fetched once however many channels watch it.
+pub async fn poll_repos(http: Arc<Http>, data: Data) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        // Every watch of a repository is claimed together, so it's fetched
+        // once per `poll_minutes` however many channels watch it.
+        let minutes = match poll_minutes(&data).await {
+            Ok(minutes) => minutes,
+            Err(e) => {
+                error!("failed to count the watched GitHub repositories: {}", e);
+                continue;
+            }
+        };
+        let due: Vec<Watch> = match sqlx::query_as(
+            "UPDATE github_watches SET checked_at = now()
+            WHERE repo IN (
+                SELECT repo FROM github_watches
+                WHERE checked_at <= now() - make_interval(mins => $1)
+            )
+            RETURNING watch_id, guild_id, channel_id, repo, releases, issues, pulls,
+            last_release_id, last_issue_number",
+        )
+        .bind(minutes)
+        .fetch_all(&data.pool)
+        .await
+        {
+            Ok(due) => due,
+            Err(e) => {
+                error!("failed to load due GitHub watches: {}", e);
+                continue;
+            }
+        };
+
+        let mut repos: HashMap<String, Vec<Watch>> = HashMap::new();
+        for watch in due {
+            repos.entry(watch.repo.clone()).or_default().push(watch);
+        }
+        for (repo, watches) in repos {
+            // It's tried again next time.
+            let activity = match fetch(&data.web, data.github_token.as_deref(), &repo).await {
+                Ok(activity) => activity,
+                Err(e) => {
+                    warn!("failed to check GitHub repository {}: {}", repo, e);
+                    continue;
+                }
+            };
+            for watch in watches {
+                if let Err(e) = deliver(&http, &data.pool, &watch, &activity).await {
+                    error!(
+                        "failed to post GitHub watch {} in guild {}: {}",
+                        watch.watch_id, watch.guild_id, e
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Posts a GitHub repository's releases, issues and pull requests in channels
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("watch", "list", "unwatch")
+)]
+pub async fn github(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Posts a repository's new activity in a channel, or changes what gets posted
+#[poise::command(slash_command, guild_only)]
+async fn watch(
+    ctx: Context<'_>,
+    #[description = "Repository as owner/repo"]
+    #[max_length = 200]
+    repo: String,
+    #[description = "Channel to post in"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+    #[description = "What to post (default all)"] events: Option<Events>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(repo) = parse_repo(&repo) else {
+        return reply(
+            ctx,
+            format!("`{repo}` is not a repository like owner/repo."),
+        )
+        .await;
+    };
+
+    let watches: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM github_watches WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .fetch_one(&data.pool)
+            .await?;
+    let watched: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM github_watches WHERE channel_id = $1 AND repo = $2)",
+    )
+    .bind(i64::from(channel.id))
+    .bind(&repo)
+    .fetch_one(&data.pool)
+    .await?;
+    if watches >= MAX_WATCHES && !watched {
+        let content =
+            format!("This server watches {MAX_WATCHES} repositories already, unwatch one first.");
+        return reply(ctx, content).await;
+    }
+
+    // What the repository has already isn't posted, only what comes after.
+    ctx.defer_ephemeral().await?;
+    let activity = match fetch(&data.web, data.github_token.as_deref(), &repo).await {
+        Ok(activity) => activity,
+        Err(e) => return reply(ctx, format!("That didn't work, {e}.")).await,
+    };
+    let (release_id, issue_number) = latest(&activity, 0, 0);
+
+    let (releases, issues, pulls) = events.unwrap_or(Events::All).flags();
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let watch: Watch = sqlx::query_as(
+        "INSERT INTO github_watches
+        (guild_id, channel_id, repo, releases, issues, pulls, last_release_id, last_issue_number,
+        added_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (channel_id, repo) DO UPDATE SET
+        releases = EXCLUDED.releases, issues = EXCLUDED.issues, pulls = EXCLUDED.pulls
+        RETURNING watch_id, guild_id, channel_id, repo, releases, issues, pulls, last_release_id,
+        last_issue_number",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(&repo)
+    .bind(releases)
+    .bind(issues)
+    .bind(pulls)
+    .bind(release_id)
+    .bind(issue_number)
+    .bind(i64::from(ctx.author().id))
+    .fetch_one(&data.pool)
+    .await?;
+
+    let verb = if watched { "Updated" } else { "Watching" };
+    reply(ctx, format!("{verb} {}.", watch.line())).await
+}
+
+/// Lists the repositories this server watches
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let watches: Vec<Watch> = sqlx::query_as(
+        "SELECT watch_id, guild_id, channel_id, repo, releases, issues, pulls, last_release_id,
+        last_issue_number
+        FROM github_watches WHERE guild_id = $1 ORDER BY watch_id",
+    )
+    .bind(i64::from(ctx.guild_id().unwrap()))
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if watches.is_empty() {
+        let content = "This server watches no repositories, add one with `/github watch`.";
+        return reply(ctx, content.to_string()).await;
+    }
+
+    let content = watches
+        .iter()
+        .map(|watch| format!("- {}", watch.line()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    reply(ctx, content).await
+}
+
+/// Stops posting a repository's activity
+#[poise::command(slash_command, guild_only)]
+async fn unwatch(
+    ctx: Context<'_>,
+    #[description = "Watch to remove, see /github list"] id: i64,
+) -> Result<(), SlimeError> {
+    let removed = sqlx::query("DELETE FROM github_watches WHERE guild_id = $1 AND watch_id = $2")
+        .bind(i64::from(ctx.guild_id().unwrap()))
+        .bind(id)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Watch `{id}` is removed.")
+    } else {
+        format!("There is no watch `{id}`.")
+    };
+    reply(ctx, content).await
+}
@@ -0,0 +1,429 @@
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{db, duration::HumanDuration, localtime, replies::reply, Context, Data, SlimeError};
+
+/// What the custom id of every giveaway button starts with, followed by the
+/// giveaway, as in `pond-slime:giveaway:12`. It has to stay the same across
+/// restarts, since the buttons outlive the bot process.
+const GIVEAWAY_BUTTON: &str = "pond-slime:giveaway";
+
+/// The longest a giveaway can run.
+const MAX_GIVEAWAY_DAYS: i64 = 28;
+
+/// A giveaway from the `giveaways` table.
+#[derive(sqlx::FromRow)]
+struct Giveaway {
+    giveaway_id: i64,
+    channel_id: i64,
+    message_id: Option<i64>,
+    created_by: i64,
+    prize: String,
+    winner_count: i32,
+    ends_at: DateTime<Utc>,
+    ended: bool,
+    winners: Vec<i64>,
+}
+
+impl Giveaway {
+    fn base_embed(&self) -> CreateEmbed {
+        CreateEmbed::new()
+            .title(format!("Giveaway: {}", self.prize))
+            .colour(Colour::FABLED_PINK)
+            .field(
+                "Hosted by",
+                UserId::new(self.created_by as u64).mention().to_string(),
+                true,
+            )
+    }
+
+    fn open_embed(&self, timezone: Option<FixedOffset>) -> CreateEmbed {
+        self.base_embed()
+            .description(format!(
+                "Press the button below to enter, {} of you will win.",
+                self.winner_count
+            ))
+            .footer(CreateEmbedFooter::new(format!(
+                "Ends {}",
+                localtime::format_datetime(self.ends_at, timezone)
+            )))
+    }
+
+    fn ended_embed(&self, entries: i64) -> CreateEmbed {
+        let winners = if self.winners.is_empty() {
+            "Nobody entered.".to_string()
+        } else {
+            mentions(&self.winners)
+        };
+        self.base_embed()
+            .field("Winners", winners, false)
+            .footer(CreateEmbedFooter::new(format!(
+                "Ended with {entries} entries"
+            )))
+    }
+
+    fn button(&self) -> CreateActionRow {
+        let button = CreateButton::new(format!("{GIVEAWAY_BUTTON}:{}", self.giveaway_id))
+            .label("Enter")
+            .emoji('🎉')
+            .style(ButtonStyle::Primary);
+        CreateActionRow::Buttons(vec![button])
+    }
+}
+
+fn mentions(user_ids: &[i64]) -> String {
+    user_ids
+        .iter()
+        .map(|&user_id| UserId::new(user_id as u64).mention().to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The giveaway a giveaway button stands for.
+fn parse_button(custom_id: &str) -> Option<i64> {
+    custom_id
+        .strip_prefix(GIVEAWAY_BUTTON)?
+        .strip_prefix(':')?
+        .parse()
+        .ok()
+}
+
+async fn load(
+    pool: &PgPool,
+    guild_id: GuildId,
+    giveaway_id: i64,
+) -> sqlx::Result<Option<Giveaway>> {
+    sqlx::query_as(
+        "SELECT giveaway_id, channel_id, message_id, created_by, prize, winner_count, ends_at,
+        ended, winners
+        FROM giveaways WHERE guild_id = $1 AND giveaway_id = $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(giveaway_id)
+    .fetch_optional(pool)
+    .await
+}
+
+async fn entries(pool: &PgPool, giveaway_id: i64) -> sqlx::Result<i64> {
+    sqlx::query_scalar("SELECT count(*) FROM giveaway_entries WHERE giveaway_id = $1")
+        .bind(giveaway_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Answers whoever pressed a giveaway button, only they see it.
+async fn respond(
+    ctx: &serenity::Context,
+    press: &ComponentInteraction,
+    content: &str,
+) -> Result<(), SlimeError> {
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    press
+        .create_response(ctx, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+/// Enters whoever pressed a giveaway button, or takes them out again if
+/// they entered before. Other component interactions are left alone.
+pub async fn interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    interaction: &Interaction,
+) -> Result<(), SlimeError> {
+    let Interaction::Component(press) = interaction else {
+        return Ok(());
+    };
+    let Some(giveaway_id) = parse_button(&press.data.custom_id) else {
+        return Ok(());
+    };
+    let Some(guild_id) = press.guild_id else {
+        return Ok(());
+    };
+
+    let Some(giveaway) = load(&data.pool, guild_id, giveaway_id).await? else {
+        return respond(ctx, press, "This giveaway doesn't exist anymore.").await;
+    };
+    if giveaway.ended || giveaway.ends_at <= Utc::now() {
+        return respond(ctx, press, "This giveaway is over.").await;
+    }
+
+    let user_id = i64::from(press.user.id);
+    let left = sqlx::query("DELETE FROM giveaway_entries WHERE giveaway_id = $1 AND user_id = $2")
+        .bind(giveaway_id)
+        .bind(user_id)
+        .execute(&data.pool)
+        .await?
+        .rows_affected();
+    if left > 0 {
+        return respond(ctx, press, "You left the giveaway.").await;
+    }
+    sqlx::query(
+        "INSERT INTO giveaway_entries (giveaway_id, guild_id, user_id) VALUES ($1, $2, $3)
+        ON CONFLICT (giveaway_id, user_id) DO NOTHING",
+    )
+    .bind(giveaway_id)
+    .bind(i64::from(guild_id))
+    .bind(user_id)
+    .execute(&data.pool)
+    .await?;
+
+    let content = format!(
+        "You're in the giveaway for **{}**, good luck! Press the button again to leave.",
+        giveaway.prize
+    );
+    respond(ctx, press, &content).await
+}
+
+/// Shows the winners of `giveaway` in its message, takes the button away
+/// and congratulates `winners` below it.
+async fn announce(
+    http: &Http,
+    pool: &PgPool,
+    giveaway: &Giveaway,
+    winners: &[i64],
+) -> Result<(), SlimeError> {
+    let Some(message_id) = giveaway.message_id else {
+        return Ok(());
+    };
+    let channel_id = ChannelId::new(giveaway.channel_id as u64);
+    let message_id = MessageId::new(message_id as u64);
+    let entries = entries(pool, giveaway.giveaway_id).await?;
+    let edit = EditMessage::new()
+        .embed(giveaway.ended_embed(entries))
+        .components(vec![]);
+    if let Err(e) = channel_id.edit_message(http, message_id, edit).await {
+        warn!(
+            "failed to show the winners of giveaway {}: {}",
+            giveaway.giveaway_id, e
+        );
+    }
+
+    let content = if winners.is_empty() {
+        format!("Nobody won **{}**.", giveaway.prize)
+    } else {
+        format!(
+            "Congratulations {}, you won **{}**!",
+            mentions(winners),
+            giveaway.prize
+        )
+    };
+    let message = CreateMessage::new()
+        .content(content)
+        .reference_message((channel_id, message_id))
+        .allowed_mentions(
+            CreateAllowedMentions::new()
+                .users(winners.iter().map(|&user_id| UserId::new(user_id as u64))),
+        );
+    channel_id.send_message(http, message).await?;
+
+    Ok(())
+}
+
+/// Ends every giveaway that ran out, the scheduler calls this. Winners are
+/// drawn in the same statement that marks a giveaway ended, so a restart
+/// can't end it without winners or draw them twice.
+pub async fn end_due(http: &Http, data: &Data) {
+    let due: Vec<Giveaway> = match sqlx::query_as(
+        "UPDATE giveaways SET ended = true, winners = ARRAY(
+            SELECT user_id FROM giveaway_entries
+            WHERE giveaway_entries.giveaway_id = giveaways.giveaway_id
+            ORDER BY random() LIMIT giveaways.winner_count
+        )
+        WHERE NOT ended AND ends_at <= now()
+        RETURNING giveaway_id, channel_id, message_id, created_by, prize, winner_count, ends_at,
+        ended, winners",
+    )
+    .fetch_all(&data.pool)
+    .await
+    {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to end due giveaways: {}", e);
+            return;
+        }
+    };
+
+    for giveaway in due {
+        if let Err(e) = announce(http, &data.pool, &giveaway, &giveaway.winners).await {
+            error!(
+                "failed to announce the winners of giveaway {}: {}",
+                giveaway.giveaway_id, e
+            );
+        }
+    }
+}
+
+/// Gives things away to members who enter
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("start", "end", "reroll")
+)]
+pub async fn giveaway(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Posts a giveaway members enter with a button
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn start(
+    ctx: Context<'_>,
+    #[description = "What the winners get"]
+    #[max_length = 200]
+    prize: String,
+    #[description = "How long members can enter, e.g. 2h or 3d"] duration: HumanDuration,
+    #[description = "How many winners to draw"]
+    #[min = 1]
+    #[max = 20]
+    winners: i32,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let duration = duration.as_duration();
+    if duration <= Duration::zero() || duration > Duration::days(MAX_GIVEAWAY_DAYS) {
+        let content = format!("Giveaways can run for at most {MAX_GIVEAWAY_DAYS} days.");
+        return reply(ctx, content).await;
+    }
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let giveaway: Giveaway = sqlx::query_as(
+        "INSERT INTO giveaways (guild_id, channel_id, created_by, prize, winner_count, ends_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING giveaway_id, channel_id, message_id, created_by, prize, winner_count, ends_at,
+        ended, winners",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.channel_id()))
+    .bind(i64::from(ctx.author().id))
+    .bind(&prize)
+    .bind(winners)
+    .bind(Utc::now() + duration)
+    .fetch_one(&data.pool)
+    .await?;
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let message = CreateMessage::new()
+        .embed(giveaway.open_embed(timezone))
+        .components(vec![giveaway.button()]);
+    let message = match ctx.channel_id().send_message(ctx, message).await {
+        Ok(message) => message,
+        Err(e) => {
+            sqlx::query("DELETE FROM giveaways WHERE giveaway_id = $1")
+                .bind(giveaway.giveaway_id)
+                .execute(&data.pool)
+                .await?;
+            return Err(e.into());
+        }
+    };
+    sqlx::query("UPDATE giveaways SET message_id = $2 WHERE giveaway_id = $1")
+        .bind(giveaway.giveaway_id)
+        .bind(i64::from(message.id))
+        .execute(&data.pool)
+        .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Giveaway `{}` is up, it ends at {}.",
+            giveaway.giveaway_id,
+            localtime::format_datetime(giveaway.ends_at, timezone)
+        ),
+    )
+    .await
+}
+
+/// Ends a giveaway early and draws its winners
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn end(
+    ctx: Context<'_>,
+    #[description = "Giveaway to end"]
+    #[min = 1]
+    id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let ended: Option<Giveaway> = sqlx::query_as(
+        "UPDATE giveaways SET ended = true, ends_at = least(ends_at, now()), winners = ARRAY(
+            SELECT user_id FROM giveaway_entries
+            WHERE giveaway_entries.giveaway_id = giveaways.giveaway_id
+            ORDER BY random() LIMIT giveaways.winner_count
+        )
+        WHERE guild_id = $1 AND giveaway_id = $2 AND NOT ended
+        RETURNING giveaway_id, channel_id, message_id, created_by, prize, winner_count, ends_at,
+        ended, winners",
+    )
+    .bind(i64::from(guild_id))
+    .bind(id)
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(giveaway) = ended else {
+        let content = match load(&data.pool, guild_id, id).await? {
+            Some(_) => format!("Giveaway `{id}` is over already."),
+            None => format!("There is no giveaway `{id}`."),
+        };
+        return reply(ctx, content).await;
+    };
+
+    announce(ctx.http(), &data.pool, &giveaway, &giveaway.winners).await?;
+    reply(ctx, format!("Giveaway `{id}` is over.")).await
+}
+
+/// Draws more winners for a giveaway that's over, e.g. when one never
+/// claimed their prize
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn reroll(
+    ctx: Context<'_>,
+    #[description = "Giveaway to draw again"]
+    #[min = 1]
+    id: i64,
+    #[description = "How many winners to draw (default 1)"]
+    #[min = 1]
+    #[max = 20]
+    winners: Option<i32>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(giveaway) = load(&data.pool, guild_id, id).await? else {
+        return reply(ctx, format!("There is no giveaway `{id}`.")).await;
+    };
+    if !giveaway.ended {
+        let content = format!("Giveaway `{id}` isn't over yet, end it with `/giveaway end`.");
+        return reply(ctx, content).await;
+    }
+
+    // Whoever won before can't win again.
+    let drawn: Vec<i64> = sqlx::query_scalar(
+        "SELECT user_id FROM giveaway_entries WHERE giveaway_id = $1 AND user_id <> ALL($2)
+        ORDER BY random() LIMIT $3",
+    )
+    .bind(id)
+    .bind(&giveaway.winners)
+    .bind(i64::from(winners.unwrap_or(1)))
+    .fetch_all(&data.pool)
+    .await?;
+    if drawn.is_empty() {
+        let content = format!("Everyone who entered giveaway `{id}` won already.");
+        return reply(ctx, content).await;
+    }
+    let giveaway: Giveaway = sqlx::query_as(
+        "UPDATE giveaways SET winners = winners || $2 WHERE giveaway_id = $1
+        RETURNING giveaway_id, channel_id, message_id, created_by, prize, winner_count, ends_at,
+        ended, winners",
+    )
+    .bind(id)
+    .bind(&drawn)
+    .fetch_one(&data.pool)
+    .await?;
+
+    announce(ctx.http(), &data.pool, &giveaway, &drawn).await?;
+    reply(
+        ctx,
+        format!("Drew {} more for giveaway `{id}`.", drawn.len()),
+    )
+    .await
+}
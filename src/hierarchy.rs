@@ -0,0 +1,53 @@
+use poise::serenity_prelude::*;
+
+use crate::{members::MemberCache, Context, SlimeError};
+
+/// The position of the highest of `roles`, 0 for members without any.
+fn top_position(guild: &PartialGuild, roles: &[RoleId]) -> u16 {
+    roles
+        .iter()
+        .filter_map(|role_id| guild.roles.get(role_id))
+        .map(|role| role.position)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Why neither the invoker nor the bot may act on `target`, if they may
+/// not. Only members whose highest role is above the target's can, and
+/// nobody can act on the owner. Users who aren't members can still be
+/// banned, so they only count as a problem if `must_be_member`.
+pub async fn target_problem(
+    ctx: Context<'_>,
+    guild: &PartialGuild,
+    target: UserId,
+    must_be_member: bool,
+) -> Result<Option<String>, SlimeError> {
+    if target == guild.owner_id {
+        return Ok(Some("Nobody can do that to the server owner.".to_string()));
+    }
+    let members = MemberCache::new(guild.id);
+    let http = ctx.http();
+    let Some(target_roles) = members.roles(http, target).await? else {
+        return Ok(must_be_member.then(|| format!("{} isn't a member.", target.mention())));
+    };
+    let target_top = top_position(guild, &target_roles);
+
+    let author = ctx.author().id;
+    if author != guild.owner_id {
+        let author_roles = members.roles(http, author).await?.unwrap_or_default();
+        if top_position(guild, &author_roles) <= target_top {
+            let content = format!("{}'s highest role isn't below yours.", target.mention());
+            return Ok(Some(content));
+        }
+    }
+    let bot_roles = members
+        .roles(http, ctx.framework().bot_id)
+        .await?
+        .unwrap_or_default();
+    if top_position(guild, &bot_roles) <= target_top {
+        let content = format!("{}'s highest role isn't below mine.", target.mention());
+        return Ok(Some(content));
+    }
+
+    Ok(None)
+}
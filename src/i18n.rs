@@ -0,0 +1,103 @@
+use crate::{Context, SlimeError};
+
+/// A user-facing string, see `tr`. Placeholders are written `{name}` and
+/// filled in by `tr_args`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Msg {
+    NothingToDelete,
+    /// `{what}` and `{eta}`.
+    WillBeDeleted,
+    /// `{what}` and `{eta}`, for purges of several channels.
+    WillBeDeletedInTurn,
+    /// `{first}` and `{last}`, links to messages.
+    FirstAndLast,
+    Continue,
+    Yes,
+    No,
+    NoAnswer,
+    Declined,
+    NotAllowed,
+    /// `{channel}`, for this and the other errors.
+    MissingAccess,
+    MissingPermissions,
+    RateLimited,
+    DiscordFailed,
+    DatabaseFailed,
+    /// `{channel}` and `{permissions}`.
+    MissingPermissionsIn,
+}
+
+fn english(msg: Msg) -> &'static str {
+    match msg {
+        Msg::NothingToDelete => "There are no messages to delete.",
+        Msg::WillBeDeleted => "{what} will be deleted, which should take about {eta}.",
+        Msg::WillBeDeletedInTurn => {
+            "{what} will be deleted, one channel after the other, which should take about {eta}."
+        }
+        Msg::FirstAndLast => "The first message to be deleted is {first}, the last is {last}.",
+        Msg::Continue => "Continue?",
+        Msg::Yes => "yes",
+        Msg::No => "no",
+        Msg::NoAnswer => "No answer, so nothing was done.",
+        Msg::Declined => "Okay, nothing was done.",
+        Msg::NotAllowed => "Only administrators and moderators can do that.",
+        Msg::MissingAccess => "I can't see {channel}, please give me access to it.",
+        Msg::MissingPermissions => {
+            "I lack the permissions for that in {channel}, I need Read Message History and Manage Messages."
+        }
+        Msg::RateLimited => "Discord is rate limiting me, please try again in a bit.",
+        Msg::DiscordFailed => "Discord didn't let me do that, please try again later.",
+        Msg::DatabaseFailed => "Something went wrong on my end, please try again later.",
+        Msg::MissingPermissionsIn => "I can't purge {channel}, I'm missing {permissions}.",
+    }
+}
+
+fn german(msg: Msg) -> &'static str {
+    match msg {
+        Msg::NothingToDelete => "Es gibt keine Nachrichten zu löschen.",
+        Msg::WillBeDeleted => "Gelöscht werden {what}, das dauert ungefähr {eta}.",
+        Msg::WillBeDeletedInTurn => {
+            "Gelöscht werden {what}, ein Kanal nach dem anderen, das dauert ungefähr {eta}."
+        }
+        Msg::FirstAndLast => "Die erste zu löschende Nachricht ist {first}, die letzte {last}.",
+        Msg::Continue => "Fortfahren?",
+        Msg::Yes => "ja",
+        Msg::No => "nein",
+        Msg::NoAnswer => "Keine Antwort, es wurde nichts getan.",
+        Msg::Declined => "Okay, es wurde nichts getan.",
+        Msg::NotAllowed => "Das dürfen nur Administratoren und Moderatoren.",
+        Msg::MissingAccess => "Ich kann {channel} nicht sehen, bitte gib mir Zugriff darauf.",
+        Msg::MissingPermissions => {
+            "Mir fehlen dafür Berechtigungen in {channel}, ich brauche Nachrichtenverlauf lesen und Nachrichten verwalten."
+        }
+        Msg::RateLimited => "Discord bremst mich gerade aus, bitte versuch es gleich noch einmal.",
+        Msg::DiscordFailed => "Discord hat das nicht zugelassen, bitte versuch es später noch einmal.",
+        Msg::DatabaseFailed => "Bei mir ist etwas schiefgelaufen, bitte versuch es später noch einmal.",
+        Msg::MissingPermissionsIn => "Ich kann {channel} nicht leeren, mir fehlt: {permissions}.",
+    }
+}
+
+/// `msg` in `locale`, a Discord locale like "de". Anything without a
+/// catalog gets English.
+pub fn tr(locale: Option<&str>, msg: Msg) -> &'static str {
+    match locale {
+        Some("de") => german(msg),
+        _ => english(msg),
+    }
+}
+
+/// `tr` with the placeholders in `args` filled in.
+pub fn tr_args(locale: Option<&str>, msg: Msg, args: &[(&str, &str)]) -> String {
+    args.iter()
+        .fold(tr(locale, msg).to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+}
+
+/// The locale set with `/config set locale` for the guild `ctx` is in.
+pub async fn locale(ctx: Context<'_>) -> Result<Option<String>, SlimeError> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(None);
+    };
+    Ok(ctx.data().settings.get(guild_id).await?.locale)
+}
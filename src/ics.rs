@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+
+/// The longest a line of an iCalendar file may be, in bytes.
+const MAX_LINE_LEN: usize = 75;
+
+/// An event as calendar apps see it.
+pub struct CalendarEvent {
+    /// Stays the same for the event across exports, so apps update it
+    /// rather than adding it twice.
+    pub uid: String,
+    pub summary: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    pub url: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+fn format_time(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes the characters that mean something in iCalendar text.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Adds `name:value` to `ics`, folded onto continuation lines that start
+/// with a space wherever it gets too long.
+fn push_line(ics: &mut String, name: &str, value: &str) {
+    let line = format!("{name}:{value}");
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > MAX_LINE_LEN {
+            ics.push_str("\r\n ");
+            len = 1;
+        }
+        ics.push(c);
+        len += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// Renders `events` as an iCalendar file called `name` in calendar apps.
+pub fn calendar(name: &str, events: &[CalendarEvent]) -> String {
+    let now = format_time(Utc::now());
+    let mut ics = String::new();
+    push_line(&mut ics, "BEGIN", "VCALENDAR");
+    push_line(&mut ics, "VERSION", "2.0");
+    push_line(&mut ics, "PRODID", "-//pond-slime//events//EN");
+    push_line(&mut ics, "X-WR-CALNAME", &escape(name));
+    for event in events {
+        push_line(&mut ics, "BEGIN", "VEVENT");
+        push_line(&mut ics, "UID", &event.uid);
+        push_line(&mut ics, "DTSTAMP", &now);
+        push_line(&mut ics, "DTSTART", &format_time(event.starts_at));
+        push_line(&mut ics, "DTEND", &format_time(event.ends_at));
+        push_line(&mut ics, "SUMMARY", &escape(&event.summary));
+        if let Some(description) = &event.description {
+            push_line(&mut ics, "DESCRIPTION", &escape(description));
+        }
+        if let Some(location) = &event.location {
+            push_line(&mut ics, "LOCATION", &escape(location));
+        }
+        push_line(&mut ics, "URL", &event.url);
+        push_line(&mut ics, "END", "VEVENT");
+    }
+    push_line(&mut ics, "END", "VCALENDAR");
+    ics
+}
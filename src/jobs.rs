@@ -0,0 +1,346 @@
+use std::{
+    fmt,
+    num::ParseIntError,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use poise::serenity_prelude::{ChannelId, GuildId, MessageId, UserId};
+use sqlx::{types::Json, PgPool};
+use tokio::sync::{Mutex, OwnedMutexGuard};
+use tokio_util::sync::CancellationToken;
+
+use crate::filter::MessageFilter;
+
+/// Identifies a running purge. This is the id of the interaction that
+/// started it, so it is unique and shown to the admin who ran the command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct JobId(pub u64);
+
+impl JobId {
+    /// A fresh id for jobs that weren't started by an interaction. Ids
+    /// generated in the same microsecond still differ.
+    pub fn generate() -> Self {
+        static LAST: AtomicU64 = AtomicU64::new(0);
+
+        let now = Utc::now().timestamp_micros() as u64;
+        let previous = LAST
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+                Some(now.max(last + 1))
+            })
+            .unwrap();
+        JobId(now.max(previous + 1))
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for JobId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().trim_matches('`').parse().map(JobId)
+    }
+}
+
+struct Job {
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    cancel: CancellationToken,
+    /// Whoever started the job, `None` for automatic ones.
+    started_by: Option<UserId>,
+    /// Whether the job is waiting for its guild's other jobs to finish.
+    queued: AtomicBool,
+    /// Published by the job as it goes, see `JobGuard::publish_progress`.
+    deleted: AtomicUsize,
+    bulk_remaining: AtomicUsize,
+    slow_remaining: AtomicUsize,
+}
+
+/// A registered job, as listed by `JobRegistry::in_guild`.
+pub struct JobInfo {
+    pub id: JobId,
+    pub channel_id: ChannelId,
+    pub started_by: Option<UserId>,
+    pub queued: bool,
+    pub deleted: usize,
+    pub bulk_remaining: usize,
+    pub slow_remaining: usize,
+}
+
+/// Every deletion job currently running, so they can be cancelled from
+/// outside the command that started them.
+///
+/// Jobs in the same guild take turns, see `JobGuard::wait_turn`, so they
+/// don't compete for the same rate limits.
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: DashMap<JobId, Job>,
+    queues: DashMap<GuildId, Arc<Mutex<()>>>,
+}
+
+impl JobRegistry {
+    /// Registers a new job. It stays registered until the returned guard is
+    /// dropped.
+    pub fn register(
+        self: &Arc<Self>,
+        id: JobId,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        started_by: Option<UserId>,
+    ) -> JobGuard {
+        let cancel = CancellationToken::new();
+        self.jobs.insert(
+            id,
+            Job {
+                guild_id,
+                channel_id,
+                cancel: cancel.clone(),
+                started_by,
+                queued: AtomicBool::new(false),
+                deleted: AtomicUsize::new(0),
+                bulk_remaining: AtomicUsize::new(0),
+                slow_remaining: AtomicUsize::new(0),
+            },
+        );
+
+        JobGuard {
+            registry: Arc::clone(self),
+            id,
+            guild_id,
+            cancel,
+        }
+    }
+
+    fn queue(&self, guild_id: GuildId) -> Arc<Mutex<()>> {
+        Arc::clone(&self.queues.entry(guild_id).or_default())
+    }
+
+    /// The jobs registered in `guild_id`, oldest first.
+    pub fn in_guild(&self, guild_id: GuildId) -> Vec<JobInfo> {
+        let mut jobs: Vec<_> = self
+            .jobs
+            .iter()
+            .filter(|job| job.guild_id == guild_id)
+            .map(|job| JobInfo {
+                id: *job.key(),
+                channel_id: job.channel_id,
+                started_by: job.started_by,
+                queued: job.queued.load(Ordering::Relaxed),
+                deleted: job.deleted.load(Ordering::Relaxed),
+                bulk_remaining: job.bulk_remaining.load(Ordering::Relaxed),
+                slow_remaining: job.slow_remaining.load(Ordering::Relaxed),
+            })
+            .collect();
+        jobs.sort_by_key(|job| job.id.0);
+        jobs
+    }
+
+    /// Cancels the job with the given id, as long as it belongs to `guild_id`.
+    pub fn cancel(&self, guild_id: GuildId, id: JobId) -> bool {
+        match self.jobs.get(&id) {
+            Some(job) if job.guild_id == guild_id => {
+                job.cancel.cancel();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether any job is currently running in `channel_id`.
+    pub fn running_in(&self, channel_id: ChannelId) -> bool {
+        self.jobs.iter().any(|job| job.channel_id == channel_id)
+    }
+
+    /// Cancels every job running in `channel_id`, returning how many there were.
+    pub fn cancel_channel(&self, channel_id: ChannelId) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| job.channel_id == channel_id)
+            .inspect(|job| job.cancel.cancel())
+            .count()
+    }
+
+    /// Cancels every job running in `guild_id`, returning how many there were.
+    pub fn cancel_guild(&self, guild_id: GuildId) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| job.guild_id == guild_id)
+            .inspect(|job| job.cancel.cancel())
+            .count()
+    }
+}
+
+/// Keeps a job registered while it runs.
+pub struct JobGuard {
+    registry: Arc<JobRegistry>,
+    pub id: JobId,
+    guild_id: GuildId,
+    pub cancel: CancellationToken,
+}
+
+impl JobGuard {
+    /// Takes the guild's turn if no other job in it is deleting right now.
+    pub fn try_turn(&self) -> Option<OwnedMutexGuard<()>> {
+        self.registry.queue(self.guild_id).try_lock_owned().ok()
+    }
+
+    /// Waits until every job in the guild that queued up earlier is done.
+    /// The turn lasts until the returned guard is dropped. Returns `None` if
+    /// the job was cancelled while waiting.
+    pub async fn wait_turn(&self) -> Option<OwnedMutexGuard<()>> {
+        let queue = self.registry.queue(self.guild_id);
+        self.set_queued(true);
+        let turn = tokio::select! {
+            turn = queue.lock_owned() => Some(turn),
+            () = self.cancel.cancelled() => None,
+        };
+        self.set_queued(false);
+
+        turn
+    }
+
+    /// Shares how far along the job is with `JobRegistry::in_guild`.
+    pub fn publish_progress(&self, deleted: usize, bulk_remaining: usize, slow_remaining: usize) {
+        if let Some(job) = self.registry.jobs.get(&self.id) {
+            job.deleted.store(deleted, Ordering::Relaxed);
+            job.bulk_remaining.store(bulk_remaining, Ordering::Relaxed);
+            job.slow_remaining.store(slow_remaining, Ordering::Relaxed);
+        }
+    }
+
+    fn set_queued(&self, queued: bool) {
+        if let Some(job) = self.registry.jobs.get(&self.id) {
+            job.queued.store(queued, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        self.registry.jobs.remove(&self.id);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobState {
+    Queued,
+    Running,
+    Finished,
+    Cancelled,
+    Failed,
+}
+
+impl JobState {
+    fn as_str(self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Finished => "finished",
+            JobState::Cancelled => "cancelled",
+            JobState::Failed => "failed",
+        }
+    }
+}
+
+/// A purge as stored in the `purge_jobs` table, with enough information to
+/// pick it back up after a restart.
+pub struct StoredJob {
+    pub id: JobId,
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub filter: MessageFilter,
+    pub cutoff: DateTime<Utc>,
+    /// The oldest message deleted so far. Everything newer has been handled.
+    pub last_message_id: Option<MessageId>,
+    /// For range purges and `purge_after`, the oldest message to delete.
+    pub oldest_message_id: Option<MessageId>,
+}
+
+#[derive(sqlx::FromRow)]
+struct JobRow {
+    job_id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    filter: Json<MessageFilter>,
+    cutoff: DateTime<Utc>,
+    last_message_id: Option<i64>,
+    oldest_message_id: Option<i64>,
+}
+
+impl From<JobRow> for StoredJob {
+    fn from(row: JobRow) -> Self {
+        StoredJob {
+            id: JobId(row.job_id as u64),
+            guild_id: GuildId::new(row.guild_id as u64),
+            channel_id: ChannelId::new(row.channel_id as u64),
+            filter: row.filter.0,
+            cutoff: row.cutoff,
+            last_message_id: row.last_message_id.map(|id| MessageId::new(id as u64)),
+            oldest_message_id: row.oldest_message_id.map(|id| MessageId::new(id as u64)),
+        }
+    }
+}
+
+pub async fn insert(pool: &PgPool, job: &StoredJob) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO purge_jobs
+        (job_id, guild_id, channel_id, filter, cutoff, last_message_id, oldest_message_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(job.id.0 as i64)
+    .bind(i64::from(job.guild_id))
+    .bind(i64::from(job.channel_id))
+    .bind(Json(&job.filter))
+    .bind(job.cutoff)
+    .bind(job.last_message_id.map(i64::from))
+    .bind(job.oldest_message_id.map(i64::from))
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Remembers that everything up to and including `last_message_id` was deleted.
+pub async fn checkpoint(pool: &PgPool, id: JobId, last_message_id: MessageId) -> sqlx::Result<()> {
+    sqlx::query("UPDATE purge_jobs SET last_message_id = $2 WHERE job_id = $1")
+        .bind(id.0 as i64)
+        .bind(i64::from(last_message_id))
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn set_state(pool: &PgPool, id: JobId, state: JobState) -> sqlx::Result<()> {
+    sqlx::query("UPDATE purge_jobs SET state = $2 WHERE job_id = $1")
+        .bind(id.0 as i64)
+        .bind(state.as_str())
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Jobs that were still running or queued when the bot last shut down.
+pub async fn unfinished(pool: &PgPool) -> sqlx::Result<Vec<StoredJob>> {
+    let rows: Vec<JobRow> = sqlx::query_as(
+        "SELECT job_id, guild_id, channel_id, filter, cutoff, last_message_id, oldest_message_id
+        FROM purge_jobs WHERE state IN ($1, $2) ORDER BY created_at",
+    )
+    .bind(JobState::Running.as_str())
+    .bind(JobState::Queued.as_str())
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(StoredJob::from).collect())
+}
@@ -0,0 +1,187 @@
+use poise::{
+    serenity_prelude::{self as serenity, *},
+    CreateReply,
+};
+use tracing::warn;
+
+use crate::{replies::reply, settings::ChannelPurpose, Context, Data, SlimeError};
+
+/// How much XP a message earns.
+const XP_PER_MESSAGE: i64 = 20;
+
+/// How long after earning XP a member's messages earn none, so spamming
+/// doesn't pay.
+const XP_COOLDOWN_SECONDS: f64 = 60.0;
+
+/// How many members `/leaderboard` shows.
+const LEADERBOARD_SIZE: i64 = 10;
+
+/// How much XP it takes to get from `level` to the next one.
+fn xp_to_next(level: u32) -> i64 {
+    let level = i64::from(level);
+    5 * level * level + 50 * level + 100
+}
+
+/// The level a member with `xp` XP is at, and how much XP they have
+/// towards the next one.
+fn level(mut xp: i64) -> (u32, i64) {
+    let mut level = 0;
+    while xp >= xp_to_next(level) {
+        xp -= xp_to_next(level);
+        level += 1;
+    }
+    (level, xp)
+}
+
+/// Gives the author of `message` XP, unless they got some too recently.
+/// Members who reach a new level get the roles for it, and are announced in
+/// the level channel if there is one.
+pub async fn message_sent(
+    ctx: &serenity::Context,
+    data: &Data,
+    message: &Message,
+) -> Result<(), SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    if message.author.bot {
+        return Ok(());
+    }
+    let settings = data.settings.get(guild_id).await?;
+    if !settings.leveling() {
+        return Ok(());
+    }
+
+    let user_id = message.author.id;
+    let xp: Option<i64> = sqlx::query_scalar(
+        "INSERT INTO member_xp (guild_id, user_id, xp) VALUES ($1, $2, $3)
+        ON CONFLICT (guild_id, user_id) DO UPDATE
+        SET xp = member_xp.xp + EXCLUDED.xp, last_awarded_at = now()
+        WHERE member_xp.last_awarded_at <= now() - make_interval(secs => $4)
+        RETURNING xp",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user_id))
+    .bind(XP_PER_MESSAGE)
+    .bind(XP_COOLDOWN_SECONDS)
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(xp) = xp else {
+        return Ok(());
+    };
+    let (before, _) = level(xp - XP_PER_MESSAGE);
+    let (after, _) = level(xp);
+    if after == before {
+        return Ok(());
+    }
+
+    for (&reached, &role_id) in settings.level_roles.range(before + 1..=after) {
+        let given = ctx
+            .http
+            .add_member_role(guild_id, user_id, role_id, Some("Level reward"))
+            .await;
+        if let Err(e) = given {
+            warn!(
+                "failed to give {} the role for level {} in guild {}: {}",
+                user_id, reached, guild_id, e
+            );
+        }
+    }
+
+    if let Some(channel) = settings.channel(ChannelPurpose::Levels) {
+        let announcement = CreateMessage::new()
+            .content(format!("{} reached level **{after}**!", user_id.mention()))
+            .allowed_mentions(CreateAllowedMentions::new().users([user_id]));
+        if let Err(e) = channel.send_message(ctx, announcement).await {
+            warn!("failed to announce a level up in guild {}: {}", guild_id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows the level and rank of a member
+#[poise::command(slash_command, guild_only)]
+pub async fn rank(
+    ctx: Context<'_>,
+    #[description = "Member to look up (default you)"] member: Option<Member>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    if !data.settings.get(guild_id).await?.leveling() {
+        return reply(ctx, "Leveling is off in this server.".to_string()).await;
+    }
+    let user = member.map_or_else(|| ctx.author().clone(), |member| member.user);
+
+    let standing: Option<(i64, i64)> = sqlx::query_as(
+        "SELECT xp, (
+            SELECT count(*) FROM member_xp AS other
+            WHERE other.guild_id = member_xp.guild_id AND other.xp > member_xp.xp
+        ) + 1
+        FROM member_xp WHERE guild_id = $1 AND user_id = $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user.id))
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some((xp, position)) = standing else {
+        let content = format!("{} hasn't earned any XP yet.", user.mention());
+        return reply(ctx, content).await;
+    };
+    let (level, progress) = level(xp);
+
+    let embed = CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(user.tag()).icon_url(user.face()))
+        .colour(Colour::BLURPLE)
+        .field("Level", level.to_string(), true)
+        .field("Rank", format!("#{position}"), true)
+        .field("XP", xp.to_string(), true)
+        .field(
+            "Next level",
+            format!("{progress}/{} XP", xp_to_next(level)),
+            false,
+        );
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Shows the members with the most XP
+#[poise::command(slash_command, guild_only)]
+pub async fn leaderboard(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    if !data.settings.get(guild_id).await?.leveling() {
+        return reply(ctx, "Leveling is off in this server.".to_string()).await;
+    }
+
+    let top: Vec<(i64, i64)> = sqlx::query_as(
+        "SELECT user_id, xp FROM member_xp WHERE guild_id = $1 ORDER BY xp DESC LIMIT $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(LEADERBOARD_SIZE)
+    .fetch_all(&data.pool)
+    .await?;
+    if top.is_empty() {
+        return reply(ctx, "Nobody has earned any XP yet.".to_string()).await;
+    }
+
+    let standings = top
+        .into_iter()
+        .enumerate()
+        .map(|(index, (user_id, xp))| {
+            format!(
+                "{}. {}: level {} ({xp} XP)",
+                index + 1,
+                UserId::new(user_id as u64).mention(),
+                level(xp).0
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let embed = CreateEmbed::new()
+        .title("Leaderboard")
+        .colour(Colour::BLURPLE)
+        .description(standings);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use poise::serenity_prelude::{
+    self as serenity, CreateMessage, FullEvent, Guild, Member, Message, UnavailableGuild,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    afk, agegate, automod, autorole, birthdays, bookmarks, db, events, giveaways, leveling, msglog,
+    pinboard, polls, raid, rolemenu, snipe, starboard, stats, sticky, suggestions, threads,
+    tickets, verify, welcome, Data, SlimeError,
+};
+
+const SETUP_HINT: &str = "Thanks for adding me! Pick a channel for my status updates with \
+`/admin_bot_spam_channel set`, and see `/config list` for everything else I can be told.";
+
+/// Keeps the database in step with the guilds the bot is in, and passes
+/// message events on to automod, the message log, snipes, AFK notes,
+/// stickies, channel stats and leveling, joins on to raid detection, the
+/// account age gate, autoroles and welcome messages, and leaves on to
+/// farewell messages and birthdays. Reactions and edits go to the starboard,
+/// emoji stats and bookmarks, new pins to the pin archive, new threads to
+/// their channel's thread policy, and server events and RSVPs to their
+/// announcements, reminders and attendance.
+/// Verify, poll, suggestion, ticket and giveaway buttons and role menus are
+/// handled here too, since they outlive any one command.
+pub async fn handle_event(
+    ctx: &serenity::Context,
+    event: &FullEvent,
+    data: &Data,
+) -> Result<(), SlimeError> {
+    match event {
+        FullEvent::GuildCreate { guild, is_new } => {
+            guild_joined(ctx, data, guild, *is_new == Some(true)).await
+        }
+        FullEvent::GuildDelete { incomplete, .. } => guild_left(data, incomplete).await,
+        FullEvent::InteractionCreate { interaction } => {
+            log_failed("verify", verify::interaction(ctx, data, interaction).await);
+            log_failed("polls", polls::interaction(ctx, data, interaction).await);
+            log_failed(
+                "rolemenu",
+                rolemenu::interaction(ctx, data, interaction).await,
+            );
+            let suggestions = suggestions::interaction(ctx, data, interaction).await;
+            log_failed("suggestions", suggestions);
+            log_failed(
+                "tickets",
+                tickets::interaction(ctx, data, interaction).await,
+            );
+            log_failed(
+                "giveaways",
+                giveaways::interaction(ctx, data, interaction).await,
+            );
+            Ok(())
+        }
+        FullEvent::GuildMemberAddition { new_member } => member_joined(ctx, data, new_member).await,
+        FullEvent::GuildMemberRemoval { guild_id, user, .. } => {
+            log_failed(
+                "birthdays",
+                birthdays::member_left(data, *guild_id, user).await,
+            );
+            log_failed(
+                "welcome",
+                welcome::member_left(ctx, data, *guild_id, user).await,
+            );
+            Ok(())
+        }
+        FullEvent::GuildScheduledEventCreate { event } => {
+            events::event_created(ctx, data, event).await
+        }
+        FullEvent::GuildScheduledEventUpdate { event } => events::event_updated(data, event).await,
+        FullEvent::GuildScheduledEventDelete { event } => events::event_deleted(data, event).await,
+        FullEvent::GuildScheduledEventUserAdd { subscribed } => {
+            events::rsvp_added(data, subscribed).await
+        }
+        FullEvent::GuildScheduledEventUserRemove { unsubscribed } => {
+            events::rsvp_removed(data, unsubscribed).await
+        }
+        FullEvent::Message { new_message } => message_sent(ctx, data, new_message).await,
+        FullEvent::MessageUpdate { event, .. } => {
+            snipe::message_edited(data, event);
+            log_failed("msglog", msglog::message_edited(ctx, data, event).await);
+            log_failed(
+                "starboard",
+                starboard::message_edited(ctx, data, event).await,
+            );
+            Ok(())
+        }
+        FullEvent::ReactionAdd { add_reaction } => {
+            log_failed("stats", stats::reaction_added(data, add_reaction).await);
+            let bookmarks = bookmarks::reaction_added(ctx, data, add_reaction).await;
+            log_failed("bookmarks", bookmarks);
+            let starboard = starboard::reaction_changed(ctx, data, add_reaction).await;
+            log_failed("starboard", starboard);
+            Ok(())
+        }
+        FullEvent::ReactionRemove { removed_reaction } => {
+            starboard::reaction_changed(ctx, data, removed_reaction).await
+        }
+        FullEvent::ReactionRemoveEmoji { removed_reactions } => {
+            starboard::reaction_changed(ctx, data, removed_reactions).await
+        }
+        FullEvent::ReactionRemoveAll {
+            channel_id,
+            removed_from_message_id,
+        } => starboard::reactions_cleared(ctx, data, *channel_id, *removed_from_message_id).await,
+        FullEvent::ThreadCreate { thread } => threads::thread_created(ctx, data, thread).await,
+        FullEvent::ChannelPinsUpdate { pin } => pinboard::pins_updated(ctx, data, pin).await,
+        FullEvent::MessageDelete {
+            channel_id,
+            deleted_message_id,
+            guild_id,
+        } => {
+            snipe::message_deleted(data, *channel_id, *deleted_message_id);
+            msglog::message_deleted(ctx, data, *guild_id, *channel_id, *deleted_message_id).await
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Logs what went wrong in `handler`, so that one failing handler doesn't
+/// keep the others from seeing an event.
+fn log_failed(handler: &str, result: Result<(), SlimeError>) {
+    if let Err(e) = result {
+        error!("{} failed to handle an event: {}", handler, e);
+    }
+}
+
+/// Discord sends `GuildCreate` for every guild on startup too, so only
+/// guilds that are `new`, the bot just joined them, get the setup hint.
+/// Guilds the bot was in before it had a database don't.
+async fn guild_joined(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild: &Guild,
+    new: bool,
+) -> Result<(), SlimeError> {
+    db::ensure_guild(&data.pool, guild.id).await?;
+    if !new {
+        return Ok(());
+    }
+    info!("joined guild {}", guild.id);
+
+    let Some(channel) = guild.system_channel_id else {
+        return Ok(());
+    };
+    if let Err(e) = channel
+        .send_message(ctx, CreateMessage::new().content(SETUP_HINT))
+        .await
+    {
+        warn!("failed to post the setup hint in guild {}: {}", guild.id, e);
+    }
+
+    Ok(())
+}
+
+/// Messages automod deleted are logged as such, so the message log skips
+/// them, and they aren't counted or earn XP. The others go to every handler,
+/// whether or not the ones before it failed.
+async fn message_sent(
+    ctx: &serenity::Context,
+    data: &Data,
+    message: &Message,
+) -> Result<(), SlimeError> {
+    match automod::check_message(ctx, data, message).await {
+        Ok(true) => return Ok(()),
+        Ok(false) => {}
+        Err(e) => error!("automod failed to check message {}: {}", message.id, e),
+    }
+    log_failed("msglog", msglog::message_sent(data, message).await);
+    log_failed("snipe", snipe::message_sent(data, message).await);
+    log_failed("afk", afk::message_sent(ctx, data, message).await);
+    log_failed("sticky", sticky::message_sent(ctx, data, message).await);
+    log_failed("stats", stats::message_sent(data, message).await);
+    log_failed("leveling", leveling::message_sent(ctx, data, message).await);
+    Ok(())
+}
+
+/// Raid mode kicks first, the age gate only sees members it let in, and
+/// only members both let in get autoroles and a welcome, whether or not
+/// giving the autoroles worked.
+async fn member_joined(
+    ctx: &serenity::Context,
+    data: &Data,
+    member: &Member,
+) -> Result<(), SlimeError> {
+    if raid::member_joined(ctx, data, member).await? {
+        return Ok(());
+    }
+    if agegate::check_member(ctx, data, member).await? {
+        return Ok(());
+    }
+    log_failed("autorole", autorole::member_joined(ctx, data, member).await);
+    log_failed("welcome", welcome::member_joined(ctx, data, member).await);
+    Ok(())
+}
+
+async fn guild_left(data: &Data, guild: &UnavailableGuild) -> Result<(), SlimeError> {
+    // An outage makes guilds unavailable too, but the bot is still in them.
+    if guild.unavailable {
+        return Ok(());
+    }
+    info!("left guild {}", guild.id);
+
+    data.jobs.cancel_guild(guild.id);
+    data.settings.forget(guild.id);
+    data.automod.forget(guild.id);
+    data.raids.forget(guild.id);
+    db::forget_guild(&data.pool, guild.id).await?;
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, NaiveTime, Utc};
+use poise::serenity_prelude::Timestamp;
+
+/// How a timezone is written in replies, e.g. "UTC" or "UTC+02:00".
+pub fn zone_name(timezone: Option<FixedOffset>) -> String {
+    match timezone.filter(|timezone| timezone.local_minus_utc() != 0) {
+        Some(timezone) => format!("UTC{timezone}"),
+        None => "UTC".to_string(),
+    }
+}
+
+fn offset(timezone: Option<FixedOffset>) -> Duration {
+    Duration::seconds(
+        timezone
+            .map_or(0, |timezone| timezone.local_minus_utc())
+            .into(),
+    )
+}
+
+/// A time Discord sent, to compare and format like any other.
+pub fn from_timestamp(timestamp: Timestamp) -> DateTime<Utc> {
+    DateTime::from_timestamp(timestamp.unix_timestamp(), 0).unwrap_or_default()
+}
+
+/// `at` the way Discord takes times.
+pub fn to_timestamp(at: DateTime<Utc>) -> Timestamp {
+    Timestamp::from_unix_timestamp(at.timestamp()).unwrap_or_default()
+}
+
+/// What the clocks in the guild's timezone show at `at`.
+pub fn to_local(at: DateTime<Utc>, timezone: Option<FixedOffset>) -> NaiveDateTime {
+    at.naive_utc() + offset(timezone)
+}
+
+/// Renders `at` in the guild's timezone, e.g. "2024-05-01 05:00 UTC+02:00".
+pub fn format_datetime(at: DateTime<Utc>, timezone: Option<FixedOffset>) -> String {
+    let local = to_local(at, timezone);
+    format!("{} {}", local.format("%Y-%m-%d %H:%M"), zone_name(timezone))
+}
+
+/// Renders a daily time of day kept in UTC in the guild's timezone, e.g.
+/// "06:00 UTC+02:00".
+pub fn format_time_of_day(time: NaiveTime, timezone: Option<FixedOffset>) -> String {
+    let local = time + offset(timezone);
+    format!("{} {}", local.format("%H:%M"), zone_name(timezone))
+}
+
+/// The UTC moment a time typed in the guild's timezone stands for.
+pub fn datetime_to_utc(local: NaiveDateTime, timezone: Option<FixedOffset>) -> DateTime<Utc> {
+    (local - offset(timezone)).and_utc()
+}
+
+/// The UTC time of day a time of day in the guild's timezone stands for.
+pub fn time_of_day_to_utc(local: NaiveTime, timezone: Option<FixedOffset>) -> NaiveTime {
+    local - offset(timezone)
+}
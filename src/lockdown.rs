@@ -0,0 +1,276 @@
+use std::{collections::HashMap, sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{cases, duration::HumanDuration, localtime, replies::reply, Context, Data, SlimeError};
+
+/// The error code Discord answers with for channels that were deleted.
+pub const UNKNOWN_CHANNEL: isize = 10003;
+
+/// How often the background task looks for lockdowns that ran out.
+const UNLOCK_INTERVAL: StdDuration = StdDuration::from_secs(30);
+
+/// A locked channel, from the `lockdowns` table. The previous permissions
+/// are `None` if @everyone had no overwrite in the channel before.
+#[derive(sqlx::FromRow)]
+struct Lockdown {
+    guild_id: i64,
+    channel_id: i64,
+    previous_allow: Option<i64>,
+    previous_deny: Option<i64>,
+}
+
+impl Lockdown {
+    fn guild_id(&self) -> GuildId {
+        GuildId::new(self.guild_id as u64)
+    }
+
+    fn channel_id(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+}
+
+fn everyone(guild_id: GuildId) -> PermissionOverwriteType {
+    PermissionOverwriteType::Role(guild_id.everyone_role())
+}
+
+/// The channel itself, and for a category every channel in it too.
+async fn targets(http: &Http, channel: GuildChannel) -> Result<Vec<GuildChannel>, SlimeError> {
+    if channel.kind != ChannelType::Category {
+        return Ok(vec![channel]);
+    }
+
+    let mut targets: Vec<_> = channel
+        .guild_id
+        .channels(http)
+        .await?
+        .into_values()
+        .filter(|child| child.parent_id == Some(channel.id))
+        .collect();
+    targets.sort_by_key(|child| child.position);
+    targets.insert(0, channel);
+    Ok(targets)
+}
+
+/// Takes SEND_MESSAGES away from @everyone in `channel` and remembers the
+/// overwrite it had before. Returns false if the channel is locked already.
+async fn lock(
+    http: &Http,
+    pool: &PgPool,
+    channel: &GuildChannel,
+    locked_by: UserId,
+    unlock_at: Option<DateTime<Utc>>,
+) -> Result<bool, SlimeError> {
+    let kind = everyone(channel.guild_id);
+    let previous = channel
+        .permission_overwrites
+        .iter()
+        .find(|overwrite| overwrite.kind == kind);
+
+    let locked = sqlx::query(
+        "INSERT INTO lockdowns
+        (guild_id, channel_id, locked_by, previous_allow, previous_deny, unlock_at)
+        VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING",
+    )
+    .bind(i64::from(channel.guild_id))
+    .bind(i64::from(channel.id))
+    .bind(i64::from(locked_by))
+    .bind(previous.map(|overwrite| overwrite.allow.bits() as i64))
+    .bind(previous.map(|overwrite| overwrite.deny.bits() as i64))
+    .bind(unlock_at)
+    .execute(pool)
+    .await?
+    .rows_affected()
+        > 0;
+    if !locked {
+        return Ok(false);
+    }
+
+    let overwrite = PermissionOverwrite {
+        allow: previous.map_or(Permissions::empty(), |overwrite| overwrite.allow)
+            - Permissions::SEND_MESSAGES,
+        deny: previous.map_or(Permissions::empty(), |overwrite| overwrite.deny)
+            | Permissions::SEND_MESSAGES,
+        kind,
+    };
+    if let Err(e) = channel.id.create_permission(http, overwrite).await {
+        remove_lockdown(pool, channel.guild_id, channel.id).await?;
+        return Err(e.into());
+    }
+    Ok(true)
+}
+
+async fn remove_lockdown(
+    pool: &PgPool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM lockdowns WHERE guild_id = $1 AND channel_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(i64::from(channel_id))
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Gives @everyone back the overwrite it had before the lockdown. A channel
+/// somebody deleted since counts as unlocked.
+async fn unlock(http: &Http, pool: &PgPool, lockdown: &Lockdown) -> Result<(), SlimeError> {
+    let channel_id = lockdown.channel_id();
+    let kind = everyone(lockdown.guild_id());
+    let result = match (lockdown.previous_allow, lockdown.previous_deny) {
+        (Some(allow), Some(deny)) => {
+            let overwrite = PermissionOverwrite {
+                allow: Permissions::from_bits_truncate(allow as u64),
+                deny: Permissions::from_bits_truncate(deny as u64),
+                kind,
+            };
+            channel_id.create_permission(http, overwrite).await
+        }
+        _ => channel_id.delete_permission(http, kind).await,
+    };
+    match result {
+        Ok(()) => {}
+        Err(Error::Http(HttpError::UnsuccessfulRequest(response)))
+            if response.error.code == UNKNOWN_CHANNEL => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    Ok(remove_lockdown(pool, lockdown.guild_id(), channel_id).await?)
+}
+
+async fn due_unlocks(pool: &PgPool) -> sqlx::Result<Vec<Lockdown>> {
+    sqlx::query_as(
+        "SELECT guild_id, channel_id, previous_allow, previous_deny FROM lockdowns
+        WHERE unlock_at <= now()",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Lifts lockdowns once they run out and announces it in each guild's log
+/// channel. Lockdowns that ran out while the bot was down are lifted on the
+/// first check after it starts.
+pub async fn run_unlocks(http: Arc<Http>, data: Data) {
+    let mut interval = tokio::time::interval(UNLOCK_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let due = match due_unlocks(&data.pool).await {
+            Ok(due) => due,
+            Err(e) => {
+                error!("failed to load due unlocks: {}", e);
+                continue;
+            }
+        };
+
+        // A failed unlock stays in the table and is tried again next time.
+        let mut unlocked: HashMap<GuildId, Vec<String>> = HashMap::new();
+        for lockdown in due {
+            match unlock(&http, &data.pool, &lockdown).await {
+                Ok(()) => unlocked
+                    .entry(lockdown.guild_id())
+                    .or_default()
+                    .push(lockdown.channel_id().mention().to_string()),
+                Err(e) => error!(
+                    "failed to unlock channel {} in guild {}: {}",
+                    lockdown.channel_id, lockdown.guild_id, e
+                ),
+            }
+        }
+        for (guild_id, channels) in unlocked {
+            let content = format!("The lockdown of {} is over.", channels.join(", "));
+            cases::announce(&http, &data, guild_id, &content).await;
+        }
+    }
+}
+
+/// Stops everyone from sending messages in a channel or category
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn lockdown(
+    ctx: Context<'_>,
+    #[description = "Channel or category to lock"]
+    #[channel_types("Text", "News", "Category")]
+    channel: GuildChannel,
+    #[description = "Unlock it again after this long, e.g. 1h (default never)"] duration: Option<
+        HumanDuration,
+    >,
+    #[description = "Why it's locked"]
+    #[max_length = 512]
+    reason: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    if duration.is_some_and(|duration| duration.as_duration() <= chrono::Duration::zero()) {
+        return reply(ctx, "The lockdown has to last a while.".to_string()).await;
+    }
+
+    let unlock_at = duration.map(|duration| Utc::now() + duration.as_duration());
+    let mention = channel.mention();
+    let mut locked = 0;
+    for target in targets(ctx.http(), channel).await? {
+        if lock(ctx.http(), &data.pool, &target, ctx.author().id, unlock_at).await? {
+            locked += 1;
+        }
+    }
+    if locked == 0 {
+        return reply(ctx, format!("{mention} is locked already.")).await;
+    }
+
+    let until = match unlock_at {
+        Some(unlock_at) => {
+            let timezone = data.settings.get(guild_id).await?.timezone;
+            format!("until {}", localtime::format_datetime(unlock_at, timezone))
+        }
+        None => "until it's unlocked".to_string(),
+    };
+    let reason = reason.map_or(String::new(), |reason| format!(": {reason}"));
+    let content = format!(
+        "{} locked down {mention} {until}{reason}",
+        ctx.author().mention()
+    );
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
+
+/// Lets everyone send messages in a locked channel or category again
+#[poise::command(slash_command, guild_only, category = "Moderation", rename = "unlock")]
+pub async fn unlock_channel(
+    ctx: Context<'_>,
+    #[description = "Channel or category to unlock"]
+    #[channel_types("Text", "News", "Category")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let mention = channel.mention();
+    let channel_ids: Vec<i64> = targets(ctx.http(), channel)
+        .await?
+        .iter()
+        .map(|target| i64::from(target.id))
+        .collect();
+    let lockdowns: Vec<Lockdown> = sqlx::query_as(
+        "SELECT guild_id, channel_id, previous_allow, previous_deny FROM lockdowns
+        WHERE guild_id = $1 AND channel_id = ANY($2)",
+    )
+    .bind(i64::from(guild_id))
+    .bind(&channel_ids)
+    .fetch_all(&data.pool)
+    .await?;
+    if lockdowns.is_empty() {
+        return reply(ctx, format!("{mention} isn't locked.")).await;
+    }
+
+    for lockdown in &lockdowns {
+        unlock(ctx.http(), &data.pool, lockdown).await?;
+    }
+    let content = format!("{} unlocked {mention}.", ctx.author().mention());
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
@@ -1,121 +1,327 @@
+use std::{net::SocketAddr, sync::Arc};
+
 use anyhow::anyhow;
 use serenity::Error as SerenityError;
 use shuttle_secrets::SecretStore;
 use thiserror::Error;
-use tracing::error;
 
-use poise::{serenity_prelude::*, CreateReply};
+use poise::serenity_prelude::*;
+
+use automod::AutomodCache;
+use jobs::JobRegistry;
+use raid::RaidTracker;
+use ratelimit::{Governor, RatelimitHandler};
+use settings::SettingsCache;
+use snipe::SnipeCache;
+use spam::SpamTracker;
+use sticky::StickyCache;
+use twitch::TwitchApi;
+use webhooks::{Relay, RelayKeys};
+
+mod admin;
+mod afk;
+mod agegate;
+mod announcements;
+mod archive;
+mod audit;
+mod automod;
+mod autorole;
+mod bans;
+mod birthdays;
+mod bookmarks;
+mod cases;
+mod confirm;
+mod counters;
+mod db;
+mod duration;
+mod embeds;
+mod errors;
+mod escalation;
+mod events;
+mod exempt;
+mod feeds;
+mod filter;
+mod github;
+mod giveaways;
+mod hierarchy;
+mod i18n;
+mod ics;
+mod jobs;
+mod leveling;
+mod lifecycle;
+mod localtime;
+mod lockdown;
+mod members;
+mod msglog;
+mod notes;
+mod owner;
+mod pinboard;
+mod polls;
+mod preflight;
+mod purge;
+mod quotes;
+mod raid;
+mod ratelimit;
+mod reminders;
+mod replies;
+mod reports;
+mod retention;
+mod rolemenu;
+mod schedule;
+mod settings;
+mod slowmode;
+mod snipe;
+mod spam;
+mod starboard;
+mod stats;
+mod sticky;
+mod suggestions;
+mod syndication;
+mod tags;
+mod threads;
+mod tickets;
+mod timeouts;
+mod todos;
+mod twitch;
+mod usage;
+mod verify;
+mod warnings;
+mod webhooks;
+mod welcome;
+mod whois;
+mod youtube;
 
 #[derive(Clone)]
 struct Data {
-    _pool: sqlx::PgPool,
+    pool: sqlx::PgPool,
+    jobs: Arc<JobRegistry>,
+    governor: Arc<Governor>,
+    settings: Arc<SettingsCache>,
+    automod: Arc<AutomodCache>,
+    spam: Arc<SpamTracker>,
+    raids: Arc<RaidTracker>,
+    stickies: Arc<StickyCache>,
+    snipes: Arc<SnipeCache>,
+    web: reqwest::Client,
+    github_token: Option<String>,
+    twitch: Option<Arc<TwitchApi>>,
+    relay_keys: Option<Arc<RelayKeys>>,
 }
 
 #[derive(Error, Debug)]
 enum SlimeError {
     #[error("an occur occurred within Serenity: {0}")]
     SerenityError(#[from] SerenityError),
+    #[error("an error occurred within the database: {0}")]
+    DatabaseError(#[from] sqlx::Error),
 }
 type Context<'a> = poise::Context<'a, Data, SlimeError>;
+type ApplicationContext<'a> = poise::ApplicationContext<'a, Data, SlimeError>;
 
-fn make_uuid_buttons(yes_uuid: &str, no_uuid: &str, disabled: bool) -> CreateActionRow {
-    CreateActionRow::Buttons(vec![
-        CreateButton::new(yes_uuid)
-            .label("yes")
-            .style(ButtonStyle::Danger)
-            .disabled(disabled),
-        CreateButton::new(no_uuid)
-            .label("no")
-            .style(ButtonStyle::Secondary)
-            .disabled(disabled),
-    ])
+/// The bot, and the webhook relay endpoint next to it when relays are set up.
+struct SlimeService {
+    client: Client,
+    relay: Option<Relay>,
 }
 
-#[poise::command(slash_command)]
-async fn purge_old(ctx: Context<'_>) -> Result<(), SlimeError> {
-    let _channel = ctx.guild_channel().await.unwrap();
-
-    let id = ctx.id();
-    let yes_uuid: String = format!("{id}-yes");
-    let no_uuid: String = format!("{id}-no");
-
-    let buttons = make_uuid_buttons(&yes_uuid, &no_uuid, false);
-
-    let reply = CreateReply::default()
-        .content("The first message to be deleted is <foo>, the last is <bar> continue?")
-        .components(vec![buttons])
-        .ephemeral(true);
-
-    ctx.send(reply).await?;
-
-    if let Some(interactions) = ComponentInteractionCollector::new(ctx.serenity_context())
-        .timeout(std::time::Duration::from_secs(120))
-        .custom_ids(vec![yes_uuid.clone(), no_uuid.clone()])
-        .await
-    {
-        let message = CreateInteractionResponseMessage::new()
-            .components(vec![make_uuid_buttons("yes_disabled", "no_disabled", true)])
-            .content(&interactions.message.content);
-
-        let disable_buttons = CreateInteractionResponse::UpdateMessage(message);
-        interactions
-            .create_response(ctx, disable_buttons)
-            .await
-            .inspect_err(|e| error!("{}", e))?;
-
-        let content = match &interactions.data.custom_id {
-            id if id == &yes_uuid => "yes",
-            id if id == &no_uuid => "no",
-            _ => unreachable!(),
+#[shuttle_runtime::async_trait]
+impl shuttle_runtime::Service for SlimeService {
+    async fn bind(self, addr: SocketAddr) -> Result<(), shuttle_runtime::Error> {
+        let SlimeService { mut client, relay } = self;
+        let Some(relay) = relay else {
+            client
+                .start_autosharded()
+                .await
+                .map_err(shuttle_runtime::CustomError::new)?;
+            return Ok(());
         };
-
-        let followup = CreateInteractionResponseFollowup::new()
-            .content(content)
-            .ephemeral(true);
-        interactions
-            .create_followup(ctx, followup)
-            .await
-            .inspect_err(|e| error!("{}", e))?;
+        tokio::select! {
+            result = client.start_autosharded() => {
+                result.map_err(shuttle_runtime::CustomError::new)?;
+            }
+            result = webhooks::serve(addr, relay) => {
+                result.map_err(shuttle_runtime::CustomError::new)?;
+            }
+        }
+        Ok(())
     }
-
-    Ok(())
 }
 
 #[shuttle_runtime::main]
 async fn serenity(
     #[shuttle_secrets::Secrets] secret_store: SecretStore,
     #[shuttle_shared_db::Postgres] pool: sqlx::PgPool,
-) -> shuttle_serenity::ShuttleSerenity {
+) -> Result<SlimeService, shuttle_runtime::Error> {
     // Get the discord token set in `Secrets.toml`
     let token = if let Some(token) = secret_store.get("DISCORD_TOKEN") {
         token
     } else {
         return Err(anyhow!("'DISCORD_TOKEN' was not found").into());
     };
+    // A GitHub token is optional, it only raises GitHub's rate limit.
+    let github_token = secret_store.get("GITHUB_TOKEN");
+    // Without Twitch credentials `/twitch watch` says it isn't set up.
+    let twitch = secret_store
+        .get("TWITCH_CLIENT_ID")
+        .zip(secret_store.get("TWITCH_CLIENT_SECRET"))
+        .map(|(id, secret)| Arc::new(TwitchApi::new(id, secret)));
+    // Without a secret and the bot's public URL `/webhook create` says relays
+    // aren't set up, and nothing listens for them.
+    let relay_keys = secret_store
+        .get("WEBHOOK_SECRET")
+        .zip(secret_store.get("WEBHOOK_URL"))
+        .map(|(secret, url)| Arc::new(RelayKeys::new(secret, url)));
+    let relay = relay_keys.clone().map(|keys| (pool.clone(), keys));
+
+    db::migrate(&pool)
+        .await
+        .map_err(|e| anyhow!("failed to run migrations: {e}"))?;
 
     // Set gateway intents, which decides what events the bot will be notified about
-    let intents = GatewayIntents::GUILD_MESSAGES
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MEMBERS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS
         | GatewayIntents::MESSAGE_CONTENT
         | GatewayIntents::GUILD_SCHEDULED_EVENTS
         | GatewayIntents::DIRECT_MESSAGES;
 
+    let governor = Arc::new(Governor::default());
+    let handler = RatelimitHandler(Arc::clone(&governor));
+
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![purge_old()],
+            commands: vec![
+                admin::admin_bot_spam_channel(),
+                admin::config(),
+                purge::purge_old(),
+                purge::purge_range(),
+                purge::purge_matching(),
+                purge::purge_keep(),
+                purge::purge_departed(),
+                purge::purge_channels(),
+                purge::purge_user_everywhere(),
+                purge::purge_after(),
+                purge::purge_user_here(),
+                purge::purge_author_here(),
+                purge::purge_status(),
+                purge::purge_cancel(),
+                exempt::purge_exempt(),
+                audit::purge_history(),
+                archive::purge_restore(),
+                retention::retention_policy(),
+                schedule::purge_at(),
+                reminders::remind(),
+                announcements::announce(),
+                polls::poll(),
+                rolemenu::rolemenu(),
+                events::event(),
+                sticky::sticky(),
+                tags::tag(),
+                suggestions::suggest(),
+                suggestions::suggestion(),
+                tickets::ticket(),
+                giveaways::giveaway(),
+                leveling::rank(),
+                leveling::leaderboard(),
+                birthdays::birthday(),
+                stats::stats(),
+                snipe::snipe(),
+                snipe::editsnipe(),
+                pinboard::pinboard(),
+                bookmarks::bookmarks(),
+                afk::afk(),
+                threads::threads(),
+                quotes::quote_message(),
+                quotes::quote(),
+                todos::todo(),
+                embeds::embed(),
+                feeds::feed(),
+                github::github(),
+                twitch::twitch(),
+                youtube::youtube(),
+                webhooks::webhook(),
+                welcome::welcome(),
+                ratelimit::ratelimit_status(),
+                usage::usage_stats(),
+                owner::owner(),
+                warnings::warn(),
+                warnings::warnings(),
+                notes::note(),
+                whois::whois(),
+                escalation::escalation(),
+                timeouts::timeout(),
+                bans::kick(),
+                bans::ban(),
+                bans::tempban(),
+                bans::softban(),
+                cases::case(),
+                automod::automod(),
+                raid::raidmode(),
+                lockdown::lockdown(),
+                lockdown::unlock_channel(),
+                slowmode::slowmode(),
+                verify::verify(),
+                reports::report_message(),
+                reports::reports(),
+            ],
+            command_check: Some(|ctx| Box::pin(admin::command_check(ctx))),
+            on_error: |error| Box::pin(errors::on_error(error)),
+            pre_command: |ctx| Box::pin(usage::pre_command(ctx)),
+            post_command: |ctx| Box::pin(usage::post_command(ctx)),
+            event_handler: |ctx, event, _framework, data| {
+                Box::pin(lifecycle::handle_event(ctx, event, data))
+            },
             ..Default::default()
         })
         .setup(|ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data { _pool: pool })
+                let data = Data {
+                    settings: Arc::new(SettingsCache::new(pool.clone())),
+                    automod: Arc::new(AutomodCache::new(pool.clone())),
+                    spam: Arc::default(),
+                    raids: Arc::default(),
+                    stickies: Arc::new(StickyCache::new(pool.clone())),
+                    snipes: Arc::default(),
+                    web: feeds::client(),
+                    github_token,
+                    twitch,
+                    relay_keys,
+                    pool,
+                    jobs: Arc::default(),
+                    governor,
+                };
+                tokio::spawn(purge::resume_jobs(Arc::clone(&ctx.http), data.clone()));
+                tokio::spawn(retention::run_policies(Arc::clone(&ctx.http), data.clone()));
+                tokio::spawn(schedule::run_scheduled(Arc::clone(&ctx.http), data.clone()));
+                tokio::spawn(archive::expire_archive(data.pool.clone()));
+                tokio::spawn(timeouts::announce_expired(
+                    Arc::clone(&ctx.http),
+                    data.clone(),
+                ));
+                tokio::spawn(bans::run_unbans(Arc::clone(&ctx.http), data.clone()));
+                tokio::spawn(msglog::expire_message_cache(data.pool.clone()));
+                tokio::spawn(stats::expire_activity(data.pool.clone()));
+                tokio::spawn(lockdown::run_unlocks(Arc::clone(&ctx.http), data.clone()));
+                tokio::spawn(slowmode::run_reverts(Arc::clone(&ctx.http), data.clone()));
+                tokio::spawn(spam::prune_spam(Arc::clone(&data.spam)));
+                tokio::spawn(snipe::prune_snipes(Arc::clone(&data.snipes)));
+                tokio::spawn(feeds::poll_feeds(Arc::clone(&ctx.http), data.clone()));
+                tokio::spawn(github::poll_repos(Arc::clone(&ctx.http), data.clone()));
+                tokio::spawn(twitch::poll_streams(Arc::clone(&ctx.http), data.clone()));
+                tokio::spawn(youtube::poll_uploads(Arc::clone(&ctx.http), data.clone()));
+                Ok(data)
             })
         })
         .build();
 
     let client = Client::builder(&token, intents)
         .framework(framework)
+        .event_handler(handler)
         .await
         .expect("Err creating client");
 
-    Ok(client.into())
+    let relay = relay.map(|(pool, keys)| Relay::new(pool, Arc::clone(&client.http), keys));
+    Ok(SlimeService { client, relay })
 }
@@ -0,0 +1,46 @@
+use dashmap::DashMap;
+use poise::serenity_prelude::*;
+
+use crate::SlimeError;
+
+/// Who is a member of a guild and with which roles, looked up one user at
+/// a time. Messages fetched over HTTP don't say, and listing every member
+/// needs a privileged intent.
+pub struct MemberCache {
+    guild_id: GuildId,
+    /// `None` for users who aren't members.
+    roles: DashMap<UserId, Option<Vec<RoleId>>>,
+}
+
+impl MemberCache {
+    pub fn new(guild_id: GuildId) -> Self {
+        MemberCache {
+            guild_id,
+            roles: DashMap::new(),
+        }
+    }
+
+    /// The roles of `user_id`, or `None` if they aren't a member (anymore).
+    pub async fn roles(
+        &self,
+        http: &Http,
+        user_id: UserId,
+    ) -> Result<Option<Vec<RoleId>>, SlimeError> {
+        if let Some(roles) = self.roles.get(&user_id) {
+            return Ok(roles.clone());
+        }
+
+        let roles = match self.guild_id.member(http, user_id).await {
+            Ok(member) => Some(member.roles),
+            Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => None,
+            Err(e) => return Err(e.into()),
+        };
+        self.roles.insert(user_id, roles.clone());
+
+        Ok(roles)
+    }
+
+    pub async fn is_member(&self, http: &Http, user_id: UserId) -> Result<bool, SlimeError> {
+        Ok(self.roles(http, user_id).await?.is_some())
+    }
+}
@@ -0,0 +1,266 @@
+use std::time::Duration as StdDuration;
+
+use chrono::{Duration, Utc};
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    duration::HumanDuration,
+    replies::shorten,
+    settings::{ChannelPurpose, GuildSettings},
+    Data, SlimeError,
+};
+
+/// How many hours messages are kept around, and so how old a message can
+/// be for an edit or deletion to be logged with its original content.
+const MESSAGE_CACHE_TTL_HOURS: i64 = 24;
+
+/// How many minutes after sending a message deleting it counts as a ghost
+/// ping, if it mentioned anyone.
+const GHOST_PING_MINUTES: i64 = 5;
+
+/// How often expired messages are dropped from the cache.
+const EXPIRE_INTERVAL: StdDuration = StdDuration::from_secs(3600);
+
+/// Discord allows at most this many characters in an embed field.
+const FIELD_LEN: usize = 1024;
+
+/// Where to log what happens in `channel_id`, or `None` if it isn't logged.
+/// The log channel itself never is, or every logged edit would log another.
+fn log_channel(settings: &GuildSettings, channel_id: ChannelId) -> Option<ChannelId> {
+    settings
+        .channel(ChannelPurpose::Messages)
+        .filter(|&log_channel| log_channel != channel_id)
+        .filter(|_| !settings.log_ignored.contains(&channel_id))
+}
+
+/// Shortens `content` to fit an embed field.
+pub fn field_text(content: &str) -> String {
+    if content.is_empty() {
+        return "*no text*".to_string();
+    }
+    shorten(content, FIELD_LEN)
+}
+
+#[derive(sqlx::FromRow)]
+struct CachedMessage {
+    author_id: i64,
+    content: String,
+    /// The users the message mentioned, leaving out its author and bots.
+    mentions: Vec<i64>,
+}
+
+/// Keeps a copy of every message in a logged channel, so its content is
+/// still known once it's edited or deleted. Messages that mention someone
+/// are kept for a few minutes anywhere else, to catch ghost pings.
+pub async fn message_sent(data: &Data, message: &Message) -> Result<(), SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    if message.author.bot {
+        return Ok(());
+    }
+    let mentions: Vec<i64> = message
+        .mentions
+        .iter()
+        .filter(|user| user.id != message.author.id && !user.bot)
+        .map(|user| i64::from(user.id))
+        .collect();
+    let settings = data.settings.get(guild_id).await?;
+    let ttl = if log_channel(&settings, message.channel_id).is_some() {
+        Duration::hours(MESSAGE_CACHE_TTL_HOURS)
+    } else if !mentions.is_empty() && settings.channel(ChannelPurpose::Logs).is_some() {
+        Duration::minutes(GHOST_PING_MINUTES)
+    } else {
+        return Ok(());
+    };
+
+    sqlx::query(
+        "INSERT INTO message_cache
+        (message_id, guild_id, channel_id, author_id, content, mentions, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT DO NOTHING",
+    )
+    .bind(i64::from(message.id))
+    .bind(i64::from(guild_id))
+    .bind(i64::from(message.channel_id))
+    .bind(i64::from(message.author.id))
+    .bind(&message.content)
+    .bind(&mentions)
+    .bind(Utc::now() + ttl)
+    .execute(&data.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Logs the old and new content of an edited message.
+pub async fn message_edited(
+    ctx: &serenity::Context,
+    data: &Data,
+    event: &MessageUpdateEvent,
+) -> Result<(), SlimeError> {
+    let (Some(guild_id), Some(content)) = (event.guild_id, &event.content) else {
+        return Ok(());
+    };
+    let settings = data.settings.get(guild_id).await?;
+    let Some(log_channel) = log_channel(&settings, event.channel_id) else {
+        return Ok(());
+    };
+
+    let cached: Option<CachedMessage> = sqlx::query_as(
+        "UPDATE message_cache new SET content = $2
+        FROM message_cache old WHERE new.message_id = $1 AND old.message_id = $1
+        RETURNING old.author_id, old.content, old.mentions",
+    )
+    .bind(i64::from(event.id))
+    .bind(content)
+    .fetch_optional(&data.pool)
+    .await?;
+    // Messages from bots aren't cached, and neither are their edits logged.
+    let Some(cached) = cached else {
+        return Ok(());
+    };
+    // Discord also sends updates when it adds link previews.
+    if &cached.content == content {
+        return Ok(());
+    }
+
+    let embed = CreateEmbed::new()
+        .title("Message edited")
+        .colour(Colour::GOLD)
+        .description(event.id.link(event.channel_id, Some(guild_id)))
+        .field(
+            "Author",
+            UserId::new(cached.author_id as u64).mention().to_string(),
+            true,
+        )
+        .field("Channel", event.channel_id.mention().to_string(), true)
+        .field("Before", field_text(&cached.content), false)
+        .field("After", field_text(content), false);
+    log_channel
+        .send_message(ctx, CreateMessage::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Whether a purge deleted the message, which the purge reports already.
+async fn purged(pool: &PgPool, message_id: MessageId) -> sqlx::Result<bool> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM archived_messages WHERE message_id = $1)")
+        .bind(i64::from(message_id))
+        .fetch_one(pool)
+        .await
+}
+
+/// Posts a notice to the guild's log channel when a message that mentioned
+/// someone is deleted right after it was sent, since they got notified of
+/// a message they can't find anymore.
+async fn report_ghost_ping(
+    http: &Http,
+    settings: &GuildSettings,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    cached: &CachedMessage,
+) -> Result<(), SlimeError> {
+    let Some(log_channel) = settings.channel(ChannelPurpose::Logs) else {
+        return Ok(());
+    };
+    let age = Utc::now().timestamp() - message_id.created_at().unix_timestamp();
+    if cached.mentions.is_empty() || age > GHOST_PING_MINUTES * 60 {
+        return Ok(());
+    }
+
+    let mentioned = cached
+        .mentions
+        .iter()
+        .map(|&user_id| UserId::new(user_id as u64).mention().to_string())
+        .collect::<Vec<_>>();
+    let embed = CreateEmbed::new()
+        .title("Ghost ping")
+        .colour(Colour::ORANGE)
+        .field(
+            "Author",
+            UserId::new(cached.author_id as u64).mention().to_string(),
+            true,
+        )
+        .field("Channel", channel_id.mention().to_string(), true)
+        .field("Mentioned", field_text(&mentioned.join(" ")), false)
+        .field("Content", field_text(&cached.content), false)
+        .footer(CreateEmbedFooter::new(format!(
+            "Deleted {} after it was sent",
+            HumanDuration(Duration::seconds(age.max(0)))
+        )));
+    log_channel
+        .send_message(http, CreateMessage::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Logs the content of a deleted message, and reports it if it was a ghost
+/// ping. Messages that aren't cached, like those of bots or the ones
+/// automod deleted, aren't logged, and neither are bulk deletions, which
+/// only come from purges and bots cleaning up.
+pub async fn message_deleted(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    message_id: MessageId,
+) -> Result<(), SlimeError> {
+    let Some(guild_id) = guild_id else {
+        return Ok(());
+    };
+    let cached: Option<CachedMessage> = sqlx::query_as(
+        "DELETE FROM message_cache WHERE message_id = $1 RETURNING author_id, content, mentions",
+    )
+    .bind(i64::from(message_id))
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(cached) = cached else {
+        return Ok(());
+    };
+    if purged(&data.pool, message_id).await? {
+        return Ok(());
+    }
+    let settings = data.settings.get(guild_id).await?;
+    // The deletion is still worth logging if the notice couldn't be posted.
+    if let Err(e) = report_ghost_ping(&ctx.http, &settings, channel_id, message_id, &cached).await {
+        error!("failed to report a ghost ping in guild {}: {}", guild_id, e);
+    }
+    let Some(log_channel) = log_channel(&settings, channel_id) else {
+        return Ok(());
+    };
+
+    let embed = CreateEmbed::new()
+        .title("Message deleted")
+        .colour(Colour::RED)
+        .field(
+            "Author",
+            UserId::new(cached.author_id as u64).mention().to_string(),
+            true,
+        )
+        .field("Channel", channel_id.mention().to_string(), true)
+        .field("Content", field_text(&cached.content), false);
+    log_channel
+        .send_message(ctx, CreateMessage::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Drops cached messages once they expire, every `EXPIRE_INTERVAL`.
+pub async fn expire_message_cache(pool: PgPool) {
+    let mut interval = tokio::time::interval(EXPIRE_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = sqlx::query("DELETE FROM message_cache WHERE expires_at <= now()")
+            .execute(&pool)
+            .await
+        {
+            error!("failed to expire cached messages: {}", e);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+
+use crate::{audit, db, localtime, replies::reply, Context, SlimeError};
+
+/// How many notes one page of `/note list` shows.
+const PAGE_LEN: usize = 10;
+
+#[derive(sqlx::FromRow)]
+struct NoteRow {
+    author_id: i64,
+    content: String,
+    created_at: DateTime<Utc>,
+}
+
+/// How many notes moderators wrote about `user_id`.
+pub async fn count(pool: &PgPool, guild_id: GuildId, user_id: UserId) -> sqlx::Result<i64> {
+    sqlx::query_scalar("SELECT count(*) FROM user_notes WHERE guild_id = $1 AND user_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(i64::from(user_id))
+        .fetch_one(pool)
+        .await
+}
+
+/// Manages what moderators note down about a member
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("add", "list")
+)]
+pub async fn note(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Notes something about a member, only moderators see it
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Member the note is about"] user: User,
+    #[description = "What to note down"]
+    #[max_length = 1000]
+    text: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    db::ensure_guild(&data.pool, guild_id).await?;
+    sqlx::query(
+        "INSERT INTO user_notes (guild_id, user_id, author_id, content) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user.id))
+    .bind(i64::from(ctx.author().id))
+    .bind(&text)
+    .execute(&data.pool)
+    .await?;
+
+    reply(ctx, format!("Noted about {}: {text}", user.mention())).await
+}
+
+/// Lists what moderators noted down about a member
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn list(
+    ctx: Context<'_>,
+    #[description = "Member whose notes to show"] user: User,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let notes: Vec<NoteRow> = sqlx::query_as(
+        "SELECT author_id, content, created_at FROM user_notes
+        WHERE guild_id = $1 AND user_id = $2 ORDER BY created_at",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user.id))
+    .fetch_all(&data.pool)
+    .await?;
+
+    if notes.is_empty() {
+        return reply(ctx, format!("There are no notes about {}.", user.mention())).await;
+    }
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let lines = notes.iter().map(|note| {
+        format!(
+            "- `{}` by {}: {}",
+            localtime::format_datetime(note.created_at, timezone),
+            UserId::new(note.author_id as u64).mention(),
+            note.content
+        )
+    });
+    let title = format!("{} notes about {}", notes.len(), user.name);
+    audit::paginate(ctx, &title, &audit::pages(lines, PAGE_LEN)).await
+}
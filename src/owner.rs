@@ -0,0 +1,114 @@
+use poise::serenity_prelude::*;
+use tracing::info;
+
+use crate::{audit, replies::reply, Context, SlimeError};
+
+/// How many guilds one page of `owner guilds` shows.
+const PAGE_LEN: usize = 20;
+
+/// How many guilds Discord lists per request at most.
+const GUILDS_PER_REQUEST: u64 = 200;
+
+/// How many tables `owner sql-stats` lists, the biggest ones, to stay below
+/// Discord's message length limit.
+const TABLES_SHOWN: usize = 25;
+
+/// Manages the bot itself
+#[poise::command(
+    slash_command,
+    owners_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("guilds", "leave", "sql_stats", "shutdown")
+)]
+pub async fn owner(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Lists the servers the bot is in
+#[poise::command(slash_command, owners_only)]
+async fn guilds(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let mut guilds = Vec::new();
+    loop {
+        let after = guilds
+            .last()
+            .map(|guild: &GuildInfo| GuildPagination::After(guild.id));
+        let page = ctx
+            .http()
+            .get_guilds(after, Some(GUILDS_PER_REQUEST))
+            .await?;
+        let done = (page.len() as u64) < GUILDS_PER_REQUEST;
+        guilds.extend(page);
+        if done {
+            break;
+        }
+    }
+
+    if guilds.is_empty() {
+        return reply(ctx, "I'm in no servers.".to_string()).await;
+    }
+
+    let lines = guilds
+        .iter()
+        .map(|guild| format!("- {} (`{}`)", guild.name, guild.id));
+    let title = format!("I'm in {} servers", guilds.len());
+    audit::paginate(ctx, &title, &audit::pages(lines, PAGE_LEN)).await
+}
+
+/// Makes the bot leave a server
+#[poise::command(slash_command, owners_only)]
+async fn leave(
+    ctx: Context<'_>,
+    #[description = "Id of the server to leave, see /owner guilds"] guild: GuildId,
+) -> Result<(), SlimeError> {
+    guild.leave(ctx).await?;
+    info!("left guild {} on request of {}", guild, ctx.author().id);
+
+    reply(ctx, format!("Left server `{guild}`.")).await
+}
+
+/// Shows how big the database is and how the connection pool is doing
+#[poise::command(slash_command, owners_only, rename = "sql-stats")]
+async fn sql_stats(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let pool = &ctx.data().pool;
+    let size: String =
+        sqlx::query_scalar("SELECT pg_size_pretty(pg_database_size(current_database()))")
+            .fetch_one(pool)
+            .await?;
+    let tables: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT relname::text, n_live_tup FROM pg_stat_user_tables ORDER BY n_live_tup DESC",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut lines = vec![
+        format!("The database takes up {size}."),
+        format!(
+            "{} connections are open, {} of them idle.",
+            pool.size(),
+            pool.num_idle()
+        ),
+    ];
+    lines.extend(
+        tables
+            .iter()
+            .take(TABLES_SHOWN)
+            .map(|(table, rows)| format!("- `{table}`: about {rows} rows")),
+    );
+    if tables.len() > TABLES_SHOWN {
+        lines.push(format!(
+            "…and {} smaller tables",
+            tables.len() - TABLES_SHOWN
+        ));
+    }
+    reply(ctx, lines.join("\n")).await
+}
+
+/// Shuts the bot down, purges in progress resume after the next start
+#[poise::command(slash_command, owners_only)]
+async fn shutdown(ctx: Context<'_>) -> Result<(), SlimeError> {
+    reply(ctx, "Shutting down.".to_string()).await?;
+    info!("shutting down on request of {}", ctx.author().id);
+    ctx.framework().shard_manager().shutdown_all().await;
+
+    Ok(())
+}
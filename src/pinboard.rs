@@ -0,0 +1,179 @@
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{replies::reply, settings::ChannelPurpose, Context, Data, SlimeError};
+
+/// How many pins Discord allows in a channel.
+const PIN_LIMIT: usize = 50;
+
+/// How many of the oldest pins are archived once a channel is full, so it
+/// isn't full again after the next pin.
+const ARCHIVE_BATCH: usize = 10;
+
+/// The archive copy of a pin.
+fn copy(guild_id: GuildId, message: &Message) -> (String, CreateEmbed) {
+    let content = format!("📌 Pinned in {}", message.channel_id.mention());
+    let mut embed = CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(message.author.tag()).icon_url(message.author.face()))
+        .colour(Colour::DARK_GREY)
+        .field(
+            "Source",
+            message.id.link(message.channel_id, Some(guild_id)),
+            false,
+        )
+        .timestamp(message.timestamp);
+    if !message.content.is_empty() {
+        embed = embed.description(&message.content);
+    }
+    let image = message.attachments.iter().find(|attachment| {
+        attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|content_type| content_type.starts_with("image/"))
+    });
+    if let Some(image) = image {
+        embed = embed.image(&image.url);
+    }
+    (content, embed)
+}
+
+/// Copies `pins` to `archive`, oldest first, and unpins them. Returns how
+/// many were copied.
+async fn archive(
+    http: &Http,
+    pool: &PgPool,
+    guild_id: GuildId,
+    archive: ChannelId,
+    pins: &[Message],
+) -> Result<usize, SlimeError> {
+    let mut archived = 0;
+    for message in pins {
+        // Claiming the pin first means only one copy is ever posted. A pin
+        // that was copied before only needs unpinning.
+        let claimed = sqlx::query(
+            "INSERT INTO pin_archive (message_id, guild_id, channel_id, author_id,
+            archive_channel_id)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (message_id) DO NOTHING",
+        )
+        .bind(i64::from(message.id))
+        .bind(i64::from(guild_id))
+        .bind(i64::from(message.channel_id))
+        .bind(i64::from(message.author.id))
+        .bind(i64::from(archive))
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        if claimed > 0 {
+            let (content, embed) = copy(guild_id, message);
+            let posted = archive
+                .send_message(http, CreateMessage::new().content(content).embed(embed))
+                .await;
+            let posted = match posted {
+                Ok(posted) => posted,
+                Err(e) => {
+                    sqlx::query("DELETE FROM pin_archive WHERE message_id = $1")
+                        .bind(i64::from(message.id))
+                        .execute(pool)
+                        .await?;
+                    return Err(e.into());
+                }
+            };
+            sqlx::query("UPDATE pin_archive SET archive_message_id = $2 WHERE message_id = $1")
+                .bind(i64::from(message.id))
+                .bind(i64::from(posted.id))
+                .execute(pool)
+                .await?;
+            archived += 1;
+        }
+
+        http.unpin_message(message.channel_id, message.id, Some("Archived pin"))
+            .await?;
+    }
+
+    Ok(archived)
+}
+
+/// Archives the oldest pins of a channel that just hit the pin limit, if
+/// the guild has a pin archive channel.
+pub async fn pins_updated(
+    ctx: &serenity::Context,
+    data: &Data,
+    event: &ChannelPinsUpdateEvent,
+) -> Result<(), SlimeError> {
+    let Some(guild_id) = event.guild_id else {
+        return Ok(());
+    };
+    let settings = data.settings.get(guild_id).await?;
+    let Some(archive_channel) = settings.channel(ChannelPurpose::Pins) else {
+        return Ok(());
+    };
+    if event.channel_id == archive_channel {
+        return Ok(());
+    }
+
+    let pins = event.channel_id.pins(ctx).await?;
+    if pins.len() < PIN_LIMIT {
+        return Ok(());
+    }
+    // Pins come newest first.
+    let oldest: Vec<Message> = pins.into_iter().rev().take(ARCHIVE_BATCH).collect();
+    if let Err(e) = archive(&ctx.http, &data.pool, guild_id, archive_channel, &oldest).await {
+        error!(
+            "failed to archive the pins of {} in guild {}: {}",
+            event.channel_id, guild_id, e
+        );
+    }
+
+    Ok(())
+}
+
+/// Moves the oldest pins of a channel to the pin archive channel
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn pinboard(
+    ctx: Context<'_>,
+    #[description = "Channel to archive pins of (default this one)"] channel: Option<GuildChannel>,
+    #[description = "How many of the oldest pins to archive (default 10)"]
+    #[min = 1]
+    #[max = 50]
+    count: Option<usize>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(archive_channel) = data
+        .settings
+        .get(guild_id)
+        .await?
+        .channel(ChannelPurpose::Pins)
+    else {
+        let content =
+            "There's no pin archive channel, set one with `/config set pin_archive_channel`.";
+        return reply(ctx, content.to_string()).await;
+    };
+    let channel_id = channel.map_or(ctx.channel_id(), |channel| channel.id);
+    if channel_id == archive_channel {
+        return reply(ctx, "That's the pin archive channel.".to_string()).await;
+    }
+    ctx.defer_ephemeral().await?;
+
+    let pins = channel_id.pins(ctx).await?;
+    if pins.is_empty() {
+        let content = format!("{} has no pins.", channel_id.mention());
+        return reply(ctx, content).await;
+    }
+    let oldest: Vec<Message> = pins
+        .into_iter()
+        .rev()
+        .take(count.unwrap_or(ARCHIVE_BATCH))
+        .collect();
+    let archived = archive(ctx.http(), &data.pool, guild_id, archive_channel, &oldest).await?;
+
+    let content = format!(
+        "Archived {archived} pins of {} to {}.",
+        channel_id.mention(),
+        archive_channel.mention()
+    );
+    reply(ctx, content).await
+}
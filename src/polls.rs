@@ -0,0 +1,313 @@
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{db, duration::HumanDuration, localtime, replies::reply, Context, Data, SlimeError};
+
+/// What the custom id of every poll button starts with, followed by the
+/// poll and the option, as in `pond-slime:poll:12:0`. It has to stay the
+/// same across restarts, since the buttons outlive the bot process.
+const POLL_BUTTON: &str = "pond-slime:poll";
+
+/// How long polls stay open unless they're given a duration.
+const DEFAULT_POLL_HOURS: i64 = 24;
+
+/// The longest a poll can stay open.
+const MAX_POLL_DAYS: i64 = 28;
+
+/// A poll from the `polls` table.
+#[derive(sqlx::FromRow)]
+struct Poll {
+    poll_id: i64,
+    channel_id: i64,
+    message_id: Option<i64>,
+    created_by: i64,
+    question: String,
+    options: Vec<String>,
+    closes_at: DateTime<Utc>,
+    closed: bool,
+}
+
+impl Poll {
+    fn options_embed(&self) -> CreateEmbed {
+        let options = self
+            .options
+            .iter()
+            .enumerate()
+            .map(|(index, option)| format!("{}. {option}", index + 1))
+            .collect::<Vec<_>>()
+            .join("\n");
+        CreateEmbed::new()
+            .title(&self.question)
+            .description(options)
+            .field(
+                "Asked by",
+                UserId::new(self.created_by as u64).mention().to_string(),
+                true,
+            )
+    }
+
+    fn open_embed(&self, timezone: Option<FixedOffset>) -> CreateEmbed {
+        self.options_embed().footer(CreateEmbedFooter::new(format!(
+            "Closes {}, you can change your vote until then",
+            localtime::format_datetime(self.closes_at, timezone)
+        )))
+    }
+
+    fn results_embed(&self, votes: &[i64]) -> CreateEmbed {
+        let total: i64 = votes.iter().sum();
+        let results = self
+            .options
+            .iter()
+            .zip(votes)
+            .map(|(option, &count)| {
+                let share = if total > 0 { count * 100 / total } else { 0 };
+                format!("**{option}**: {count} votes ({share}%)")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.options_embed()
+            .field("Results", results, false)
+            .footer(CreateEmbedFooter::new(format!("Closed with {total} votes")))
+    }
+
+    fn buttons(&self) -> CreateActionRow {
+        let buttons = self
+            .options
+            .iter()
+            .enumerate()
+            .map(|(index, option)| {
+                CreateButton::new(format!("{POLL_BUTTON}:{}:{index}", self.poll_id))
+                    .label(option)
+                    .style(ButtonStyle::Primary)
+            })
+            .collect();
+        CreateActionRow::Buttons(buttons)
+    }
+}
+
+/// The poll and option a poll button stands for.
+fn parse_button(custom_id: &str) -> Option<(i64, usize)> {
+    let rest = custom_id.strip_prefix(POLL_BUTTON)?.strip_prefix(':')?;
+    let (poll_id, index) = rest.split_once(':')?;
+    Some((poll_id.parse().ok()?, index.parse().ok()?))
+}
+
+/// How many votes each option of `poll` got, in order.
+async fn tally(pool: &PgPool, poll: &Poll) -> sqlx::Result<Vec<i64>> {
+    let counts: Vec<(i32, i64)> = sqlx::query_as(
+        "SELECT option_index, count(*) FROM poll_votes WHERE poll_id = $1 GROUP BY option_index",
+    )
+    .bind(poll.poll_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut votes = vec![0; poll.options.len()];
+    for (index, count) in counts {
+        if let Some(votes) = votes.get_mut(index as usize) {
+            *votes = count;
+        }
+    }
+    Ok(votes)
+}
+
+/// Answers whoever pressed a poll button, only they see it.
+async fn respond(
+    ctx: &serenity::Context,
+    press: &ComponentInteraction,
+    content: &str,
+) -> Result<(), SlimeError> {
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    press
+        .create_response(ctx, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+/// Records the vote of whoever pressed a poll button, replacing any vote
+/// they cast before. Other component interactions are left alone.
+pub async fn interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    interaction: &Interaction,
+) -> Result<(), SlimeError> {
+    let Interaction::Component(press) = interaction else {
+        return Ok(());
+    };
+    let Some((poll_id, index)) = parse_button(&press.data.custom_id) else {
+        return Ok(());
+    };
+    let Some(guild_id) = press.guild_id else {
+        return Ok(());
+    };
+
+    let poll: Option<Poll> = sqlx::query_as(
+        "SELECT poll_id, channel_id, message_id, created_by, question, options, closes_at, closed
+        FROM polls WHERE poll_id = $1 AND guild_id = $2",
+    )
+    .bind(poll_id)
+    .bind(i64::from(guild_id))
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(poll) = poll else {
+        return respond(ctx, press, "This poll doesn't exist anymore.").await;
+    };
+    if poll.closed || poll.closes_at <= Utc::now() {
+        return respond(ctx, press, "This poll is closed.").await;
+    }
+    let Some(option) = poll.options.get(index) else {
+        return Ok(());
+    };
+
+    sqlx::query(
+        "INSERT INTO poll_votes (poll_id, guild_id, user_id, option_index) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (poll_id, user_id) DO UPDATE SET option_index = EXCLUDED.option_index",
+    )
+    .bind(poll_id)
+    .bind(i64::from(guild_id))
+    .bind(i64::from(press.user.id))
+    .bind(index as i32)
+    .execute(&data.pool)
+    .await?;
+
+    let content = format!("You voted for **{option}**, press another option to change your vote.");
+    respond(ctx, press, &content).await
+}
+
+/// Shows the results of `poll` in its message and takes the buttons away.
+async fn close(http: &Http, pool: &PgPool, poll: &Poll) -> Result<(), SlimeError> {
+    let Some(message_id) = poll.message_id else {
+        return Ok(());
+    };
+    let votes = tally(pool, poll).await?;
+    let edit = EditMessage::new()
+        .embed(poll.results_embed(&votes))
+        .components(vec![]);
+    ChannelId::new(poll.channel_id as u64)
+        .edit_message(http, MessageId::new(message_id as u64), edit)
+        .await?;
+
+    Ok(())
+}
+
+/// Closes every poll that ran out, the scheduler calls this. Each is marked
+/// closed before its results are shown, so it's only ever closed once.
+pub async fn close_due(http: &Http, data: &Data) {
+    let due: Vec<Poll> = match sqlx::query_as(
+        "UPDATE polls SET closed = true WHERE NOT closed AND closes_at <= now()
+        RETURNING poll_id, channel_id, message_id, created_by, question, options, closes_at,
+        closed",
+    )
+    .fetch_all(&data.pool)
+    .await
+    {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to close due polls: {}", e);
+            return;
+        }
+    };
+
+    for poll in due {
+        if let Err(e) = close(http, &data.pool, &poll).await {
+            error!("failed to show the results of poll {}: {}", poll.poll_id, e);
+        }
+    }
+}
+
+/// Asks the server a question
+#[poise::command(slash_command, guild_only, subcommands("create"))]
+pub async fn poll(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Posts a poll members vote on with buttons
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command, guild_only)]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "What to ask"]
+    #[max_length = 256]
+    question: String,
+    #[description = "The first answer"]
+    #[max_length = 80]
+    option_1: String,
+    #[description = "The second answer"]
+    #[max_length = 80]
+    option_2: String,
+    #[description = "Another answer"]
+    #[max_length = 80]
+    option_3: Option<String>,
+    #[description = "Another answer"]
+    #[max_length = 80]
+    option_4: Option<String>,
+    #[description = "Another answer"]
+    #[max_length = 80]
+    option_5: Option<String>,
+    #[description = "How long voting is open, e.g. 2h or 3d (default 1d)"] duration: Option<
+        HumanDuration,
+    >,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let duration = duration.map_or(Duration::hours(DEFAULT_POLL_HOURS), |duration| {
+        duration.as_duration()
+    });
+    if duration <= Duration::zero() || duration > Duration::days(MAX_POLL_DAYS) {
+        let content = format!("Polls can stay open for at most {MAX_POLL_DAYS} days.");
+        return reply(ctx, content).await;
+    }
+    let options: Vec<String> = [Some(option_1), Some(option_2), option_3, option_4, option_5]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let poll: Poll = sqlx::query_as(
+        "INSERT INTO polls (guild_id, channel_id, created_by, question, options, closes_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING poll_id, channel_id, message_id, created_by, question, options, closes_at,
+        closed",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.channel_id()))
+    .bind(i64::from(ctx.author().id))
+    .bind(&question)
+    .bind(&options)
+    .bind(Utc::now() + duration)
+    .fetch_one(&data.pool)
+    .await?;
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let message = CreateMessage::new()
+        .embed(poll.open_embed(timezone))
+        .components(vec![poll.buttons()]);
+    let message = match ctx.channel_id().send_message(ctx, message).await {
+        Ok(message) => message,
+        Err(e) => {
+            sqlx::query("DELETE FROM polls WHERE poll_id = $1")
+                .bind(poll.poll_id)
+                .execute(&data.pool)
+                .await?;
+            return Err(e.into());
+        }
+    };
+    sqlx::query("UPDATE polls SET message_id = $2 WHERE poll_id = $1")
+        .bind(poll.poll_id)
+        .bind(i64::from(message.id))
+        .execute(&data.pool)
+        .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Poll `{}` is up, it closes at {}.",
+            poll.poll_id,
+            localtime::format_datetime(poll.closes_at, timezone)
+        ),
+    )
+    .await
+}
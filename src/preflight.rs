@@ -0,0 +1,113 @@
+use poise::serenity_prelude::{ChannelId, ChannelType, Error, HttpError, Mentionable, Permissions};
+
+use crate::{
+    i18n::{self, Msg},
+    Context, SlimeError,
+};
+
+/// What the bot needs in every channel it purges.
+const REQUIRED: Permissions = Permissions::VIEW_CHANNEL
+    .union(Permissions::READ_MESSAGE_HISTORY)
+    .union(Permissions::MANAGE_MESSAGES);
+
+/// Discord's error code for channels the bot can't see.
+const MISSING_ACCESS: isize = 50001;
+
+/// The permissions the bot lacks in each of `channel_ids`, leaving out the
+/// channels where it has everything. Threads use their parent channel's.
+async fn missing_permissions(
+    ctx: Context<'_>,
+    channel_ids: &[ChannelId],
+) -> Result<Vec<(ChannelId, Permissions)>, SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let guild = guild_id.to_partial_guild(ctx).await?;
+    let member = guild_id.member(ctx, ctx.framework().bot_id).await?;
+
+    let mut missing = Vec::new();
+    for &channel_id in channel_ids {
+        let channel = match channel_id.to_channel(ctx).await {
+            Ok(channel) => channel.guild(),
+            Err(Error::Http(HttpError::UnsuccessfulRequest(response)))
+                if response.error.code == MISSING_ACCESS =>
+            {
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let channel = match channel {
+            Some(channel) if channel.thread_metadata.is_some() => match channel.parent_id {
+                Some(parent_id) => parent_id.to_channel(ctx).await?.guild(),
+                None => Some(channel),
+            },
+            channel => channel,
+        };
+
+        let permissions = channel.map_or(Permissions::empty(), |channel| {
+            guild.user_permissions_in(&channel, &member)
+        });
+        if !permissions.contains(REQUIRED) {
+            missing.push((channel_id, REQUIRED.difference(permissions)));
+        }
+    }
+
+    Ok(missing)
+}
+
+/// The guild's text channels the bot may purge, in channel list order.
+pub async fn purgeable_channels(ctx: Context<'_>) -> Result<Vec<ChannelId>, SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let guild = guild_id.to_partial_guild(ctx).await?;
+    let member = guild_id.member(ctx, ctx.framework().bot_id).await?;
+
+    let mut channels: Vec<_> = guild_id
+        .channels(ctx)
+        .await?
+        .into_values()
+        .filter(|channel| {
+            channel.kind == ChannelType::Text
+                && guild
+                    .user_permissions_in(channel, &member)
+                    .contains(REQUIRED)
+        })
+        .collect();
+    channels.sort_by_key(|channel| channel.position);
+
+    Ok(channels.into_iter().map(|channel| channel.id).collect())
+}
+
+/// Makes sure the bot may purge every one of `channel_ids`, so a purge
+/// doesn't fail halfway through. If it may not, tells whoever ran the
+/// command which permissions are missing where and returns `false`.
+pub async fn check(ctx: Context<'_>, channel_ids: &[ChannelId]) -> Result<bool, SlimeError> {
+    let missing = missing_permissions(ctx, channel_ids).await?;
+    if missing.is_empty() {
+        return Ok(true);
+    }
+
+    let locale = i18n::locale(ctx).await?;
+    let content = missing
+        .iter()
+        .map(|(channel_id, permissions)| {
+            i18n::tr_args(
+                locale.as_deref(),
+                Msg::MissingPermissionsIn,
+                &[
+                    ("channel", &channel_id.mention().to_string()),
+                    (
+                        "permissions",
+                        &permissions.get_permission_names().join(", "),
+                    ),
+                ],
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(false)
+}
@@ -0,0 +1,1932 @@
+use std::{sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::{
+    future,
+    stream::{self, BoxStream, TryChunksError},
+    Stream, StreamExt, TryStreamExt,
+};
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+use tokio::time::{sleep_until, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{
+    archive, audit,
+    cases::{self, Action, NewCase},
+    confirm::confirm,
+    duration::HumanDuration,
+    exempt::Exemptions,
+    filter::{MessageFilter, Pattern, Skip},
+    i18n::{self, Msg},
+    jobs::{self, JobGuard, JobId, JobInfo, JobState, StoredJob},
+    preflight,
+    ratelimit::Governor,
+    replies::shorten,
+    settings::ChannelPurpose,
+    Context, Data, SlimeError,
+};
+
+/// The error code Discord answers with when a bulk deletion includes a
+/// message older than two weeks.
+const MESSAGE_TOO_OLD: isize = 50034;
+
+/// How many chunks `bulk_delete` deletes between progress updates.
+const PROGRESS_CHUNKS: usize = 5;
+
+/// How often `slow_bulk_delete` saves and reports its progress.
+const PROGRESS_INTERVAL: StdDuration = StdDuration::from_secs(30);
+
+/// How long to back off when a deletion is rate limited without Discord
+/// saying for how long.
+const RETRY_AFTER: StdDuration = StdDuration::from_secs(1);
+
+/// How many messages a purge preview shows.
+const PREVIEW_LEN: usize = 5;
+
+/// How many messages a dry run's file lists at most, which keeps it well
+/// under Discord's attachment size limit.
+const DRY_RUN_ROWS: usize = 10_000;
+
+/// How many jobs one page of `/purge_status` shows.
+const STATUS_PAGE_LEN: usize = 10;
+
+/// Discord allows at most this many characters in a message.
+const MESSAGE_LEN: usize = 2000;
+
+/// The first millisecond of 2015, which Discord's ids count from.
+const DISCORD_EPOCH_MS: i64 = 1_420_070_400_000;
+
+/// How far back the "Purge this user here" menu offers to go.
+const AUTHOR_WINDOWS: [(&str, &str); 5] = [
+    ("The last hour", "1h"),
+    ("The last day", "1d"),
+    ("The last week", "1w"),
+    ("The last 30 days", "30d"),
+    ("Everything", "all"),
+];
+
+/// Cuts `content` short to fit in a message. Channel lists go last, so
+/// they are what gets cut when a purge spans many channels.
+fn fit_message(content: String) -> String {
+    shorten(&content, MESSAGE_LEN)
+}
+
+/// The smallest message id a message sent at `time` or later can have.
+fn first_id_since(time: DateTime<Utc>) -> MessageId {
+    let millis = (time.timestamp_millis() - DISCORD_EPOCH_MS).max(0) as u64;
+    MessageId::new((millis << 22).max(1))
+}
+
+/// Discord refuses to bulk delete messages older than two weeks. The hour of
+/// margin keeps messages from aging past the limit while a purge runs.
+fn bulk_cutoff() -> DateTime<Utc> {
+    Utc::now() - Duration::days(14) + Duration::hours(1)
+}
+
+/// Whether `message` is recent enough to be bulk deleted, see `bulk_cutoff`.
+fn is_bulk(message: &Message, bulk_cutoff: i64) -> bool {
+    message.timestamp.unix_timestamp() > bulk_cutoff
+}
+
+/// A rough guess of how long deleting `bulk` recent and `slow` old messages
+/// takes at `rate` deletions per minute.
+fn estimate(bulk: usize, slow: usize, rate: usize) -> HumanDuration {
+    let bulk_seconds = bulk.div_ceil(100) as i64;
+    let slow_seconds = (slow * 60).div_ceil(rate.max(1)) as i64;
+    HumanDuration(Duration::seconds(bulk_seconds + slow_seconds))
+}
+
+fn make_cancel_button(cancel_uuid: &str) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![CreateButton::new(cancel_uuid)
+        .label("cancel")
+        .style(ButtonStyle::Secondary)])
+}
+
+/// Streams every message in `channel` sent before `cutoff`, newest first.
+///
+/// When `before` is given only messages older than that message are looked at.
+fn messages_before(
+    http: &Http,
+    channel: ChannelId,
+    cutoff: DateTime<Utc>,
+    before: Option<MessageId>,
+) -> impl Stream<Item = Result<Message, SlimeError>> + '_ {
+    let cutoff = cutoff.timestamp();
+
+    channel
+        .messages_iter(http)
+        .try_skip_while(move |message| {
+            future::ready(Ok(message.timestamp.unix_timestamp() > cutoff
+                || before.is_some_and(|before| message.id >= before)))
+        })
+        .map_err(SlimeError::from)
+}
+
+/// Finds the oldest of the `keep` newest messages in `channel`, or `None`
+/// if the channel doesn't have that many.
+async fn oldest_kept(
+    http: &Http,
+    channel: ChannelId,
+    keep: usize,
+) -> Result<Option<MessageId>, SlimeError> {
+    let messages = channel.messages_iter(http).skip(keep.saturating_sub(1));
+    futures::pin_mut!(messages);
+    let kept = messages.try_next().await?;
+
+    Ok(kept.map(|message| message.id))
+}
+
+/// Streams every message in `channel` older than `before` down to and
+/// including `oldest`, newest first.
+fn messages_between(
+    http: &Http,
+    channel: ChannelId,
+    before: MessageId,
+    oldest: MessageId,
+) -> impl Stream<Item = Result<Message, SlimeError>> + '_ {
+    stream::try_unfold(Some(before), move |before| async move {
+        let Some(before) = before else {
+            return Ok::<_, SlimeError>(None);
+        };
+
+        let page = channel
+            .messages(http, GetMessages::new().before(before).limit(100))
+            .await?;
+        let next = page
+            .last()
+            .map(|message| message.id)
+            .filter(|&id| page.len() == 100 && id > oldest);
+        let page = page
+            .into_iter()
+            .filter(move |message| message.id >= oldest)
+            .map(Ok::<_, SlimeError>);
+
+        Ok(Some((stream::iter(page), next)))
+    })
+    .try_flatten()
+}
+
+/// The messages a stored job still has to look at, before its filter is
+/// applied. Messages from exempt users and roles are never among them, and
+/// neither are messages from members if the job only wants departed ones.
+fn candidate_messages<'a>(
+    http: &'a Http,
+    exemptions: &'a Exemptions,
+    stored: &StoredJob,
+) -> BoxStream<'a, Result<Message, SlimeError>> {
+    let messages = match (stored.last_message_id, stored.oldest_message_id) {
+        (Some(before), Some(oldest)) => {
+            messages_between(http, stored.channel_id, before, oldest).boxed()
+        }
+        (before, oldest) => messages_before(http, stored.channel_id, stored.cutoff, before)
+            .try_take_while(move |message| {
+                future::ready(Ok(oldest.is_none_or(|oldest| message.id >= oldest)))
+            })
+            .boxed(),
+    };
+
+    let departed_only = stored.filter.departed_only;
+    messages
+        .try_filter_map(move |message| async move {
+            if exemptions.covers(http, &message).await? {
+                return Ok(None);
+            }
+            // Webhooks never were members, so they didn't leave either.
+            if departed_only
+                && (message.webhook_id.is_some()
+                    || exemptions
+                        .members()
+                        .is_member(http, message.author.id)
+                        .await?)
+            {
+                return Ok(None);
+            }
+            Ok(Some(message))
+        })
+        .boxed()
+}
+
+/// The messages a stored job still has to delete.
+fn stored_messages<'a>(
+    http: &'a Http,
+    exemptions: &'a Exemptions,
+    stored: &StoredJob,
+) -> BoxStream<'a, Result<Message, SlimeError>> {
+    let filter = stored.filter.clone();
+    candidate_messages(http, exemptions, stored)
+        .try_filter(move |message| future::ready(filter.deletes(message)))
+        .boxed()
+}
+
+/// What a purge is about to delete. Only the counts and the two ends are
+/// kept, so big channels don't have to fit in memory.
+#[derive(Default)]
+struct PurgeSummary {
+    bulk: usize,
+    slow: usize,
+    /// Pinned messages that would have been deleted but are kept.
+    pinned: usize,
+    newest: Option<Message>,
+    oldest: Option<Message>,
+    /// The first `PREVIEW_LEN` messages to be deleted.
+    preview: Vec<Message>,
+}
+
+impl PurgeSummary {
+    fn total(&self) -> usize {
+        self.bulk + self.slow
+    }
+
+    fn count(&mut self, message: Message, bulk_cutoff: i64) {
+        if is_bulk(&message, bulk_cutoff) {
+            self.bulk += 1;
+        } else {
+            self.slow += 1;
+        }
+        if self.newest.is_none() {
+            self.newest = Some(message.clone());
+        }
+        if self.preview.len() < PREVIEW_LEN {
+            self.preview.push(message.clone());
+        }
+        self.oldest = Some(message);
+    }
+}
+
+async fn summarize(
+    http: &Http,
+    pool: &PgPool,
+    stored: &StoredJob,
+) -> Result<PurgeSummary, SlimeError> {
+    let bulk_cutoff = bulk_cutoff().timestamp();
+    let exemptions = Exemptions::load(pool, stored.guild_id).await?;
+
+    candidate_messages(http, &exemptions, stored)
+        .try_fold(PurgeSummary::default(), |mut summary, message| {
+            match stored.filter.skip_reason(&message) {
+                Some(Skip::Pinned) => summary.pinned += 1,
+                Some(Skip::Filtered) => {}
+                None => summary.count(message, bulk_cutoff),
+            }
+            future::ready(Ok(summary))
+        })
+        .await
+}
+
+/// The ephemeral message a purge reports its progress in.
+struct ProgressMessage<'a> {
+    interaction: &'a ComponentInteraction,
+    message: MessageId,
+    cancel_uuid: &'a str,
+    expired: bool,
+}
+
+/// Everything `bulk_delete` and `slow_bulk_delete` need to delete messages
+/// for one purge job and keep track of how far along it is.
+struct PurgeRun<'a> {
+    http: &'a Http,
+    pool: &'a PgPool,
+    governor: &'a Governor,
+    guild: GuildId,
+    channel: ChannelId,
+    /// The guild's own limit of deletions per minute.
+    rate: Option<u32>,
+    job: &'a JobGuard,
+    progress: Option<ProgressMessage<'a>>,
+    deleted: usize,
+    bulk_remaining: usize,
+    slow_remaining: usize,
+    /// How many chunks `bulk_delete` went through, to space out progress updates.
+    bulk_chunks: usize,
+    /// When `slow_bulk_delete` last saved and reported its progress.
+    progress_at: Instant,
+}
+
+impl<'a> PurgeRun<'a> {
+    fn new(
+        http: &'a Http,
+        data: &'a Data,
+        stored: &StoredJob,
+        rate: Option<u32>,
+        job: &'a JobGuard,
+        progress: Option<ProgressMessage<'a>>,
+        summary: &PurgeSummary,
+    ) -> Self {
+        PurgeRun {
+            http,
+            pool: &data.pool,
+            governor: &data.governor,
+            guild: stored.guild_id,
+            channel: stored.channel_id,
+            rate,
+            job,
+            progress,
+            deleted: 0,
+            bulk_remaining: summary.bulk,
+            slow_remaining: summary.slow,
+            bulk_chunks: 0,
+            progress_at: Instant::now(),
+        }
+    }
+
+    fn cancel(&self) -> &CancellationToken {
+        &self.job.cancel
+    }
+
+    /// Makes the current counts visible to `purge_status`.
+    fn publish(&self) {
+        self.job
+            .publish_progress(self.deleted, self.bulk_remaining, self.slow_remaining);
+    }
+
+    /// Waits until the governor allows another deletion request. Returns
+    /// `false` if the job was cancelled in the meantime.
+    async fn wait_for_governor(&self) -> bool {
+        let at = self.governor.reserve(self.guild, self.rate);
+        tokio::select! {
+            () = sleep_until(at) => !self.cancel().is_cancelled(),
+            () = self.cancel().cancelled() => false,
+        }
+    }
+
+    /// Records that everything up to `last` was deleted, so the job can be
+    /// resumed from there after a restart.
+    async fn checkpoint(&self, last: MessageId) -> Result<(), SlimeError> {
+        jobs::checkpoint(self.pool, self.job.id, last).await?;
+        Ok(())
+    }
+
+    async fn update_progress(&mut self) {
+        let content = format!(
+            "Deleted {} messages, {} to go, which should take about {}.",
+            self.deleted,
+            self.bulk_remaining + self.slow_remaining,
+            estimate(
+                self.bulk_remaining,
+                self.slow_remaining,
+                self.governor.rate(self.rate)
+            ),
+        );
+        self.show_progress(content).await;
+    }
+
+    async fn show_progress(&mut self, content: String) {
+        let Some(progress) = self.progress.as_mut().filter(|progress| !progress.expired) else {
+            return;
+        };
+
+        let builder = CreateInteractionResponseFollowup::new()
+            .content(content)
+            .components(vec![make_cancel_button(progress.cancel_uuid)]);
+
+        // The interaction token is only valid for 15 minutes, after that the
+        // progress message can't be edited anymore.
+        if let Err(e) = progress
+            .interaction
+            .edit_followup(self.http, progress.message, builder)
+            .await
+        {
+            warn!("stopped updating purge progress: {}", e);
+            progress.expired = true;
+        }
+    }
+
+    /// Waits for the guild's turn, deletes every message in the stream and
+    /// records how the job ended.
+    async fn execute(
+        &mut self,
+        messages: impl Stream<Item = Result<Message, SlimeError>>,
+    ) -> Result<(), SlimeError> {
+        self.publish();
+        let _turn = match self.job.try_turn() {
+            Some(turn) => turn,
+            None => {
+                jobs::set_state(self.pool, self.job.id, JobState::Queued).await?;
+                self.show_progress(
+                    "Waiting for the other purges in this server to finish first.".to_string(),
+                )
+                .await;
+                let Some(turn) = self.job.wait_turn().await else {
+                    jobs::set_state(self.pool, self.job.id, JobState::Cancelled).await?;
+                    return Ok(());
+                };
+                jobs::set_state(self.pool, self.job.id, JobState::Running).await?;
+                self.update_progress().await;
+                turn
+            }
+        };
+
+        let result = self.delete_all(messages).await;
+
+        let state = match &result {
+            Err(_) => JobState::Failed,
+            Ok(()) if self.cancel().is_cancelled() => JobState::Cancelled,
+            Ok(()) => JobState::Finished,
+        };
+        jobs::set_state(self.pool, self.job.id, state).await?;
+
+        result
+    }
+
+    fn report<'s>(
+        &self,
+        stored: &'s StoredJob,
+        invoker: Option<UserId>,
+        started: Instant,
+        result: &Result<(), SlimeError>,
+    ) -> PurgeReport<'s> {
+        PurgeReport {
+            stored,
+            invoker,
+            deleted: self.deleted,
+            took: started.elapsed(),
+            cancelled: self.cancel().is_cancelled(),
+            error: result.as_ref().err().map(ToString::to_string),
+        }
+    }
+
+    /// Feeds the stream in chunks of 100 to `bulk_delete` or, once messages
+    /// get too old for that, `slow_bulk_delete`. Each chunk is archived
+    /// first, so `purge_restore` can bring it back.
+    async fn delete_all(
+        &mut self,
+        messages: impl Stream<Item = Result<Message, SlimeError>>,
+    ) -> Result<(), SlimeError> {
+        let chunks = messages.try_chunks(100).map_err(|TryChunksError(_, e)| e);
+        futures::pin_mut!(chunks);
+
+        while let Some(chunk) = chunks.try_next().await? {
+            if self.cancel().is_cancelled() {
+                break;
+            }
+            archive::store(self.pool, self.job.id, self.guild, &chunk).await?;
+
+            // Slow deletion can take hours, so the cutoff has to keep moving.
+            let bulk_cutoff = bulk_cutoff().timestamp();
+            let (bulk, slow): (Vec<_>, Vec<_>) = chunk
+                .into_iter()
+                .partition(|message| is_bulk(message, bulk_cutoff));
+            if !bulk.is_empty() {
+                bulk_delete(self, &bulk).await?;
+            }
+            if !slow.is_empty() {
+                slow_bulk_delete(self, &slow).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How a purge went, for the audit log and the guild's log channels.
+struct PurgeReport<'a> {
+    stored: &'a StoredJob,
+    /// Whoever started the purge, `None` for automatic ones.
+    invoker: Option<UserId>,
+    deleted: usize,
+    took: StdDuration,
+    cancelled: bool,
+    error: Option<String>,
+}
+
+impl PurgeReport<'_> {
+    fn embed(&self) -> CreateEmbed {
+        let (title, colour) = match (&self.error, self.cancelled) {
+            (Some(_), _) => ("Purge failed", Colour::RED),
+            (None, true) => ("Purge cancelled", Colour::ORANGE),
+            (None, false) => ("Purge finished", Colour::DARK_GREEN),
+        };
+        let invoker = match self.invoker {
+            Some(invoker) => invoker.mention().to_string(),
+            None => "automatic".to_string(),
+        };
+        let took = Duration::milliseconds(self.took.as_millis() as i64);
+
+        let mut embed = CreateEmbed::new()
+            .title(title)
+            .colour(colour)
+            .field("Started by", invoker, true)
+            .field(
+                "Channel",
+                self.stored.channel_id.mention().to_string(),
+                true,
+            )
+            .field("Deleted", self.deleted.to_string(), true)
+            .field("Took", HumanDuration(took).to_string(), true)
+            .footer(CreateEmbedFooter::new(format!("Job {}", self.stored.id)));
+        if let Some(error) = &self.error {
+            embed = embed.field("Error", error, false);
+        }
+
+        embed
+    }
+
+    /// Files a case for purges someone started.
+    async fn file_case(&self, pool: &PgPool) -> sqlx::Result<Option<i64>> {
+        let Some(invoker) = self.invoker else {
+            return Ok(None);
+        };
+        let mut details = format!("Deleted {} messages", self.deleted);
+        let filter = self.stored.filter.description();
+        if !filter.is_empty() {
+            details.push_str(&format!(" ({filter})"));
+        }
+
+        let case = NewCase {
+            action: Action::Purge,
+            user_id: self.stored.filter.user_id,
+            channel_id: Some(self.stored.channel_id),
+            moderator_id: Some(invoker),
+            reason: None,
+            details: Some(details),
+        };
+        cases::file(pool, self.stored.guild_id, case)
+            .await
+            .map(Some)
+    }
+
+    /// Records the purge in the audit log and the guild's cases, and posts
+    /// the report to the log channel, or the error channel if it failed.
+    /// The purge is over either way, so failures are only logged.
+    async fn publish(&self, http: &Http, data: &Data) {
+        let invoker = self.invoker;
+        audit::record(
+            &data.pool,
+            self.stored,
+            invoker,
+            self.deleted,
+            self.took,
+            false,
+        )
+        .await
+        .unwrap_or_else(|e| error!("failed to record purge: {}", e));
+        let case_id = self.file_case(&data.pool).await.unwrap_or_else(|e| {
+            error!("failed to file a case for the purge: {}", e);
+            None
+        });
+
+        let purpose = match self.error {
+            Some(_) => ChannelPurpose::Errors,
+            None => ChannelPurpose::Logs,
+        };
+        let settings = data.settings.get(self.stored.guild_id).await;
+        let channel = match settings.map(|settings| settings.channel(purpose)) {
+            Ok(Some(channel)) => channel,
+            Ok(None) => return,
+            Err(e) => {
+                error!("failed to look up the report channel: {}", e);
+                return;
+            }
+        };
+        let mut embed = self.embed();
+        if let Some(case_id) = case_id {
+            embed = embed.field("Case", case_id.to_string(), true);
+        }
+        if let Err(e) = channel
+            .send_message(http, CreateMessage::new().embed(embed))
+            .await
+        {
+            error!("failed to post purge report: {}", e);
+        }
+    }
+}
+
+/// Whether Discord refused a bulk deletion because a message was older
+/// than two weeks.
+fn is_too_old(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Http(HttpError::UnsuccessfulRequest(response))
+            if response.error.code == MESSAGE_TOO_OLD
+    )
+}
+
+/// Deletes a chunk of at most 100 messages younger than two weeks at once.
+/// Should Discord think some of them are too old after all, they are
+/// deleted one by one instead.
+async fn bulk_delete(run: &mut PurgeRun<'_>, messages: &[Message]) -> Result<(), SlimeError> {
+    if !run.wait_for_governor().await {
+        archive::discard(run.pool, messages.iter().map(|message| message.id)).await?;
+        return Ok(());
+    }
+    if let Err(e) = run
+        .channel
+        .delete_messages(run.http, messages.iter().map(|message| message.id))
+        .await
+    {
+        if !is_too_old(&e) {
+            return Err(e.into());
+        }
+        warn!("falling back to deleting messages one by one: {}", e);
+        run.bulk_remaining = run.bulk_remaining.saturating_sub(messages.len());
+        run.slow_remaining += messages.len();
+        run.publish();
+        return slow_bulk_delete(run, messages).await;
+    }
+    run.deleted += messages.len();
+    run.bulk_remaining = run.bulk_remaining.saturating_sub(messages.len());
+    run.publish();
+    run.checkpoint(messages.last().unwrap().id).await?;
+
+    run.bulk_chunks += 1;
+    if run.bulk_chunks.is_multiple_of(PROGRESS_CHUNKS) {
+        run.update_progress().await;
+    }
+
+    Ok(())
+}
+
+/// Whether a request failed because Discord rate limited it.
+fn is_rate_limited(error: &Error) -> bool {
+    matches!(error, Error::Http(e) if e.status_code() == Some(StatusCode::TOO_MANY_REQUESTS))
+}
+
+/// Deletes messages one by one, as fast as the governor allows.
+async fn slow_bulk_delete(run: &mut PurgeRun<'_>, messages: &[Message]) -> Result<(), SlimeError> {
+    let mut last_deleted = None;
+    'messages: for (i, message) in messages.iter().enumerate() {
+        loop {
+            if !run.wait_for_governor().await {
+                let kept = &messages[i..];
+                archive::discard(run.pool, kept.iter().map(|message| message.id)).await?;
+                break 'messages;
+            }
+            match run.channel.delete_message(run.http, message.id).await {
+                Err(e) if is_rate_limited(&e) => run.governor.throttled(RETRY_AFTER),
+                result => break result?,
+            }
+        }
+        run.deleted += 1;
+        run.slow_remaining = run.slow_remaining.saturating_sub(1);
+        run.publish();
+        last_deleted = Some(message.id);
+
+        if run.progress_at.elapsed() >= PROGRESS_INTERVAL {
+            run.checkpoint(message.id).await?;
+            run.update_progress().await;
+            run.progress_at = Instant::now();
+        }
+    }
+    if let Some(last_deleted) = last_deleted {
+        run.checkpoint(last_deleted).await?;
+    }
+
+    Ok(())
+}
+
+/// Waits for the cancel button on a progress message to be pressed.
+async fn wait_for_cancel(ctx: Context<'_>, cancel_uuid: &str, cancel: &CancellationToken) {
+    if let Some(interaction) = ComponentInteractionCollector::new(ctx.serenity_context())
+        .custom_ids(vec![cancel_uuid.to_string()])
+        .await
+    {
+        cancel.cancel();
+        let _ = interaction
+            .create_response(ctx, CreateInteractionResponse::Acknowledge)
+            .await
+            .inspect_err(|e| error!("{}", e));
+    }
+}
+
+/// Lists the first few messages of `summary`, one line each.
+fn preview_lines(summary: &PurgeSummary) -> String {
+    summary
+        .preview
+        .iter()
+        .map(|message| {
+            let mut content: String = message.content.chars().take(80).collect();
+            if content.len() < message.content.len() {
+                content.push('…');
+            }
+            format!(
+                "- {} {}: {}",
+                message.link(),
+                message.author.name,
+                content.replace('\n', " ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// How a confirmed purge ended.
+struct RunOutcome {
+    deleted: usize,
+    cancelled: bool,
+}
+
+/// Deletes every message of the confirmed purge `stored`, which `summary`
+/// describes, and reports progress in a followup to `interactions`.
+async fn run_confirmed(
+    ctx: Context<'_>,
+    interactions: &ComponentInteraction,
+    stored: &StoredJob,
+    summary: &PurgeSummary,
+    rate: Option<u32>,
+) -> Result<RunOutcome, SlimeError> {
+    let data = ctx.data();
+    let eta = estimate(summary.bulk, summary.slow, data.governor.rate(rate));
+    let exemptions = Exemptions::load(&data.pool, stored.guild_id).await?;
+    let cancel_uuid = format!("{}-cancel", stored.id);
+
+    let followup = CreateInteractionResponseFollowup::new()
+        .content(format!(
+            "Deleting {} messages in {}, this should take about {eta}. Job id: `{}`",
+            summary.total(),
+            stored.channel_id.mention(),
+            stored.id,
+        ))
+        .components(vec![make_cancel_button(&cancel_uuid)])
+        .ephemeral(true);
+    // The interaction's token only lasts 15 minutes, a purge queued for
+    // longer or the later channels of a multi-channel purge go on without a
+    // progress message.
+    let progress = match interactions.create_followup(ctx, followup).await {
+        Ok(progress_message) => Some(ProgressMessage {
+            interaction: interactions,
+            message: progress_message.id,
+            cancel_uuid: &cancel_uuid,
+            expired: false,
+        }),
+        Err(e) => {
+            warn!("no progress message for purge {}: {}", stored.id, e);
+            None
+        }
+    };
+
+    jobs::insert(&data.pool, stored).await?;
+    let job = data.jobs.register(
+        stored.id,
+        stored.guild_id,
+        stored.channel_id,
+        Some(ctx.author().id),
+    );
+    let mut run = PurgeRun::new(ctx.http(), data, stored, rate, &job, progress, summary);
+
+    let started = Instant::now();
+    let result = {
+        let deletion = run.execute(stored_messages(ctx.http(), &exemptions, stored));
+        tokio::pin!(deletion);
+
+        tokio::select! {
+            result = &mut deletion => result,
+            () = wait_for_cancel(ctx, &cancel_uuid, &job.cancel) => deletion.await,
+        }
+    };
+    run.report(stored, Some(ctx.author().id), started, &result)
+        .publish(ctx.http(), data)
+        .await;
+    result?;
+
+    let cancelled = job.cancel.is_cancelled();
+    let content = if cancelled {
+        format!(
+            "Cancelled after deleting {} of {} messages in {}.",
+            run.deleted,
+            summary.total(),
+            stored.channel_id.mention(),
+        )
+    } else {
+        format!(
+            "Deleted {} messages in {}.",
+            run.deleted,
+            stored.channel_id.mention()
+        )
+    };
+    let done = CreateInteractionResponseFollowup::new()
+        .content(content)
+        .components(vec![]);
+    if let Some(progress) = run.progress.filter(|progress| !progress.expired) {
+        if let Err(e) = interactions
+            .edit_followup(ctx, progress.message, done)
+            .await
+        {
+            error!("{}", e);
+        }
+    }
+
+    Ok(RunOutcome {
+        deleted: run.deleted,
+        cancelled,
+    })
+}
+
+/// Quotes `field` for a CSV file.
+fn csv_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// Instead of deleting anything, replies with a CSV file listing every
+/// message `purges` would delete. `what` names them, as in "`what` would be
+/// deleted".
+async fn dry_run_report(
+    ctx: Context<'_>,
+    what: String,
+    purges: &[(StoredJob, PurgeSummary)],
+) -> Result<(), SlimeError> {
+    let started = Instant::now();
+    let data = ctx.data();
+
+    let mut csv = String::from("channel_id,message_id,author_id,author,timestamp,content\n");
+    let mut rows = 0;
+    let mut truncated = false;
+    for (stored, _) in purges {
+        let exemptions = Exemptions::load(&data.pool, stored.guild_id).await?;
+        let messages = stored_messages(ctx.http(), &exemptions, stored);
+        futures::pin_mut!(messages);
+
+        let mut count = 0;
+        while let Some(message) = messages.try_next().await? {
+            count += 1;
+            if rows == DRY_RUN_ROWS {
+                truncated = true;
+                continue;
+            }
+            let content: String = message.content.chars().take(80).collect();
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                message.channel_id,
+                message.id,
+                message.author.id,
+                csv_field(&message.author.name),
+                message.timestamp,
+                csv_field(&content.replace('\n', " ")),
+            ));
+            rows += 1;
+        }
+
+        let invoker = Some(ctx.author().id);
+        audit::record(&data.pool, stored, invoker, count, started.elapsed(), true)
+            .await
+            .unwrap_or_else(|e| error!("failed to record dry run: {}", e));
+    }
+
+    let bulk = purges.iter().map(|(_, summary)| summary.bulk).sum();
+    let slow = purges.iter().map(|(_, summary)| summary.slow).sum();
+    let guild_id = ctx.guild_id().unwrap();
+    let rate = data.settings.get(guild_id).await?.purge_rate;
+    let eta = estimate(bulk, slow, data.governor.rate(rate));
+
+    let listed = if truncated {
+        csv.push_str(&format!("# truncated after {DRY_RUN_ROWS} rows\n"));
+        format!("the attached file lists only the first {DRY_RUN_ROWS} messages")
+    } else {
+        "the attached file lists every message".to_string()
+    };
+    let reply = CreateReply::default()
+        .content(format!(
+            "Dry run: {what} would be deleted, which should take about {eta}. Nothing was deleted, {listed}."
+        ))
+        .attachment(CreateAttachment::bytes(csv, "purge-dry-run.csv"))
+        .ephemeral(true);
+    ctx.send(reply).await?;
+
+    Ok(())
+}
+
+/// Asks for confirmation and then deletes every message of `stored`, which
+/// `summary` describes. `what` names the messages in the prompt, as in
+/// "`what` will be deleted", and `details` is shown below it.
+async fn confirm_and_purge(
+    ctx: Context<'_>,
+    what: String,
+    details: Option<String>,
+    summary: PurgeSummary,
+    stored: StoredJob,
+) -> Result<(), SlimeError> {
+    let locale = i18n::locale(ctx).await?;
+    let locale = locale.as_deref();
+    let (Some(first), Some(last)) = (&summary.oldest, &summary.newest) else {
+        let reply = CreateReply::default()
+            .content(i18n::tr(locale, Msg::NothingToDelete))
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    let rate = ctx.data().settings.get(stored.guild_id).await?.purge_rate;
+    let eta = estimate(summary.bulk, summary.slow, ctx.data().governor.rate(rate));
+
+    let eta = eta.to_string();
+    let mut content = format!(
+        "{} {} {}",
+        i18n::tr_args(
+            locale,
+            Msg::WillBeDeleted,
+            &[("what", &what), ("eta", &eta)]
+        ),
+        i18n::tr_args(
+            locale,
+            Msg::FirstAndLast,
+            &[("first", &first.link()), ("last", &last.link())]
+        ),
+        i18n::tr(locale, Msg::Continue),
+    );
+    if let Some(details) = details {
+        content.push_str("\n\n");
+        content.push_str(&details);
+    }
+
+    if let Some(interactions) = confirm(ctx, content).await? {
+        run_confirmed(ctx, &interactions, &stored, &summary, rate).await?;
+    }
+
+    Ok(())
+}
+
+/// Runs each of the confirmed `purges` in turn and sums up how many
+/// messages every one of them deleted. Channels in `archived` are archived
+/// threads, they are reopened for the purge and archived again afterwards.
+async fn run_each(
+    ctx: Context<'_>,
+    interactions: &ComponentInteraction,
+    purges: &[(StoredJob, PurgeSummary)],
+    archived: &[ChannelId],
+    rate: Option<u32>,
+) -> Result<(), SlimeError> {
+    let mut lines = Vec::new();
+    let mut total = 0;
+    let mut cancelled = false;
+    for (stored, summary) in purges {
+        let reopen = archived.contains(&stored.channel_id);
+        if reopen {
+            stored
+                .channel_id
+                .edit_thread(ctx, EditThread::new().archived(false))
+                .await?;
+        }
+        let outcome = run_confirmed(ctx, interactions, stored, summary, rate).await;
+        if reopen {
+            if let Err(e) = stored
+                .channel_id
+                .edit_thread(ctx, EditThread::new().archived(true))
+                .await
+            {
+                warn!(
+                    "failed to archive thread {} again: {}",
+                    stored.channel_id, e
+                );
+            }
+        }
+
+        let outcome = outcome?;
+        total += outcome.deleted;
+        lines.push(format!(
+            "- {}: {} messages",
+            stored.channel_id.mention(),
+            outcome.deleted
+        ));
+        if outcome.cancelled {
+            cancelled = true;
+            break;
+        }
+    }
+
+    let skipped = if cancelled && lines.len() < purges.len() {
+        " The remaining channels were skipped."
+    } else {
+        ""
+    };
+    let followup = CreateInteractionResponseFollowup::new()
+        .content(fit_message(format!(
+            "Deleted {total} messages in total.{skipped}\n{}",
+            lines.join("\n")
+        )))
+        .ephemeral(true);
+    if let Err(e) = interactions.create_followup(ctx, followup).await {
+        warn!("failed to sum up a multi-channel purge: {}", e);
+    }
+
+    Ok(())
+}
+
+/// Lists how many messages each of `purges` deletes, one line per channel.
+fn channel_lines(purges: &[(StoredJob, PurgeSummary)]) -> String {
+    purges
+        .iter()
+        .map(|(stored, summary)| {
+            format!(
+                "- {}: {} messages",
+                stored.channel_id.mention(),
+                summary.total()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Every thread of `channel` the bot can see: the active ones and the 100
+/// most recently archived public and private ones.
+async fn channel_threads(
+    http: &Http,
+    channel: &GuildChannel,
+) -> Result<Vec<GuildChannel>, SlimeError> {
+    let mut threads: Vec<_> = channel
+        .guild_id
+        .get_active_threads(http)
+        .await?
+        .threads
+        .into_iter()
+        .filter(|thread| thread.parent_id == Some(channel.id))
+        .collect();
+
+    let public = channel
+        .id
+        .get_archived_public_threads(http, None, Some(100))
+        .await?;
+    threads.extend(public.threads);
+    // Listing private threads needs Manage Threads, without it they are skipped.
+    match channel
+        .id
+        .get_archived_private_threads(http, None, Some(100))
+        .await
+    {
+        Ok(private) => threads.extend(private.threads),
+        Err(e) => warn!("skipping private threads of {}: {}", channel.id, e),
+    }
+
+    Ok(threads)
+}
+
+/// Deletes old messages in this channel
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_old(
+    ctx: Context<'_>,
+    #[description = "Only delete messages sent by this user"] user: Option<User>,
+    #[description = "Only delete messages older than this, e.g. 3d, 12h or 2w (default 7d)"]
+    older_than: Option<HumanDuration>,
+    #[description = "Keep pinned messages (default true)"] exclude_pinned: Option<bool>,
+    #[description = "Only delete messages sent by bots and webhooks"] bots_only: Option<bool>,
+    #[description = "Only delete messages with attachments"] has_attachment: Option<bool>,
+    #[description = "Only delete messages with embeds"] has_embed: Option<bool>,
+    #[description = "Only delete messages with links"] has_link: Option<bool>,
+    #[description = "Also purge the threads of this channel"] include_threads: Option<bool>,
+    #[description = "Only list what would be deleted, in a file"] dry_run: Option<bool>,
+) -> Result<(), SlimeError> {
+    let channel = ctx.guild_channel().await.unwrap();
+
+    ctx.defer_ephemeral().await?;
+    if !preflight::check(ctx, &[channel.id]).await? {
+        return Ok(());
+    }
+
+    let older_than = older_than.unwrap_or(HumanDuration(Duration::days(7)));
+    let bots_only = bots_only.unwrap_or(false);
+    let stored = StoredJob {
+        id: JobId(ctx.id()),
+        guild_id: channel.guild_id,
+        channel_id: channel.id,
+        filter: MessageFilter {
+            user_id: user.as_ref().map(|user| user.id),
+            exclude_pinned: exclude_pinned.unwrap_or(true),
+            bots_only,
+            has_attachment: has_attachment.unwrap_or(false),
+            has_embed: has_embed.unwrap_or(false),
+            has_link: has_link.unwrap_or(false),
+            ..Default::default()
+        },
+        cutoff: Utc::now() - older_than.as_duration(),
+        last_message_id: None,
+        oldest_message_id: None,
+    };
+    let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+
+    let mut threads = Vec::new();
+    let mut archived = Vec::new();
+    if include_threads.unwrap_or(false) {
+        for thread in channel_threads(ctx.http(), &channel).await? {
+            let stored = StoredJob {
+                id: JobId::generate(),
+                channel_id: thread.id,
+                filter: stored.filter.clone(),
+                ..stored
+            };
+            let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+            if summary.total() == 0 {
+                continue;
+            }
+            if thread
+                .thread_metadata
+                .is_some_and(|metadata| metadata.archived)
+            {
+                archived.push(thread.id);
+            }
+            threads.push((stored, summary));
+        }
+    }
+
+    let author = match &user {
+        Some(user) => format!(" from {}", user.mention()),
+        None => String::new(),
+    };
+    let pinned = match summary.pinned {
+        0 => String::new(),
+        1 => " (1 pinned message is kept)".to_string(),
+        n => format!(" ({n} pinned messages are kept)"),
+    };
+    let kind = if bots_only {
+        "bot messages"
+    } else {
+        "messages"
+    };
+    let content = match stored.filter.content_description() {
+        Some(content) => format!(" {content}"),
+        None => String::new(),
+    };
+
+    if threads.is_empty() {
+        let what = format!(
+            "{} {kind}{author}{content} older than {older_than}{pinned}",
+            summary.total()
+        );
+        if dry_run.unwrap_or(false) {
+            return dry_run_report(ctx, what, &[(stored, summary)]).await;
+        }
+        return confirm_and_purge(ctx, what, None, summary, stored).await;
+    }
+
+    let mut purges = Vec::new();
+    if summary.total() > 0 {
+        purges.push((stored, summary));
+    }
+    purges.extend(threads);
+
+    let rate = ctx.data().settings.get(channel.guild_id).await?.purge_rate;
+    let bulk = purges.iter().map(|(_, summary)| summary.bulk).sum();
+    let slow = purges.iter().map(|(_, summary)| summary.slow).sum();
+    let eta = estimate(bulk, slow, ctx.data().governor.rate(rate));
+    let what = format!(
+        "{} {kind}{author}{content} older than {older_than} in {} and its threads",
+        bulk + slow,
+        channel.mention(),
+    );
+    if dry_run.unwrap_or(false) {
+        return dry_run_report(ctx, what, &purges).await;
+    }
+    let locale = i18n::locale(ctx).await?;
+    let locale = locale.as_deref();
+    let eta = eta.to_string();
+    let content = format!(
+        "{} {}\n\n{}",
+        i18n::tr_args(
+            locale,
+            Msg::WillBeDeleted,
+            &[("what", &what), ("eta", &eta)]
+        ),
+        i18n::tr(locale, Msg::Continue),
+        channel_lines(&purges),
+    );
+
+    if let Some(interactions) = confirm(ctx, fit_message(content)).await? {
+        run_each(ctx, &interactions, &purges, &archived, rate).await?;
+    }
+
+    Ok(())
+}
+
+/// Deletes every message between two messages in this channel
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_range(
+    ctx: Context<'_>,
+    #[description = "Link or id of the first message"] start: Message,
+    #[description = "Link or id of the last message"] end: Message,
+    #[description = "Also delete the two messages themselves (default true)"] inclusive: Option<
+        bool,
+    >,
+    #[description = "Only list what would be deleted, in a file"] dry_run: Option<bool>,
+) -> Result<(), SlimeError> {
+    let channel = ctx.guild_channel().await.unwrap();
+
+    if start.channel_id != channel.id || end.channel_id != channel.id {
+        let reply = CreateReply::default()
+            .content("Both messages have to be in this channel.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    ctx.defer_ephemeral().await?;
+    if !preflight::check(ctx, &[channel.id]).await? {
+        return Ok(());
+    }
+
+    let inclusive = inclusive.unwrap_or(true);
+    let newest = start.id.max(end.id).get();
+    let oldest = start.id.min(end.id).get();
+    let (before, oldest) = if inclusive {
+        (newest + 1, oldest)
+    } else {
+        (newest, oldest + 1)
+    };
+
+    let stored = StoredJob {
+        id: JobId(ctx.id()),
+        guild_id: channel.guild_id,
+        channel_id: channel.id,
+        filter: MessageFilter::default(),
+        cutoff: Utc::now(),
+        last_message_id: Some(MessageId::new(before)),
+        oldest_message_id: Some(MessageId::new(oldest)),
+    };
+    let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+
+    let bounds = if inclusive { "including" } else { "excluding" };
+    let what = format!(
+        "{} messages between {} and {}, {bounds} those two,",
+        summary.total(),
+        start.link(),
+        end.link(),
+    );
+
+    if dry_run.unwrap_or(false) {
+        return dry_run_report(ctx, what, &[(stored, summary)]).await;
+    }
+    confirm_and_purge(ctx, what, None, summary, stored).await
+}
+
+/// Deletes messages in this channel whose content matches a regex
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_matching(
+    ctx: Context<'_>,
+    #[description = "Regex the message content has to match"] pattern: Pattern,
+    #[description = "Only delete messages older than this, e.g. 3d, 12h or 2w"] older_than: Option<
+        HumanDuration,
+    >,
+    #[description = "Keep pinned messages (default true)"] exclude_pinned: Option<bool>,
+    #[description = "Only list what would be deleted, in a file"] dry_run: Option<bool>,
+) -> Result<(), SlimeError> {
+    let channel = ctx.guild_channel().await.unwrap();
+
+    ctx.defer_ephemeral().await?;
+    if !preflight::check(ctx, &[channel.id]).await? {
+        return Ok(());
+    }
+
+    let older_than = older_than.map_or(Duration::zero(), |older_than| older_than.as_duration());
+    let stored = StoredJob {
+        id: JobId(ctx.id()),
+        guild_id: channel.guild_id,
+        channel_id: channel.id,
+        filter: MessageFilter {
+            exclude_pinned: exclude_pinned.unwrap_or(true),
+            pattern: Some(pattern.clone()),
+            ..Default::default()
+        },
+        cutoff: Utc::now() - older_than,
+        last_message_id: None,
+        oldest_message_id: None,
+    };
+    let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+
+    let what = format!("{} messages matching `{pattern}`", summary.total());
+    if dry_run.unwrap_or(false) {
+        return dry_run_report(ctx, what, &[(stored, summary)]).await;
+    }
+    let details = format!("These are the newest matches:\n{}", preview_lines(&summary));
+
+    confirm_and_purge(ctx, what, Some(details), summary, stored).await
+}
+
+/// Deletes messages in this channel from users who left the server
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_departed(
+    ctx: Context<'_>,
+    #[description = "Only delete messages older than this, e.g. 3d, 12h or 2w"] older_than: Option<
+        HumanDuration,
+    >,
+    #[description = "Keep pinned messages (default true)"] exclude_pinned: Option<bool>,
+    #[description = "Only list what would be deleted, in a file"] dry_run: Option<bool>,
+) -> Result<(), SlimeError> {
+    let channel = ctx.guild_channel().await.unwrap();
+
+    ctx.defer_ephemeral().await?;
+    if !preflight::check(ctx, &[channel.id]).await? {
+        return Ok(());
+    }
+
+    let age = older_than.map_or(Duration::zero(), |older_than| older_than.as_duration());
+    let stored = StoredJob {
+        id: JobId(ctx.id()),
+        guild_id: channel.guild_id,
+        channel_id: channel.id,
+        filter: MessageFilter {
+            exclude_pinned: exclude_pinned.unwrap_or(true),
+            departed_only: true,
+            ..Default::default()
+        },
+        cutoff: Utc::now() - age,
+        last_message_id: None,
+        oldest_message_id: None,
+    };
+    let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+
+    let older_than = match older_than {
+        Some(older_than) => format!(" older than {older_than}"),
+        None => String::new(),
+    };
+    let what = format!(
+        "{} messages from departed members{older_than}",
+        summary.total()
+    );
+
+    if dry_run.unwrap_or(false) {
+        return dry_run_report(ctx, what, &[(stored, summary)]).await;
+    }
+    confirm_and_purge(ctx, what, None, summary, stored).await
+}
+
+/// Deletes everything in this channel except for the newest messages
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_keep(
+    ctx: Context<'_>,
+    #[description = "How many of the newest messages to keep"]
+    #[min = 1]
+    keep: u32,
+    #[description = "Keep pinned messages (default true)"] exclude_pinned: Option<bool>,
+    #[description = "Only list what would be deleted, in a file"] dry_run: Option<bool>,
+) -> Result<(), SlimeError> {
+    let channel = ctx.guild_channel().await.unwrap();
+
+    ctx.defer_ephemeral().await?;
+    if !preflight::check(ctx, &[channel.id]).await? {
+        return Ok(());
+    }
+
+    let Some(oldest_kept) = oldest_kept(ctx.http(), channel.id, keep as usize).await? else {
+        let reply = CreateReply::default()
+            .content(format!("This channel has no more than {keep} messages."))
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    };
+
+    let stored = StoredJob {
+        id: JobId(ctx.id()),
+        guild_id: channel.guild_id,
+        channel_id: channel.id,
+        filter: MessageFilter {
+            exclude_pinned: exclude_pinned.unwrap_or(true),
+            ..Default::default()
+        },
+        cutoff: Utc::now(),
+        // Everything older than the oldest kept message goes.
+        last_message_id: Some(oldest_kept),
+        oldest_message_id: None,
+    };
+    let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+
+    let what = format!(
+        "Everything but the newest {keep} messages, {} messages in total,",
+        summary.total()
+    );
+
+    if dry_run.unwrap_or(false) {
+        return dry_run_report(ctx, what, &[(stored, summary)]).await;
+    }
+    confirm_and_purge(ctx, what, None, summary, stored).await
+}
+
+/// The text channels a `purge_channels` call names, in channel list order.
+async fn selected_channels(
+    ctx: Context<'_>,
+    category: Option<GuildChannel>,
+    channels: impl IntoIterator<Item = Option<GuildChannel>>,
+) -> Result<Vec<GuildChannel>, SlimeError> {
+    let mut selected = Vec::new();
+    if let Some(category) = category {
+        let guild_id = ctx.guild_id().unwrap();
+        let mut children: Vec<_> = guild_id
+            .channels(ctx)
+            .await?
+            .into_values()
+            .filter(|channel| {
+                channel.parent_id == Some(category.id) && channel.kind == ChannelType::Text
+            })
+            .collect();
+        children.sort_by_key(|channel| channel.position);
+        selected.extend(children);
+    }
+    for channel in channels.into_iter().flatten() {
+        if !selected.iter().any(|selected| selected.id == channel.id) {
+            selected.push(channel);
+        }
+    }
+
+    Ok(selected)
+}
+
+/// Deletes old messages in several channels, one after the other
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_channels(
+    ctx: Context<'_>,
+    #[description = "Purge every text channel in this category"]
+    #[channel_types("Category")]
+    category: Option<GuildChannel>,
+    #[description = "A channel to purge"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+    #[description = "Another channel to purge"]
+    #[channel_types("Text")]
+    channel_2: Option<GuildChannel>,
+    #[description = "Another channel to purge"]
+    #[channel_types("Text")]
+    channel_3: Option<GuildChannel>,
+    #[description = "Only delete messages older than this, e.g. 3d, 12h or 2w (default 7d)"]
+    older_than: Option<HumanDuration>,
+    #[description = "Keep pinned messages (default true)"] exclude_pinned: Option<bool>,
+) -> Result<(), SlimeError> {
+    ctx.defer_ephemeral().await?;
+
+    let channels = selected_channels(ctx, category, [channel, channel_2, channel_3]).await?;
+    if channels.is_empty() {
+        let reply = CreateReply::default()
+            .content("Pick a category or at least one channel.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+    let channel_ids: Vec<_> = channels.iter().map(|channel| channel.id).collect();
+    if !preflight::check(ctx, &channel_ids).await? {
+        return Ok(());
+    }
+
+    let older_than = older_than.unwrap_or(HumanDuration(Duration::days(7)));
+    let cutoff = Utc::now() - older_than.as_duration();
+    let mut purges = Vec::new();
+    for channel in &channels {
+        let stored = StoredJob {
+            id: JobId::generate(),
+            guild_id: channel.guild_id,
+            channel_id: channel.id,
+            filter: MessageFilter {
+                exclude_pinned: exclude_pinned.unwrap_or(true),
+                ..Default::default()
+            },
+            cutoff,
+            last_message_id: None,
+            oldest_message_id: None,
+        };
+        let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+        if summary.total() > 0 {
+            purges.push((stored, summary));
+        }
+    }
+
+    let locale = i18n::locale(ctx).await?;
+    let locale = locale.as_deref();
+    if purges.is_empty() {
+        let reply = CreateReply::default()
+            .content(i18n::tr(locale, Msg::NothingToDelete))
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let rate = ctx.data().settings.get(guild_id).await?.purge_rate;
+    let bulk = purges
+        .iter()
+        .map(|(_, summary)| summary.bulk)
+        .sum::<usize>();
+    let slow = purges
+        .iter()
+        .map(|(_, summary)| summary.slow)
+        .sum::<usize>();
+    let eta = estimate(bulk, slow, ctx.data().governor.rate(rate)).to_string();
+
+    let what = format!(
+        "{} messages older than {older_than} in {} channels",
+        bulk + slow,
+        purges.len()
+    );
+    let content = format!(
+        "{} {}\n\n{}",
+        i18n::tr_args(
+            locale,
+            Msg::WillBeDeletedInTurn,
+            &[("what", &what), ("eta", &eta)]
+        ),
+        i18n::tr(locale, Msg::Continue),
+        channel_lines(&purges),
+    );
+
+    if let Some(interactions) = confirm(ctx, fit_message(content)).await? {
+        run_each(ctx, &interactions, &purges, &[], rate).await?;
+    }
+
+    Ok(())
+}
+
+/// Deletes a user's recent messages in every channel, e.g. after their account was compromised
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_user_everywhere(
+    ctx: Context<'_>,
+    #[description = "User whose messages to delete"] user: User,
+    #[description = "Delete their messages from this far back, e.g. 1h or 3d"]
+    window: HumanDuration,
+    #[description = "Keep pinned messages (default true)"] exclude_pinned: Option<bool>,
+) -> Result<(), SlimeError> {
+    if window.as_duration() <= Duration::zero() {
+        let reply = CreateReply::default()
+            .content("Pick how far back to delete.")
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    ctx.defer_ephemeral().await?;
+    // Channels the bot can't purge are left out instead of refusing the
+    // whole purge, a compromised account has to be cleaned up quickly.
+    let channel_ids = preflight::purgeable_channels(ctx).await?;
+
+    let now = Utc::now();
+    let mut purges = Vec::new();
+    for channel_id in channel_ids {
+        let stored = StoredJob {
+            id: JobId::generate(),
+            guild_id: ctx.guild_id().unwrap(),
+            channel_id,
+            filter: MessageFilter {
+                user_id: Some(user.id),
+                exclude_pinned: exclude_pinned.unwrap_or(true),
+                ..Default::default()
+            },
+            cutoff: now,
+            last_message_id: None,
+            oldest_message_id: Some(first_id_since(now - window.as_duration())),
+        };
+        let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+        if summary.total() > 0 {
+            purges.push((stored, summary));
+        }
+    }
+
+    let locale = i18n::locale(ctx).await?;
+    let locale = locale.as_deref();
+    if purges.is_empty() {
+        let reply = CreateReply::default()
+            .content(i18n::tr(locale, Msg::NothingToDelete))
+            .ephemeral(true);
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let rate = ctx.data().settings.get(guild_id).await?.purge_rate;
+    let bulk = purges
+        .iter()
+        .map(|(_, summary)| summary.bulk)
+        .sum::<usize>();
+    let slow = purges
+        .iter()
+        .map(|(_, summary)| summary.slow)
+        .sum::<usize>();
+    let eta = estimate(bulk, slow, ctx.data().governor.rate(rate)).to_string();
+
+    let what = format!(
+        "{} messages from {} sent in the last {window} in {} channels",
+        bulk + slow,
+        user.mention(),
+        purges.len()
+    );
+    let content = format!(
+        "{} {}\n\n{}",
+        i18n::tr_args(
+            locale,
+            Msg::WillBeDeletedInTurn,
+            &[("what", &what), ("eta", &eta)]
+        ),
+        i18n::tr(locale, Msg::Continue),
+        channel_lines(&purges),
+    );
+
+    if let Some(interactions) = confirm(ctx, fit_message(content)).await? {
+        run_each(ctx, &interactions, &purges, &[], rate).await?;
+    }
+
+    Ok(())
+}
+
+/// Describes one job for `purge_status`.
+fn status_line(job: &JobInfo, rate: usize) -> String {
+    let started_by = match job.started_by {
+        Some(user_id) => format!("started by {}", user_id.mention()),
+        None => "started automatically".to_string(),
+    };
+    let remaining = job.bulk_remaining + job.slow_remaining;
+    let progress = if job.queued {
+        "queued".to_string()
+    } else if remaining == 0 {
+        // Background jobs don't count their messages up front.
+        format!("{} deleted so far", job.deleted)
+    } else {
+        format!(
+            "{}/{} deleted, about {} to go",
+            job.deleted,
+            job.deleted + remaining,
+            estimate(job.bulk_remaining, job.slow_remaining, rate)
+        )
+    };
+
+    format!(
+        "- `{}` in {}, {started_by}: {progress}",
+        job.id,
+        job.channel_id.mention()
+    )
+}
+
+/// Lists the purges running or waiting in this server
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_status(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let jobs = data.jobs.in_guild(guild_id);
+    let rate = data
+        .governor
+        .rate(data.settings.get(guild_id).await?.purge_rate);
+
+    if jobs.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .content("There are no purges running.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let lines = jobs.iter().map(|job| status_line(job, rate));
+    audit::paginate(ctx, "Purges", &audit::pages(lines, STATUS_PAGE_LEN)).await
+}
+
+/// Deletes every message in the channel that was sent after this one
+#[poise::command(
+    context_menu_command = "Delete messages after this",
+    guild_only,
+    category = "Delete"
+)]
+pub async fn purge_after(ctx: Context<'_>, message: Message) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+
+    ctx.defer_ephemeral().await?;
+    if !preflight::check(ctx, &[message.channel_id]).await? {
+        return Ok(());
+    }
+
+    let stored = StoredJob {
+        id: JobId(ctx.id()),
+        guild_id,
+        channel_id: message.channel_id,
+        filter: MessageFilter {
+            exclude_pinned: true,
+            ..Default::default()
+        },
+        cutoff: Utc::now(),
+        last_message_id: None,
+        oldest_message_id: Some(MessageId::new(message.id.get() + 1)),
+    };
+    let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+
+    let what = format!("{} messages sent after {}", summary.total(), message.link());
+
+    confirm_and_purge(ctx, what, None, summary, stored).await
+}
+
+/// Asks how far back to go and then deletes the messages `user` sent in
+/// this channel.
+async fn purge_author(ctx: Context<'_>, user: User) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !preflight::check(ctx, &[ctx.channel_id()]).await? {
+        return Ok(());
+    }
+
+    let window_uuid = format!("{}-window", ctx.id());
+    let options = AUTHOR_WINDOWS
+        .iter()
+        .map(|(label, value)| CreateSelectMenuOption::new(*label, *value))
+        .collect();
+    let menu = CreateSelectMenu::new(&window_uuid, CreateSelectMenuKind::String { options })
+        .placeholder("How far back?");
+    let reply = CreateReply::default()
+        .content(format!(
+            "Which messages from {} in this channel should be deleted?",
+            user.mention()
+        ))
+        .components(vec![CreateActionRow::SelectMenu(menu)])
+        .ephemeral(true);
+    ctx.send(reply).await?;
+
+    let Some(choice) = ComponentInteractionCollector::new(ctx.serenity_context())
+        .timeout(std::time::Duration::from_secs(120))
+        .custom_ids(vec![window_uuid])
+        .await
+    else {
+        return Ok(());
+    };
+    let ComponentInteractionDataKind::StringSelect { values } = &choice.data.kind else {
+        return Ok(());
+    };
+    // "all" doesn't parse, which means no window.
+    let window: Option<HumanDuration> = values.first().and_then(|value| value.parse().ok());
+
+    let message = CreateInteractionResponseMessage::new()
+        .content(format!("Looking for messages from {}…", user.mention()))
+        .components(vec![]);
+    choice
+        .create_response(ctx, CreateInteractionResponse::UpdateMessage(message))
+        .await?;
+
+    let now = Utc::now();
+    let stored = StoredJob {
+        id: JobId(ctx.id()),
+        guild_id,
+        channel_id: ctx.channel_id(),
+        filter: MessageFilter {
+            user_id: Some(user.id),
+            exclude_pinned: true,
+            ..Default::default()
+        },
+        cutoff: now,
+        last_message_id: None,
+        oldest_message_id: window.map(|window| first_id_since(now - window.as_duration())),
+    };
+    let summary = summarize(ctx.http(), &ctx.data().pool, &stored).await?;
+
+    let since = match window {
+        Some(window) => format!(" sent in the last {window}"),
+        None => String::new(),
+    };
+    let what = format!(
+        "{} messages from {}{since}",
+        summary.total(),
+        user.mention()
+    );
+
+    confirm_and_purge(ctx, what, None, summary, stored).await
+}
+
+/// Deletes this user's messages in the current channel
+#[poise::command(
+    context_menu_command = "Purge this user here",
+    guild_only,
+    category = "Delete"
+)]
+pub async fn purge_user_here(ctx: Context<'_>, user: User) -> Result<(), SlimeError> {
+    purge_author(ctx, user).await
+}
+
+/// Deletes the author's messages in this channel
+#[poise::command(
+    context_menu_command = "Purge this author here",
+    guild_only,
+    category = "Delete"
+)]
+pub async fn purge_author_here(ctx: Context<'_>, message: Message) -> Result<(), SlimeError> {
+    purge_author(ctx, message.author).await
+}
+
+/// Stops a running purge
+#[poise::command(slash_command, guild_only, category = "Delete")]
+pub async fn purge_cancel(
+    ctx: Context<'_>,
+    #[description = "Job id from the progress message, defaults to every purge in this channel"]
+    job: Option<JobId>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let jobs = &ctx.data().jobs;
+
+    let cancelled = match job {
+        Some(job) => usize::from(jobs.cancel(guild_id, job)),
+        None => jobs.cancel_channel(ctx.channel_id()),
+    };
+
+    let content = match cancelled {
+        0 => "There is no running purge to cancel.".to_string(),
+        1 => "Cancelling the purge.".to_string(),
+        n => format!("Cancelling {n} purges."),
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Picks up every purge that was still running when the bot shut down.
+pub async fn resume_jobs(http: Arc<Http>, data: Data) {
+    let stored = match jobs::unfinished(&data.pool).await {
+        Ok(stored) => stored,
+        Err(e) => {
+            error!("failed to load unfinished purges: {}", e);
+            return;
+        }
+    };
+
+    for job in stored {
+        let http = Arc::clone(&http);
+        let data = data.clone();
+        tokio::spawn(async move {
+            let id = job.id;
+            if let Err(e) = resume_job(&http, &data, job).await {
+                error!("failed to resume purge {}: {}", id, e);
+            }
+        });
+    }
+}
+
+async fn resume_job(http: &Http, data: &Data, stored: StoredJob) -> Result<(), SlimeError> {
+    let notice = format!(
+        "Resuming purge `{}` in {}, it was interrupted by a restart.",
+        stored.id,
+        stored.channel_id.mention()
+    );
+    let settings = data.settings.get(stored.guild_id).await?;
+    match settings.channel(ChannelPurpose::Status) {
+        Some(status_channel) => {
+            status_channel.say(http, notice).await?;
+        }
+        None => info!("{}", notice),
+    }
+
+    run_in_background(http, data, &stored, None).await?;
+    Ok(())
+}
+
+/// Runs a purge that nobody is watching, like a resumed or scheduled one.
+/// The job has to be stored already. `invoker` is whoever caused it, if
+/// anyone. Returns how many messages were deleted.
+pub async fn run_in_background(
+    http: &Http,
+    data: &Data,
+    stored: &StoredJob,
+    invoker: Option<UserId>,
+) -> Result<usize, SlimeError> {
+    let exemptions = Exemptions::load(&data.pool, stored.guild_id).await?;
+    let job = data
+        .jobs
+        .register(stored.id, stored.guild_id, stored.channel_id, invoker);
+    // Nobody sees the progress, so there is no point in counting up front.
+    let summary = PurgeSummary::default();
+    let rate = data.settings.get(stored.guild_id).await?.purge_rate;
+    let mut run = PurgeRun::new(http, data, stored, rate, &job, None, &summary);
+
+    let started = Instant::now();
+    let result = run
+        .execute(stored_messages(http, &exemptions, stored))
+        .await;
+    run.report(stored, invoker, started, &result)
+        .publish(http, data)
+        .await;
+    result?;
+
+    Ok(run.deleted)
+}
+
+/// Deletes everything `user_id` sent in `channel_ids` since `since`, one
+/// channel after the other, for `invoker`. Every channel with messages to
+/// delete becomes a purge job of its own, so the deletions are metered and
+/// reported like any other purge.
+pub async fn wipe_user(
+    http: Arc<Http>,
+    data: Data,
+    guild_id: GuildId,
+    channel_ids: Vec<ChannelId>,
+    user_id: UserId,
+    since: DateTime<Utc>,
+    invoker: UserId,
+) {
+    let now = Utc::now();
+    for channel_id in channel_ids {
+        let stored = StoredJob {
+            id: JobId::generate(),
+            guild_id,
+            channel_id,
+            filter: MessageFilter {
+                user_id: Some(user_id),
+                ..Default::default()
+            },
+            cutoff: now,
+            last_message_id: None,
+            oldest_message_id: Some(first_id_since(since)),
+        };
+        // Don't report an empty purge for every channel the user never wrote in.
+        match summarize(&http, &data.pool, &stored).await {
+            Ok(summary) if summary.total() == 0 => continue,
+            Ok(_) => {}
+            Err(e) => {
+                error!("failed to look for messages in {}: {}", channel_id, e);
+                continue;
+            }
+        }
+        if let Err(e) = jobs::insert(&data.pool, &stored).await {
+            error!("failed to start purge in {}: {}", channel_id, e);
+            continue;
+        }
+        if let Err(e) = run_in_background(&http, &data, &stored, Some(invoker)).await {
+            error!("purge {} failed: {}", stored.id, e);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+
+use crate::{
+    db, localtime, replies::reply, settings::ChannelPurpose, ApplicationContext, Context,
+    SlimeError,
+};
+
+/// A quoted message, from the `quotes` table.
+#[derive(sqlx::FromRow)]
+struct Quote {
+    quote_id: i64,
+    channel_id: i64,
+    message_id: i64,
+    author_id: i64,
+    content: String,
+    said_at: DateTime<Utc>,
+}
+
+impl Quote {
+    fn embed(&self, guild_id: GuildId) -> CreateEmbed {
+        let channel_id = ChannelId::new(self.channel_id as u64);
+        let mut embed = CreateEmbed::new()
+            .colour(Colour::TEAL)
+            .field(
+                "Said by",
+                UserId::new(self.author_id as u64).mention().to_string(),
+                true,
+            )
+            .field(
+                "Source",
+                MessageId::new(self.message_id as u64).link(channel_id, Some(guild_id)),
+                true,
+            )
+            .footer(CreateEmbedFooter::new(format!("Quote #{}", self.quote_id)))
+            .timestamp(localtime::to_timestamp(self.said_at));
+        if !self.content.is_empty() {
+            embed = embed.description(&self.content);
+        }
+        embed
+    }
+}
+
+/// Reposts a message in the server's quote channel
+#[poise::command(context_menu_command = "Quote", guild_only)]
+pub async fn quote_message(
+    ctx: ApplicationContext<'_>,
+    #[description = "Message to quote"] message: Message,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(quote_channel) = data
+        .settings
+        .get(guild_id)
+        .await?
+        .channel(ChannelPurpose::Quotes)
+    else {
+        let content = "There's no quote channel, set one with `/config set quote_channel`.";
+        return reply(ctx.into(), content.to_string()).await;
+    };
+    if message.author.bot {
+        return reply(ctx.into(), "Bots aren't worth quoting.".to_string()).await;
+    }
+    let image = message.attachments.iter().find(|attachment| {
+        attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|content_type| content_type.starts_with("image/"))
+    });
+    if message.content.is_empty() && image.is_none() {
+        return reply(
+            ctx.into(),
+            "There's nothing in that message to quote.".to_string(),
+        )
+        .await;
+    }
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let quote: Option<Quote> = sqlx::query_as(
+        "INSERT INTO quotes (guild_id, channel_id, message_id, author_id, content, said_at,
+        quoted_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (guild_id, message_id) DO NOTHING
+        RETURNING quote_id, channel_id, message_id, author_id, content, said_at",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(message.channel_id))
+    .bind(i64::from(message.id))
+    .bind(i64::from(message.author.id))
+    .bind(&message.content)
+    .bind(localtime::from_timestamp(message.timestamp))
+    .bind(i64::from(ctx.author().id))
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(quote) = quote else {
+        return reply(ctx.into(), "That message was quoted already.".to_string()).await;
+    };
+
+    let mut embed = quote
+        .embed(guild_id)
+        .author(CreateEmbedAuthor::new(message.author.tag()).icon_url(message.author.face()));
+    if let Some(image) = image {
+        embed = embed.image(&image.url);
+    }
+    let content = format!("💬 Quoted by {}", ctx.author().mention());
+    let posted = quote_channel
+        .send_message(
+            ctx.http(),
+            CreateMessage::new()
+                .content(content)
+                .embed(embed)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await;
+    if let Err(e) = posted {
+        sqlx::query("DELETE FROM quotes WHERE quote_id = $1")
+            .bind(quote.quote_id)
+            .execute(&data.pool)
+            .await?;
+        return Err(e.into());
+    }
+
+    let content = format!("Quoted in {}.", quote_channel.mention());
+    reply(ctx.into(), content).await
+}
+
+/// Shows the messages quoted in this server
+#[poise::command(slash_command, guild_only, subcommands("random"))]
+pub async fn quote(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Shows a random quote
+#[poise::command(slash_command, guild_only)]
+async fn random(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let quote: Option<Quote> = sqlx::query_as(
+        "SELECT quote_id, channel_id, message_id, author_id, content, said_at FROM quotes
+        WHERE guild_id = $1 ORDER BY random() LIMIT 1",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_optional(&ctx.data().pool)
+    .await?;
+    let Some(quote) = quote else {
+        let content = "Nothing was quoted yet, right-click a message and pick Apps > Quote.";
+        return reply(ctx, content.to_string()).await;
+    };
+
+    ctx.send(CreateReply::default().embed(quote.embed(guild_id)))
+        .await?;
+    Ok(())
+}
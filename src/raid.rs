@@ -0,0 +1,329 @@
+use std::{collections::VecDeque, fmt, str::FromStr, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tokio::time::Instant;
+
+use crate::{
+    agegate,
+    cases::{self, Action, NewCase},
+    db,
+    duration::HumanDuration,
+    localtime,
+    replies::reply,
+    settings::GuildSettings,
+    Context, Data, SlimeError,
+};
+
+/// The longest window a `JoinRate` may have.
+const MAX_WINDOW: StdDuration = StdDuration::from_secs(600);
+
+/// This many joins in this window start raid mode, e.g. `10/30s`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct JoinRate {
+    pub joins: u32,
+    pub window: StdDuration,
+}
+
+impl FromStr for JoinRate {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (joins, window) = s.split_once('/').ok_or(())?;
+        let joins = joins.trim().parse().map_err(|_| ())?;
+        let window: HumanDuration = window.parse().map_err(|_| ())?;
+        let window = window.as_duration().to_std().map_err(|_| ())?;
+        if !(3..=100).contains(&joins) || window.is_zero() || window > MAX_WINDOW {
+            return Err(());
+        }
+
+        Ok(JoinRate { joins, window })
+    }
+}
+
+impl fmt::Display for JoinRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let window = Duration::seconds(self.window.as_secs() as i64);
+        write!(f, "{}/{}", self.joins, HumanDuration(window))
+    }
+}
+
+/// When members joined each guild lately, to tell when a raid starts.
+#[derive(Default)]
+pub struct RaidTracker {
+    joins: DashMap<GuildId, VecDeque<Instant>>,
+}
+
+impl RaidTracker {
+    /// Counts a join and returns whether it makes `rate`. The joins are
+    /// forgotten then, so that a raid only starts raid mode once.
+    fn record(&self, guild_id: GuildId, rate: JoinRate) -> bool {
+        let now = Instant::now();
+        let mut joins = self.joins.entry(guild_id).or_default();
+        joins.retain(|&joined| now.duration_since(joined) < rate.window);
+        joins.push_back(now);
+        if joins.len() < rate.joins as usize {
+            return false;
+        }
+
+        joins.clear();
+        true
+    }
+
+    /// Drops the joins of a guild the bot left.
+    pub fn forget(&self, guild_id: GuildId) {
+        self.joins.remove(&guild_id);
+    }
+}
+
+/// Raid mode as stored in the `raid_modes` table, for guilds that have it on.
+#[derive(sqlx::FromRow)]
+struct RaidMode {
+    started_by: Option<i64>,
+    started_at: DateTime<Utc>,
+}
+
+async fn raid_mode(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<Option<RaidMode>> {
+    sqlx::query_as("SELECT started_by, started_at FROM raid_modes WHERE guild_id = $1")
+        .bind(i64::from(guild_id))
+        .fetch_optional(pool)
+        .await
+}
+
+/// Turns raid mode on and raises the verification level to high, if the
+/// guild wants that and it's lower. `started_by` is `None` when too many
+/// members joined at once. Returns whether the level was raised, or `None`
+/// if raid mode was on already.
+async fn start(
+    http: &Http,
+    data: &Data,
+    guild_id: GuildId,
+    started_by: Option<UserId>,
+) -> Result<Option<bool>, SlimeError> {
+    let settings = data.settings.get(guild_id).await?;
+    let mut previous = None;
+    if settings.raid_verification() {
+        let level = guild_id.to_partial_guild(http).await?.verification_level;
+        previous = (level < VerificationLevel::High).then_some(level);
+    }
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let started = sqlx::query(
+        "INSERT INTO raid_modes (guild_id, started_by, previous_verification)
+        VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+    )
+    .bind(i64::from(guild_id))
+    .bind(started_by.map(i64::from))
+    .bind(previous.map(|level| i16::from(u8::from(level))))
+    .execute(&data.pool)
+    .await?
+    .rows_affected()
+        > 0;
+    if !started {
+        return Ok(None);
+    }
+
+    if previous.is_some() {
+        let builder = EditGuild::new()
+            .verification_level(VerificationLevel::High)
+            .audit_log_reason("Raid mode");
+        guild_id.edit(http, builder).await?;
+    }
+    Ok(Some(previous.is_some()))
+}
+
+/// Turns raid mode off and puts back the verification level it raised.
+/// Returns whether the level was put back, or `None` if raid mode was off.
+async fn stop(http: &Http, data: &Data, guild_id: GuildId) -> Result<Option<bool>, SlimeError> {
+    let stopped: Option<Option<i16>> = sqlx::query_scalar(
+        "DELETE FROM raid_modes WHERE guild_id = $1 RETURNING previous_verification",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(previous) = stopped else {
+        return Ok(None);
+    };
+
+    if let Some(previous) = previous {
+        let builder = EditGuild::new()
+            .verification_level(VerificationLevel::from(previous as u8))
+            .audit_log_reason("Raid mode is over");
+        guild_id.edit(http, builder).await?;
+    }
+    Ok(Some(previous.is_some()))
+}
+
+/// What raid mode does in a guild, e.g. " It kicks accounts younger than 7d."
+fn effects(settings: &GuildSettings, raised: bool) -> String {
+    let mut effects = String::new();
+    if raised {
+        effects.push_str(" It raises the verification level to high.");
+    }
+    if let Some(min_age) = settings.raid_min_age {
+        effects.push_str(&format!(" It kicks accounts younger than {min_age}."));
+    }
+    effects
+}
+
+/// Kicks `member` if their account is younger than `min_age`, and files a
+/// case for it. Returns whether they were kicked.
+async fn kick_if_new(
+    ctx: &serenity::Context,
+    data: &Data,
+    member: &Member,
+    min_age: HumanDuration,
+) -> Result<bool, SlimeError> {
+    let age = agegate::account_age(member.user.id);
+    if age >= min_age.as_duration() {
+        return Ok(false);
+    }
+
+    let guild_id = member.guild_id;
+    guild_id
+        .kick_with_reason(ctx, member.user.id, "Raid mode: the account is too new")
+        .await?;
+
+    let age = HumanDuration(age);
+    let case = NewCase {
+        action: Action::Kick,
+        user_id: Some(member.user.id),
+        channel_id: None,
+        moderator_id: None,
+        reason: Some("raid mode"),
+        details: Some(format!("The account was {age} old")),
+    };
+    let case_id = cases::file(&data.pool, guild_id, case).await?;
+    let content = format!(
+        "Case {case_id}: raid mode kicked {}, their account is only {age} old.",
+        member.user.mention()
+    );
+    cases::announce(&ctx.http, data, guild_id, &content).await;
+
+    Ok(true)
+}
+
+/// Counts joins towards the guild's `raid_joins`, and kicks accounts younger
+/// than its `raid_min_age` while raid mode is on. Returns whether `member`
+/// was kicked.
+pub async fn member_joined(
+    ctx: &serenity::Context,
+    data: &Data,
+    member: &Member,
+) -> Result<bool, SlimeError> {
+    if member.user.bot {
+        return Ok(false);
+    }
+    let guild_id = member.guild_id;
+    let settings = data.settings.get(guild_id).await?;
+
+    let mut active = raid_mode(&data.pool, guild_id).await?.is_some();
+    if let Some(rate) = settings.raid_joins.filter(|_| !active) {
+        if data.raids.record(guild_id, rate) {
+            if let Some(raised) = start(&ctx.http, data, guild_id, None).await? {
+                let window = Duration::seconds(rate.window.as_secs() as i64);
+                let content = format!(
+                    "Raid mode is on, {} members joined within {}.{} Turn it off with \
+                    `/raidmode off` once the raid is over.",
+                    rate.joins,
+                    HumanDuration(window),
+                    effects(&settings, raised)
+                );
+                cases::announce(&ctx.http, data, guild_id, &content).await;
+            }
+            active = true;
+        }
+    }
+
+    match settings.raid_min_age {
+        Some(min_age) if active => kick_if_new(ctx, data, member, min_age).await,
+        _ => Ok(false),
+    }
+}
+
+/// Controls raid mode, which keeps new accounts out during a raid
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("raidmode_on", "raidmode_off", "status")
+)]
+pub async fn raidmode(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Turns raid mode on until it's turned off again
+#[poise::command(slash_command, guild_only, category = "Moderation", rename = "on")]
+async fn raidmode_on(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(raised) = start(ctx.http(), data, guild_id, Some(ctx.author().id)).await? else {
+        return reply(ctx, "Raid mode is on already.".to_string()).await;
+    };
+
+    let settings = data.settings.get(guild_id).await?;
+    let content = format!(
+        "{} turned raid mode on.{}",
+        ctx.author().mention(),
+        effects(&settings, raised)
+    );
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
+
+/// Turns raid mode off and puts back the verification level
+#[poise::command(slash_command, guild_only, category = "Moderation", rename = "off")]
+async fn raidmode_off(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(restored) = stop(ctx.http(), data, guild_id).await? else {
+        return reply(ctx, "Raid mode isn't on.".to_string()).await;
+    };
+
+    let mut content = format!("{} turned raid mode off.", ctx.author().mention());
+    if restored {
+        content.push_str(" The verification level is back to what it was.");
+    }
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
+
+/// Shows whether raid mode is on and when it starts by itself
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn status(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let settings = data.settings.get(guild_id).await?;
+
+    let mut lines = vec![match raid_mode(&data.pool, guild_id).await? {
+        Some(mode) => {
+            let by = match mode.started_by {
+                Some(user_id) => format!("by {}", UserId::new(user_id as u64).mention()),
+                None => "automatically".to_string(),
+            };
+            format!(
+                "Raid mode is on, it was turned on {by} at {}.",
+                localtime::format_datetime(mode.started_at, settings.timezone)
+            )
+        }
+        None => "Raid mode is off.".to_string(),
+    }];
+    lines.push(match settings.raid_joins {
+        Some(rate) => format!(
+            "It turns on by itself when {} members join within {}.",
+            rate.joins,
+            HumanDuration(Duration::seconds(rate.window.as_secs() as i64))
+        ),
+        None => "It only turns on when a moderator turns it on.".to_string(),
+    });
+    let effects = effects(&settings, settings.raid_verification());
+    if !effects.is_empty() {
+        lines.push(effects.trim_start().to_string());
+    }
+
+    reply(ctx, lines.join("\n")).await
+}
@@ -0,0 +1,251 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use poise::{
+    serenity_prelude::{async_trait, EventHandler, GuildId, LightMethod, RatelimitInfo},
+    CreateReply,
+};
+use tokio::time::Instant;
+
+use crate::{duration::HumanDuration, Context, SlimeError};
+
+/// Deletions per minute a fresh governor allows.
+const START_RATE: f64 = 500.0;
+const MIN_RATE: f64 = 30.0;
+const MAX_RATE: f64 = 3000.0;
+
+/// How much the rate grows for every minute without a rate limit.
+const RATE_STEP: f64 = 100.0;
+
+/// How many seconds' worth of deletions may happen in one burst.
+const BURST_SECONDS: f64 = 10.0;
+
+/// The longest pause after running into rate limits over and over.
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Paces the deletion requests of every purge. It starts out at
+/// `START_RATE`, halves the rate and pauses whenever Discord rate limits a
+/// deletion, and slowly speeds back up once the rate limits stop.
+///
+/// Guilds can ask for their purges to go slower than that, see
+/// `GuildSettings::purge_rate`.
+pub struct Governor {
+    state: Mutex<State>,
+    /// When each rate limited guild may send its next deletion request.
+    guilds: DashMap<GuildId, Instant>,
+}
+
+struct State {
+    /// Deletions per minute.
+    rate: f64,
+    tokens: f64,
+    refilled: Instant,
+    paused_until: Instant,
+    last_change: Instant,
+    /// `granted` at `last_change`, so the rate only grows while it's used.
+    granted_at_change: u64,
+    /// Rate limits in a row, each one doubles the pause.
+    strikes: u32,
+    hits: u64,
+    last_hit: Option<Instant>,
+    granted: u64,
+}
+
+impl State {
+    fn per_second(&self) -> f64 {
+        self.rate / 60.0
+    }
+
+    fn capacity(&self) -> f64 {
+        self.per_second() * BURST_SECONDS
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now.duration_since(self.last_change) >= Duration::from_secs(60)
+            && self.granted > self.granted_at_change
+        {
+            self.rate = (self.rate + RATE_STEP).min(MAX_RATE);
+            self.strikes = 0;
+            self.last_change = now;
+            self.granted_at_change = self.granted;
+        }
+
+        let elapsed = now.duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second()).min(self.capacity());
+        self.refilled = now;
+    }
+}
+
+impl Default for Governor {
+    fn default() -> Self {
+        let now = Instant::now();
+        Governor {
+            state: Mutex::new(State {
+                rate: START_RATE,
+                tokens: START_RATE / 60.0 * BURST_SECONDS,
+                refilled: now,
+                paused_until: now,
+                last_change: now,
+                granted_at_change: 0,
+                strikes: 0,
+                hits: 0,
+                last_hit: None,
+                granted: 0,
+            }),
+            guilds: DashMap::new(),
+        }
+    }
+}
+
+/// A snapshot of the governor for `ratelimit_status`.
+pub struct Status {
+    pub rate: usize,
+    pub hits: u64,
+    pub since_last_hit: Option<Duration>,
+    pub paused_for: Option<Duration>,
+    pub granted: u64,
+}
+
+impl Governor {
+    /// Takes a slot for one deletion request in `guild_id` and returns when
+    /// it may be sent. `guild_rate` is the guild's own limit per minute.
+    pub fn reserve(&self, guild_id: GuildId, guild_rate: Option<u32>) -> Instant {
+        let at = self.reserve_global();
+        let Some(guild_rate) = guild_rate else {
+            return at;
+        };
+
+        let spacing = Duration::from_secs(60) / guild_rate.max(1);
+        let mut next = self.guilds.entry(guild_id).or_insert(at);
+        let slot = (*next).max(at);
+        *next = slot + spacing;
+
+        slot
+    }
+
+    fn reserve_global(&self) -> Instant {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.refill(now);
+
+        state.tokens -= 1.0;
+        state.granted += 1;
+        let wait = if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / state.per_second())
+        };
+
+        (now + wait).max(state.paused_until)
+    }
+
+    /// Slows down after Discord rate limited a deletion for `retry_after`.
+    pub fn throttled(&self, retry_after: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        state.rate = (state.rate / 2.0).max(MIN_RATE);
+        state.tokens = state.tokens.min(0.0);
+        state.strikes += 1;
+        let pause = retry_after
+            .saturating_mul(1 << (state.strikes - 1).min(10))
+            .min(MAX_BACKOFF);
+        state.paused_until = state.paused_until.max(now + pause);
+        state.last_change = now;
+        state.granted_at_change = state.granted;
+        state.hits += 1;
+        state.last_hit = Some(now);
+    }
+
+    /// The current deletions per minute, capped at `guild_rate`.
+    pub fn rate(&self, guild_rate: Option<u32>) -> usize {
+        let rate = self.state.lock().unwrap().rate as usize;
+        guild_rate.map_or(rate, |guild_rate| rate.min(guild_rate as usize))
+    }
+
+    pub fn status(&self) -> Status {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        Status {
+            rate: state.rate as usize,
+            hits: state.hits,
+            since_last_hit: state.last_hit.map(|last_hit| now.duration_since(last_hit)),
+            paused_for: (state.paused_until > now).then(|| state.paused_until - now),
+            granted: state.granted,
+        }
+    }
+}
+
+/// Whether `method` on `path` is one of the requests purges delete messages
+/// with: deleting one message, or bulk deleting some. Other deletions, of
+/// channels or permission overwrites, have rate limits of their own.
+fn purge_route(method: LightMethod, path: &str) -> bool {
+    let Some((_, route)) = path.split_once("/channels/") else {
+        return false;
+    };
+    let segments: Vec<&str> = route.split('/').collect();
+    matches!(
+        (method, &segments[..]),
+        (LightMethod::Delete, [_, "messages", _])
+            | (LightMethod::Post, [_, "messages", "bulk-delete"])
+    )
+}
+
+/// Passes Serenity's rate limit notices for message deletions on to the
+/// governor.
+pub struct RatelimitHandler(pub Arc<Governor>);
+
+#[async_trait]
+impl EventHandler for RatelimitHandler {
+    async fn ratelimit(&self, info: RatelimitInfo) {
+        if purge_route(info.method, &info.path) {
+            self.0.throttled(info.timeout);
+        }
+    }
+}
+
+fn std_to_human(duration: Duration) -> HumanDuration {
+    HumanDuration(chrono::Duration::seconds(duration.as_secs() as i64))
+}
+
+/// Shows how fast purges are currently allowed to delete
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+pub async fn ratelimit_status(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let status = ctx.data().governor.status();
+
+    let mut lines = vec![format!(
+        "Deleting up to {} messages per minute.",
+        status.rate
+    )];
+    lines.push(match status.since_last_hit {
+        Some(ago) => format!(
+            "Ran into {} rate limits, the last one {} ago.",
+            status.hits,
+            std_to_human(ago)
+        ),
+        None => "Hasn't run into any rate limits yet.".to_string(),
+    });
+    if let Some(guild_id) = ctx.guild_id() {
+        if let Some(rate) = ctx.data().settings.get(guild_id).await?.purge_rate {
+            lines.push(format!("This server limits purges to {rate} per minute."));
+        }
+    }
+    if let Some(paused_for) = status.paused_for {
+        lines.push(format!("Paused for another {}.", std_to_human(paused_for)));
+    }
+    lines.push(format!(
+        "{} deletion requests since the bot started.",
+        status.granted
+    ));
+
+    let reply = CreateReply::default()
+        .content(lines.join("\n"))
+        .ephemeral(true);
+    ctx.send(reply).await?;
+
+    Ok(())
+}
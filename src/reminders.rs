@@ -0,0 +1,205 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{audit, localtime, replies::reply, schedule::RunAt, Context, Data, SlimeError};
+
+/// How many reminders a member can have waiting in a guild at once.
+const MAX_REMINDERS: i64 = 25;
+
+/// How many reminders one page of `/remind list` shows.
+const PAGE_LEN: usize = 10;
+
+/// "Remind `user_id` of `content` at `remind_at`", from the `reminders` table.
+#[derive(sqlx::FromRow)]
+struct Reminder {
+    reminder_id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    user_id: i64,
+    content: String,
+    remind_at: DateTime<Utc>,
+}
+
+/// Removes the reminders that are due and returns them, so each one is
+/// only ever sent once.
+async fn take_due(pool: &PgPool) -> sqlx::Result<Vec<Reminder>> {
+    sqlx::query_as(
+        "DELETE FROM reminders WHERE remind_at <= now()
+        RETURNING reminder_id, guild_id, channel_id, user_id, content, remind_at",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// DMs the reminder to whoever set it. If their DMs are closed, they are
+/// pinged in the channel they set it in instead.
+async fn send(http: &Http, reminder: &Reminder) -> Result<(), SlimeError> {
+    let user_id = UserId::new(reminder.user_id as u64);
+    let channel_id = ChannelId::new(reminder.channel_id as u64);
+    let content = format!(
+        "Reminder from {}: {}",
+        channel_id.mention(),
+        reminder.content
+    );
+    let dm = match user_id.create_dm_channel(http).await {
+        Ok(dm) => dm
+            .send_message(http, CreateMessage::new().content(content))
+            .await
+            .map(|_| ()),
+        Err(e) => Err(e),
+    };
+    let Err(e) = dm else {
+        return Ok(());
+    };
+    warn!(
+        "failed to DM reminder {}, pinging them instead: {}",
+        reminder.reminder_id, e
+    );
+
+    // The reminder is whatever they typed, so it mustn't ping anyone else.
+    let message = CreateMessage::new()
+        .content(format!(
+            "{}, you asked me to remind you: {}",
+            user_id.mention(),
+            reminder.content
+        ))
+        .allowed_mentions(CreateAllowedMentions::new().users([user_id]));
+    channel_id.send_message(http, message).await?;
+
+    Ok(())
+}
+
+/// Sends every reminder that is due, the scheduler calls this.
+pub async fn send_due(http: &Http, data: &Data) {
+    let due = match take_due(&data.pool).await {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to load due reminders: {}", e);
+            return;
+        }
+    };
+
+    for reminder in due {
+        if let Err(e) = send(http, &reminder).await {
+            error!(
+                "failed to send reminder {} in guild {}: {}",
+                reminder.reminder_id, reminder.guild_id, e
+            );
+        }
+    }
+}
+
+/// Reminds you of something later
+#[poise::command(slash_command, guild_only, subcommands("me", "list", "delete"))]
+pub async fn remind(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Sets a reminder, which you get as a DM
+#[poise::command(slash_command, guild_only)]
+async fn me(
+    ctx: Context<'_>,
+    #[description = "When to remind you, e.g. 2h, 18:00 or 2024-05-01 09:00"] when: RunAt,
+    #[description = "What to remind you of"]
+    #[max_length = 1000]
+    text: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let now = Utc::now();
+    let remind_at = when.resolve(timezone, now);
+    if remind_at <= now {
+        return reply(ctx, "That time has already passed.".to_string()).await;
+    }
+
+    let waiting: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM reminders WHERE guild_id = $1 AND user_id = $2")
+            .bind(i64::from(guild_id))
+            .bind(i64::from(ctx.author().id))
+            .fetch_one(&data.pool)
+            .await?;
+    if waiting >= MAX_REMINDERS {
+        let content = format!("You have {MAX_REMINDERS} reminders already, delete one first.");
+        return reply(ctx, content).await;
+    }
+
+    let reminder_id: i64 = sqlx::query_scalar(
+        "INSERT INTO reminders (guild_id, channel_id, user_id, content, remind_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING reminder_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.channel_id()))
+    .bind(i64::from(ctx.author().id))
+    .bind(&text)
+    .bind(remind_at)
+    .fetch_one(&data.pool)
+    .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Reminder `{reminder_id}`: I'll remind you at {}.",
+            localtime::format_datetime(remind_at, timezone)
+        ),
+    )
+    .await
+}
+
+/// Lists the reminders you have waiting in this server
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let reminders: Vec<Reminder> = sqlx::query_as(
+        "SELECT reminder_id, guild_id, channel_id, user_id, content, remind_at FROM reminders
+        WHERE guild_id = $1 AND user_id = $2 ORDER BY remind_at",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .fetch_all(&data.pool)
+    .await?;
+
+    if reminders.is_empty() {
+        return reply(ctx, "You have no reminders.".to_string()).await;
+    }
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let lines = reminders.iter().map(|reminder| {
+        format!(
+            "`{}` {}: {}",
+            reminder.reminder_id,
+            localtime::format_datetime(reminder.remind_at, timezone),
+            reminder.content
+        )
+    });
+    audit::paginate(ctx, "Your reminders", &audit::pages(lines, PAGE_LEN)).await
+}
+
+/// Deletes one of your reminders before you get it
+#[poise::command(slash_command, guild_only)]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "Reminder to delete, see /remind list"] id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = sqlx::query(
+        "DELETE FROM reminders WHERE guild_id = $1 AND user_id = $2 AND reminder_id = $3",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .bind(id)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Reminder `{id}` is deleted.")
+    } else {
+        format!("You have no reminder `{id}`.")
+    };
+    reply(ctx, content).await
+}
@@ -0,0 +1,21 @@
+use poise::CreateReply;
+
+use crate::{Context, SlimeError};
+
+/// Answers the command with `content`, only visible to whoever ran it.
+pub async fn reply(ctx: Context<'_>, content: String) -> Result<(), SlimeError> {
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Cuts `text` short to `len` characters, with an ellipsis if anything was
+/// cut.
+pub fn shorten(text: &str, len: usize) -> String {
+    if text.chars().count() <= len {
+        return text.to_string();
+    }
+    let mut text: String = text.chars().take(len - 1).collect();
+    text.push('…');
+    text
+}
@@ -0,0 +1,188 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use poise::{serenity_prelude::*, Modal};
+use tracing::error;
+
+use crate::{
+    cases, db, localtime, msglog, replies::reply, settings::ChannelPurpose, ApplicationContext,
+    Context, SlimeError,
+};
+
+/// How many open reports `/reports list` shows at most.
+const LIST_LEN: i64 = 20;
+
+#[derive(Modal)]
+#[name = "Report to moderators"]
+struct ReportModal {
+    #[name = "What's wrong with this message?"]
+    #[paragraph]
+    #[max_length = 500]
+    reason: String,
+}
+
+/// A report that is still open, from the `reports` table.
+#[derive(sqlx::FromRow)]
+struct OpenReport {
+    report_id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    message_id: i64,
+    author_id: i64,
+    reporter_id: i64,
+    reason: String,
+    created_at: DateTime<Utc>,
+}
+
+impl OpenReport {
+    fn line(&self, timezone: Option<FixedOffset>) -> String {
+        let link = MessageId::new(self.message_id as u64).link(
+            ChannelId::new(self.channel_id as u64),
+            Some(GuildId::new(self.guild_id as u64)),
+        );
+        format!(
+            "`{}` {}: [message]({link}) by {}, reported by {}: {}",
+            self.report_id,
+            localtime::format_datetime(self.created_at, timezone),
+            UserId::new(self.author_id as u64).mention(),
+            UserId::new(self.reporter_id as u64).mention(),
+            self.reason
+        )
+    }
+}
+
+/// Reports a message to the server's moderators
+#[poise::command(context_menu_command = "Report to moderators", guild_only)]
+pub async fn report_message(
+    ctx: ApplicationContext<'_>,
+    #[description = "Message to report"] message: Message,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let reporter = ctx.author().id;
+    if message.author.id == reporter {
+        return reply(ctx.into(), "You can't report your own message.".to_string()).await;
+    }
+    // The modal has to be the first response, so nothing can be deferred.
+    let Some(ReportModal { reason }) = ReportModal::execute(ctx).await? else {
+        return Ok(());
+    };
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let report_id: Option<i64> = sqlx::query_scalar(
+        "INSERT INTO reports (guild_id, channel_id, message_id, author_id, reporter_id, reason)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (guild_id, message_id, reporter_id) DO NOTHING
+        RETURNING report_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(message.channel_id))
+    .bind(i64::from(message.id))
+    .bind(i64::from(message.author.id))
+    .bind(i64::from(reporter))
+    .bind(&reason)
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(report_id) = report_id else {
+        return reply(ctx.into(), "You reported this message already.".to_string()).await;
+    };
+
+    let settings = data.settings.get(guild_id).await?;
+    if let Some(channel) = settings.channel(ChannelPurpose::Logs) {
+        let embed = CreateEmbed::new()
+            .title(format!("Report {report_id}"))
+            .url(message.link())
+            .colour(Colour::ORANGE)
+            .field("Author", message.author.mention().to_string(), true)
+            .field("Reported by", reporter.mention().to_string(), true)
+            .field("Channel", message.channel_id.mention().to_string(), true)
+            .field("Message", msglog::field_text(&message.content), false)
+            .field("Reason", reason, false)
+            .footer(CreateEmbedFooter::new(format!(
+                "/reports resolve {report_id} once it's dealt with"
+            )));
+        if let Err(e) = channel
+            .send_message(ctx.http(), CreateMessage::new().embed(embed))
+            .await
+        {
+            error!("failed to post report {}: {}", report_id, e);
+        }
+    }
+
+    reply(
+        ctx.into(),
+        "Thanks, the moderators will have a look.".to_string(),
+    )
+    .await
+}
+
+/// Manages the messages members reported
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("list", "resolve")
+)]
+pub async fn reports(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Lists the reports nobody resolved yet
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let reports: Vec<OpenReport> = sqlx::query_as(
+        "SELECT report_id, guild_id, channel_id, message_id, author_id, reporter_id, reason,
+        created_at FROM reports WHERE guild_id = $1 AND status = 'open'
+        ORDER BY created_at LIMIT $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(LIST_LEN)
+    .fetch_all(&data.pool)
+    .await?;
+
+    let content = if reports.is_empty() {
+        "There are no open reports.".to_string()
+    } else {
+        let timezone = data.settings.get(guild_id).await?.timezone;
+        reports
+            .iter()
+            .map(|report| report.line(timezone))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    reply(ctx, content).await
+}
+
+/// Marks a report as dealt with
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn resolve(
+    ctx: Context<'_>,
+    #[description = "Report to resolve, see /reports list"] id: i64,
+    #[description = "What was done about it"]
+    #[max_length = 512]
+    note: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let resolved = sqlx::query(
+        "UPDATE reports SET status = 'resolved', resolved_by = $3, resolution = $4,
+        resolved_at = now()
+        WHERE guild_id = $1 AND report_id = $2 AND status = 'open'",
+    )
+    .bind(i64::from(guild_id))
+    .bind(id)
+    .bind(i64::from(ctx.author().id))
+    .bind(&note)
+    .execute(&data.pool)
+    .await?
+    .rows_affected();
+    if resolved == 0 {
+        return reply(ctx, format!("There is no open report `{id}`.")).await;
+    }
+
+    let note = note.map_or(String::new(), |note| format!(": {note}"));
+    let content = format!("{} resolved report {id}{note}", ctx.author().mention());
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
@@ -0,0 +1,294 @@
+use std::{str::FromStr, sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+use tracing::{error, info};
+
+use crate::{
+    audit,
+    duration::HumanDuration,
+    filter::MessageFilter,
+    jobs::{self, JobId, StoredJob},
+    localtime, purge, Context, Data, SlimeError,
+};
+
+/// How often the background task checks for policies that are due.
+const POLICY_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+/// How many policies one page of `/retention_policy list` shows.
+const PAGE_LEN: usize = 10;
+
+/// A time of day, written as `HH:MM`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeOfDay(pub NaiveTime);
+
+impl FromStr for TimeOfDay {
+    type Err = chrono::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NaiveTime::parse_from_str(s.trim(), "%H:%M").map(TimeOfDay)
+    }
+}
+
+/// "Delete messages older than `max_age` in `channel_id`, daily at `run_at`."
+struct RetentionPolicy {
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    max_age: Duration,
+    run_at: NaiveTime,
+    last_run: Option<DateTime<Utc>>,
+}
+
+impl RetentionPolicy {
+    /// The most recent time this policy should have run at.
+    fn last_due(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = now.date_naive().and_time(self.run_at).and_utc();
+        if today <= now {
+            today
+        } else {
+            today - Duration::days(1)
+        }
+    }
+
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        let last_due = self.last_due(now);
+        self.last_run.is_none_or(|last_run| last_run < last_due)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct PolicyRow {
+    guild_id: i64,
+    channel_id: i64,
+    max_age_seconds: i64,
+    run_at: NaiveTime,
+    last_run: Option<DateTime<Utc>>,
+}
+
+impl From<PolicyRow> for RetentionPolicy {
+    fn from(row: PolicyRow) -> Self {
+        RetentionPolicy {
+            guild_id: GuildId::new(row.guild_id as u64),
+            channel_id: ChannelId::new(row.channel_id as u64),
+            max_age: Duration::seconds(row.max_age_seconds),
+            run_at: row.run_at,
+            last_run: row.last_run,
+        }
+    }
+}
+
+async fn all_policies(pool: &PgPool) -> sqlx::Result<Vec<RetentionPolicy>> {
+    let rows: Vec<PolicyRow> = sqlx::query_as(
+        "SELECT guild_id, channel_id, max_age_seconds, run_at, last_run FROM retention_policies",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(RetentionPolicy::from).collect())
+}
+
+async fn guild_policies(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<Vec<RetentionPolicy>> {
+    let rows: Vec<PolicyRow> = sqlx::query_as(
+        "SELECT guild_id, channel_id, max_age_seconds, run_at, last_run
+        FROM retention_policies WHERE guild_id = $1 ORDER BY channel_id",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(RetentionPolicy::from).collect())
+}
+
+async fn mark_run(pool: &PgPool, channel_id: ChannelId, at: DateTime<Utc>) -> sqlx::Result<()> {
+    sqlx::query("UPDATE retention_policies SET last_run = $2 WHERE channel_id = $1")
+        .bind(i64::from(channel_id))
+        .bind(at)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Runs every retention policy once a day at its scheduled time.
+pub async fn run_policies(http: Arc<Http>, data: Data) {
+    let mut interval = tokio::time::interval(POLICY_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let policies = match all_policies(&data.pool).await {
+            Ok(policies) => policies,
+            Err(e) => {
+                error!("failed to load retention policies: {}", e);
+                continue;
+            }
+        };
+
+        let now = Utc::now();
+        for policy in policies {
+            if !policy.is_due(now) || data.jobs.running_in(policy.channel_id) {
+                continue;
+            }
+
+            if let Err(e) = mark_run(&data.pool, policy.channel_id, now).await {
+                error!("failed to record retention run: {}", e);
+                continue;
+            }
+
+            let http = Arc::clone(&http);
+            let data = data.clone();
+            tokio::spawn(async move {
+                if let Err(e) = apply_policy(&http, &data, &policy, now).await {
+                    error!(
+                        "retention policy for channel {} failed: {}",
+                        policy.channel_id, e
+                    );
+                }
+            });
+        }
+    }
+}
+
+async fn apply_policy(
+    http: &Http,
+    data: &Data,
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+) -> Result<(), SlimeError> {
+    let stored = StoredJob {
+        id: JobId::generate(),
+        guild_id: policy.guild_id,
+        channel_id: policy.channel_id,
+        filter: MessageFilter {
+            exclude_pinned: true,
+            ..Default::default()
+        },
+        cutoff: now - policy.max_age,
+        last_message_id: None,
+        oldest_message_id: None,
+    };
+    jobs::insert(&data.pool, &stored).await?;
+
+    let deleted = purge::run_in_background(http, data, &stored, None).await?;
+    info!(
+        "retention policy deleted {} messages in channel {}",
+        deleted, policy.channel_id
+    );
+
+    Ok(())
+}
+
+/// Manages automatic, recurring purges
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("set", "remove", "list")
+)]
+pub async fn retention_policy(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Deletes old messages in a channel every day
+#[poise::command(slash_command, guild_only)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Channel to keep clean"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+    #[description = "Delete messages older than this, e.g. 30d"] older_than: HumanDuration,
+    #[description = "Time of day to run at, e.g. 04:00 (default 04:00)"] at: Option<TimeOfDay>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let timezone = ctx.data().settings.get(guild_id).await?.timezone;
+    let local = at.map_or(NaiveTime::from_hms_opt(4, 0, 0).unwrap(), |at| at.0);
+    // Policies run at a time of day in UTC.
+    let run_at = localtime::time_of_day_to_utc(local, timezone);
+
+    sqlx::query(
+        "INSERT INTO retention_policies (guild_id, channel_id, max_age_seconds, run_at, last_run)
+        VALUES ($1, $2, $3, $4, now())
+        ON CONFLICT (channel_id) DO UPDATE
+        SET max_age_seconds = EXCLUDED.max_age_seconds, run_at = EXCLUDED.run_at",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(older_than.as_duration().num_seconds())
+    .bind(run_at)
+    .execute(&ctx.data().pool)
+    .await?;
+
+    let reply = CreateReply::default()
+        .content(format!(
+            "Every day at {} I'll delete messages older than {older_than} in {}.",
+            localtime::format_time_of_day(run_at, timezone),
+            channel.mention()
+        ))
+        .ephemeral(true);
+    ctx.send(reply).await?;
+
+    Ok(())
+}
+
+/// Stops automatically purging a channel
+#[poise::command(slash_command, guild_only)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Channel to stop purging"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed =
+        sqlx::query("DELETE FROM retention_policies WHERE guild_id = $1 AND channel_id = $2")
+            .bind(i64::from(guild_id))
+            .bind(i64::from(channel.id))
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+
+    let content = if removed > 0 {
+        format!(
+            "{} won't be purged automatically anymore.",
+            channel.mention()
+        )
+    } else {
+        format!("{} has no retention policy.", channel.mention())
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    Ok(())
+}
+
+/// Lists the retention policies of this server
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let policies = guild_policies(&ctx.data().pool, guild_id).await?;
+    let timezone = ctx.data().settings.get(guild_id).await?.timezone;
+
+    if policies.is_empty() {
+        let content = "There are no retention policies.";
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let lines = policies.iter().map(|policy| {
+        let last_run = match policy.last_run {
+            Some(last_run) => format!(
+                "last ran {}",
+                localtime::format_datetime(last_run, timezone)
+            ),
+            None => "hasn't run yet".to_string(),
+        };
+        format!(
+            "{}: older than {}, daily at {}, {last_run}",
+            policy.channel_id.mention(),
+            HumanDuration(policy.max_age),
+            localtime::format_time_of_day(policy.run_at, timezone),
+        )
+    });
+    audit::paginate(ctx, "Retention policies", &audit::pages(lines, PAGE_LEN)).await
+}
@@ -0,0 +1,387 @@
+use std::collections::HashSet;
+
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{audit, db, replies::reply, Context, Data, SlimeError};
+
+/// What the custom id of every role menu starts with, followed by the
+/// menu, as in `pond-slime:rolemenu:3`. It has to stay the same across
+/// restarts, since the menus outlive the bot process.
+const ROLE_MENU: &str = "pond-slime:rolemenu";
+
+/// How many role menus one page of `/rolemenu list` shows.
+const PAGE_LEN: usize = 10;
+
+/// A role menu from the `role_menus` table.
+#[derive(sqlx::FromRow)]
+struct RoleMenu {
+    menu_id: i64,
+    channel_id: i64,
+    message_id: Option<i64>,
+    title: String,
+    role_ids: Vec<i64>,
+}
+
+impl RoleMenu {
+    fn channel_id(&self) -> ChannelId {
+        ChannelId::new(self.channel_id as u64)
+    }
+
+    fn role_ids(&self) -> impl Iterator<Item = RoleId> + '_ {
+        self.role_ids
+            .iter()
+            .map(|&role_id| RoleId::new(role_id as u64))
+    }
+
+    fn content(&self) -> String {
+        format!(
+            "**{}**\nPick the roles you want, leave out the ones you don't.",
+            self.title
+        )
+    }
+
+    /// The select menu members pick their roles in. Roles somebody deleted
+    /// since are left out.
+    async fn components(
+        &self,
+        http: &Http,
+        guild_id: GuildId,
+    ) -> Result<Vec<CreateActionRow>, SlimeError> {
+        let roles = guild_id.roles(http).await?;
+        let options: Vec<_> = self
+            .role_ids()
+            .filter_map(|role_id| roles.get(&role_id))
+            .map(|role| CreateSelectMenuOption::new(&role.name, role.id.to_string()))
+            .collect();
+        let count = options.len() as u8;
+        let menu = CreateSelectMenu::new(
+            format!("{ROLE_MENU}:{}", self.menu_id),
+            CreateSelectMenuKind::String { options },
+        )
+        .placeholder("Pick your roles")
+        .min_values(0)
+        .max_values(count);
+
+        Ok(vec![CreateActionRow::SelectMenu(menu)])
+    }
+}
+
+async fn find(pool: &PgPool, guild_id: GuildId, menu_id: i64) -> sqlx::Result<Option<RoleMenu>> {
+    sqlx::query_as(
+        "SELECT menu_id, channel_id, message_id, title, role_ids FROM role_menus
+        WHERE guild_id = $1 AND menu_id = $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(menu_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Answers whoever picked from a role menu, only they see it.
+async fn respond(
+    ctx: &serenity::Context,
+    pick: &ComponentInteraction,
+    content: &str,
+) -> Result<(), SlimeError> {
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    pick.create_response(ctx, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+/// Gives whoever picked from a role menu the roles they picked, and takes
+/// the menu's other roles away. Other component interactions are left
+/// alone.
+pub async fn interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    interaction: &Interaction,
+) -> Result<(), SlimeError> {
+    let Interaction::Component(pick) = interaction else {
+        return Ok(());
+    };
+    let Some(menu_id) = pick
+        .data
+        .custom_id
+        .strip_prefix(ROLE_MENU)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(|menu_id| menu_id.parse().ok())
+    else {
+        return Ok(());
+    };
+    let (Some(guild_id), Some(member), ComponentInteractionDataKind::StringSelect { values }) =
+        (pick.guild_id, &pick.member, &pick.data.kind)
+    else {
+        return Ok(());
+    };
+
+    let Some(menu) = find(&data.pool, guild_id, menu_id).await? else {
+        return respond(ctx, pick, "This role menu doesn't exist anymore.").await;
+    };
+    let picked: HashSet<RoleId> = values
+        .iter()
+        .filter_map(|value| value.parse().ok())
+        .map(RoleId::new)
+        .collect();
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    for role_id in menu.role_ids() {
+        let has = member.roles.contains(&role_id);
+        let result = if picked.contains(&role_id) && !has {
+            added.push(role_id.mention().to_string());
+            member.add_role(ctx, role_id).await
+        } else if !picked.contains(&role_id) && has {
+            removed.push(role_id.mention().to_string());
+            member.remove_role(ctx, role_id).await
+        } else {
+            continue;
+        };
+        if let Err(e) = result {
+            error!(
+                "failed to update role {} of {} in guild {}: {}",
+                role_id, member.user.id, guild_id, e
+            );
+            let content = "I couldn't change your roles, please tell a moderator.";
+            return respond(ctx, pick, content).await;
+        }
+    }
+
+    let content = match (added.is_empty(), removed.is_empty()) {
+        (true, true) => "Your roles are already like that.".to_string(),
+        (false, true) => format!("You got {}.", added.join(", ")),
+        (true, false) => format!("You don't have {} anymore.", removed.join(", ")),
+        (false, false) => format!(
+            "You got {} and don't have {} anymore.",
+            added.join(", "),
+            removed.join(", ")
+        ),
+    };
+    respond(ctx, pick, &content).await
+}
+
+/// The roles given to `create` or `edit`, or why one of them can't be in a
+/// role menu.
+fn menu_roles(
+    guild_id: GuildId,
+    roles: impl IntoIterator<Item = Option<Role>>,
+) -> Result<Vec<i64>, String> {
+    let mut role_ids = Vec::new();
+    for role in roles.into_iter().flatten() {
+        if role.id == guild_id.everyone_role() || role.managed {
+            return Err(format!("Nobody can be given {}.", role.mention()));
+        }
+        if !role_ids.contains(&i64::from(role.id)) {
+            role_ids.push(i64::from(role.id));
+        }
+    }
+    Ok(role_ids)
+}
+
+/// Manages menus members pick their own roles from
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("create", "edit", "delete", "list")
+)]
+pub async fn rolemenu(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Posts a menu members pick roles from
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command, guild_only)]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "Channel to post the menu in"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+    #[description = "What the menu is about, e.g. Pronouns"]
+    #[max_length = 256]
+    title: String,
+    #[description = "A role to offer"] role_1: Role,
+    #[description = "Another role to offer"] role_2: Option<Role>,
+    #[description = "Another role to offer"] role_3: Option<Role>,
+    #[description = "Another role to offer"] role_4: Option<Role>,
+    #[description = "Another role to offer"] role_5: Option<Role>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let role_ids = match menu_roles(guild_id, [Some(role_1), role_2, role_3, role_4, role_5]) {
+        Ok(role_ids) => role_ids,
+        Err(problem) => return reply(ctx, problem).await,
+    };
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let menu: RoleMenu = sqlx::query_as(
+        "INSERT INTO role_menus (guild_id, channel_id, title, role_ids, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING menu_id, channel_id, message_id, title, role_ids",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(&title)
+    .bind(&role_ids)
+    .bind(i64::from(ctx.author().id))
+    .fetch_one(&data.pool)
+    .await?;
+
+    let posted = match menu.components(ctx.http(), guild_id).await {
+        Ok(components) => {
+            let message = CreateMessage::new()
+                .content(menu.content())
+                .components(components);
+            channel
+                .id
+                .send_message(ctx, message)
+                .await
+                .map_err(Into::into)
+        }
+        Err(e) => Err(e),
+    };
+    let message = match posted {
+        Ok(message) => message,
+        Err(e) => {
+            sqlx::query("DELETE FROM role_menus WHERE menu_id = $1")
+                .bind(menu.menu_id)
+                .execute(&data.pool)
+                .await?;
+            return Err(e);
+        }
+    };
+    sqlx::query("UPDATE role_menus SET message_id = $2 WHERE menu_id = $1")
+        .bind(menu.menu_id)
+        .bind(i64::from(message.id))
+        .execute(&data.pool)
+        .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Role menu `{}` is up in {}.",
+            menu.menu_id,
+            channel.mention()
+        ),
+    )
+    .await
+}
+
+/// Changes the title or the roles of a role menu
+#[allow(clippy::too_many_arguments)]
+#[poise::command(slash_command, guild_only)]
+async fn edit(
+    ctx: Context<'_>,
+    #[description = "Role menu to change, see /rolemenu list"] id: i64,
+    #[description = "The new title"]
+    #[max_length = 256]
+    title: Option<String>,
+    #[description = "Offer this role instead of the current ones"] role_1: Option<Role>,
+    #[description = "Another role to offer"] role_2: Option<Role>,
+    #[description = "Another role to offer"] role_3: Option<Role>,
+    #[description = "Another role to offer"] role_4: Option<Role>,
+    #[description = "Another role to offer"] role_5: Option<Role>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let role_ids = match menu_roles(guild_id, [role_1, role_2, role_3, role_4, role_5]) {
+        Ok(role_ids) => role_ids,
+        Err(problem) => return reply(ctx, problem).await,
+    };
+    let Some(mut menu) = find(&data.pool, guild_id, id).await? else {
+        return reply(ctx, format!("There is no role menu `{id}`.")).await;
+    };
+    if let Some(title) = title {
+        menu.title = title;
+    }
+    if !role_ids.is_empty() {
+        menu.role_ids = role_ids;
+    }
+
+    if let Some(message_id) = menu.message_id {
+        let edit = EditMessage::new()
+            .content(menu.content())
+            .components(menu.components(ctx.http(), guild_id).await?);
+        menu.channel_id()
+            .edit_message(ctx, MessageId::new(message_id as u64), edit)
+            .await?;
+    }
+    sqlx::query("UPDATE role_menus SET title = $2, role_ids = $3 WHERE menu_id = $1")
+        .bind(menu.menu_id)
+        .bind(&menu.title)
+        .bind(&menu.role_ids)
+        .execute(&data.pool)
+        .await?;
+
+    reply(ctx, format!("Updated role menu `{id}`.")).await
+}
+
+/// Deletes a role menu, members keep the roles they picked
+#[poise::command(slash_command, guild_only)]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "Role menu to delete, see /rolemenu list"] id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let menu: Option<RoleMenu> = sqlx::query_as(
+        "DELETE FROM role_menus WHERE guild_id = $1 AND menu_id = $2
+        RETURNING menu_id, channel_id, message_id, title, role_ids",
+    )
+    .bind(i64::from(guild_id))
+    .bind(id)
+    .fetch_optional(&ctx.data().pool)
+    .await?;
+    let Some(menu) = menu else {
+        return reply(ctx, format!("There is no role menu `{id}`.")).await;
+    };
+
+    if let Some(message_id) = menu.message_id {
+        if let Err(e) = menu
+            .channel_id()
+            .delete_message(ctx, MessageId::new(message_id as u64))
+            .await
+        {
+            warn!(
+                "failed to delete the message of role menu {} in guild {}: {}",
+                id, guild_id, e
+            );
+        }
+    }
+
+    reply(ctx, format!("Deleted role menu `{id}`.")).await
+}
+
+/// Lists the role menus in this server
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let menus: Vec<RoleMenu> = sqlx::query_as(
+        "SELECT menu_id, channel_id, message_id, title, role_ids FROM role_menus
+        WHERE guild_id = $1 ORDER BY menu_id",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if menus.is_empty() {
+        return reply(ctx, "There are no role menus.".to_string()).await;
+    }
+
+    let lines = menus.iter().map(|menu| {
+        let roles = menu
+            .role_ids()
+            .map(|role_id| role_id.mention().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "`{}` {} in {}: {roles}",
+            menu.menu_id,
+            menu.title,
+            menu.channel_id().mention()
+        )
+    });
+    audit::paginate(ctx, "Role menus", &audit::pages(lines, PAGE_LEN)).await
+}
@@ -0,0 +1,327 @@
+use std::{str::FromStr, sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, NaiveTime, Utc};
+use poise::serenity_prelude::*;
+use sqlx::{types::Json, PgPool};
+use thiserror::Error;
+use tracing::{error, info};
+
+use crate::{
+    announcements, audit, autorole, birthdays, counters,
+    duration::HumanDuration,
+    events,
+    filter::MessageFilter,
+    giveaways,
+    jobs::{self, JobId, StoredJob},
+    localtime, polls, purge, reminders,
+    replies::reply,
+    retention::TimeOfDay,
+    threads, Context, Data, SlimeError,
+};
+
+/// How often the background task checks for scheduled purges, reminders,
+/// announcements, polls, autoroles, event reminders, recurring events,
+/// giveaways, birthdays, stats channels and idle threads that are due.
+const SCHEDULE_INTERVAL: StdDuration = StdDuration::from_secs(30);
+
+/// How many scheduled purges one page of `/purge_at list` shows.
+const PAGE_LEN: usize = 10;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("expected a time like 03:00 or 2024-05-01 03:00, or a duration like 2h")]
+pub struct ParseRunAtError;
+
+/// A moment in the guild's timezone, written as `YYYY-MM-DD HH:MM`, just
+/// `HH:MM` for the next time the clock shows it, or a duration from now.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunAt {
+    On(NaiveDateTime),
+    Next(NaiveTime),
+    In(HumanDuration),
+}
+
+impl FromStr for RunAt {
+    type Err = ParseRunAtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(at) = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M") {
+            return Ok(RunAt::On(at));
+        }
+        if let Ok(TimeOfDay(time)) = s.parse() {
+            return Ok(RunAt::Next(time));
+        }
+
+        s.parse().map(RunAt::In).map_err(|_| ParseRunAtError)
+    }
+}
+
+impl RunAt {
+    /// The moment this stands for in a guild in `timezone`.
+    pub fn resolve(self, timezone: Option<FixedOffset>, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            RunAt::On(at) => localtime::datetime_to_utc(at, timezone),
+            RunAt::Next(time) => {
+                let today = localtime::to_local(now, timezone).date().and_time(time);
+                let today = localtime::datetime_to_utc(today, timezone);
+                if today > now {
+                    today
+                } else {
+                    today + Duration::days(1)
+                }
+            }
+            RunAt::In(duration) => now + duration.as_duration(),
+        }
+    }
+}
+
+/// "Delete messages older than `max_age` in `channel_id` at `run_at`."
+struct ScheduledPurge {
+    schedule_id: i64,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    scheduled_by: UserId,
+    run_at: DateTime<Utc>,
+    max_age: Duration,
+    filter: MessageFilter,
+}
+
+#[derive(sqlx::FromRow)]
+struct ScheduleRow {
+    schedule_id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    scheduled_by: i64,
+    run_at: DateTime<Utc>,
+    max_age_seconds: i64,
+    filter: Json<MessageFilter>,
+}
+
+impl From<ScheduleRow> for ScheduledPurge {
+    fn from(row: ScheduleRow) -> Self {
+        ScheduledPurge {
+            schedule_id: row.schedule_id,
+            guild_id: GuildId::new(row.guild_id as u64),
+            channel_id: ChannelId::new(row.channel_id as u64),
+            scheduled_by: UserId::new(row.scheduled_by as u64),
+            run_at: row.run_at,
+            max_age: Duration::seconds(row.max_age_seconds),
+            filter: row.filter.0,
+        }
+    }
+}
+
+/// Removes the scheduled purges that are due and returns them, so each one
+/// only ever runs once.
+async fn take_due(pool: &PgPool) -> sqlx::Result<Vec<ScheduledPurge>> {
+    let rows: Vec<ScheduleRow> = sqlx::query_as(
+        "DELETE FROM scheduled_jobs WHERE run_at <= now()
+        RETURNING schedule_id, guild_id, channel_id, scheduled_by, run_at, max_age_seconds, filter",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(ScheduledPurge::from).collect())
+}
+
+async fn guild_schedule(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<Vec<ScheduledPurge>> {
+    let rows: Vec<ScheduleRow> = sqlx::query_as(
+        "SELECT schedule_id, guild_id, channel_id, scheduled_by, run_at, max_age_seconds, filter
+        FROM scheduled_jobs WHERE guild_id = $1 ORDER BY run_at",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(ScheduledPurge::from).collect())
+}
+
+/// Starts every scheduled purge, sends every reminder and announcement,
+/// closes every poll, gives every delayed autorole, reminds of every server
+/// event, plans the next one of every recurring event, draws the winners of
+/// every giveaway, wishes every member a happy birthday, refreshes every
+/// stats channel and closes every idle thread once its time has come.
+pub async fn run_scheduled(http: Arc<Http>, data: Data) {
+    let mut interval = tokio::time::interval(SCHEDULE_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        start_due_purges(&http, &data).await;
+        reminders::send_due(&http, &data).await;
+        announcements::send_due(&http, &data).await;
+        polls::close_due(&http, &data).await;
+        autorole::assign_due(&http, &data).await;
+        events::remind_due(&http, &data).await;
+        events::recur_due(&http, &data).await;
+        giveaways::end_due(&http, &data).await;
+        birthdays::announce_due(&http, &data).await;
+        counters::refresh_due(&http, &data).await;
+        threads::close_idle(&http, &data).await;
+    }
+}
+
+async fn start_due_purges(http: &Arc<Http>, data: &Data) {
+    let due = match take_due(&data.pool).await {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to load scheduled purges: {}", e);
+            return;
+        }
+    };
+
+    for scheduled in due {
+        let stored = StoredJob {
+            id: JobId::generate(),
+            guild_id: scheduled.guild_id,
+            channel_id: scheduled.channel_id,
+            filter: scheduled.filter,
+            cutoff: Utc::now() - scheduled.max_age,
+            last_message_id: None,
+            oldest_message_id: None,
+        };
+        // Once it's a purge job it resumes after a restart like any other.
+        if let Err(e) = jobs::insert(&data.pool, &stored).await {
+            error!(
+                "failed to start scheduled purge {}: {}",
+                scheduled.schedule_id, e
+            );
+            continue;
+        }
+
+        let http = Arc::clone(http);
+        let data = data.clone();
+        tokio::spawn(async move {
+            match purge::run_in_background(&http, &data, &stored, None).await {
+                Ok(deleted) => info!(
+                    "scheduled purge {} deleted {} messages in channel {}",
+                    scheduled.schedule_id, deleted, stored.channel_id
+                ),
+                Err(e) => error!("scheduled purge {} failed: {}", scheduled.schedule_id, e),
+            }
+        });
+    }
+}
+
+/// Manages purges that run once at a later time
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("add", "cancel", "list")
+)]
+pub async fn purge_at(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Schedules a purge for a later time
+#[poise::command(slash_command, guild_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "When to purge, e.g. 03:00, 2024-05-01 03:00 or 6h"] at: RunAt,
+    #[description = "Channel to purge (default this one)"]
+    #[channel_types("Text")]
+    channel: Option<GuildChannel>,
+    #[description = "Only delete messages older than this by then, e.g. 3d"] older_than: Option<
+        HumanDuration,
+    >,
+    #[description = "Only delete messages from this user"] user: Option<User>,
+    #[description = "Keep pinned messages (default true)"] exclude_pinned: Option<bool>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let timezone = ctx.data().settings.get(guild_id).await?.timezone;
+    let now = Utc::now();
+    let run_at = at.resolve(timezone, now);
+    if run_at <= now {
+        return reply(ctx, "That time has already passed.".to_string()).await;
+    }
+
+    let channel_id = channel.map_or(ctx.channel_id(), |channel| channel.id);
+    let max_age = older_than.map_or(Duration::zero(), |older_than| older_than.as_duration());
+    let filter = MessageFilter {
+        user_id: user.map(|user| user.id),
+        exclude_pinned: exclude_pinned.unwrap_or(true),
+        ..Default::default()
+    };
+
+    let schedule_id: i64 = sqlx::query_scalar(
+        "INSERT INTO scheduled_jobs
+        (guild_id, channel_id, scheduled_by, run_at, max_age_seconds, filter)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING schedule_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel_id))
+    .bind(i64::from(ctx.author().id))
+    .bind(run_at)
+    .bind(max_age.num_seconds())
+    .bind(Json(&filter))
+    .fetch_one(&ctx.data().pool)
+    .await?;
+
+    let older_than = match older_than {
+        Some(older_than) => format!(" older than {older_than}"),
+        None => String::new(),
+    };
+    reply(
+        ctx,
+        format!(
+            "Scheduled purge `{schedule_id}`: at {} I'll delete messages{older_than} in {}.",
+            localtime::format_datetime(run_at, timezone),
+            channel_id.mention()
+        ),
+    )
+    .await
+}
+
+/// Cancels a scheduled purge before it runs
+#[poise::command(slash_command, guild_only)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "Scheduled purge to cancel, see /purge_at list"] id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed =
+        sqlx::query("DELETE FROM scheduled_jobs WHERE guild_id = $1 AND schedule_id = $2")
+            .bind(i64::from(guild_id))
+            .bind(id)
+            .execute(&ctx.data().pool)
+            .await?
+            .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Scheduled purge `{id}` won't run.")
+    } else {
+        format!("There is no scheduled purge `{id}`.")
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the purges scheduled in this server
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let schedule = guild_schedule(&ctx.data().pool, guild_id).await?;
+    let timezone = ctx.data().settings.get(guild_id).await?.timezone;
+
+    if schedule.is_empty() {
+        return reply(ctx, "No purges are scheduled.".to_string()).await;
+    }
+
+    let lines = schedule.iter().map(|scheduled| {
+        let filter = scheduled.filter.description();
+        let filter = if filter.is_empty() {
+            String::new()
+        } else {
+            format!(" ({filter})")
+        };
+        format!(
+            "`{}` {}: {}, older than {}{filter}, by {}",
+            scheduled.schedule_id,
+            localtime::format_datetime(scheduled.run_at, timezone),
+            scheduled.channel_id.mention(),
+            HumanDuration(scheduled.max_age),
+            scheduled.scheduled_by.mention(),
+        )
+    });
+    audit::paginate(ctx, "Scheduled purges", &audit::pages(lines, PAGE_LEN)).await
+}
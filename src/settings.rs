@@ -0,0 +1,1165 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+};
+
+use chrono::{Duration, FixedOffset};
+use dashmap::DashMap;
+use poise::serenity_prelude::{ChannelId, GuildId, Mentionable, ReactionType, RoleId};
+use sqlx::PgPool;
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::{
+    db,
+    duration::HumanDuration,
+    localtime,
+    raid::JoinRate,
+    spam::{SpamAction, SpamLimits, SpamRate},
+    timeouts,
+};
+
+/// The locales Discord knows, the only ones worth storing.
+const LOCALES: [&str; 32] = [
+    "id", "da", "de", "en-GB", "en-US", "es-ES", "es-419", "fr", "hr", "it", "lt", "hu", "nl",
+    "no", "pl", "pt-BR", "ro", "fi", "sv-SE", "vi", "tr", "cs", "el", "bg", "ru", "uk", "hi", "th",
+    "zh-CN", "ja", "zh-TW", "ko",
+];
+
+/// What the bot posts in a configured channel. Purposes without a channel
+/// of their own fall back to the `Status` one, the bot spam channel, except
+/// for `Messages`, which is too chatty for that.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelPurpose {
+    /// Operational notices, e.g. purges resuming after a restart.
+    Status,
+    /// Reports of finished and cancelled purges.
+    Logs,
+    /// Reports of purges that failed.
+    Errors,
+    /// Edited and deleted messages.
+    Messages,
+    /// Copies of messages that got enough stars.
+    Starboard,
+    /// Suggestions members made with `/suggest`.
+    Suggestions,
+    /// Members reaching a new level.
+    Levels,
+    /// Birthday wishes.
+    Birthdays,
+    /// Copies of pins taken down to make room for new ones.
+    Pins,
+    /// Messages quoted with the Quote context menu.
+    Quotes,
+}
+
+impl ChannelPurpose {
+    fn as_str(self) -> &'static str {
+        match self {
+            ChannelPurpose::Status => "status",
+            ChannelPurpose::Logs => "logs",
+            ChannelPurpose::Errors => "errors",
+            ChannelPurpose::Messages => "messages",
+            ChannelPurpose::Starboard => "starboard",
+            ChannelPurpose::Suggestions => "suggestions",
+            ChannelPurpose::Levels => "levels",
+            ChannelPurpose::Birthdays => "birthdays",
+            ChannelPurpose::Pins => "pins",
+            ChannelPurpose::Quotes => "quotes",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "status" => Some(ChannelPurpose::Status),
+            "logs" => Some(ChannelPurpose::Logs),
+            "errors" => Some(ChannelPurpose::Errors),
+            "messages" => Some(ChannelPurpose::Messages),
+            "starboard" => Some(ChannelPurpose::Starboard),
+            "suggestions" => Some(ChannelPurpose::Suggestions),
+            "levels" => Some(ChannelPurpose::Levels),
+            "birthdays" => Some(ChannelPurpose::Birthdays),
+            "pins" => Some(ChannelPurpose::Pins),
+            "quotes" => Some(ChannelPurpose::Quotes),
+            _ => None,
+        }
+    }
+}
+
+/// One of the values in `GuildSettings`, as named in `/config`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Setting {
+    Channel(ChannelPurpose),
+    PurgeRate,
+    Locale,
+    Timezone,
+    DmMembers,
+    BlockInvites,
+    MaxMentions,
+    BlockEveryone,
+    MentionTimeout,
+    SpamRate,
+    SpamRepeats,
+    SpamAction,
+    SpamTimeout,
+    RaidJoins,
+    RaidMinAge,
+    RaidVerification,
+    MinAccountAge,
+    QuarantineRole,
+    AutoroleDelay,
+    StarboardStars,
+    EventReminders,
+    Leveling,
+    Snipe,
+    Bookmarks,
+    BookmarkEmoji,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 34] = [
+        Setting::Channel(ChannelPurpose::Status),
+        Setting::Channel(ChannelPurpose::Logs),
+        Setting::Channel(ChannelPurpose::Errors),
+        Setting::Channel(ChannelPurpose::Messages),
+        Setting::Channel(ChannelPurpose::Starboard),
+        Setting::Channel(ChannelPurpose::Suggestions),
+        Setting::Channel(ChannelPurpose::Levels),
+        Setting::Channel(ChannelPurpose::Birthdays),
+        Setting::Channel(ChannelPurpose::Pins),
+        Setting::Channel(ChannelPurpose::Quotes),
+        Setting::PurgeRate,
+        Setting::Locale,
+        Setting::Timezone,
+        Setting::DmMembers,
+        Setting::BlockInvites,
+        Setting::MaxMentions,
+        Setting::BlockEveryone,
+        Setting::MentionTimeout,
+        Setting::SpamRate,
+        Setting::SpamRepeats,
+        Setting::SpamAction,
+        Setting::SpamTimeout,
+        Setting::RaidJoins,
+        Setting::RaidMinAge,
+        Setting::RaidVerification,
+        Setting::MinAccountAge,
+        Setting::QuarantineRole,
+        Setting::AutoroleDelay,
+        Setting::StarboardStars,
+        Setting::EventReminders,
+        Setting::Leveling,
+        Setting::Snipe,
+        Setting::Bookmarks,
+        Setting::BookmarkEmoji,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Setting::Channel(ChannelPurpose::Status) => "bot_spam_channel",
+            Setting::Channel(ChannelPurpose::Logs) => "log_channel",
+            Setting::Channel(ChannelPurpose::Errors) => "error_channel",
+            Setting::Channel(ChannelPurpose::Messages) => "message_log_channel",
+            Setting::Channel(ChannelPurpose::Starboard) => "starboard_channel",
+            Setting::Channel(ChannelPurpose::Suggestions) => "suggestion_channel",
+            Setting::Channel(ChannelPurpose::Levels) => "level_channel",
+            Setting::Channel(ChannelPurpose::Birthdays) => "birthday_channel",
+            Setting::Channel(ChannelPurpose::Pins) => "pin_archive_channel",
+            Setting::Channel(ChannelPurpose::Quotes) => "quote_channel",
+            Setting::PurgeRate => "purge_rate",
+            Setting::Locale => "locale",
+            Setting::Timezone => "timezone",
+            Setting::DmMembers => "dm_members",
+            Setting::BlockInvites => "block_invites",
+            Setting::MaxMentions => "max_mentions",
+            Setting::BlockEveryone => "block_everyone",
+            Setting::MentionTimeout => "mention_timeout",
+            Setting::SpamRate => "spam_rate",
+            Setting::SpamRepeats => "spam_repeats",
+            Setting::SpamAction => "spam_action",
+            Setting::SpamTimeout => "spam_timeout",
+            Setting::RaidJoins => "raid_joins",
+            Setting::RaidMinAge => "raid_min_age",
+            Setting::RaidVerification => "raid_verification",
+            Setting::MinAccountAge => "min_account_age",
+            Setting::QuarantineRole => "quarantine_role",
+            Setting::AutoroleDelay => "autorole_delay",
+            Setting::StarboardStars => "starboard_stars",
+            Setting::EventReminders => "event_reminders",
+            Setting::Leveling => "leveling",
+            Setting::Snipe => "snipe",
+            Setting::Bookmarks => "bookmarks",
+            Setting::BookmarkEmoji => "bookmark_emoji",
+        }
+    }
+
+    /// What the setting does and what a valid value looks like.
+    pub fn help(self) -> &'static str {
+        match self {
+            Setting::Channel(ChannelPurpose::Status) => {
+                "channel for status updates, e.g. #bot-spam"
+            }
+            Setting::Channel(ChannelPurpose::Logs) => {
+                "channel for purge reports, defaults to the bot spam channel"
+            }
+            Setting::Channel(ChannelPurpose::Errors) => {
+                "channel for failed purges, defaults to the bot spam channel"
+            }
+            Setting::Channel(ChannelPurpose::Messages) => {
+                "channel for edited and deleted messages, none by default"
+            }
+            Setting::Channel(ChannelPurpose::Starboard) => {
+                "channel for messages that got enough stars, none by default"
+            }
+            Setting::Channel(ChannelPurpose::Suggestions) => {
+                "channel for suggestions made with /suggest, none by default"
+            }
+            Setting::Channel(ChannelPurpose::Levels) => {
+                "channel for level-up announcements, none by default"
+            }
+            Setting::Channel(ChannelPurpose::Birthdays) => {
+                "channel for birthday wishes, none by default"
+            }
+            Setting::Channel(ChannelPurpose::Pins) => {
+                "channel to archive pins to once a channel is full, none by default"
+            }
+            Setting::Channel(ChannelPurpose::Quotes) => {
+                "channel for messages quoted with the Quote menu, none by default"
+            }
+            Setting::PurgeRate => "most deletions per minute, 10 to 3000",
+            Setting::Locale => "language for replies, e.g. en-US",
+            Setting::Timezone => "UTC offset for times, e.g. +02:00",
+            Setting::DmMembers => "tell members why they were kicked or banned, on or off",
+            Setting::BlockInvites => "delete invites to other servers, on or off",
+            Setting::MaxMentions => "most members and roles one message may mention, 1 to 50",
+            Setting::BlockEveryone => {
+                "delete @everyone and @here from members who may not use them, on or off"
+            }
+            Setting::MentionTimeout => {
+                "how long to time out members for the two above, e.g. 10m, none by default"
+            }
+            Setting::SpamRate => "most messages a member may send in a while, e.g. 5/10s",
+            Setting::SpamRepeats => "most times a member may send the same message in 1m, 2 to 20",
+            Setting::SpamAction => {
+                "what to do about spammers besides deleting, delete, warn or timeout"
+            }
+            Setting::SpamTimeout => "how long to time out spammers, defaults to 10m",
+            Setting::RaidJoins => "joins that turn raid mode on, e.g. 10/30s, off by default",
+            Setting::RaidMinAge => "kick accounts younger than this during raid mode, e.g. 7d",
+            Setting::RaidVerification => {
+                "raise the verification level during raid mode, on or off, on by default"
+            }
+            Setting::MinAccountAge => "kick accounts younger than this when they join, e.g. 3d",
+            Setting::QuarantineRole => "give too new accounts this role instead of kicking them",
+            Setting::AutoroleDelay => {
+                "wait this long after a join before giving the autoroles, e.g. 10m"
+            }
+            Setting::StarboardStars => {
+                "stars a message needs to make the starboard, 1 to 100, defaults to 3"
+            }
+            Setting::EventReminders => {
+                "how long before server events to remind of them, e.g. 1h,10m, none by default"
+            }
+            Setting::Leveling => "give members XP for chatting, on or off, off by default",
+            Setting::Snipe => {
+                "let members see deleted and edited messages, on or off, on by default"
+            }
+            Setting::Bookmarks => {
+                "DM members messages they react to with the bookmark emoji, on or off, off by default"
+            }
+            Setting::BookmarkEmoji => "emoji that bookmarks a message, defaults to 🔖",
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SettingError {
+    #[error("there is no setting called '{0}'")]
+    UnknownKey(String),
+    #[error("'{0}' is not a channel, try mentioning it like #bot-spam")]
+    InvalidChannel(String),
+    #[error("'{0}' is not a number between 10 and 3000")]
+    InvalidRate(String),
+    #[error("'{0}' is not a locale Discord knows, try something like en-US")]
+    InvalidLocale(String),
+    #[error("'{0}' is not a UTC offset, try something like +02:00")]
+    InvalidTimezone(String),
+    #[error("'{0}' is neither on nor off")]
+    InvalidSwitch(String),
+    #[error("'{0}' is not a number between 1 and 50")]
+    InvalidMentions(String),
+    #[error(
+        "'{0}' is not a duration between 1s and {}d",
+        timeouts::MAX_TIMEOUT_DAYS
+    )]
+    InvalidTimeout(String),
+    #[error("'{0}' is not a rate like 5/10s, with 2 to 50 messages in at most 2m")]
+    InvalidSpamRate(String),
+    #[error("'{0}' is not a number between 2 and 20")]
+    InvalidRepeats(String),
+    #[error("'{0}' is not delete, warn or timeout")]
+    InvalidSpamAction(String),
+    #[error("'{0}' is not a rate like 10/30s, with 3 to 100 joins in at most 10m")]
+    InvalidJoinRate(String),
+    #[error("'{0}' is not a duration between 1m and 365d")]
+    InvalidAccountAge(String),
+    #[error("'{0}' is not a role, try mentioning it like @quarantine")]
+    InvalidRole(String),
+    #[error("'{0}' is not a number between 1 and 100")]
+    InvalidStars(String),
+    #[error(
+        "'{0}' is not a list of up to {} durations between 1m and 7d, like 1h,10m",
+        MAX_EVENT_REMINDERS
+    )]
+    InvalidReminders(String),
+    #[error("'{0}' is not an emoji")]
+    InvalidEmoji(String),
+}
+
+impl FromStr for Setting {
+    type Err = SettingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        Setting::ALL
+            .into_iter()
+            .find(|setting| setting.key() == s)
+            .ok_or_else(|| SettingError::UnknownKey(s.to_string()))
+    }
+}
+
+/// Reads a channel mention like `<#123>` or a bare id.
+pub fn parse_channel(value: &str) -> Option<ChannelId> {
+    let id = value
+        .strip_prefix("<#")
+        .and_then(|value| value.strip_suffix('>'))
+        .unwrap_or(value);
+    id.parse().ok().filter(|&id| id != 0).map(ChannelId::new)
+}
+
+/// Reads a role mention like `<@&123>` or a bare id.
+fn parse_role(value: &str) -> Option<RoleId> {
+    let id = value
+        .strip_prefix("<@&")
+        .and_then(|value| value.strip_suffix('>'))
+        .unwrap_or(value);
+    id.parse().ok().filter(|&id| id != 0).map(RoleId::new)
+}
+
+/// Reads a custom emoji like `<:name:123>` or a Unicode one. Unicode emojis
+/// can't be told apart from other text, so anything short without letters,
+/// digits or spaces passes for one.
+fn parse_emoji(value: &str) -> Result<String, SettingError> {
+    let value = value.trim();
+    let valid = match ReactionType::try_from(value) {
+        Ok(ReactionType::Custom { .. }) => true,
+        Ok(_) => {
+            value.chars().count() <= 8
+                && !value
+                    .chars()
+                    .any(|c| c.is_ascii_alphanumeric() || c.is_whitespace())
+        }
+        Err(_) => false,
+    };
+    if !valid {
+        return Err(SettingError::InvalidEmoji(value.to_string()));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_account_age(value: &str) -> Result<HumanDuration, SettingError> {
+    value
+        .parse::<HumanDuration>()
+        .ok()
+        .filter(|age| {
+            let age = age.as_duration();
+            age >= Duration::minutes(1) && age <= Duration::days(365)
+        })
+        .ok_or_else(|| SettingError::InvalidAccountAge(value.to_string()))
+}
+
+fn parse_switch(value: &str) -> Result<bool, SettingError> {
+    match value.to_ascii_lowercase().as_str() {
+        "on" | "yes" | "true" => Ok(true),
+        "off" | "no" | "false" => Ok(false),
+        _ => Err(SettingError::InvalidSwitch(value.to_string())),
+    }
+}
+
+fn switch_name(on: bool) -> String {
+    if on { "on" } else { "off" }.to_string()
+}
+
+/// How many reminders of an event a guild can have.
+const MAX_EVENT_REMINDERS: usize = 5;
+
+/// Reads a list of lead times like `1h,10m`, longest first.
+fn parse_reminders(value: &str) -> Result<Vec<HumanDuration>, SettingError> {
+    let invalid = || SettingError::InvalidReminders(value.to_string());
+    let mut leads = value
+        .split(',')
+        .map(|lead| {
+            lead.trim()
+                .parse::<HumanDuration>()
+                .ok()
+                .filter(|lead| {
+                    let lead = lead.as_duration();
+                    lead >= Duration::minutes(1) && lead <= Duration::days(7)
+                })
+                .ok_or_else(invalid)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    leads.sort_by_key(|lead| Reverse(lead.as_duration()));
+    leads.dedup();
+    if leads.len() > MAX_EVENT_REMINDERS {
+        return Err(invalid());
+    }
+    Ok(leads)
+}
+
+/// How many stars a message needs for the starboard unless the guild says
+/// otherwise.
+const DEFAULT_STARBOARD_STARS: u32 = 3;
+
+/// The emoji that bookmarks a message unless the guild says otherwise.
+const DEFAULT_BOOKMARK_EMOJI: &str = "🔖";
+
+/// How long spammers are timed out for unless the guild says otherwise.
+const DEFAULT_SPAM_TIMEOUT_MINUTES: i64 = 10;
+
+fn parse_timeout(value: &str) -> Result<HumanDuration, SettingError> {
+    value
+        .parse::<HumanDuration>()
+        .ok()
+        .filter(|timeout| {
+            let timeout = timeout.as_duration();
+            timeout > Duration::zero() && timeout <= Duration::days(timeouts::MAX_TIMEOUT_DAYS)
+        })
+        .ok_or_else(|| SettingError::InvalidTimeout(value.to_string()))
+}
+
+/// Everything a guild can configure. Unset values fall back to the bot's
+/// defaults.
+#[derive(Clone, Debug, Default)]
+pub struct GuildSettings {
+    /// The channels the bot posts to, see `channel`.
+    pub channels: HashMap<ChannelPurpose, ChannelId>,
+    /// Roles whose members may run purges without being administrators.
+    pub mod_roles: HashSet<RoleId>,
+    /// Channels whose edited and deleted messages aren't logged.
+    pub log_ignored: HashSet<ChannelId>,
+    /// The most deletions per minute the guild allows purges.
+    pub purge_rate: Option<u32>,
+    pub locale: Option<String>,
+    pub timezone: Option<FixedOffset>,
+    /// Whether members get a DM when they are kicked or banned, see
+    /// `dm_members`.
+    pub dm_members: Option<bool>,
+    /// Whether automod deletes invite links, which it doesn't by default.
+    pub block_invites: Option<bool>,
+    /// Channels where invite links are fine anyway.
+    pub invite_channels: HashSet<ChannelId>,
+    /// Roles whose members may post invite links anyway.
+    pub invite_roles: HashSet<RoleId>,
+    /// The most mentions automod lets through in one message, if it checks.
+    pub max_mentions: Option<u32>,
+    /// Whether automod deletes attempts to ping everyone without permission.
+    pub block_everyone: Option<bool>,
+    /// How long automod times out members for mentioning too much.
+    pub mention_timeout: Option<HumanDuration>,
+    /// How fast members may send messages, see `spam_limits`.
+    pub spam_rate: Option<SpamRate>,
+    /// How often members may repeat a message, see `spam_limits`.
+    pub spam_repeats: Option<u32>,
+    pub spam_action: Option<SpamAction>,
+    /// How long spammers are timed out for, see `spam_timeout`.
+    pub spam_timeout: Option<HumanDuration>,
+    /// How many joins start raid mode, which is only started by hand otherwise.
+    pub raid_joins: Option<JoinRate>,
+    /// How old accounts must be to join during raid mode.
+    pub raid_min_age: Option<HumanDuration>,
+    /// Whether raid mode raises the verification level, see `raid_verification`.
+    pub raid_verification: Option<bool>,
+    /// How old accounts must be to join at all.
+    pub min_account_age: Option<HumanDuration>,
+    /// The role too new accounts get instead of being kicked.
+    pub quarantine_role: Option<RoleId>,
+    /// Roles every member gets when they join.
+    pub autoroles: HashSet<RoleId>,
+    /// How long after joining members get the autoroles, right away if unset.
+    pub autorole_delay: Option<HumanDuration>,
+    /// How many stars put a message on the starboard, see `starboard_stars`.
+    pub starboard_stars: Option<u32>,
+    /// How long before server events their reminders are sent, longest first.
+    pub event_reminders: Vec<HumanDuration>,
+    /// Roles pinged by event reminders.
+    pub event_roles: HashSet<RoleId>,
+    /// Whether members earn XP for chatting, see `leveling`.
+    pub leveling: Option<bool>,
+    /// The role members get on reaching each level.
+    pub level_roles: BTreeMap<u32, RoleId>,
+    /// Whether `/snipe` and `/editsnipe` work, see `snipe`.
+    pub snipe: Option<bool>,
+    /// Whether reacting with `bookmark_emoji` DMs members a copy.
+    pub bookmarks: Option<bool>,
+    pub bookmark_emoji: Option<String>,
+}
+
+impl GuildSettings {
+    /// Where to post what `purpose` covers, if anywhere.
+    pub fn channel(&self, purpose: ChannelPurpose) -> Option<ChannelId> {
+        let fallback = match purpose {
+            ChannelPurpose::Messages
+            | ChannelPurpose::Starboard
+            | ChannelPurpose::Suggestions
+            | ChannelPurpose::Levels
+            | ChannelPurpose::Birthdays
+            | ChannelPurpose::Pins
+            | ChannelPurpose::Quotes => None,
+            _ => self.channels.get(&ChannelPurpose::Status),
+        };
+        self.channels.get(&purpose).or(fallback).copied()
+    }
+
+    /// The limits of automod's spam detection, which is off by default.
+    pub fn spam_limits(&self) -> SpamLimits {
+        SpamLimits {
+            rate: self.spam_rate,
+            repeats: self.spam_repeats,
+        }
+    }
+
+    pub fn spam_timeout(&self) -> HumanDuration {
+        self.spam_timeout.unwrap_or(HumanDuration(Duration::minutes(
+            DEFAULT_SPAM_TIMEOUT_MINUTES,
+        )))
+    }
+
+    pub fn starboard_stars(&self) -> u32 {
+        self.starboard_stars.unwrap_or(DEFAULT_STARBOARD_STARS)
+    }
+
+    /// Whether raid mode raises the verification level, which is the default.
+    pub fn raid_verification(&self) -> bool {
+        self.raid_verification.unwrap_or(true)
+    }
+
+    /// Whether members earn XP, which they don't by default.
+    pub fn leveling(&self) -> bool {
+        self.leveling.unwrap_or(false)
+    }
+
+    /// Whether recently deleted and edited messages can be sniped, which is
+    /// the default.
+    pub fn snipe(&self) -> bool {
+        self.snipe.unwrap_or(true)
+    }
+
+    /// Whether members can bookmark messages, which they can't by default.
+    pub fn bookmarks(&self) -> bool {
+        self.bookmarks.unwrap_or(false)
+    }
+
+    pub fn bookmark_emoji(&self) -> ReactionType {
+        let emoji = self
+            .bookmark_emoji
+            .as_deref()
+            .unwrap_or(DEFAULT_BOOKMARK_EMOJI);
+        ReactionType::try_from(emoji)
+            .unwrap_or_else(|_| ReactionType::Unicode(DEFAULT_BOOKMARK_EMOJI.to_string()))
+    }
+
+    /// Whether to DM members about kicks and bans, which is the default.
+    pub fn dm_members(&self) -> bool {
+        self.dm_members.unwrap_or(true)
+    }
+
+    /// The value of `setting` for `/config`, or `None` if it isn't set.
+    pub fn display(&self, setting: Setting) -> Option<String> {
+        match setting {
+            Setting::Channel(purpose) => self
+                .channels
+                .get(&purpose)
+                .map(|channel| channel.mention().to_string()),
+            Setting::PurgeRate => self.purge_rate.map(|rate| format!("{rate} per minute")),
+            Setting::Locale => self.locale.clone(),
+            Setting::Timezone => self
+                .timezone
+                .map(|timezone| localtime::zone_name(Some(timezone))),
+            Setting::DmMembers => self.dm_members.map(switch_name),
+            Setting::BlockInvites => self.block_invites.map(switch_name),
+            Setting::MaxMentions => self.max_mentions.map(|max| max.to_string()),
+            Setting::BlockEveryone => self.block_everyone.map(switch_name),
+            Setting::MentionTimeout => self.mention_timeout.map(|timeout| timeout.to_string()),
+            Setting::SpamRate => self.spam_rate.map(|rate| rate.to_string()),
+            Setting::SpamRepeats => self.spam_repeats.map(|repeats| repeats.to_string()),
+            Setting::SpamAction => self.spam_action.map(|action| action.as_str().to_string()),
+            Setting::SpamTimeout => self.spam_timeout.map(|timeout| timeout.to_string()),
+            Setting::RaidJoins => self.raid_joins.map(|rate| rate.to_string()),
+            Setting::RaidMinAge => self.raid_min_age.map(|age| age.to_string()),
+            Setting::RaidVerification => self.raid_verification.map(switch_name),
+            Setting::MinAccountAge => self.min_account_age.map(|age| age.to_string()),
+            Setting::QuarantineRole => self.quarantine_role.map(|role| role.mention().to_string()),
+            Setting::AutoroleDelay => self.autorole_delay.map(|delay| delay.to_string()),
+            Setting::StarboardStars => self.starboard_stars.map(|stars| stars.to_string()),
+            Setting::EventReminders => (!self.event_reminders.is_empty()).then(|| {
+                self.event_reminders
+                    .iter()
+                    .map(|lead| lead.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }),
+            Setting::Leveling => self.leveling.map(switch_name),
+            Setting::Snipe => self.snipe.map(switch_name),
+            Setting::Bookmarks => self.bookmarks.map(switch_name),
+            Setting::BookmarkEmoji => self.bookmark_emoji.clone(),
+        }
+    }
+
+    /// Parses `value` and stores it as `setting`.
+    pub fn set(&mut self, setting: Setting, value: &str) -> Result<(), SettingError> {
+        let value = value.trim();
+        match setting {
+            Setting::Channel(purpose) => {
+                let channel = parse_channel(value)
+                    .ok_or_else(|| SettingError::InvalidChannel(value.to_string()))?;
+                self.channels.insert(purpose, channel);
+            }
+            Setting::PurgeRate => {
+                let rate = value
+                    .parse()
+                    .ok()
+                    .filter(|rate| (10..=3000).contains(rate))
+                    .ok_or_else(|| SettingError::InvalidRate(value.to_string()))?;
+                self.purge_rate = Some(rate);
+            }
+            Setting::Locale => {
+                let locale = LOCALES
+                    .into_iter()
+                    .find(|locale| locale.eq_ignore_ascii_case(value))
+                    .ok_or_else(|| SettingError::InvalidLocale(value.to_string()))?;
+                self.locale = Some(locale.to_string());
+            }
+            Setting::Timezone => {
+                let offset = value.strip_prefix("UTC").unwrap_or(value);
+                let timezone = offset
+                    .parse()
+                    .map_err(|_| SettingError::InvalidTimezone(value.to_string()))?;
+                self.timezone = Some(timezone);
+            }
+            Setting::DmMembers => self.dm_members = Some(parse_switch(value)?),
+            Setting::BlockInvites => self.block_invites = Some(parse_switch(value)?),
+            Setting::MaxMentions => {
+                let max = value
+                    .parse()
+                    .ok()
+                    .filter(|max| (1..=50).contains(max))
+                    .ok_or_else(|| SettingError::InvalidMentions(value.to_string()))?;
+                self.max_mentions = Some(max);
+            }
+            Setting::BlockEveryone => self.block_everyone = Some(parse_switch(value)?),
+            Setting::MentionTimeout => self.mention_timeout = Some(parse_timeout(value)?),
+            Setting::SpamRate => {
+                let rate = value
+                    .parse()
+                    .map_err(|()| SettingError::InvalidSpamRate(value.to_string()))?;
+                self.spam_rate = Some(rate);
+            }
+            Setting::SpamRepeats => {
+                let repeats = value
+                    .parse()
+                    .ok()
+                    .filter(|repeats| (2..=20).contains(repeats))
+                    .ok_or_else(|| SettingError::InvalidRepeats(value.to_string()))?;
+                self.spam_repeats = Some(repeats);
+            }
+            Setting::SpamAction => {
+                let action = SpamAction::from_str(&value.to_ascii_lowercase())
+                    .ok_or_else(|| SettingError::InvalidSpamAction(value.to_string()))?;
+                self.spam_action = Some(action);
+            }
+            Setting::SpamTimeout => self.spam_timeout = Some(parse_timeout(value)?),
+            Setting::RaidJoins => {
+                let rate = value
+                    .parse()
+                    .map_err(|()| SettingError::InvalidJoinRate(value.to_string()))?;
+                self.raid_joins = Some(rate);
+            }
+            Setting::RaidMinAge => self.raid_min_age = Some(parse_account_age(value)?),
+            Setting::RaidVerification => self.raid_verification = Some(parse_switch(value)?),
+            Setting::MinAccountAge => self.min_account_age = Some(parse_account_age(value)?),
+            Setting::QuarantineRole => {
+                let role = parse_role(value)
+                    .ok_or_else(|| SettingError::InvalidRole(value.to_string()))?;
+                self.quarantine_role = Some(role);
+            }
+            Setting::AutoroleDelay => self.autorole_delay = Some(parse_timeout(value)?),
+            Setting::StarboardStars => {
+                let stars = value
+                    .parse()
+                    .ok()
+                    .filter(|stars| (1..=100).contains(stars))
+                    .ok_or_else(|| SettingError::InvalidStars(value.to_string()))?;
+                self.starboard_stars = Some(stars);
+            }
+            Setting::EventReminders => self.event_reminders = parse_reminders(value)?,
+            Setting::Leveling => self.leveling = Some(parse_switch(value)?),
+            Setting::Snipe => self.snipe = Some(parse_switch(value)?),
+            Setting::Bookmarks => self.bookmarks = Some(parse_switch(value)?),
+            Setting::BookmarkEmoji => self.bookmark_emoji = Some(parse_emoji(value)?),
+        }
+
+        Ok(())
+    }
+
+    pub fn reset(&mut self, setting: Setting) {
+        match setting {
+            Setting::Channel(purpose) => {
+                self.channels.remove(&purpose);
+            }
+            Setting::PurgeRate => self.purge_rate = None,
+            Setting::Locale => self.locale = None,
+            Setting::Timezone => self.timezone = None,
+            Setting::DmMembers => self.dm_members = None,
+            Setting::BlockInvites => self.block_invites = None,
+            Setting::MaxMentions => self.max_mentions = None,
+            Setting::BlockEveryone => self.block_everyone = None,
+            Setting::MentionTimeout => self.mention_timeout = None,
+            Setting::SpamRate => self.spam_rate = None,
+            Setting::SpamRepeats => self.spam_repeats = None,
+            Setting::SpamAction => self.spam_action = None,
+            Setting::SpamTimeout => self.spam_timeout = None,
+            Setting::RaidJoins => self.raid_joins = None,
+            Setting::RaidMinAge => self.raid_min_age = None,
+            Setting::RaidVerification => self.raid_verification = None,
+            Setting::MinAccountAge => self.min_account_age = None,
+            Setting::QuarantineRole => self.quarantine_role = None,
+            Setting::AutoroleDelay => self.autorole_delay = None,
+            Setting::StarboardStars => self.starboard_stars = None,
+            Setting::EventReminders => self.event_reminders.clear(),
+            Setting::Leveling => self.leveling = None,
+            Setting::Snipe => self.snipe = None,
+            Setting::Bookmarks => self.bookmarks = None,
+            Setting::BookmarkEmoji => self.bookmark_emoji = None,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct SettingsRow {
+    purge_rate: Option<i32>,
+    locale: Option<String>,
+    timezone: Option<String>,
+    dm_members: Option<bool>,
+    block_invites: Option<bool>,
+    invite_channels: Vec<i64>,
+    invite_roles: Vec<i64>,
+    max_mentions: Option<i32>,
+    block_everyone: Option<bool>,
+    mention_timeout_seconds: Option<i64>,
+    spam_messages: Option<i32>,
+    spam_window_seconds: Option<i32>,
+    spam_repeats: Option<i32>,
+    spam_action: Option<String>,
+    spam_timeout_seconds: Option<i64>,
+    raid_joins: Option<i32>,
+    raid_window_seconds: Option<i32>,
+    raid_min_age_seconds: Option<i64>,
+    raid_verification: Option<bool>,
+    min_account_age_seconds: Option<i64>,
+    quarantine_role: Option<i64>,
+    autorole_delay_seconds: Option<i64>,
+    starboard_stars: Option<i32>,
+    event_reminder_seconds: Vec<i64>,
+    leveling: Option<bool>,
+    snipe: Option<bool>,
+    bookmarks: Option<bool>,
+    bookmark_emoji: Option<String>,
+}
+
+impl From<SettingsRow> for GuildSettings {
+    fn from(row: SettingsRow) -> Self {
+        GuildSettings {
+            channels: HashMap::new(),
+            mod_roles: HashSet::new(),
+            log_ignored: HashSet::new(),
+            autoroles: HashSet::new(),
+            event_roles: HashSet::new(),
+            level_roles: BTreeMap::new(),
+            purge_rate: row.purge_rate.map(|rate| rate as u32),
+            locale: row.locale,
+            timezone: row.timezone.and_then(|timezone| timezone.parse().ok()),
+            dm_members: row.dm_members,
+            block_invites: row.block_invites,
+            invite_channels: row
+                .invite_channels
+                .into_iter()
+                .map(|channel_id| ChannelId::new(channel_id as u64))
+                .collect(),
+            invite_roles: row
+                .invite_roles
+                .into_iter()
+                .map(|role_id| RoleId::new(role_id as u64))
+                .collect(),
+            max_mentions: row.max_mentions.map(|max| max as u32),
+            block_everyone: row.block_everyone,
+            mention_timeout: row
+                .mention_timeout_seconds
+                .map(|seconds| HumanDuration(Duration::seconds(seconds))),
+            spam_rate: row
+                .spam_messages
+                .zip(row.spam_window_seconds)
+                .map(|(messages, seconds)| SpamRate {
+                    messages: messages as u32,
+                    window: std::time::Duration::from_secs(seconds as u64),
+                }),
+            spam_repeats: row.spam_repeats.map(|repeats| repeats as u32),
+            spam_action: row
+                .spam_action
+                .and_then(|action| SpamAction::from_str(&action)),
+            spam_timeout: row
+                .spam_timeout_seconds
+                .map(|seconds| HumanDuration(Duration::seconds(seconds))),
+            raid_joins: row
+                .raid_joins
+                .zip(row.raid_window_seconds)
+                .map(|(joins, seconds)| JoinRate {
+                    joins: joins as u32,
+                    window: std::time::Duration::from_secs(seconds as u64),
+                }),
+            raid_min_age: row
+                .raid_min_age_seconds
+                .map(|seconds| HumanDuration(Duration::seconds(seconds))),
+            raid_verification: row.raid_verification,
+            min_account_age: row
+                .min_account_age_seconds
+                .map(|seconds| HumanDuration(Duration::seconds(seconds))),
+            quarantine_role: row
+                .quarantine_role
+                .map(|role_id| RoleId::new(role_id as u64)),
+            autorole_delay: row
+                .autorole_delay_seconds
+                .map(|seconds| HumanDuration(Duration::seconds(seconds))),
+            starboard_stars: row.starboard_stars.map(|stars| stars as u32),
+            event_reminders: row
+                .event_reminder_seconds
+                .into_iter()
+                .map(|seconds| HumanDuration(Duration::seconds(seconds)))
+                .collect(),
+            leveling: row.leveling,
+            snipe: row.snipe,
+            bookmarks: row.bookmarks,
+            bookmark_emoji: row.bookmark_emoji,
+        }
+    }
+}
+
+/// The settings of every guild that used the bot since it started, kept in
+/// memory because purges look them up all the time.
+pub struct SettingsCache {
+    pool: PgPool,
+    guilds: DashMap<GuildId, GuildSettings>,
+    /// Held while a guild's settings are updated, so two updates at once
+    /// can't both start from the old settings and lose one of the changes.
+    updates: DashMap<GuildId, Arc<Mutex<()>>>,
+}
+
+impl SettingsCache {
+    pub fn new(pool: PgPool) -> Self {
+        SettingsCache {
+            pool,
+            guilds: DashMap::new(),
+            updates: DashMap::new(),
+        }
+    }
+
+    /// Drops the cached settings of a guild the bot left.
+    pub fn forget(&self, guild_id: GuildId) {
+        self.guilds.remove(&guild_id);
+        self.updates.remove(&guild_id);
+    }
+
+    pub async fn get(&self, guild_id: GuildId) -> sqlx::Result<GuildSettings> {
+        if let Some(settings) = self.guilds.get(&guild_id) {
+            return Ok(settings.clone());
+        }
+
+        let row: Option<SettingsRow> = sqlx::query_as(
+            "SELECT purge_rate, locale, timezone, dm_members, block_invites, invite_channels,
+            invite_roles, max_mentions, block_everyone, mention_timeout_seconds, spam_messages,
+            spam_window_seconds, spam_repeats, spam_action, spam_timeout_seconds, raid_joins,
+            raid_window_seconds, raid_min_age_seconds, raid_verification, min_account_age_seconds,
+            quarantine_role, autorole_delay_seconds, starboard_stars, event_reminder_seconds,
+            leveling, snipe, bookmarks, bookmark_emoji
+            FROM guild_settings WHERE guild_id = $1",
+        )
+        .bind(i64::from(guild_id))
+        .fetch_optional(&self.pool)
+        .await?;
+        let mut settings = row.map(GuildSettings::from).unwrap_or_default();
+
+        let channels: Vec<(String, i64)> =
+            sqlx::query_as("SELECT purpose, channel_id FROM channels WHERE guild_id = $1")
+                .bind(i64::from(guild_id))
+                .fetch_all(&self.pool)
+                .await?;
+        settings.channels = channels
+            .into_iter()
+            .filter_map(|(purpose, channel_id)| {
+                let purpose = ChannelPurpose::from_str(&purpose)?;
+                Some((purpose, ChannelId::new(channel_id as u64)))
+            })
+            .collect();
+
+        let mod_roles: Vec<i64> =
+            sqlx::query_scalar("SELECT role_id FROM mod_roles WHERE guild_id = $1")
+                .bind(i64::from(guild_id))
+                .fetch_all(&self.pool)
+                .await?;
+        settings.mod_roles = mod_roles
+            .into_iter()
+            .map(|role_id| RoleId::new(role_id as u64))
+            .collect();
+
+        let log_ignored: Vec<i64> =
+            sqlx::query_scalar("SELECT channel_id FROM message_log_ignored WHERE guild_id = $1")
+                .bind(i64::from(guild_id))
+                .fetch_all(&self.pool)
+                .await?;
+        settings.log_ignored = log_ignored
+            .into_iter()
+            .map(|channel_id| ChannelId::new(channel_id as u64))
+            .collect();
+
+        let autoroles: Vec<i64> =
+            sqlx::query_scalar("SELECT role_id FROM autoroles WHERE guild_id = $1")
+                .bind(i64::from(guild_id))
+                .fetch_all(&self.pool)
+                .await?;
+        settings.autoroles = autoroles
+            .into_iter()
+            .map(|role_id| RoleId::new(role_id as u64))
+            .collect();
+
+        let event_roles: Vec<i64> =
+            sqlx::query_scalar("SELECT role_id FROM event_roles WHERE guild_id = $1")
+                .bind(i64::from(guild_id))
+                .fetch_all(&self.pool)
+                .await?;
+        settings.event_roles = event_roles
+            .into_iter()
+            .map(|role_id| RoleId::new(role_id as u64))
+            .collect();
+
+        let level_roles: Vec<(i32, i64)> =
+            sqlx::query_as("SELECT level, role_id FROM level_roles WHERE guild_id = $1")
+                .bind(i64::from(guild_id))
+                .fetch_all(&self.pool)
+                .await?;
+        settings.level_roles = level_roles
+            .into_iter()
+            .map(|(level, role_id)| (level as u32, RoleId::new(role_id as u64)))
+            .collect();
+        self.guilds.insert(guild_id, settings.clone());
+
+        Ok(settings)
+    }
+
+    /// Changes the guild's settings with `change` and saves them, one update
+    /// per guild at a time. Returns whatever `change` returns, e.g. whether
+    /// the new value was valid.
+    pub async fn update<T>(
+        &self,
+        guild_id: GuildId,
+        change: impl FnOnce(&mut GuildSettings) -> T,
+    ) -> sqlx::Result<T> {
+        let lock = Arc::clone(&self.updates.entry(guild_id).or_default());
+        let _updating = lock.lock().await;
+        let mut settings = self.get(guild_id).await?;
+        let result = change(&mut settings);
+
+        db::ensure_guild(&self.pool, guild_id).await?;
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO guild_settings
+            (guild_id, purge_rate, locale, timezone, dm_members, block_invites, invite_channels,
+            invite_roles, max_mentions, block_everyone, mention_timeout_seconds, spam_messages,
+            spam_window_seconds, spam_repeats, spam_action, spam_timeout_seconds, raid_joins,
+            raid_window_seconds, raid_min_age_seconds, raid_verification, min_account_age_seconds,
+            quarantine_role, autorole_delay_seconds, starboard_stars, event_reminder_seconds,
+            leveling, snipe, bookmarks, bookmark_emoji)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17,
+            $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29)
+            ON CONFLICT (guild_id) DO UPDATE
+            SET purge_rate = EXCLUDED.purge_rate, locale = EXCLUDED.locale,
+            timezone = EXCLUDED.timezone, dm_members = EXCLUDED.dm_members,
+            block_invites = EXCLUDED.block_invites, invite_channels = EXCLUDED.invite_channels,
+            invite_roles = EXCLUDED.invite_roles, max_mentions = EXCLUDED.max_mentions,
+            block_everyone = EXCLUDED.block_everyone,
+            mention_timeout_seconds = EXCLUDED.mention_timeout_seconds,
+            spam_messages = EXCLUDED.spam_messages,
+            spam_window_seconds = EXCLUDED.spam_window_seconds,
+            spam_repeats = EXCLUDED.spam_repeats, spam_action = EXCLUDED.spam_action,
+            spam_timeout_seconds = EXCLUDED.spam_timeout_seconds,
+            raid_joins = EXCLUDED.raid_joins, raid_window_seconds = EXCLUDED.raid_window_seconds,
+            raid_min_age_seconds = EXCLUDED.raid_min_age_seconds,
+            raid_verification = EXCLUDED.raid_verification,
+            min_account_age_seconds = EXCLUDED.min_account_age_seconds,
+            quarantine_role = EXCLUDED.quarantine_role,
+            autorole_delay_seconds = EXCLUDED.autorole_delay_seconds,
+            starboard_stars = EXCLUDED.starboard_stars,
+            event_reminder_seconds = EXCLUDED.event_reminder_seconds,
+            leveling = EXCLUDED.leveling, snipe = EXCLUDED.snipe,
+            bookmarks = EXCLUDED.bookmarks, bookmark_emoji = EXCLUDED.bookmark_emoji",
+        )
+        .bind(i64::from(guild_id))
+        .bind(settings.purge_rate.map(|rate| rate as i32))
+        .bind(&settings.locale)
+        .bind(settings.timezone.map(|timezone| timezone.to_string()))
+        .bind(settings.dm_members)
+        .bind(settings.block_invites)
+        .bind(
+            settings
+                .invite_channels
+                .iter()
+                .map(|&channel_id| i64::from(channel_id))
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            settings
+                .invite_roles
+                .iter()
+                .map(|&role_id| i64::from(role_id))
+                .collect::<Vec<_>>(),
+        )
+        .bind(settings.max_mentions.map(|max| max as i32))
+        .bind(settings.block_everyone)
+        .bind(
+            settings
+                .mention_timeout
+                .map(|timeout| timeout.as_duration().num_seconds()),
+        )
+        .bind(settings.spam_rate.map(|rate| rate.messages as i32))
+        .bind(settings.spam_rate.map(|rate| rate.window.as_secs() as i32))
+        .bind(settings.spam_repeats.map(|repeats| repeats as i32))
+        .bind(settings.spam_action.map(SpamAction::as_str))
+        .bind(
+            settings
+                .spam_timeout
+                .map(|timeout| timeout.as_duration().num_seconds()),
+        )
+        .bind(settings.raid_joins.map(|rate| rate.joins as i32))
+        .bind(settings.raid_joins.map(|rate| rate.window.as_secs() as i32))
+        .bind(
+            settings
+                .raid_min_age
+                .map(|age| age.as_duration().num_seconds()),
+        )
+        .bind(settings.raid_verification)
+        .bind(
+            settings
+                .min_account_age
+                .map(|age| age.as_duration().num_seconds()),
+        )
+        .bind(settings.quarantine_role.map(i64::from))
+        .bind(
+            settings
+                .autorole_delay
+                .map(|delay| delay.as_duration().num_seconds()),
+        )
+        .bind(settings.starboard_stars.map(|stars| stars as i32))
+        .bind(
+            settings
+                .event_reminders
+                .iter()
+                .map(|lead| lead.as_duration().num_seconds())
+                .collect::<Vec<_>>(),
+        )
+        .bind(settings.leveling)
+        .bind(settings.snipe)
+        .bind(settings.bookmarks)
+        .bind(&settings.bookmark_emoji)
+        .execute(&mut *transaction)
+        .await?;
+
+        sqlx::query("DELETE FROM channels WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .execute(&mut *transaction)
+            .await?;
+        for (purpose, channel_id) in &settings.channels {
+            sqlx::query("INSERT INTO channels (guild_id, purpose, channel_id) VALUES ($1, $2, $3)")
+                .bind(i64::from(guild_id))
+                .bind(purpose.as_str())
+                .bind(i64::from(*channel_id))
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        sqlx::query("DELETE FROM mod_roles WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .execute(&mut *transaction)
+            .await?;
+        for role_id in &settings.mod_roles {
+            sqlx::query("INSERT INTO mod_roles (guild_id, role_id) VALUES ($1, $2)")
+                .bind(i64::from(guild_id))
+                .bind(i64::from(*role_id))
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        sqlx::query("DELETE FROM message_log_ignored WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .execute(&mut *transaction)
+            .await?;
+        for channel_id in &settings.log_ignored {
+            sqlx::query("INSERT INTO message_log_ignored (guild_id, channel_id) VALUES ($1, $2)")
+                .bind(i64::from(guild_id))
+                .bind(i64::from(*channel_id))
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        sqlx::query("DELETE FROM autoroles WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .execute(&mut *transaction)
+            .await?;
+        for role_id in &settings.autoroles {
+            sqlx::query("INSERT INTO autoroles (guild_id, role_id) VALUES ($1, $2)")
+                .bind(i64::from(guild_id))
+                .bind(i64::from(*role_id))
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        sqlx::query("DELETE FROM event_roles WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .execute(&mut *transaction)
+            .await?;
+        for role_id in &settings.event_roles {
+            sqlx::query("INSERT INTO event_roles (guild_id, role_id) VALUES ($1, $2)")
+                .bind(i64::from(guild_id))
+                .bind(i64::from(*role_id))
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        sqlx::query("DELETE FROM level_roles WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .execute(&mut *transaction)
+            .await?;
+        for (level, role_id) in &settings.level_roles {
+            sqlx::query("INSERT INTO level_roles (guild_id, level, role_id) VALUES ($1, $2, $3)")
+                .bind(i64::from(guild_id))
+                .bind(*level as i32)
+                .bind(i64::from(*role_id))
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        self.guilds.insert(guild_id, settings);
+
+        Ok(result)
+    }
+}
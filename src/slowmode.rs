@@ -0,0 +1,180 @@
+use std::{sync::Arc, time::Duration as StdDuration};
+
+use chrono::{Duration, Utc};
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    cases, duration::HumanDuration, localtime, lockdown::UNKNOWN_CHANNEL, replies::reply, Context,
+    Data, SlimeError,
+};
+
+/// How often the background task looks for slowmodes to revert.
+const REVERT_INTERVAL: StdDuration = StdDuration::from_secs(30);
+
+/// A slowmode that is due to be reverted, from the `slowmodes` table.
+#[derive(sqlx::FromRow)]
+struct DueRevert {
+    guild_id: i64,
+    channel_id: i64,
+    previous_seconds: i32,
+}
+
+/// Describes a slowmode for people, e.g. "30s" or "off".
+fn describe(seconds: u16) -> String {
+    if seconds == 0 {
+        "off".to_string()
+    } else {
+        HumanDuration(Duration::seconds(i64::from(seconds))).to_string()
+    }
+}
+
+async fn due_reverts(pool: &PgPool) -> sqlx::Result<Vec<DueRevert>> {
+    sqlx::query_as(
+        "SELECT guild_id, channel_id, previous_seconds FROM slowmodes WHERE revert_at <= now()",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn remove_revert(
+    pool: &PgPool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM slowmodes WHERE guild_id = $1 AND channel_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(i64::from(channel_id))
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Puts the slowmode back to what it was and announces that. A channel
+/// somebody deleted since counts as reverted.
+async fn revert(http: &Http, data: &Data, due: &DueRevert) -> Result<(), SlimeError> {
+    let guild_id = GuildId::new(due.guild_id as u64);
+    let channel_id = ChannelId::new(due.channel_id as u64);
+    let seconds = due.previous_seconds as u16;
+    let builder = EditChannel::new().rate_limit_per_user(seconds);
+    match channel_id.edit(http, builder).await {
+        Ok(_) => {}
+        Err(Error::Http(HttpError::UnsuccessfulRequest(response)))
+            if response.error.code == UNKNOWN_CHANNEL =>
+        {
+            return Ok(remove_revert(&data.pool, guild_id, channel_id).await?);
+        }
+        Err(e) => return Err(e.into()),
+    }
+    remove_revert(&data.pool, guild_id, channel_id).await?;
+
+    let content = format!(
+        "The slowmode of {} is back to {}.",
+        channel_id.mention(),
+        describe(seconds)
+    );
+    cases::announce(http, data, guild_id, &content).await;
+
+    Ok(())
+}
+
+/// Reverts temporary slowmodes once they run out. Slowmodes that ran out
+/// while the bot was down are reverted on the first check after it starts.
+pub async fn run_reverts(http: Arc<Http>, data: Data) {
+    let mut interval = tokio::time::interval(REVERT_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let due = match due_reverts(&data.pool).await {
+            Ok(due) => due,
+            Err(e) => {
+                error!("failed to load due slowmode reverts: {}", e);
+                continue;
+            }
+        };
+
+        // A failed revert stays in the table and is tried again next time.
+        for due in due {
+            if let Err(e) = revert(&http, &data, &due).await {
+                error!(
+                    "failed to revert the slowmode of channel {} in guild {}: {}",
+                    due.channel_id, due.guild_id, e
+                );
+            }
+        }
+    }
+}
+
+/// Sets how long members have to wait between messages in a channel
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn slowmode(
+    ctx: Context<'_>,
+    #[description = "Channel to slow down"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+    #[description = "Seconds between messages, 0 turns slowmode off"]
+    // Discord allows at most 6 hours.
+    #[max = 21600]
+    seconds: u16,
+    #[description = "Put it back after this long, e.g. 1h (default never)"] duration: Option<
+        HumanDuration,
+    >,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    if duration.is_some_and(|duration| duration.as_duration() <= Duration::zero()) {
+        return reply(ctx, "The slowmode has to last a while.".to_string()).await;
+    }
+
+    let previous = channel.rate_limit_per_user.unwrap_or(0);
+    channel
+        .id
+        .edit(ctx, EditChannel::new().rate_limit_per_user(seconds))
+        .await?;
+
+    // Another temporary slowmode keeps the one from before that, so the
+    // channel ends up back where it started.
+    let until = match duration {
+        Some(duration) => {
+            let revert_at = Utc::now() + duration.as_duration();
+            let previous: i32 = sqlx::query_scalar(
+                "INSERT INTO slowmodes
+                (guild_id, channel_id, set_by, previous_seconds, revert_at)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (guild_id, channel_id) DO UPDATE
+                SET set_by = EXCLUDED.set_by, revert_at = EXCLUDED.revert_at
+                RETURNING previous_seconds",
+            )
+            .bind(i64::from(guild_id))
+            .bind(i64::from(channel.id))
+            .bind(i64::from(ctx.author().id))
+            .bind(i32::from(previous))
+            .bind(revert_at)
+            .fetch_one(&data.pool)
+            .await?;
+
+            let timezone = data.settings.get(guild_id).await?.timezone;
+            format!(
+                ", going back to {} at {}",
+                describe(previous as u16),
+                localtime::format_datetime(revert_at, timezone)
+            )
+        }
+        None => {
+            remove_revert(&data.pool, guild_id, channel.id).await?;
+            String::new()
+        }
+    };
+
+    let content = format!(
+        "{} set the slowmode of {} to {}{until}.",
+        ctx.author().mention(),
+        channel.mention(),
+        describe(seconds)
+    );
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
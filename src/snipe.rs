@@ -0,0 +1,251 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use poise::{serenity_prelude::*, CreateReply};
+use tokio::time::Instant;
+
+use crate::{
+    duration::HumanDuration, msglog::field_text, replies::reply, Context, Data, SlimeError,
+};
+
+/// How long messages are kept, both to be sniped once they're deleted or
+/// edited and to know what they said before that.
+const SNIPE_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// How many of the latest messages of a channel are kept at most, in case
+/// one of them is deleted or edited.
+const MAX_SENT: usize = 50;
+
+/// How many deleted and edited messages of a channel can be sniped.
+const MAX_SNIPES: usize = 5;
+
+/// How often channels nothing happened in for a while are dropped.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A message as it was before it was deleted or edited.
+#[derive(Clone)]
+struct Snipe {
+    message_id: MessageId,
+    author: User,
+    content: String,
+    /// What an edited message says now.
+    edited_to: Option<String>,
+    /// When it was sent, or deleted or edited once it's a snipe.
+    at: Instant,
+}
+
+#[derive(Default)]
+struct ChannelSnipes {
+    sent: VecDeque<Snipe>,
+    deleted: VecDeque<Snipe>,
+    edited: VecDeque<Snipe>,
+}
+
+impl ChannelSnipes {
+    fn retain_fresh(&mut self, now: Instant) {
+        for snipes in [&mut self.sent, &mut self.deleted, &mut self.edited] {
+            snipes.retain(|snipe| now.duration_since(snipe.at) < SNIPE_TTL);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sent.is_empty() && self.deleted.is_empty() && self.edited.is_empty()
+    }
+}
+
+/// Pushes `snipe` in front of `snipes`, dropping the oldest past `max`.
+fn push(snipes: &mut VecDeque<Snipe>, snipe: Snipe, max: usize) {
+    snipes.push_front(snipe);
+    snipes.truncate(max);
+}
+
+/// The latest messages of every channel someone wrote in recently, and the
+/// ones deleted or edited among them, kept in memory only so nothing sniped
+/// outlives `SNIPE_TTL` or a restart.
+#[derive(Default)]
+pub struct SnipeCache {
+    channels: DashMap<ChannelId, ChannelSnipes>,
+}
+
+impl SnipeCache {
+    fn sent(&self, message: &Message) {
+        let now = Instant::now();
+        let mut channel = self.channels.entry(message.channel_id).or_default();
+        channel.retain_fresh(now);
+        let snipe = Snipe {
+            message_id: message.id,
+            author: message.author.clone(),
+            content: message.content.clone(),
+            edited_to: None,
+            at: now,
+        };
+        push(&mut channel.sent, snipe, MAX_SENT);
+    }
+
+    fn deleted(&self, channel_id: ChannelId, message_id: MessageId) {
+        let Some(mut channel) = self.channels.get_mut(&channel_id) else {
+            return;
+        };
+        let Some(index) = channel
+            .sent
+            .iter()
+            .position(|snipe| snipe.message_id == message_id)
+        else {
+            return;
+        };
+        if let Some(mut snipe) = channel.sent.remove(index) {
+            snipe.at = Instant::now();
+            push(&mut channel.deleted, snipe, MAX_SNIPES);
+        }
+    }
+
+    fn edited(&self, channel_id: ChannelId, message_id: MessageId, content: &str) {
+        let Some(mut channel) = self.channels.get_mut(&channel_id) else {
+            return;
+        };
+        let Some(sent) = channel
+            .sent
+            .iter_mut()
+            .find(|snipe| snipe.message_id == message_id)
+        else {
+            return;
+        };
+        // Discord also sends updates when it adds link previews.
+        if sent.content == content {
+            return;
+        }
+        let mut snipe = sent.clone();
+        sent.content = content.to_string();
+        snipe.edited_to = Some(content.to_string());
+        snipe.at = Instant::now();
+        push(&mut channel.edited, snipe, MAX_SNIPES);
+    }
+
+    /// The `index`th latest message deleted, or edited if `edits`, in the
+    /// channel, counting from 0.
+    fn get(&self, channel_id: ChannelId, edits: bool, index: usize) -> Option<Snipe> {
+        let channel = self.channels.get(&channel_id)?;
+        let snipes = if edits {
+            &channel.edited
+        } else {
+            &channel.deleted
+        };
+        let now = Instant::now();
+        snipes
+            .iter()
+            .filter(|snipe| now.duration_since(snipe.at) < SNIPE_TTL)
+            .nth(index)
+            .cloned()
+    }
+
+    fn prune(&self) {
+        let now = Instant::now();
+        self.channels.retain(|_, channel| {
+            channel.retain_fresh(now);
+            !channel.is_empty()
+        });
+    }
+}
+
+/// Keeps `message` for a while, so it can be sniped if it's deleted or
+/// edited, unless the guild turned sniping off.
+pub async fn message_sent(data: &Data, message: &Message) -> Result<(), SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    if message.author.bot || message.content.is_empty() {
+        return Ok(());
+    }
+    if !data.settings.get(guild_id).await?.snipe() {
+        return Ok(());
+    }
+    data.snipes.sent(message);
+
+    Ok(())
+}
+
+/// Lets a kept message be sniped with `/editsnipe`.
+pub fn message_edited(data: &Data, event: &MessageUpdateEvent) {
+    if let Some(content) = &event.content {
+        data.snipes.edited(event.channel_id, event.id, content);
+    }
+}
+
+/// Lets a kept message be sniped with `/snipe`. Bulk deletions aren't
+/// sniped, they only come from purges and bots cleaning up.
+pub fn message_deleted(data: &Data, channel_id: ChannelId, message_id: MessageId) {
+    data.snipes.deleted(channel_id, message_id);
+}
+
+/// Drops what expired, every `PRUNE_INTERVAL`.
+pub async fn prune_snipes(cache: Arc<SnipeCache>) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        cache.prune();
+    }
+}
+
+/// Shows a message deleted or edited in this channel lately.
+async fn show(ctx: Context<'_>, edits: bool, index: Option<usize>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    if !data.settings.get(guild_id).await?.snipe() {
+        return reply(ctx, "Sniping is off in this server.".to_string()).await;
+    }
+    let index = index.unwrap_or(1);
+    let Some(snipe) = data.snipes.get(ctx.channel_id(), edits, index - 1) else {
+        let what = if edits { "edited" } else { "deleted" };
+        let content = if index == 1 {
+            format!("Nothing was {what} here lately.")
+        } else {
+            format!("Fewer than {index} messages were {what} here lately.")
+        };
+        return reply(ctx, content).await;
+    };
+
+    let ago = HumanDuration(chrono::Duration::seconds(
+        snipe.at.elapsed().as_secs() as i64
+    ));
+    let author = CreateEmbedAuthor::new(snipe.author.tag()).icon_url(snipe.author.face());
+    let embed = match snipe.edited_to {
+        Some(edited_to) => CreateEmbed::new()
+            .author(author)
+            .colour(Colour::GOLD)
+            .description(snipe.message_id.link(ctx.channel_id(), Some(guild_id)))
+            .field("Before", field_text(&snipe.content), false)
+            .field("After", field_text(&edited_to), false)
+            .footer(CreateEmbedFooter::new(format!("Edited {ago} ago"))),
+        None => CreateEmbed::new()
+            .author(author)
+            .colour(Colour::RED)
+            .description(field_text(&snipe.content))
+            .footer(CreateEmbedFooter::new(format!("Deleted {ago} ago"))),
+    };
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Shows a message deleted in this channel lately
+#[poise::command(slash_command, guild_only)]
+pub async fn snipe(
+    ctx: Context<'_>,
+    #[description = "How many deletions back to go (default 1, the latest)"]
+    #[min = 1]
+    #[max = 5]
+    index: Option<usize>,
+) -> Result<(), SlimeError> {
+    show(ctx, false, index).await
+}
+
+/// Shows what a message edited in this channel lately said before
+#[poise::command(slash_command, guild_only)]
+pub async fn editsnipe(
+    ctx: Context<'_>,
+    #[description = "How many edits back to go (default 1, the latest)"]
+    #[min = 1]
+    #[max = 5]
+    index: Option<usize>,
+) -> Result<(), SlimeError> {
+    show(ctx, true, index).await
+}
@@ -0,0 +1,209 @@
+use std::{
+    collections::{hash_map::DefaultHasher, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use poise::serenity_prelude::{ChannelId, GuildId, Message, MessageId, UserId};
+use tokio::time::Instant;
+
+use crate::duration::HumanDuration;
+
+/// How long messages count towards repeated content.
+const REPEAT_WINDOW: Duration = Duration::from_secs(60);
+
+/// The longest window a `SpamRate` may have. Nothing older is kept.
+const MAX_WINDOW: Duration = Duration::from_secs(120);
+
+/// How many messages of one member are kept at most.
+const MAX_KEPT: usize = 100;
+
+/// How often members who went quiet are dropped from the tracker.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// At most this many messages in this window, e.g. `5/10s`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpamRate {
+    pub messages: u32,
+    pub window: Duration,
+}
+
+impl FromStr for SpamRate {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (messages, window) = s.split_once('/').ok_or(())?;
+        let messages = messages.trim().parse().map_err(|_| ())?;
+        let window: HumanDuration = window.parse().map_err(|_| ())?;
+        let window = window.as_duration().to_std().map_err(|_| ())?;
+        if !(2..=50).contains(&messages) || window.is_zero() || window > MAX_WINDOW {
+            return Err(());
+        }
+
+        Ok(SpamRate { messages, window })
+    }
+}
+
+impl fmt::Display for SpamRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let window = chrono::Duration::seconds(self.window.as_secs() as i64);
+        write!(f, "{}/{}", self.messages, HumanDuration(window))
+    }
+}
+
+/// What automod does about a member who spams, besides deleting the spam.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpamAction {
+    #[default]
+    Delete,
+    Warn,
+    Timeout,
+}
+
+impl SpamAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SpamAction::Delete => "delete",
+            SpamAction::Warn => "warn",
+            SpamAction::Timeout => "timeout",
+        }
+    }
+
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "delete" => Some(SpamAction::Delete),
+            "warn" => Some(SpamAction::Warn),
+            "timeout" => Some(SpamAction::Timeout),
+            _ => None,
+        }
+    }
+}
+
+/// A guild's limits, see `GuildSettings::spam_rate` and `spam_repeats`.
+pub struct SpamLimits {
+    pub rate: Option<SpamRate>,
+    pub repeats: Option<u32>,
+}
+
+/// A message as far as spam detection cares.
+struct Sent {
+    at: Instant,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    /// `None` for messages without text, which never count as repeats.
+    content: Option<u64>,
+}
+
+/// Messages that together went over a guild's limits.
+pub struct Burst {
+    /// What the member did, e.g. "sent 5 messages in 10s".
+    pub description: String,
+    pub messages: Vec<(ChannelId, MessageId)>,
+}
+
+/// The latest messages of every member who wrote recently, to tell when
+/// they are spamming.
+#[derive(Default)]
+pub struct SpamTracker {
+    recent: DashMap<(GuildId, UserId), VecDeque<Sent>>,
+}
+
+fn content_hash(content: &str) -> Option<u64> {
+    let content = content.trim().to_lowercase();
+    if content.is_empty() {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+impl SpamTracker {
+    /// Remembers `message` and returns the burst it completes, if any. The
+    /// burst is forgotten then, so that it's only dealt with once.
+    pub fn record(
+        &self,
+        guild_id: GuildId,
+        message: &Message,
+        limits: &SpamLimits,
+    ) -> Option<Burst> {
+        let now = Instant::now();
+        let content = content_hash(&message.content);
+        let mut recent = self
+            .recent
+            .entry((guild_id, message.author.id))
+            .or_default();
+        recent.retain(|sent| now.duration_since(sent.at) < MAX_WINDOW);
+        if recent.len() >= MAX_KEPT {
+            recent.pop_front();
+        }
+        recent.push_back(Sent {
+            at: now,
+            channel_id: message.channel_id,
+            message_id: message.id,
+            content,
+        });
+
+        let within = |window: Duration| {
+            recent
+                .iter()
+                .filter(move |sent| now.duration_since(sent.at) < window)
+        };
+        let mut burst = None;
+        if let Some(rate) = limits.rate {
+            let messages: Vec<_> = within(rate.window)
+                .map(|sent| (sent.channel_id, sent.message_id))
+                .collect();
+            if messages.len() >= rate.messages as usize {
+                let window = chrono::Duration::seconds(rate.window.as_secs() as i64);
+                burst = Some(Burst {
+                    description: format!(
+                        "sent {} messages in {}",
+                        messages.len(),
+                        HumanDuration(window)
+                    ),
+                    messages,
+                });
+            }
+        }
+        if let (None, Some(repeats), Some(_)) = (&burst, limits.repeats, content) {
+            let messages: Vec<_> = within(REPEAT_WINDOW)
+                .filter(|sent| sent.content == content)
+                .map(|sent| (sent.channel_id, sent.message_id))
+                .collect();
+            if messages.len() >= repeats as usize {
+                burst = Some(Burst {
+                    description: format!("sent the same message {} times", messages.len()),
+                    messages,
+                });
+            }
+        }
+
+        if burst.is_some() {
+            recent.clear();
+        }
+        burst
+    }
+
+    fn prune(&self) {
+        let now = Instant::now();
+        self.recent.retain(|_, recent| {
+            recent
+                .back()
+                .is_some_and(|sent| now.duration_since(sent.at) < MAX_WINDOW)
+        });
+    }
+}
+
+/// Drops members who haven't written for a while, every `PRUNE_INTERVAL`.
+pub async fn prune_spam(tracker: Arc<SpamTracker>) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        tracker.prune();
+    }
+}
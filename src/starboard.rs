@@ -0,0 +1,220 @@
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::{settings::ChannelPurpose, Data, SlimeError};
+
+/// The reaction that counts as a star.
+const STAR: &str = "⭐";
+
+/// A message that made the starboard, from the `starboard_entries` table.
+/// Until its copy is posted, `starboard_message_id` is `None`.
+#[derive(sqlx::FromRow)]
+struct Entry {
+    starboard_channel_id: i64,
+    starboard_message_id: Option<i64>,
+}
+
+async fn entry(pool: &PgPool, message_id: MessageId) -> sqlx::Result<Option<Entry>> {
+    sqlx::query_as(
+        "SELECT starboard_channel_id, starboard_message_id FROM starboard_entries
+        WHERE message_id = $1",
+    )
+    .bind(i64::from(message_id))
+    .fetch_optional(pool)
+    .await
+}
+
+fn stars(message: &Message) -> u64 {
+    message
+        .reactions
+        .iter()
+        .find(|reaction| reaction.reaction_type.unicode_eq(STAR))
+        .map_or(0, |reaction| reaction.count)
+}
+
+/// The starboard copy of `message`. Messages fetched over HTTP don't know
+/// their guild, so it's passed in for the link.
+fn copy(guild_id: GuildId, message: &Message, stars: u64) -> (String, CreateEmbed) {
+    let content = format!("{STAR} **{stars}** in {}", message.channel_id.mention());
+    let mut embed = CreateEmbed::new()
+        .author(CreateEmbedAuthor::new(message.author.tag()).icon_url(message.author.face()))
+        .colour(Colour::GOLD)
+        .field(
+            "Source",
+            message.id.link(message.channel_id, Some(guild_id)),
+            false,
+        )
+        .timestamp(message.timestamp);
+    if !message.content.is_empty() {
+        embed = embed.description(&message.content);
+    }
+    let image = message.attachments.iter().find(|attachment| {
+        attachment
+            .content_type
+            .as_deref()
+            .is_some_and(|content_type| content_type.starts_with("image/"))
+    });
+    if let Some(image) = image {
+        embed = embed.image(&image.url);
+    }
+    (content, embed)
+}
+
+/// Brings the starboard in step with a message whose stars or text
+/// changed: it's posted once it has enough stars, and its copy is updated
+/// after that. Copies stay up if stars are taken away again.
+async fn refresh(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+) -> Result<(), SlimeError> {
+    let settings = data.settings.get(guild_id).await?;
+    let Some(starboard) = settings.channel(ChannelPurpose::Starboard) else {
+        return Ok(());
+    };
+    if channel_id == starboard {
+        return Ok(());
+    }
+    let message = match channel_id.message(ctx, message_id).await {
+        Ok(message) => message,
+        Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let stars = stars(&message);
+    let (content, embed) = copy(guild_id, &message, stars);
+
+    match entry(&data.pool, message_id).await? {
+        Some(Entry {
+            starboard_channel_id,
+            starboard_message_id: Some(starboard_message_id),
+        }) => {
+            sqlx::query("UPDATE starboard_entries SET stars = $2 WHERE message_id = $1")
+                .bind(i64::from(message_id))
+                .bind(stars as i32)
+                .execute(&data.pool)
+                .await?;
+            let edit = EditMessage::new().content(content).embed(embed);
+            let edited = ChannelId::new(starboard_channel_id as u64)
+                .edit_message(ctx, MessageId::new(starboard_message_id as u64), edit)
+                .await;
+            if let Err(e) = edited {
+                warn!(
+                    "failed to update the starboard copy of {} in guild {}: {}",
+                    message_id, guild_id, e
+                );
+            }
+            return Ok(());
+        }
+        // Another event is posting the copy right now.
+        Some(_) => return Ok(()),
+        None if stars < u64::from(settings.starboard_stars()) => return Ok(()),
+        None => {}
+    }
+
+    // Claiming the message first means only one copy is ever posted.
+    let claimed = sqlx::query(
+        "INSERT INTO starboard_entries (guild_id, channel_id, message_id, starboard_channel_id,
+        stars)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (message_id) DO NOTHING",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel_id))
+    .bind(i64::from(message_id))
+    .bind(i64::from(starboard))
+    .bind(stars as i32)
+    .execute(&data.pool)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return Ok(());
+    }
+
+    let posted = starboard
+        .send_message(ctx, CreateMessage::new().content(content).embed(embed))
+        .await;
+    let posted = match posted {
+        Ok(posted) => posted,
+        Err(e) => {
+            sqlx::query("DELETE FROM starboard_entries WHERE message_id = $1")
+                .bind(i64::from(message_id))
+                .execute(&data.pool)
+                .await?;
+            return Err(e.into());
+        }
+    };
+    sqlx::query("UPDATE starboard_entries SET starboard_message_id = $2 WHERE message_id = $1")
+        .bind(i64::from(message_id))
+        .bind(i64::from(posted.id))
+        .execute(&data.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Recounts the stars of a message a star was added to or taken from.
+pub async fn reaction_changed(
+    ctx: &serenity::Context,
+    data: &Data,
+    reaction: &Reaction,
+) -> Result<(), SlimeError> {
+    let Some(guild_id) = reaction.guild_id else {
+        return Ok(());
+    };
+    if !reaction.emoji.unicode_eq(STAR) {
+        return Ok(());
+    }
+    refresh(
+        ctx,
+        data,
+        guild_id,
+        reaction.channel_id,
+        reaction.message_id,
+    )
+    .await
+}
+
+/// Recounts the stars of a message on the starboard whose reactions were
+/// all taken away. The event doesn't say which guild it's in, but the
+/// entry does.
+pub async fn reactions_cleared(
+    ctx: &serenity::Context,
+    data: &Data,
+    channel_id: ChannelId,
+    message_id: MessageId,
+) -> Result<(), SlimeError> {
+    let guild_id: Option<i64> =
+        sqlx::query_scalar("SELECT guild_id FROM starboard_entries WHERE message_id = $1")
+            .bind(i64::from(message_id))
+            .fetch_optional(&data.pool)
+            .await?;
+    let Some(guild_id) = guild_id else {
+        return Ok(());
+    };
+    refresh(
+        ctx,
+        data,
+        GuildId::new(guild_id as u64),
+        channel_id,
+        message_id,
+    )
+    .await
+}
+
+/// Updates the starboard copy of a message that was edited, if it has one.
+pub async fn message_edited(
+    ctx: &serenity::Context,
+    data: &Data,
+    event: &MessageUpdateEvent,
+) -> Result<(), SlimeError> {
+    let Some(guild_id) = event.guild_id else {
+        return Ok(());
+    };
+    if entry(&data.pool, event.id).await?.is_none() {
+        return Ok(());
+    }
+    refresh(ctx, data, guild_id, event.channel_id, event.id).await
+}
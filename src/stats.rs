@@ -0,0 +1,290 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    time::Duration as StdDuration,
+};
+
+use chrono::{Duration, NaiveTime, Timelike, Utc};
+use poise::{serenity_prelude::*, CreateReply};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{counters::channels, localtime, replies::reply, Context, Data, SlimeError};
+
+/// How many days of activity are kept.
+const ACTIVITY_RETENTION_DAYS: i64 = 90;
+
+/// How often activity older than that is dropped.
+const EXPIRE_INTERVAL: StdDuration = StdDuration::from_secs(3600);
+
+/// How many of the busiest hours and top posters `/stats channel` shows.
+const TOP_COUNT: i64 = 5;
+
+/// How many of the most and least used emojis `/stats emojis` shows.
+const EMOJI_COUNT: usize = 10;
+
+/// Counts `message` towards its channel's activity, for `/stats channel`,
+/// and the custom emojis in it, for `/stats emojis`.
+pub async fn message_sent(data: &Data, message: &Message) -> Result<(), SlimeError> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    if message.author.bot {
+        return Ok(());
+    }
+    let sent_at = localtime::from_timestamp(message.timestamp);
+
+    sqlx::query(
+        "INSERT INTO channel_activity (guild_id, channel_id, day, hour, messages)
+        VALUES ($1, $2, $3, $4, 1)
+        ON CONFLICT (channel_id, day, hour) DO UPDATE
+        SET messages = channel_activity.messages + 1",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(message.channel_id))
+    .bind(sent_at.date_naive())
+    .bind(sent_at.hour() as i16)
+    .execute(&data.pool)
+    .await?;
+    sqlx::query(
+        "INSERT INTO channel_posters (guild_id, channel_id, day, user_id, messages)
+        VALUES ($1, $2, $3, $4, 1)
+        ON CONFLICT (channel_id, day, user_id) DO UPDATE
+        SET messages = channel_posters.messages + 1",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(message.channel_id))
+    .bind(sent_at.date_naive())
+    .bind(i64::from(message.author.id))
+    .execute(&data.pool)
+    .await?;
+
+    let emojis: HashSet<EmojiId> = message
+        .content
+        .split('<')
+        .skip(1)
+        .filter_map(|rest| {
+            let (mention, _) = rest.split_once('>')?;
+            utils::parse_emoji(format!("<{mention}>")).map(|emoji| emoji.id)
+        })
+        .collect();
+    for emoji_id in emojis {
+        count_emoji(&data.pool, guild_id, emoji_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Counts a use of a custom emoji, once per message or reaction. Emojis of
+/// other servers are counted too, `/stats emojis` only shows the guild's own.
+async fn count_emoji(pool: &PgPool, guild_id: GuildId, emoji_id: EmojiId) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO emoji_usage (guild_id, emoji_id, uses) VALUES ($1, $2, 1)
+        ON CONFLICT (guild_id, emoji_id) DO UPDATE
+        SET uses = emoji_usage.uses + 1, last_used_at = now()",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(emoji_id))
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Counts a custom emoji someone reacted with.
+pub async fn reaction_added(data: &Data, reaction: &Reaction) -> Result<(), SlimeError> {
+    let (Some(guild_id), ReactionType::Custom { id, .. }) = (reaction.guild_id, &reaction.emoji)
+    else {
+        return Ok(());
+    };
+    if reaction
+        .member
+        .as_ref()
+        .is_some_and(|member| member.user.bot)
+    {
+        return Ok(());
+    }
+    count_emoji(&data.pool, guild_id, *id).await?;
+
+    Ok(())
+}
+
+/// Drops activity older than `ACTIVITY_RETENTION_DAYS`, every
+/// `EXPIRE_INTERVAL`.
+pub async fn expire_activity(pool: PgPool) {
+    let mut interval = tokio::time::interval(EXPIRE_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let oldest = Utc::now().date_naive() - Duration::days(ACTIVITY_RETENTION_DAYS);
+        for table in ["channel_activity", "channel_posters"] {
+            if let Err(e) = sqlx::query(&format!("DELETE FROM {table} WHERE day < $1"))
+                .bind(oldest)
+                .execute(&pool)
+                .await
+            {
+                error!("failed to expire old {}: {}", table, e);
+            }
+        }
+    }
+}
+
+/// Shows how the server is used
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("channel", "emojis", "channels")
+)]
+pub async fn stats(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Shows how busy a channel was lately, and who posted the most there
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn channel(
+    ctx: Context<'_>,
+    #[description = "Channel to look at"] channel: GuildChannel,
+    #[description = "How many days back to look (default 7)"]
+    #[min = 1]
+    #[max = 90]
+    days: Option<i64>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let days = days.unwrap_or(7);
+    // Today counts as one of the days.
+    let since = Utc::now().date_naive() - Duration::days(days - 1);
+
+    let total: i64 = sqlx::query_scalar(
+        "SELECT coalesce(sum(messages), 0) FROM channel_activity
+        WHERE guild_id = $1 AND channel_id = $2 AND day >= $3",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(since)
+    .fetch_one(&data.pool)
+    .await?;
+    if total == 0 {
+        let content = format!(
+            "Nobody posted in {} in the last {days} days.",
+            channel.mention()
+        );
+        return reply(ctx, content).await;
+    }
+
+    let hours: Vec<(i16, i64)> = sqlx::query_as(
+        "SELECT hour, sum(messages) FROM channel_activity
+        WHERE guild_id = $1 AND channel_id = $2 AND day >= $3
+        GROUP BY hour ORDER BY sum(messages) DESC LIMIT $4",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(since)
+    .bind(TOP_COUNT)
+    .fetch_all(&data.pool)
+    .await?;
+    let posters: Vec<(i64, i64)> = sqlx::query_as(
+        "SELECT user_id, sum(messages) FROM channel_posters
+        WHERE guild_id = $1 AND channel_id = $2 AND day >= $3
+        GROUP BY user_id ORDER BY sum(messages) DESC LIMIT $4",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(since)
+    .bind(TOP_COUNT)
+    .fetch_all(&data.pool)
+    .await?;
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let hours = hours
+        .into_iter()
+        .map(|(hour, messages)| {
+            let hour = NaiveTime::from_hms_opt(hour as u32, 0, 0).unwrap_or_default();
+            format!(
+                "{}: {messages} messages",
+                localtime::format_time_of_day(hour, timezone)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let posters = posters
+        .into_iter()
+        .map(|(user_id, messages)| {
+            format!(
+                "{}: {messages} messages",
+                UserId::new(user_id as u64).mention()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let embed = CreateEmbed::new()
+        .title(format!("#{} in the last {days} days", channel.name))
+        .colour(Colour::BLURPLE)
+        .field(
+            "Messages",
+            format!("{total}, {} a day", total / days),
+            false,
+        )
+        .field("Busiest hours", hours, true)
+        .field("Top posters", posters, true)
+        .footer(CreateEmbedFooter::new(format!(
+            "Messages are counted for {ACTIVITY_RETENTION_DAYS} days, bots' aren't"
+        )));
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Shows the server's most and least used emojis, e.g. to pick which to
+/// remove
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn emojis(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let emojis = guild_id.emojis(ctx).await?;
+    if emojis.is_empty() {
+        return reply(ctx, "This server has no emojis of its own.".to_string()).await;
+    }
+
+    let usage: Vec<(i64, i64)> =
+        sqlx::query_as("SELECT emoji_id, uses FROM emoji_usage WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .fetch_all(&ctx.data().pool)
+            .await?;
+    let usage: HashMap<EmojiId, i64> = usage
+        .into_iter()
+        .map(|(emoji_id, uses)| (EmojiId::new(emoji_id as u64), uses))
+        .collect();
+    let mut emojis: Vec<(Emoji, i64)> = emojis
+        .into_iter()
+        .map(|emoji| {
+            let uses = usage.get(&emoji.id).copied().unwrap_or(0);
+            (emoji, uses)
+        })
+        .collect();
+    emojis.sort_by_key(|(_, uses)| Reverse(*uses));
+
+    let list = |emojis: &mut dyn Iterator<Item = &(Emoji, i64)>| {
+        emojis
+            .map(|(emoji, uses)| format!("{emoji} `:{}:` {uses} uses", emoji.name))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let most = list(&mut emojis.iter().take(EMOJI_COUNT));
+    let least = list(&mut emojis.iter().rev().take(EMOJI_COUNT));
+    let unused = emojis.iter().filter(|(_, uses)| *uses == 0).count();
+
+    let embed = CreateEmbed::new()
+        .title("Emoji usage")
+        .colour(Colour::BLURPLE)
+        .field("Most used", most, true)
+        .field("Least used", least, true)
+        .footer(CreateEmbedFooter::new(format!(
+            "{unused} of {} emojis were never used since counting started",
+            emojis.len()
+        )));
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
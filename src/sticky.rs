@@ -0,0 +1,211 @@
+use std::{sync::Arc, time::Duration as StdDuration};
+
+use dashmap::{DashMap, DashSet};
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{db, replies::reply, Context, Data, SlimeError};
+
+/// How long a sticky waits before it's reposted, so a busy channel gets it
+/// reposted once per burst rather than after every message.
+const REPOST_DELAY: StdDuration = StdDuration::from_secs(10);
+
+/// Which channels have a sticky, kept in memory because every message is
+/// checked against them.
+pub struct StickyCache {
+    pool: PgPool,
+    channels: DashMap<ChannelId, bool>,
+    /// Channels whose sticky is about to be reposted.
+    pending: DashSet<ChannelId>,
+}
+
+impl StickyCache {
+    pub fn new(pool: PgPool) -> Self {
+        StickyCache {
+            pool,
+            channels: DashMap::new(),
+            pending: DashSet::new(),
+        }
+    }
+
+    /// Drops what's cached about a channel, after its sticky changed.
+    fn forget(&self, channel_id: ChannelId) {
+        self.channels.remove(&channel_id);
+    }
+
+    async fn has_sticky(&self, channel_id: ChannelId) -> sqlx::Result<bool> {
+        if let Some(has_sticky) = self.channels.get(&channel_id) {
+            return Ok(*has_sticky);
+        }
+
+        let has_sticky: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sticky_messages WHERE channel_id = $1)",
+        )
+        .bind(i64::from(channel_id))
+        .fetch_one(&self.pool)
+        .await?;
+        self.channels.insert(channel_id, has_sticky);
+
+        Ok(has_sticky)
+    }
+}
+
+/// Deletes a copy of a sticky, unless someone beat the bot to it.
+async fn delete_copy(
+    http: &Http,
+    channel_id: ChannelId,
+    message_id: i64,
+) -> Result<(), SlimeError> {
+    match channel_id
+        .delete_message(http, MessageId::new(message_id as u64))
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Deletes the last copy of a channel's sticky and posts it again, so it's
+/// the newest message there. The sticky is posted without pinging anyone.
+async fn repost(http: &Http, pool: &PgPool, channel_id: ChannelId) -> Result<(), SlimeError> {
+    let sticky: Option<(String, Option<i64>)> =
+        sqlx::query_as("SELECT content, message_id FROM sticky_messages WHERE channel_id = $1")
+            .bind(i64::from(channel_id))
+            .fetch_optional(pool)
+            .await?;
+    let Some((content, message_id)) = sticky else {
+        return Ok(());
+    };
+
+    if let Some(message_id) = message_id {
+        delete_copy(http, channel_id, message_id).await?;
+    }
+    let message = CreateMessage::new()
+        .content(content)
+        .allowed_mentions(CreateAllowedMentions::new());
+    let message = channel_id.send_message(http, message).await?;
+    sqlx::query("UPDATE sticky_messages SET message_id = $2 WHERE channel_id = $1")
+        .bind(i64::from(channel_id))
+        .bind(i64::from(message.id))
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Moves the sticky of the channel `message` was sent in back to the
+/// bottom, after `REPOST_DELAY`. Messages of bots, the sticky itself among
+/// them, don't move it.
+pub async fn message_sent(
+    ctx: &serenity::Context,
+    data: &Data,
+    message: &Message,
+) -> Result<(), SlimeError> {
+    if message.guild_id.is_none() || message.author.bot {
+        return Ok(());
+    }
+    let channel_id = message.channel_id;
+    if !data.stickies.has_sticky(channel_id).await? {
+        return Ok(());
+    }
+    if !data.stickies.pending.insert(channel_id) {
+        return Ok(());
+    }
+
+    let http = Arc::clone(&ctx.http);
+    let stickies = Arc::clone(&data.stickies);
+    let pool = data.pool.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(REPOST_DELAY).await;
+        stickies.pending.remove(&channel_id);
+        if let Err(e) = repost(&http, &pool, channel_id).await {
+            error!("failed to repost the sticky in {}: {}", channel_id, e);
+        }
+    });
+
+    Ok(())
+}
+
+/// Manages messages kept at the bottom of a channel
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("set", "remove")
+)]
+pub async fn sticky(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Keeps a message at the bottom of a channel, replacing its sticky if it has one
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "Channel to keep the message in"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+    #[description = "What to keep at the bottom"]
+    #[max_length = 2000]
+    text: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    db::ensure_guild(&data.pool, guild_id).await?;
+    // The old copy is kept in message_id, so the first repost deletes it.
+    sqlx::query(
+        "INSERT INTO sticky_messages (guild_id, channel_id, content, created_by)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (channel_id) DO UPDATE
+        SET content = EXCLUDED.content, created_by = EXCLUDED.created_by",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(&text)
+    .bind(i64::from(ctx.author().id))
+    .execute(&data.pool)
+    .await?;
+    data.stickies.forget(channel.id);
+    repost(ctx.http(), &data.pool, channel.id).await?;
+
+    reply(
+        ctx,
+        format!(
+            "The sticky stays at the bottom of {} now.",
+            channel.mention()
+        ),
+    )
+    .await
+}
+
+/// Stops keeping a message at the bottom of a channel and deletes it
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "Channel whose sticky to remove"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let removed: Option<Option<i64>> = sqlx::query_scalar(
+        "DELETE FROM sticky_messages WHERE guild_id = $1 AND channel_id = $2
+        RETURNING message_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .fetch_optional(&data.pool)
+    .await?;
+    data.stickies.forget(channel.id);
+
+    let Some(message_id) = removed else {
+        let content = format!("{} has no sticky.", channel.mention());
+        return reply(ctx, content).await;
+    };
+    if let Some(message_id) = message_id {
+        delete_copy(ctx.http(), channel.id, message_id).await?;
+    }
+
+    reply(ctx, format!("{} has no sticky anymore.", channel.mention())).await
+}
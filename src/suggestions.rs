@@ -0,0 +1,361 @@
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::{db, replies::reply, settings::ChannelPurpose, Context, Data, SlimeError};
+
+/// What the custom id of every suggestion button starts with, followed by
+/// the suggestion and the vote, as in `pond-slime:suggestion:12:up`. It has
+/// to stay the same across restarts, since the buttons outlive the bot
+/// process.
+const SUGGESTION_BUTTON: &str = "pond-slime:suggestion";
+
+/// A suggestion from the `suggestions` table.
+#[derive(sqlx::FromRow)]
+struct Suggestion {
+    suggestion_id: i64,
+    channel_id: i64,
+    message_id: Option<i64>,
+    user_id: i64,
+    content: String,
+    status: String,
+    reason: Option<String>,
+    decided_by: Option<i64>,
+}
+
+impl Suggestion {
+    fn embed(&self, (up, down): (i64, i64)) -> CreateEmbed {
+        let mut embed = CreateEmbed::new()
+            .title(format!("Suggestion {}", self.suggestion_id))
+            .description(&self.content)
+            .field(
+                "Suggested by",
+                UserId::new(self.user_id as u64).mention().to_string(),
+                true,
+            );
+        let colour = match self.status.as_str() {
+            "approved" => Colour::DARK_GREEN,
+            "denied" => Colour::RED,
+            _ => return embed.colour(Colour::BLURPLE),
+        };
+        let decided_by = self
+            .decided_by
+            .map_or("a moderator".to_string(), |user_id| {
+                UserId::new(user_id as u64).mention().to_string()
+            });
+        embed = embed
+            .colour(colour)
+            .field("Votes", format!("👍 {up} 👎 {down}"), true)
+            .field(
+                format!("{} by", capitalize(&self.status)),
+                format!(
+                    "{decided_by}: {}",
+                    self.reason.as_deref().unwrap_or("no reason given")
+                ),
+                false,
+            );
+        embed
+    }
+
+    fn buttons(&self, (up, down): (i64, i64)) -> CreateActionRow {
+        let button = |vote: &str, label: String| {
+            CreateButton::new(format!("{SUGGESTION_BUTTON}:{}:{vote}", self.suggestion_id))
+                .label(label)
+                .style(ButtonStyle::Secondary)
+        };
+        CreateActionRow::Buttons(vec![
+            button("up", format!("👍 {up}")),
+            button("down", format!("👎 {down}")),
+        ])
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or(String::new(), |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+/// The suggestion and vote a suggestion button stands for, `true` for up.
+fn parse_button(custom_id: &str) -> Option<(i64, bool)> {
+    let rest = custom_id
+        .strip_prefix(SUGGESTION_BUTTON)?
+        .strip_prefix(':')?;
+    let (suggestion_id, vote) = rest.split_once(':')?;
+    let upvote = match vote {
+        "up" => true,
+        "down" => false,
+        _ => return None,
+    };
+    Some((suggestion_id.parse().ok()?, upvote))
+}
+
+async fn load(
+    pool: &PgPool,
+    guild_id: GuildId,
+    suggestion_id: i64,
+) -> sqlx::Result<Option<Suggestion>> {
+    sqlx::query_as(
+        "SELECT suggestion_id, channel_id, message_id, user_id, content, status, reason,
+        decided_by
+        FROM suggestions WHERE guild_id = $1 AND suggestion_id = $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(suggestion_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// How many votes for and against a suggestion got.
+async fn tally(pool: &PgPool, suggestion_id: i64) -> sqlx::Result<(i64, i64)> {
+    sqlx::query_as(
+        "SELECT count(*) FILTER (WHERE upvote), count(*) FILTER (WHERE NOT upvote)
+        FROM suggestion_votes WHERE suggestion_id = $1",
+    )
+    .bind(suggestion_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// Answers whoever pressed a suggestion button, only they see it.
+async fn respond(
+    ctx: &serenity::Context,
+    press: &ComponentInteraction,
+    content: &str,
+) -> Result<(), SlimeError> {
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    press
+        .create_response(ctx, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+/// Counts the vote of whoever pressed a suggestion button and shows the new
+/// counts on the buttons. Pressing the same button again takes the vote
+/// back. Other component interactions are left alone.
+pub async fn interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    interaction: &Interaction,
+) -> Result<(), SlimeError> {
+    let Interaction::Component(press) = interaction else {
+        return Ok(());
+    };
+    let Some((suggestion_id, upvote)) = parse_button(&press.data.custom_id) else {
+        return Ok(());
+    };
+    let Some(guild_id) = press.guild_id else {
+        return Ok(());
+    };
+
+    let Some(suggestion) = load(&data.pool, guild_id, suggestion_id).await? else {
+        return respond(ctx, press, "This suggestion doesn't exist anymore.").await;
+    };
+    if suggestion.status != "open" {
+        return respond(ctx, press, "Voting on this suggestion is over.").await;
+    }
+
+    let user_id = i64::from(press.user.id);
+    let taken_back = sqlx::query(
+        "DELETE FROM suggestion_votes WHERE suggestion_id = $1 AND user_id = $2 AND upvote = $3",
+    )
+    .bind(suggestion_id)
+    .bind(user_id)
+    .bind(upvote)
+    .execute(&data.pool)
+    .await?
+    .rows_affected();
+    if taken_back == 0 {
+        sqlx::query(
+            "INSERT INTO suggestion_votes (suggestion_id, guild_id, user_id, upvote)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (suggestion_id, user_id) DO UPDATE SET upvote = EXCLUDED.upvote",
+        )
+        .bind(suggestion_id)
+        .bind(i64::from(guild_id))
+        .bind(user_id)
+        .bind(upvote)
+        .execute(&data.pool)
+        .await?;
+    }
+
+    let votes = tally(&data.pool, suggestion_id).await?;
+    let message = CreateInteractionResponseMessage::new()
+        .embed(suggestion.embed(votes))
+        .components(vec![suggestion.buttons(votes)]);
+    press
+        .create_response(ctx, CreateInteractionResponse::UpdateMessage(message))
+        .await?;
+    Ok(())
+}
+
+/// Suggests something for the server, members vote on it
+#[poise::command(slash_command, guild_only)]
+pub async fn suggest(
+    ctx: Context<'_>,
+    #[description = "What you'd like to see"]
+    #[max_length = 2000]
+    text: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let settings = data.settings.get(guild_id).await?;
+    let Some(channel) = settings.channel(ChannelPurpose::Suggestions) else {
+        return reply(ctx, "This server doesn't take suggestions.".to_string()).await;
+    };
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let suggestion: Suggestion = sqlx::query_as(
+        "INSERT INTO suggestions (guild_id, channel_id, user_id, content) VALUES ($1, $2, $3, $4)
+        RETURNING suggestion_id, channel_id, message_id, user_id, content, status, reason,
+        decided_by",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel))
+    .bind(i64::from(ctx.author().id))
+    .bind(&text)
+    .fetch_one(&data.pool)
+    .await?;
+
+    let message = CreateMessage::new()
+        .embed(suggestion.embed((0, 0)))
+        .components(vec![suggestion.buttons((0, 0))]);
+    let message = match channel.send_message(ctx, message).await {
+        Ok(message) => message,
+        Err(e) => {
+            sqlx::query("DELETE FROM suggestions WHERE suggestion_id = $1")
+                .bind(suggestion.suggestion_id)
+                .execute(&data.pool)
+                .await?;
+            return Err(e.into());
+        }
+    };
+    sqlx::query("UPDATE suggestions SET message_id = $2 WHERE suggestion_id = $1")
+        .bind(suggestion.suggestion_id)
+        .bind(i64::from(message.id))
+        .execute(&data.pool)
+        .await?;
+
+    reply(
+        ctx,
+        format!(
+            "Suggestion `{}` is up in {}.",
+            suggestion.suggestion_id,
+            channel.mention()
+        ),
+    )
+    .await
+}
+
+/// Decides on a suggestion: shows the outcome on it, ends voting and tells
+/// whoever suggested it.
+async fn decide(
+    ctx: Context<'_>,
+    id: i64,
+    status: &str,
+    reason: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let decided: Option<Suggestion> = sqlx::query_as(
+        "UPDATE suggestions SET status = $3, reason = $4, decided_by = $5
+        WHERE guild_id = $1 AND suggestion_id = $2 AND status = 'open'
+        RETURNING suggestion_id, channel_id, message_id, user_id, content, status, reason,
+        decided_by",
+    )
+    .bind(i64::from(guild_id))
+    .bind(id)
+    .bind(status)
+    .bind(&reason)
+    .bind(i64::from(ctx.author().id))
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(suggestion) = decided else {
+        let content = match load(&data.pool, guild_id, id).await? {
+            Some(suggestion) => format!("Suggestion `{id}` was {} already.", suggestion.status),
+            None => format!("There is no suggestion `{id}`."),
+        };
+        return reply(ctx, content).await;
+    };
+
+    let votes = tally(&data.pool, id).await?;
+    if let Some(message_id) = suggestion.message_id {
+        let edit = EditMessage::new()
+            .embed(suggestion.embed(votes))
+            .components(vec![]);
+        let edited = ChannelId::new(suggestion.channel_id as u64)
+            .edit_message(ctx, MessageId::new(message_id as u64), edit)
+            .await;
+        if let Err(e) = edited {
+            warn!(
+                "failed to show the outcome of suggestion {} in guild {}: {}",
+                id, guild_id, e
+            );
+        }
+    }
+
+    let guild_name = guild_id
+        .to_partial_guild(ctx)
+        .await
+        .map_or("the server".to_string(), |guild| guild.name);
+    let dm = format!(
+        "Your suggestion in {guild_name} was {status}: {}\n{}",
+        suggestion.reason.as_deref().unwrap_or("no reason given"),
+        suggestion.content
+    );
+    let told = match UserId::new(suggestion.user_id as u64)
+        .create_dm_channel(ctx)
+        .await
+    {
+        Ok(channel) => channel
+            .send_message(ctx, CreateMessage::new().content(dm))
+            .await
+            .is_ok(),
+        Err(_) => false,
+    };
+
+    let content = if told {
+        format!("Suggestion `{id}` is {status}, and its author knows.")
+    } else {
+        format!("Suggestion `{id}` is {status}, but its author has their DMs closed.")
+    };
+    reply(ctx, content).await
+}
+
+/// Decides on suggestions members made
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("approve", "deny")
+)]
+pub async fn suggestion(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Approves a suggestion and tells whoever made it
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn approve(
+    ctx: Context<'_>,
+    #[description = "Suggestion to approve"] id: i64,
+    #[description = "Why, shown on the suggestion"]
+    #[max_length = 1000]
+    reason: Option<String>,
+) -> Result<(), SlimeError> {
+    decide(ctx, id, "approved", reason).await
+}
+
+/// Denies a suggestion and tells whoever made it
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn deny(
+    ctx: Context<'_>,
+    #[description = "Suggestion to deny"] id: i64,
+    #[description = "Why, shown on the suggestion"]
+    #[max_length = 1000]
+    reason: Option<String>,
+) -> Result<(), SlimeError> {
+    decide(ctx, id, "denied", reason).await
+}
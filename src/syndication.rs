@@ -0,0 +1,316 @@
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("that's not an RSS or Atom feed")]
+pub struct ParseFeedError;
+
+/// An RSS or Atom feed, with its items in the order the feed lists them,
+/// which is newest first for nearly every feed.
+pub struct Feed {
+    pub title: Option<String>,
+    pub items: Vec<Item>,
+}
+
+pub struct Item {
+    /// Stays the same for the item when the feed is fetched again: its guid
+    /// or id, or failing that its link or title.
+    pub id: String,
+    pub title: Option<String>,
+    pub link: Option<String>,
+    /// Often HTML, see `plain_text`.
+    pub summary: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct ItemBuilder {
+    id: Option<String>,
+    title: Option<String>,
+    link: Option<String>,
+    summary: Option<String>,
+    content: Option<String>,
+    published: Option<DateTime<Utc>>,
+    updated: Option<DateTime<Utc>>,
+}
+
+impl ItemBuilder {
+    fn set(&mut self, element: &str, text: String) {
+        let text = text.trim().to_string();
+        if text.is_empty() {
+            return;
+        }
+        let slot = match element {
+            "title" => &mut self.title,
+            "link" => &mut self.link,
+            "guid" | "id" => &mut self.id,
+            "description" | "summary" => &mut self.summary,
+            "content" | "content:encoded" => &mut self.content,
+            "pubDate" | "published" | "dc:date" => {
+                self.published = self.published.or(parse_date(&text));
+                return;
+            }
+            "updated" => {
+                self.updated = self.updated.or(parse_date(&text));
+                return;
+            }
+            _ => return,
+        };
+        slot.get_or_insert(text);
+    }
+
+    fn build(self) -> Option<Item> {
+        let id = self
+            .id
+            .clone()
+            .or_else(|| self.link.clone())
+            .or_else(|| self.title.clone())?;
+        Some(Item {
+            id,
+            title: self.title,
+            link: self.link,
+            summary: self.summary.or(self.content),
+            published: self.published.or(self.updated),
+        })
+    }
+}
+
+/// RSS dates are written as in emails, Atom ones as in RFC 3339.
+fn parse_date(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(text)
+        .or_else(|_| DateTime::parse_from_rfc3339(text))
+        .ok()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+/// The value of attribute `name` in the inside of a start tag.
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    while let Some(at) = rest.find(name) {
+        let before = rest[..at].chars().next_back();
+        let after = rest[at + name.len()..].trim_start();
+        rest = &rest[at + name.len()..];
+        if before.is_some_and(|c| !c.is_whitespace()) {
+            continue;
+        }
+        let Some(value) = after.strip_prefix('=').map(str::trim_start) else {
+            continue;
+        };
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let end = value[1..].find(quote)?;
+        return Some(decode(&value[1..=end]));
+    }
+    None
+}
+
+/// Replaces XML's character references and the five predefined entities,
+/// plus `&nbsp;` since HTML summaries are full of it. Anything else that
+/// looks like an entity is left as it is.
+fn decode(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        decoded.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[1..=end]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#')?.parse().ok(),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (entity, c) {
+            (Some(entity), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Parses an RSS 2.0, RSS 1.0 or Atom feed. It's lenient about broken XML,
+/// feeds are often hand-rolled, but doesn't check namespaces: elements are
+/// told apart by the name they're written with.
+pub fn parse(xml: &str) -> Result<Feed, ParseFeedError> {
+    let mut root = None;
+    let mut title: Option<String> = None;
+    let mut items = Vec::new();
+    // The elements that are open, and the item being read with how deep
+    // its element is.
+    let mut open: Vec<&str> = Vec::new();
+    let mut item: Option<(usize, ItemBuilder)> = None;
+    // How deep the element whose text is being read is, and its text so far.
+    let mut reading: Option<usize> = None;
+    let mut text = String::new();
+
+    let mut rest = xml;
+    while let Some(at) = rest.find('<') {
+        if reading.is_some() {
+            text.push_str(&decode(&rest[..at]));
+        }
+        rest = &rest[at..];
+
+        if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").unwrap_or(after.len());
+            if reading.is_some() {
+                text.push_str(&after[..end]);
+            }
+            rest = after.get(end + 3..).unwrap_or("");
+            continue;
+        }
+        let skipped = [("<!--", "-->"), ("<?", "?>"), ("<!", ">")]
+            .into_iter()
+            .find(|(start, _)| rest.starts_with(start));
+        if let Some((_, end)) = skipped {
+            rest = rest
+                .find(end)
+                .map_or("", |close| &rest[close + end.len()..]);
+            continue;
+        }
+
+        // Quoted attribute values can have a `>` in them.
+        let mut quote = None;
+        let close = rest.char_indices().skip(1).find_map(|(i, c)| {
+            match (quote, c) {
+                (None, '"' | '\'') => quote = Some(c),
+                (Some(q), _) if q == c => quote = None,
+                (None, '>') => return Some(i),
+                _ => {}
+            }
+            None
+        });
+        let Some(close) = close else {
+            break;
+        };
+        let tag = &rest[1..close];
+        rest = &rest[close + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            let Some(depth) = open.iter().rposition(|&element| element == name) else {
+                continue;
+            };
+            if reading == Some(depth) {
+                let read = std::mem::take(&mut text);
+                match &mut item {
+                    Some((_, builder)) => builder.set(name, read),
+                    None => title = Some(read.trim().to_string()).filter(|title| !title.is_empty()),
+                }
+            }
+            if reading.is_some_and(|reading| reading >= depth) {
+                reading = None;
+                text.clear();
+            }
+            if item
+                .as_ref()
+                .is_some_and(|(item_depth, _)| *item_depth == depth)
+            {
+                items.extend(item.take().and_then(|(_, builder)| builder.build()));
+            }
+            open.truncate(depth);
+            continue;
+        }
+
+        let empty = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let (name, attributes) = tag.split_at(name_end);
+        let root = *root.get_or_insert(name);
+        if !matches!(root, "rss" | "feed" | "rdf:RDF") {
+            return Err(ParseFeedError);
+        }
+
+        let depth = open.len();
+        let read = match &mut item {
+            None if name == "item" || name == "entry" => {
+                item = Some((depth, ItemBuilder::default()));
+                false
+            }
+            Some((item_depth, builder)) if depth == *item_depth + 1 => {
+                // An Atom link is in its attributes, not its text.
+                let alternate = attribute(attributes, "rel").is_none_or(|rel| rel == "alternate");
+                if name == "link" && alternate {
+                    if let Some(href) = attribute(attributes, "href") {
+                        builder.link.get_or_insert(href);
+                    }
+                }
+                true
+            }
+            // The feed's own title is a child of the root in Atom and of
+            // <channel> in RSS.
+            None => {
+                name == "title"
+                    && title.is_none()
+                    && ((depth == 1 && root == "feed") || (depth == 2 && open[1] == "channel"))
+            }
+            Some(_) => false,
+        };
+        if !empty {
+            if read && reading.is_none() {
+                reading = Some(depth);
+                text.clear();
+            }
+            open.push(name);
+        }
+    }
+
+    if root.is_none() {
+        return Err(ParseFeedError);
+    }
+    Ok(Feed { title, items })
+}
+
+/// The text of an HTML snippet, as feeds put in item summaries: tags are
+/// dropped, paragraphs and line breaks kept as new lines.
+pub fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(at) = rest.find('<') {
+        text.push_str(&rest[..at]);
+        let Some(close) = rest[at..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = rest[at + 1..at + close].trim().to_ascii_lowercase();
+        let name = tag.trim_start_matches('/').split([' ', '/']).next();
+        if matches!(
+            name,
+            Some("br" | "p" | "div" | "li" | "h1" | "h2" | "h3" | "h4")
+        ) {
+            text.push('\n');
+        }
+        rest = &rest[at + close + 1..];
+    }
+    text.push_str(rest);
+
+    let text = decode(&text);
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !line.is_empty() || lines.last().is_some_and(|last| !last.is_empty()) {
+            lines.push(line);
+        }
+    }
+    lines.join("\n").trim().to_string()
+}
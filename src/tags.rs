@@ -0,0 +1,172 @@
+use poise::{serenity_prelude::*, CreateReply};
+
+use crate::{db, replies::reply, Context, SlimeError};
+
+/// How many tags a guild can have.
+const MAX_TAGS: i64 = 100;
+
+/// How many names the autocomplete of `/tag` suggests.
+const SUGGESTIONS: i64 = 25;
+
+/// Tag names are matched without regard to case or surrounding spaces.
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+async fn autocomplete_tag(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Vec::new();
+    };
+    sqlx::query_scalar(
+        "SELECT name FROM tags WHERE guild_id = $1 AND strpos(name, $2) > 0
+        ORDER BY name LIMIT $3",
+    )
+    .bind(i64::from(guild_id))
+    .bind(normalize(partial))
+    .bind(SUGGESTIONS)
+    .fetch_all(&ctx.data().pool)
+    .await
+    .unwrap_or_default()
+}
+
+/// Posts saved answers to common questions
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("create", "edit", "delete", "show")
+)]
+pub async fn tag(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Saves a new answer under a name
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "Name to post it by"]
+    #[max_length = 32]
+    name: String,
+    #[description = "What to post"]
+    #[max_length = 2000]
+    text: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let name = normalize(&name);
+    if name.is_empty() {
+        return reply(ctx, "Tags need a name.".to_string()).await;
+    }
+
+    let tags: i64 = sqlx::query_scalar("SELECT count(*) FROM tags WHERE guild_id = $1")
+        .bind(i64::from(guild_id))
+        .fetch_one(&data.pool)
+        .await?;
+    if tags >= MAX_TAGS {
+        let content = format!("There are {MAX_TAGS} tags already, delete one first.");
+        return reply(ctx, content).await;
+    }
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let created = sqlx::query(
+        "INSERT INTO tags (guild_id, name, content, created_by) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (guild_id, name) DO NOTHING",
+    )
+    .bind(i64::from(guild_id))
+    .bind(&name)
+    .bind(&text)
+    .bind(i64::from(ctx.author().id))
+    .execute(&data.pool)
+    .await?
+    .rows_affected();
+
+    let content = if created > 0 {
+        format!("Tag `{name}` is saved, post it with `/tag show`.")
+    } else {
+        format!("There is a tag `{name}` already, change it with `/tag edit`.")
+    };
+    reply(ctx, content).await
+}
+
+/// Changes what a tag posts
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn edit(
+    ctx: Context<'_>,
+    #[description = "Tag to change"]
+    #[autocomplete = "autocomplete_tag"]
+    name: String,
+    #[description = "What to post instead"]
+    #[max_length = 2000]
+    text: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = normalize(&name);
+    let edited = sqlx::query(
+        "UPDATE tags SET content = $3, updated_at = now() WHERE guild_id = $1 AND name = $2",
+    )
+    .bind(i64::from(guild_id))
+    .bind(&name)
+    .bind(&text)
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    let content = if edited > 0 {
+        format!("Tag `{name}` is changed.")
+    } else {
+        format!("There is no tag `{name}`.")
+    };
+    reply(ctx, content).await
+}
+
+/// Deletes a tag
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "Tag to delete"]
+    #[autocomplete = "autocomplete_tag"]
+    name: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = normalize(&name);
+    let removed = sqlx::query("DELETE FROM tags WHERE guild_id = $1 AND name = $2")
+        .bind(i64::from(guild_id))
+        .bind(&name)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Tag `{name}` is deleted.")
+    } else {
+        format!("There is no tag `{name}`.")
+    };
+    reply(ctx, content).await
+}
+
+/// Posts a saved answer in this channel
+#[poise::command(slash_command, guild_only)]
+async fn show(
+    ctx: Context<'_>,
+    #[description = "Tag to post"]
+    #[autocomplete = "autocomplete_tag"]
+    name: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let name = normalize(&name);
+    let content: Option<String> =
+        sqlx::query_scalar("SELECT content FROM tags WHERE guild_id = $1 AND name = $2")
+            .bind(i64::from(guild_id))
+            .bind(&name)
+            .fetch_optional(&ctx.data().pool)
+            .await?;
+    let Some(content) = content else {
+        return reply(ctx, format!("There is no tag `{name}`.")).await;
+    };
+
+    // Tags are posted by whoever asks for them, so they mustn't ping.
+    let reply = CreateReply::default()
+        .content(content)
+        .allowed_mentions(CreateAllowedMentions::new());
+    ctx.send(reply).await?;
+    Ok(())
+}
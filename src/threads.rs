@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{audit, db, duration::HumanDuration, replies::reply, Context, Data, SlimeError};
+
+/// How often the threads of a channel with a policy are checked for ones
+/// to close, at most. Listing a guild's threads is one request however
+/// many there are, but there's no point doing it every scheduler tick.
+const CHECK_MINUTES: f64 = 10.0;
+
+/// How old a thread can be when the bot hears of it for it to count as
+/// new. The bot also hears of old private threads it's added to.
+const NEW_THREAD_SECONDS: i64 = 60;
+
+/// The longest a policy can let threads sit idle before closing them.
+const MAX_CLOSE_AFTER_DAYS: i64 = 90;
+
+/// How many policies one page of `/threads list` shows.
+const PAGE_LEN: usize = 10;
+
+/// How long a new thread goes without messages before Discord archives it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum AutoArchive {
+    #[name = "1 hour"]
+    OneHour,
+    #[name = "1 day"]
+    OneDay,
+    #[name = "3 days"]
+    ThreeDays,
+    #[name = "1 week"]
+    OneWeek,
+}
+
+impl AutoArchive {
+    fn minutes(self) -> i32 {
+        match self {
+            AutoArchive::OneHour => 60,
+            AutoArchive::OneDay => 1440,
+            AutoArchive::ThreeDays => 4320,
+            AutoArchive::OneWeek => 10080,
+        }
+    }
+}
+
+/// How a channel's threads are managed, from the `thread_policies` table.
+#[derive(sqlx::FromRow)]
+struct ThreadPolicy {
+    guild_id: i64,
+    channel_id: i64,
+    auto_archive_minutes: Option<i32>,
+    close_after_seconds: Option<i64>,
+    join_role_id: Option<i64>,
+}
+
+impl ThreadPolicy {
+    fn describe(&self) -> String {
+        let mut rules = Vec::new();
+        if let Some(minutes) = self.auto_archive_minutes {
+            let after = HumanDuration(Duration::minutes(i64::from(minutes)));
+            rules.push(format!("new threads archive after {after} idle"));
+        }
+        if let Some(seconds) = self.close_after_seconds {
+            let after = HumanDuration(Duration::seconds(seconds));
+            rules.push(format!("threads are closed after {after} idle"));
+        }
+        if let Some(role_id) = self.join_role_id {
+            let role = RoleId::new(role_id as u64).mention();
+            rules.push(format!("{role} is added to new threads"));
+        }
+        if rules.is_empty() {
+            return "nothing".to_string();
+        }
+        rules.join(", ")
+    }
+}
+
+async fn load_policy(pool: &PgPool, channel_id: ChannelId) -> sqlx::Result<Option<ThreadPolicy>> {
+    sqlx::query_as(
+        "SELECT guild_id, channel_id, auto_archive_minutes, close_after_seconds, join_role_id
+        FROM thread_policies WHERE channel_id = $1",
+    )
+    .bind(i64::from(channel_id))
+    .fetch_optional(pool)
+    .await
+}
+
+/// Applies its channel's policy to a new thread: sets how soon it archives
+/// and adds the policy's role to it.
+pub async fn thread_created(
+    ctx: &serenity::Context,
+    data: &Data,
+    thread: &GuildChannel,
+) -> Result<(), SlimeError> {
+    let Some(parent_id) = thread.parent_id else {
+        return Ok(());
+    };
+    let age = Utc::now().timestamp() - thread.id.created_at().unix_timestamp();
+    if age > NEW_THREAD_SECONDS {
+        return Ok(());
+    }
+    let Some(policy) = load_policy(&data.pool, parent_id).await? else {
+        return Ok(());
+    };
+
+    if let Some(minutes) = policy.auto_archive_minutes {
+        let edit = EditThread::new()
+            .auto_archive_duration(AutoArchiveDuration::from(minutes as u16))
+            .audit_log_reason("Thread policy");
+        if let Err(e) = thread.id.edit_thread(ctx, edit).await {
+            warn!(
+                "failed to set how soon thread {} archives in guild {}: {}",
+                thread.id, thread.guild_id, e
+            );
+        }
+    }
+
+    if let Some(role_id) = policy.join_role_id {
+        // Mentioning a role adds its members to the thread. Editing the
+        // mention in rather than sending it does so without pinging them.
+        let role_id = RoleId::new(role_id as u64);
+        let added = async {
+            let mut message = thread
+                .id
+                .send_message(ctx, CreateMessage::new().content("Adding members…"))
+                .await?;
+            let edit = EditMessage::new()
+                .content(role_id.mention().to_string())
+                .allowed_mentions(CreateAllowedMentions::new().roles([role_id]));
+            message.edit(ctx, edit).await?;
+            message.delete(ctx).await
+        };
+        if let Err(e) = added.await {
+            warn!(
+                "failed to add role {} to thread {} in guild {}: {}",
+                role_id, thread.id, thread.guild_id, e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Closes the threads that sat idle for longer than their channel's policy
+/// allows, the scheduler calls this. Each channel is checked every
+/// `CHECK_MINUTES` at most.
+pub async fn close_idle(http: &Http, data: &Data) {
+    let due: Vec<ThreadPolicy> = match sqlx::query_as(
+        "UPDATE thread_policies SET checked_at = now()
+        WHERE close_after_seconds IS NOT NULL
+        AND checked_at <= now() - make_interval(mins => $1)
+        RETURNING guild_id, channel_id, auto_archive_minutes, close_after_seconds, join_role_id",
+    )
+    .bind(CHECK_MINUTES)
+    .fetch_all(&data.pool)
+    .await
+    {
+        Ok(due) => due,
+        Err(e) => {
+            error!("failed to load due thread policies: {}", e);
+            return;
+        }
+    };
+
+    let mut guilds: HashMap<i64, HashMap<ChannelId, Duration>> = HashMap::new();
+    for policy in due {
+        let Some(seconds) = policy.close_after_seconds else {
+            continue;
+        };
+        guilds.entry(policy.guild_id).or_default().insert(
+            ChannelId::new(policy.channel_id as u64),
+            Duration::seconds(seconds),
+        );
+    }
+    for (guild_id, channels) in guilds {
+        let guild_id = GuildId::new(guild_id as u64);
+        if let Err(e) = close_idle_in(http, guild_id, &channels).await {
+            error!("failed to close idle threads in guild {}: {}", guild_id, e);
+        }
+    }
+}
+
+async fn close_idle_in(
+    http: &Http,
+    guild_id: GuildId,
+    channels: &HashMap<ChannelId, Duration>,
+) -> Result<(), SlimeError> {
+    let now = Utc::now().timestamp();
+    let threads = guild_id.get_active_threads(http).await?.threads;
+    for thread in threads {
+        let Some(&close_after) = thread.parent_id.and_then(|parent| channels.get(&parent)) else {
+            continue;
+        };
+        let last_active = thread
+            .last_message_id
+            .map_or(thread.id.created_at(), |message_id| message_id.created_at());
+        if now - last_active.unix_timestamp() < close_after.num_seconds() {
+            continue;
+        }
+
+        let close = EditThread::new()
+            .archived(true)
+            .audit_log_reason("Idle thread");
+        if let Err(e) = thread.id.edit_thread(http, close).await {
+            warn!(
+                "failed to close idle thread {} in guild {}: {}",
+                thread.id, guild_id, e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Manages the threads of channels
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("policy", "clear", "list")
+)]
+pub async fn threads(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Changes how the threads of a channel are managed, or shows it
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn policy(
+    ctx: Context<'_>,
+    #[description = "Channel whose threads to manage"]
+    #[channel_types("Text", "Forum")]
+    channel: GuildChannel,
+    #[description = "How long new threads go idle before Discord archives them"]
+    auto_archive: Option<AutoArchive>,
+    #[description = "Close threads idle for this long, e.g. 3d"] auto_close_after: Option<
+        HumanDuration,
+    >,
+    #[description = "Role whose members are added to new threads"] auto_join_role: Option<Role>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    if auto_archive.is_none() && auto_close_after.is_none() && auto_join_role.is_none() {
+        let content = match load_policy(&data.pool, channel.id).await? {
+            Some(policy) => format!("In {}, {}.", channel.mention(), policy.describe()),
+            None => format!("{} has no thread policy.", channel.mention()),
+        };
+        return reply(ctx, content).await;
+    }
+    let close_after = auto_close_after.map(|after| after.as_duration());
+    if close_after.is_some_and(|after| {
+        after < Duration::hours(1) || after > Duration::days(MAX_CLOSE_AFTER_DAYS)
+    }) {
+        let content = format!("Threads can be closed after 1h to {MAX_CLOSE_AFTER_DAYS}d idle.");
+        return reply(ctx, content).await;
+    }
+
+    // Options left out keep what they were.
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let policy: ThreadPolicy = sqlx::query_as(
+        "INSERT INTO thread_policies
+        (channel_id, guild_id, auto_archive_minutes, close_after_seconds, join_role_id)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (channel_id) DO UPDATE SET
+        auto_archive_minutes = coalesce(EXCLUDED.auto_archive_minutes,
+            thread_policies.auto_archive_minutes),
+        close_after_seconds = coalesce(EXCLUDED.close_after_seconds,
+            thread_policies.close_after_seconds),
+        join_role_id = coalesce(EXCLUDED.join_role_id, thread_policies.join_role_id)
+        RETURNING guild_id, channel_id, auto_archive_minutes, close_after_seconds, join_role_id",
+    )
+    .bind(i64::from(channel.id))
+    .bind(i64::from(guild_id))
+    .bind(auto_archive.map(AutoArchive::minutes))
+    .bind(close_after.map(|after| after.num_seconds()))
+    .bind(auto_join_role.map(|role| i64::from(role.id)))
+    .fetch_one(&data.pool)
+    .await?;
+
+    let content = format!("In {} now, {}.", channel.mention(), policy.describe());
+    reply(ctx, content).await
+}
+
+/// Stops managing the threads of a channel
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn clear(
+    ctx: Context<'_>,
+    #[description = "Channel whose threads to leave alone"] channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let removed = sqlx::query("DELETE FROM thread_policies WHERE channel_id = $1")
+        .bind(i64::from(channel.id))
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("The threads of {} are left alone now.", channel.mention())
+    } else {
+        format!("{} has no thread policy.", channel.mention())
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the channels whose threads are managed
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let policies: Vec<ThreadPolicy> = sqlx::query_as(
+        "SELECT guild_id, channel_id, auto_archive_minutes, close_after_seconds, join_role_id
+        FROM thread_policies WHERE guild_id = $1 ORDER BY channel_id",
+    )
+    .bind(i64::from(ctx.guild_id().unwrap()))
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if policies.is_empty() {
+        return reply(ctx, "No channel has a thread policy.".to_string()).await;
+    }
+
+    let lines = policies.iter().map(|policy| {
+        format!(
+            "- {}: {}",
+            ChannelId::new(policy.channel_id as u64).mention(),
+            policy.describe()
+        )
+    });
+    audit::paginate(ctx, "Thread policies", &audit::pages(lines, PAGE_LEN)).await
+}
@@ -0,0 +1,274 @@
+use futures::TryStreamExt;
+use poise::serenity_prelude::{self as serenity, *};
+use tracing::{error, warn};
+
+use crate::{db, localtime, replies::reply, settings::ChannelPurpose, Context, Data, SlimeError};
+
+/// The custom id of every "Open ticket" button. It has to stay the same
+/// across restarts, since the buttons outlive the bot process.
+const TICKET_BUTTON: &str = "pond-slime:ticket";
+
+const DEFAULT_PROMPT: &str = "Need help from the staff? Press the button below to open a \
+private ticket only you and they can see.";
+
+/// Answers whoever pressed a ticket button, only they see it.
+async fn respond(
+    ctx: &serenity::Context,
+    press: &ComponentInteraction,
+    content: &str,
+) -> Result<(), SlimeError> {
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    press
+        .create_response(ctx, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+/// Opens a private thread next to the button for whoever pressed it, and
+/// pings the moderator roles into it. Discord only adds members of a role
+/// to the thread if the bot may mention that role. Members have one open
+/// ticket at a time.
+pub async fn interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    interaction: &Interaction,
+) -> Result<(), SlimeError> {
+    let Interaction::Component(press) = interaction else {
+        return Ok(());
+    };
+    if press.data.custom_id != TICKET_BUTTON {
+        return Ok(());
+    }
+    let Some(guild_id) = press.guild_id else {
+        return Ok(());
+    };
+    let user = &press.user;
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    // Claiming the ticket first means nobody gets two threads by pressing
+    // the button twice.
+    let ticket_id: Option<i64> = sqlx::query_scalar(
+        "INSERT INTO tickets (guild_id, channel_id, user_id) VALUES ($1, $2, $3)
+        ON CONFLICT (guild_id, user_id) WHERE closed_at IS NULL DO NOTHING
+        RETURNING ticket_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(press.channel_id))
+    .bind(i64::from(user.id))
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(ticket_id) = ticket_id else {
+        let thread_id: Option<Option<i64>> = sqlx::query_scalar(
+            "SELECT thread_id FROM tickets
+            WHERE guild_id = $1 AND user_id = $2 AND closed_at IS NULL",
+        )
+        .bind(i64::from(guild_id))
+        .bind(i64::from(user.id))
+        .fetch_optional(&data.pool)
+        .await?;
+        let content = match thread_id.flatten() {
+            Some(thread_id) => format!(
+                "You have a ticket open already: {}",
+                ChannelId::new(thread_id as u64).mention()
+            ),
+            None => "Your ticket is being opened.".to_string(),
+        };
+        return respond(ctx, press, &content).await;
+    };
+    press.defer_ephemeral(ctx).await?;
+
+    let thread = CreateThread::new(format!("Ticket {ticket_id} ({})", user.name))
+        .kind(ChannelType::PrivateThread)
+        .invitable(false)
+        .audit_log_reason("Ticket");
+    let opened = match press.channel_id.create_thread(ctx, thread).await {
+        Ok(thread) => thread
+            .id
+            .add_thread_member(ctx, user.id)
+            .await
+            .map(|()| thread),
+        Err(e) => Err(e),
+    };
+    let thread = match opened {
+        Ok(thread) => thread,
+        Err(e) => {
+            error!(
+                "failed to open ticket {} in guild {}: {}",
+                ticket_id, guild_id, e
+            );
+            sqlx::query("DELETE FROM tickets WHERE ticket_id = $1")
+                .bind(ticket_id)
+                .execute(&data.pool)
+                .await?;
+            let content = "I couldn't open a ticket, please tell a moderator.";
+            press
+                .edit_response(ctx, EditInteractionResponse::new().content(content))
+                .await?;
+            return Ok(());
+        }
+    };
+    sqlx::query("UPDATE tickets SET thread_id = $2 WHERE ticket_id = $1")
+        .bind(ticket_id)
+        .bind(i64::from(thread.id))
+        .execute(&data.pool)
+        .await?;
+
+    let settings = data.settings.get(guild_id).await?;
+    let staff: Vec<_> = settings
+        .mod_roles
+        .iter()
+        .map(|role_id| role_id.mention().to_string())
+        .collect();
+    let greeting = CreateMessage::new()
+        .content(format!(
+            "{} opened this ticket. {}\nStaff close it with `/ticket close` once it's dealt with.",
+            user.mention(),
+            staff.join(" ")
+        ))
+        .allowed_mentions(
+            CreateAllowedMentions::new()
+                .users([user.id])
+                .roles(settings.mod_roles.iter().copied()),
+        );
+    if let Err(e) = thread.id.send_message(ctx, greeting).await {
+        warn!(
+            "failed to greet ticket {} in guild {}: {}",
+            ticket_id, guild_id, e
+        );
+    }
+
+    let content = format!("Your ticket is open: {}", thread.mention());
+    press
+        .edit_response(ctx, EditInteractionResponse::new().content(content))
+        .await?;
+    Ok(())
+}
+
+/// Everything said in a ticket thread, oldest first, as plain text.
+async fn transcript(
+    ctx: Context<'_>,
+    thread_id: ChannelId,
+    timezone: Option<chrono::FixedOffset>,
+) -> Result<String, SlimeError> {
+    let mut messages: Vec<Message> = thread_id.messages_iter(ctx).try_collect().await?;
+    messages.reverse();
+
+    let mut transcript = String::new();
+    for message in messages {
+        let sent_at =
+            localtime::format_datetime(localtime::from_timestamp(message.timestamp), timezone);
+        transcript.push_str(&format!(
+            "[{sent_at}] {}: {}\n",
+            message.author.tag(),
+            message.content
+        ));
+        for attachment in &message.attachments {
+            transcript.push_str(&format!("    attachment: {}\n", attachment.url));
+        }
+    }
+    Ok(transcript)
+}
+
+/// Manages private support threads between members and staff
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("setup", "close")
+)]
+pub async fn ticket(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Posts a button members press to open a ticket
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn setup(
+    ctx: Context<'_>,
+    #[description = "Channel to post the button in, tickets are opened there"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+    #[description = "What the message above the button says"]
+    #[max_length = 2000]
+    prompt: Option<String>,
+) -> Result<(), SlimeError> {
+    let button = CreateButton::new(TICKET_BUTTON)
+        .label("Open ticket")
+        .style(ButtonStyle::Primary);
+    let message = CreateMessage::new()
+        .content(prompt.as_deref().unwrap_or(DEFAULT_PROMPT))
+        .components(vec![CreateActionRow::Buttons(vec![button])]);
+    channel.id.send_message(ctx, message).await?;
+
+    reply(
+        ctx,
+        format!("Members can open tickets in {} now.", channel.mention()),
+    )
+    .await
+}
+
+/// Closes the ticket this is run in, archiving it and logging a transcript
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn close(
+    ctx: Context<'_>,
+    #[description = "Why, shown in the log"]
+    #[max_length = 1000]
+    reason: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let thread_id = ctx.channel_id();
+    let closed: Option<(i64, i64)> = sqlx::query_as(
+        "UPDATE tickets SET closed_at = now(), closed_by = $3
+        WHERE guild_id = $1 AND thread_id = $2 AND closed_at IS NULL
+        RETURNING ticket_id, user_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(thread_id))
+    .bind(i64::from(ctx.author().id))
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some((ticket_id, user_id)) = closed else {
+        return reply(ctx, "This isn't an open ticket.".to_string()).await;
+    };
+    // Fetching the whole thread can take longer than Discord waits.
+    ctx.defer().await?;
+
+    let settings = data.settings.get(guild_id).await?;
+    if let Some(log_channel) = settings.channel(ChannelPurpose::Logs) {
+        let transcript = transcript(ctx, thread_id, settings.timezone).await?;
+        let embed = CreateEmbed::new()
+            .title(format!("Ticket {ticket_id} closed"))
+            .colour(Colour::DARK_GREY)
+            .field(
+                "Opened by",
+                UserId::new(user_id as u64).mention().to_string(),
+                true,
+            )
+            .field("Closed by", ctx.author().mention().to_string(), true)
+            .field("Thread", thread_id.mention().to_string(), true)
+            .field(
+                "Reason",
+                reason.as_deref().unwrap_or("no reason given"),
+                false,
+            );
+        let file = CreateAttachment::bytes(transcript, format!("ticket-{ticket_id}.txt"));
+        let log = CreateMessage::new().embed(embed).add_file(file);
+        if let Err(e) = log_channel.send_message(ctx, log).await {
+            error!(
+                "failed to log the transcript of ticket {} in guild {}: {}",
+                ticket_id, guild_id, e
+            );
+        }
+    }
+
+    ctx.say(format!("{} closed this ticket.", ctx.author().mention()))
+        .await?;
+    let archive = EditThread::new()
+        .archived(true)
+        .locked(true)
+        .audit_log_reason("Ticket closed");
+    thread_id.edit_thread(ctx, archive).await?;
+    Ok(())
+}
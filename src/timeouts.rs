@@ -0,0 +1,158 @@
+use std::{sync::Arc, time::Duration as StdDuration};
+
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+use tracing::error;
+
+use crate::{
+    cases::{self, Action, NewCase},
+    duration::HumanDuration,
+    hierarchy::target_problem,
+    localtime,
+    replies::reply,
+    Context, Data, SlimeError,
+};
+
+/// Discord refuses to time anyone out for longer than this.
+pub const MAX_TIMEOUT_DAYS: i64 = 28;
+
+/// How often the background task looks for timeouts that ran out.
+const EXPIRY_INTERVAL: StdDuration = StdDuration::from_secs(60);
+
+/// A timeout that ran out, as taken from the `timeouts` table.
+#[derive(sqlx::FromRow)]
+struct ExpiredTimeout {
+    guild_id: i64,
+    user_id: i64,
+    case_id: i64,
+}
+
+async fn take_expired(pool: &PgPool) -> sqlx::Result<Vec<ExpiredTimeout>> {
+    sqlx::query_as(
+        "DELETE FROM timeouts WHERE expires_at <= now() RETURNING guild_id, user_id, case_id",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Announces in each guild's log channel when a timeout is over. Discord
+/// lifts the timeout by itself, this only tells the moderators.
+pub async fn announce_expired(http: Arc<Http>, data: Data) {
+    let mut interval = tokio::time::interval(EXPIRY_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let expired = match take_expired(&data.pool).await {
+            Ok(expired) => expired,
+            Err(e) => {
+                error!("failed to load expired timeouts: {}", e);
+                continue;
+            }
+        };
+
+        for timeout in expired {
+            let content = format!(
+                "The timeout of {} from case {} is over.",
+                UserId::new(timeout.user_id as u64).mention(),
+                timeout.case_id
+            );
+            let guild_id = GuildId::new(timeout.guild_id as u64);
+            cases::announce(&http, &data, guild_id, &content).await;
+        }
+    }
+}
+
+/// Times `user_id` out for `duration`, at most `MAX_TIMEOUT_DAYS`, and
+/// files a case for it. `moderator_id` is `None` for automod. Returns the
+/// case number and when the timeout ends.
+pub async fn apply(
+    http: &Http,
+    pool: &PgPool,
+    guild_id: GuildId,
+    user_id: UserId,
+    duration: Duration,
+    moderator_id: Option<UserId>,
+    reason: &str,
+) -> Result<(i64, DateTime<Utc>), SlimeError> {
+    let expires_at: DateTime<Utc> = Utc::now() + duration;
+    let until = Timestamp::from_unix_timestamp(expires_at.timestamp())
+        .expect("at most 28 days from now is a valid timestamp");
+    guild_id
+        .edit_member(
+            http,
+            user_id,
+            EditMember::new()
+                .disable_communication_until_datetime(until)
+                .audit_log_reason(reason),
+        )
+        .await?;
+
+    let case = NewCase {
+        action: Action::Timeout,
+        user_id: Some(user_id),
+        channel_id: None,
+        moderator_id,
+        reason: Some(reason),
+        details: Some(format!("For {}", HumanDuration(duration))),
+    };
+    let case_id = cases::file(pool, guild_id, case).await?;
+    sqlx::query(
+        "INSERT INTO timeouts (guild_id, user_id, case_id, expires_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (guild_id, user_id)
+        DO UPDATE SET case_id = EXCLUDED.case_id, expires_at = EXCLUDED.expires_at",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user_id))
+    .bind(case_id)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    Ok((case_id, expires_at))
+}
+
+/// Keeps a member from chatting for a while
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn timeout(
+    ctx: Context<'_>,
+    #[description = "Member to time out"] user: User,
+    #[description = "How long, e.g. 10m, 2h or 1d (at most 28d)"] duration: HumanDuration,
+    #[description = "Why they are timed out"]
+    #[max_length = 1000]
+    reason: String,
+) -> Result<(), SlimeError> {
+    let duration = duration.as_duration();
+    if duration <= Duration::zero() || duration > Duration::days(MAX_TIMEOUT_DAYS) {
+        let content = format!("A timeout has to last between 1s and {MAX_TIMEOUT_DAYS}d.");
+        return reply(ctx, content).await;
+    }
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let guild = guild_id.to_partial_guild(ctx).await?;
+    if let Some(problem) = target_problem(ctx, &guild, user.id, true).await? {
+        return reply(ctx, problem).await;
+    }
+
+    let (case_id, expires_at) = apply(
+        ctx.http(),
+        &data.pool,
+        guild_id,
+        user.id,
+        duration,
+        Some(ctx.author().id),
+        &reason,
+    )
+    .await?;
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let content = format!(
+        "Case {case_id}: {} timed out {} until {}: {reason}",
+        ctx.author().mention(),
+        user.mention(),
+        localtime::format_datetime(expires_at, timezone)
+    );
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
@@ -0,0 +1,200 @@
+use chrono::{DateTime, FixedOffset, Utc};
+use poise::serenity_prelude::*;
+
+use crate::{audit, db, localtime, replies::reply, schedule::RunAt, Context, SlimeError};
+
+/// How many todos a member can have in a guild at once, done ones included.
+const MAX_TODOS: i64 = 100;
+
+/// How many todos one page of `/todo list` shows.
+const PAGE_LEN: usize = 10;
+
+/// A task from the `todos` table.
+#[derive(sqlx::FromRow)]
+struct Todo {
+    todo_id: i64,
+    content: String,
+    due_at: Option<DateTime<Utc>>,
+    done_at: Option<DateTime<Utc>>,
+}
+
+impl Todo {
+    fn line(&self, timezone: Option<FixedOffset>) -> String {
+        let due = self.due_at.map_or(String::new(), |due_at| {
+            format!(" (due {})", localtime::format_datetime(due_at, timezone))
+        });
+        if self.done_at.is_some() {
+            format!("`{}` ~~{}~~{due}", self.todo_id, self.content)
+        } else {
+            format!("`{}` {}{due}", self.todo_id, self.content)
+        }
+    }
+}
+
+/// Keeps a list of things you have to do
+#[poise::command(slash_command, guild_only, subcommands("add", "list", "done", "clear"))]
+pub async fn todo(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Adds a task to your list, with a reminder if it's due at some point
+#[poise::command(slash_command, guild_only)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "What you have to do"]
+    #[max_length = 500]
+    task: String,
+    #[description = "When it's due, e.g. 2h, 18:00 or 2024-05-01 09:00"] due: Option<RunAt>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let now = Utc::now();
+    let due_at = due.map(|due| due.resolve(timezone, now));
+    if due_at.is_some_and(|due_at| due_at <= now) {
+        return reply(ctx, "That time has already passed.".to_string()).await;
+    }
+
+    let todos: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM todos WHERE guild_id = $1 AND user_id = $2")
+            .bind(i64::from(guild_id))
+            .bind(i64::from(ctx.author().id))
+            .fetch_one(&data.pool)
+            .await?;
+    if todos >= MAX_TODOS {
+        let content =
+            format!("You have {MAX_TODOS} todos already, `/todo clear` the done ones first.");
+        return reply(ctx, content).await;
+    }
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let mut transaction = data.pool.begin().await?;
+    // The reminder scheduler sends the reminder, so it's sent even if the
+    // bot restarts in between.
+    let reminder_id: Option<i64> = match due_at {
+        Some(due_at) => Some(
+            sqlx::query_scalar(
+                "INSERT INTO reminders (guild_id, channel_id, user_id, content, remind_at)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING reminder_id",
+            )
+            .bind(i64::from(guild_id))
+            .bind(i64::from(ctx.channel_id()))
+            .bind(i64::from(ctx.author().id))
+            .bind(format!("your todo is due: {task}"))
+            .bind(due_at)
+            .fetch_one(&mut *transaction)
+            .await?,
+        ),
+        None => None,
+    };
+    let todo_id: i64 = sqlx::query_scalar(
+        "INSERT INTO todos (guild_id, user_id, content, due_at, reminder_id)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING todo_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .bind(&task)
+    .bind(due_at)
+    .bind(reminder_id)
+    .fetch_one(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+
+    let content = match due_at {
+        Some(due_at) => format!(
+            "Todo `{todo_id}` is on your list, I'll remind you at {}.",
+            localtime::format_datetime(due_at, timezone)
+        ),
+        None => format!("Todo `{todo_id}` is on your list."),
+    };
+    reply(ctx, content).await
+}
+
+/// Shows your todo list in this server
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let todos: Vec<Todo> = sqlx::query_as(
+        "SELECT todo_id, content, due_at, done_at FROM todos
+        WHERE guild_id = $1 AND user_id = $2
+        ORDER BY done_at IS NOT NULL, due_at NULLS LAST, todo_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .fetch_all(&data.pool)
+    .await?;
+    if todos.is_empty() {
+        return reply(ctx, "Your todo list is empty.".to_string()).await;
+    }
+
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let pages: Vec<String> = todos
+        .chunks(PAGE_LEN)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|todo| todo.line(timezone))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect();
+    audit::paginate(ctx, "Your todo list", &pages).await
+}
+
+/// Ticks off a task on your list
+#[poise::command(slash_command, guild_only)]
+async fn done(
+    ctx: Context<'_>,
+    #[description = "Todo you did, see /todo list"] id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let reminder_id: Option<Option<i64>> = sqlx::query_scalar(
+        "UPDATE todos SET done_at = now(), reminder_id = NULL
+        FROM todos AS old
+        WHERE todos.todo_id = old.todo_id AND todos.guild_id = $1 AND todos.user_id = $2
+        AND todos.todo_id = $3 AND todos.done_at IS NULL
+        RETURNING old.reminder_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .bind(id)
+    .fetch_optional(&data.pool)
+    .await?;
+    let Some(reminder_id) = reminder_id else {
+        return reply(ctx, format!("You have no open todo `{id}`.")).await;
+    };
+    // Nobody needs reminding of something that's done.
+    if let Some(reminder_id) = reminder_id {
+        sqlx::query("DELETE FROM reminders WHERE reminder_id = $1")
+            .bind(reminder_id)
+            .execute(&data.pool)
+            .await?;
+    }
+
+    reply(ctx, format!("Todo `{id}` is done.")).await
+}
+
+/// Removes the tasks you ticked off from your list
+#[poise::command(slash_command, guild_only)]
+async fn clear(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = sqlx::query(
+        "DELETE FROM todos WHERE guild_id = $1 AND user_id = $2 AND done_at IS NOT NULL",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(ctx.author().id))
+    .execute(&ctx.data().pool)
+    .await?
+    .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Removed {removed} done todos.")
+    } else {
+        "You have no done todos to remove.".to_string()
+    };
+    reply(ctx, content).await
+}
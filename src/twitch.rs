@@ -0,0 +1,555 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use serde::Deserialize;
+use sqlx::PgPool;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+use crate::{db, duration::HumanDuration, localtime, replies::reply, Context, Data, SlimeError};
+
+/// How often the background task checks whether watched streamers are live.
+const POLL_INTERVAL: Duration = Duration::from_secs(120);
+
+/// How many streamers a guild can watch at once.
+const MAX_WATCHES: i64 = 25;
+
+/// How many streamers Helix looks up in one request.
+const PAGE_LEN: usize = 100;
+
+/// How long before an app access token runs out a new one is asked for.
+const TOKEN_MARGIN: Duration = Duration::from_secs(300);
+
+const TWITCH_PURPLE: Colour = Colour::from_rgb(145, 70, 255);
+
+#[derive(Error, Debug)]
+enum ApiError {
+    #[error("I couldn't reach Twitch: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Twitch answered with {0}")]
+    Status(reqwest::StatusCode),
+}
+
+#[derive(Deserialize)]
+struct AppToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct Page<T> {
+    data: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct TwitchUser {
+    id: String,
+    login: String,
+    display_name: String,
+}
+
+#[derive(Deserialize)]
+struct Stream {
+    id: String,
+    user_id: String,
+    user_name: String,
+    game_name: String,
+    title: String,
+    viewer_count: i64,
+    started_at: String,
+    thumbnail_url: String,
+}
+
+/// The Twitch Helix API, with the app's credentials from `Secrets.toml`.
+pub struct TwitchApi {
+    client_id: String,
+    client_secret: String,
+    /// The app access token and when it runs out.
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+impl TwitchApi {
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        Self {
+            client_id,
+            client_secret,
+            token: Mutex::default(),
+        }
+    }
+
+    /// An app access token, a new one if the last one ran out or Twitch
+    /// turned it down.
+    async fn token(&self, web: &reqwest::Client, renew: bool) -> Result<String, ApiError> {
+        let mut token = self.token.lock().await;
+        if let Some((token, expires)) = token.as_ref() {
+            if !renew && Instant::now() + TOKEN_MARGIN < *expires {
+                return Ok(token.clone());
+            }
+        }
+
+        let response = web
+            .post("https://id.twitch.tv/oauth2/token")
+            .query(&[
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("grant_type", "client_credentials"),
+            ])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(ApiError::Status(response.status()));
+        }
+        let AppToken {
+            access_token,
+            expires_in,
+        } = response.json().await?;
+        let expires = Instant::now() + Duration::from_secs(expires_in);
+        *token = Some((access_token.clone(), expires));
+        Ok(access_token)
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(
+        &self,
+        web: &reqwest::Client,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, ApiError> {
+        let mut renew = false;
+        loop {
+            let token = self.token(web, renew).await?;
+            let response = web
+                .get(format!("https://api.twitch.tv/helix{path}"))
+                .query(query)
+                .header("Client-Id", &self.client_id)
+                .bearer_auth(token)
+                .send()
+                .await?;
+            match response.status() {
+                status if status.is_success() => {
+                    let page: Page<T> = response.json().await?;
+                    return Ok(page.data);
+                }
+                reqwest::StatusCode::UNAUTHORIZED if !renew => renew = true,
+                status => return Err(ApiError::Status(status)),
+            }
+        }
+    }
+
+    async fn user(
+        &self,
+        web: &reqwest::Client,
+        login: &str,
+    ) -> Result<Option<TwitchUser>, ApiError> {
+        let users = self.get(web, "/users", &[("login", login)]).await?;
+        Ok(users.into_iter().next())
+    }
+
+    /// The streams of the users in `user_ids` that are live.
+    async fn streams(
+        &self,
+        web: &reqwest::Client,
+        user_ids: &[&str],
+    ) -> Result<Vec<Stream>, ApiError> {
+        let mut streams = Vec::new();
+        for chunk in user_ids.chunks(PAGE_LEN) {
+            let mut query: Vec<(&str, &str)> = chunk.iter().map(|&id| ("user_id", id)).collect();
+            query.push(("first", "100"));
+            streams.extend(self.get(web, "/streams", &query).await?);
+        }
+        Ok(streams)
+    }
+}
+
+/// Reads a Twitch name, or a link to the channel, lowercased. Names are
+/// up to 25 letters, digits and underscores.
+fn parse_login(login: &str) -> Option<String> {
+    let login = login.trim().trim_end_matches('/');
+    let login = login
+        .strip_prefix("https://www.twitch.tv/")
+        .or(login.strip_prefix("https://twitch.tv/"))
+        .unwrap_or(login);
+    let valid = (1..=25).contains(&login.len())
+        && login.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    valid.then(|| login.to_lowercase())
+}
+
+/// "Announce in `channel_id` when `login` goes live", from the
+/// `twitch_watches` table. While they're live it has the stream and the
+/// announcement of it.
+#[derive(sqlx::FromRow)]
+struct Watch {
+    watch_id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    twitch_user_id: String,
+    login: String,
+    display_name: String,
+    stream_id: Option<String>,
+    stream_title: Option<String>,
+    live_since: Option<DateTime<Utc>>,
+    message_id: Option<i64>,
+}
+
+impl Watch {
+    fn url(&self) -> String {
+        format!("https://www.twitch.tv/{}", self.login)
+    }
+
+    fn line(&self) -> String {
+        let live = if self.stream_id.is_some() {
+            " (live now)"
+        } else {
+            ""
+        };
+        format!(
+            "`{}` {} in {}{live}",
+            self.watch_id,
+            self.display_name,
+            ChannelId::new(self.channel_id as u64).mention()
+        )
+    }
+}
+
+fn live_announcement(watch: &Watch, stream: &Stream) -> CreateMessage {
+    let thumbnail = stream
+        .thumbnail_url
+        .replace("{width}", "1280")
+        .replace("{height}", "720");
+    let title = if stream.title.trim().is_empty() {
+        "Live on Twitch"
+    } else {
+        &stream.title
+    };
+    let mut embed = CreateEmbed::new()
+        .title(title)
+        .url(watch.url())
+        .colour(TWITCH_PURPLE)
+        .author(CreateEmbedAuthor::new(&stream.user_name).url(watch.url()))
+        .field("Viewers", stream.viewer_count.to_string(), true)
+        // The thumbnail's link stays the same for the whole stream,
+        // Discord would keep showing the first one it fetched.
+        .image(format!("{thumbnail}?t={}", stream.id));
+    if !stream.game_name.is_empty() {
+        embed = embed.field("Playing", &stream.game_name, true);
+    }
+    if let Ok(started) = DateTime::parse_from_rfc3339(&stream.started_at) {
+        embed = embed.timestamp(localtime::to_timestamp(started.with_timezone(&Utc)));
+    }
+    CreateMessage::new()
+        .content(format!("🔴 **{}** is live!", stream.user_name))
+        .embed(embed)
+}
+
+fn offline_announcement(watch: &Watch) -> EditMessage {
+    let mut embed = CreateEmbed::new()
+        .title(
+            watch
+                .stream_title
+                .as_deref()
+                .filter(|title| !title.trim().is_empty())
+                .unwrap_or("Stream"),
+        )
+        .url(watch.url())
+        .colour(Colour::LIGHT_GREY)
+        .author(CreateEmbedAuthor::new(&watch.display_name).url(watch.url()))
+        .footer(CreateEmbedFooter::new("Stream ended"));
+    if let Some(since) = watch.live_since {
+        let seconds = (Utc::now() - since).num_seconds().max(0);
+        let streamed = HumanDuration(chrono::Duration::seconds(seconds - seconds % 60));
+        embed = embed.description(format!("Streamed for {streamed}."));
+    }
+    EditMessage::new()
+        .content(format!("{} was live.", watch.display_name))
+        .embed(embed)
+}
+
+/// Announces a stream that went live. The stream is claimed first, so it's
+/// announced once even if two checks overlap, and unclaimed if the
+/// announcement fails so the next check tries again. A stream that ended
+/// and got followed by another one between two checks is marked ended
+/// first.
+async fn went_live(
+    http: &Http,
+    pool: &PgPool,
+    watch: &Watch,
+    stream: &Stream,
+) -> Result<(), SlimeError> {
+    if watch
+        .stream_id
+        .as_ref()
+        .is_some_and(|stream_id| *stream_id != stream.id)
+    {
+        if let Err(e) = went_offline(http, pool, watch).await {
+            warn!(
+                "failed to mark the last stream of Twitch watch {} ended: {}",
+                watch.watch_id, e
+            );
+        }
+    }
+    let live_since = DateTime::parse_from_rfc3339(&stream.started_at)
+        .map(|at| at.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+    let claimed = sqlx::query(
+        "UPDATE twitch_watches SET stream_id = $2, stream_title = $3, live_since = $4,
+        display_name = $5, message_id = NULL
+        WHERE watch_id = $1 AND stream_id IS DISTINCT FROM $2",
+    )
+    .bind(watch.watch_id)
+    .bind(&stream.id)
+    .bind(&stream.title)
+    .bind(live_since)
+    .bind(&stream.user_name)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if claimed == 0 {
+        return Ok(());
+    }
+
+    let channel_id = ChannelId::new(watch.channel_id as u64);
+    let message = match channel_id
+        .send_message(http, live_announcement(watch, stream))
+        .await
+    {
+        Ok(message) => message,
+        Err(e) => {
+            sqlx::query("UPDATE twitch_watches SET stream_id = NULL WHERE watch_id = $1")
+                .bind(watch.watch_id)
+                .execute(pool)
+                .await?;
+            return Err(e.into());
+        }
+    };
+    sqlx::query("UPDATE twitch_watches SET message_id = $2 WHERE watch_id = $1")
+        .bind(watch.watch_id)
+        .bind(i64::from(message.id))
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Edits the announcement of a stream that ended to say so.
+async fn went_offline(http: &Http, pool: &PgPool, watch: &Watch) -> Result<(), SlimeError> {
+    let claimed = sqlx::query(
+        "UPDATE twitch_watches SET stream_id = NULL, message_id = NULL
+        WHERE watch_id = $1 AND stream_id IS NOT NULL",
+    )
+    .bind(watch.watch_id)
+    .execute(pool)
+    .await?
+    .rows_affected();
+    let Some(message_id) = watch.message_id.filter(|_| claimed > 0) else {
+        return Ok(());
+    };
+
+    let channel_id = ChannelId::new(watch.channel_id as u64);
+    let message_id = MessageId::new(message_id as u64);
+    match channel_id
+        .edit_message(http, message_id, offline_announcement(watch))
+        .await
+    {
+        Ok(_) => Ok(()),
+        // Someone deleted the announcement, there's nothing to edit.
+        Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Checks every `POLL_INTERVAL` which of the watched streamers are live,
+/// announces the streams that started and edits the announcements of the
+/// ones that ended. Does nothing without Twitch credentials.
+pub async fn poll_streams(http: Arc<Http>, data: Data) {
+    let Some(api) = data.twitch.clone() else {
+        return;
+    };
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let watches: Vec<Watch> = match sqlx::query_as(
+            "SELECT watch_id, guild_id, channel_id, twitch_user_id, login, display_name, stream_id,
+            stream_title, live_since, message_id
+            FROM twitch_watches",
+        )
+        .fetch_all(&data.pool)
+        .await
+        {
+            Ok(watches) => watches,
+            Err(e) => {
+                error!("failed to load Twitch watches: {}", e);
+                continue;
+            }
+        };
+        if watches.is_empty() {
+            continue;
+        }
+
+        let mut user_ids: Vec<&str> = watches
+            .iter()
+            .map(|watch| watch.twitch_user_id.as_str())
+            .collect();
+        user_ids.sort_unstable();
+        user_ids.dedup();
+        // It's tried again next time.
+        let streams = match api.streams(&data.web, &user_ids).await {
+            Ok(streams) => streams,
+            Err(e) => {
+                warn!("failed to check which Twitch streamers are live: {}", e);
+                continue;
+            }
+        };
+        let live: HashMap<&str, &Stream> = streams
+            .iter()
+            .map(|stream| (stream.user_id.as_str(), stream))
+            .collect();
+
+        for watch in &watches {
+            let stream = live.get(watch.twitch_user_id.as_str());
+            let changed = match stream {
+                Some(stream) if watch.stream_id.as_ref() != Some(&stream.id) => {
+                    went_live(&http, &data.pool, watch, stream).await
+                }
+                None if watch.stream_id.is_some() => went_offline(&http, &data.pool, watch).await,
+                _ => Ok(()),
+            };
+            if let Err(e) = changed {
+                error!(
+                    "failed to announce Twitch watch {} in guild {}: {}",
+                    watch.watch_id, watch.guild_id, e
+                );
+            }
+        }
+    }
+}
+
+/// Announces when Twitch streamers go live
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("watch", "list", "unwatch")
+)]
+pub async fn twitch(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Announces in a channel when a streamer goes live
+#[poise::command(slash_command, guild_only)]
+async fn watch(
+    ctx: Context<'_>,
+    #[description = "Twitch name of the streamer"]
+    #[max_length = 60]
+    channel_name: String,
+    #[description = "Channel to announce in"]
+    #[channel_types("Text", "News")]
+    announce_channel: GuildChannel,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(api) = &data.twitch else {
+        let content = "Twitch isn't set up for this bot, its owner has to add Twitch credentials.";
+        return reply(ctx, content.to_string()).await;
+    };
+    let Some(login) = parse_login(&channel_name) else {
+        return reply(ctx, format!("`{channel_name}` is not a Twitch name.")).await;
+    };
+
+    let watches: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM twitch_watches WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .fetch_one(&data.pool)
+            .await?;
+    if watches >= MAX_WATCHES {
+        let content =
+            format!("This server watches {MAX_WATCHES} streamers already, unwatch one first.");
+        return reply(ctx, content).await;
+    }
+
+    ctx.defer_ephemeral().await?;
+    let user = match api.user(&data.web, &login).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return reply(ctx, format!("There's no Twitch streamer `{login}`.")).await,
+        Err(e) => return reply(ctx, format!("That didn't work, {e}.")).await,
+    };
+
+    // A stream that's live already is announced on the next check.
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let watch: Option<Watch> = sqlx::query_as(
+        "INSERT INTO twitch_watches
+        (guild_id, channel_id, twitch_user_id, login, display_name, added_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (channel_id, twitch_user_id) DO NOTHING
+        RETURNING watch_id, guild_id, channel_id, twitch_user_id, login, display_name, stream_id,
+        stream_title, live_since, message_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(announce_channel.id))
+    .bind(&user.id)
+    .bind(&user.login)
+    .bind(&user.display_name)
+    .bind(i64::from(ctx.author().id))
+    .fetch_optional(&data.pool)
+    .await?;
+
+    let content = match watch {
+        Some(watch) => format!("Watching {}.", watch.line()),
+        None => format!(
+            "{} is announced in {} already.",
+            user.display_name,
+            announce_channel.mention()
+        ),
+    };
+    reply(ctx, content).await
+}
+
+/// Lists the streamers this server watches
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let watches: Vec<Watch> = sqlx::query_as(
+        "SELECT watch_id, guild_id, channel_id, twitch_user_id, login, display_name, stream_id,
+        stream_title, live_since, message_id
+        FROM twitch_watches WHERE guild_id = $1 ORDER BY watch_id",
+    )
+    .bind(i64::from(ctx.guild_id().unwrap()))
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if watches.is_empty() {
+        let content = "This server watches no streamers, add one with `/twitch watch`.";
+        return reply(ctx, content.to_string()).await;
+    }
+
+    let content = watches
+        .iter()
+        .map(|watch| format!("- {}", watch.line()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    reply(ctx, content).await
+}
+
+/// Stops announcing when a streamer goes live
+#[poise::command(slash_command, guild_only)]
+async fn unwatch(
+    ctx: Context<'_>,
+    #[description = "Watch to remove, see /twitch list"] id: i64,
+) -> Result<(), SlimeError> {
+    let removed = sqlx::query("DELETE FROM twitch_watches WHERE guild_id = $1 AND watch_id = $2")
+        .bind(i64::from(ctx.guild_id().unwrap()))
+        .bind(id)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Watch `{id}` is removed.")
+    } else {
+        format!("There is no watch `{id}`.")
+    };
+    reply(ctx, content).await
+}
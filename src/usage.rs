@@ -0,0 +1,92 @@
+use poise::CreateReply;
+use tokio::time::Instant;
+use tracing::error;
+
+use crate::{audit, Context, SlimeError};
+
+/// How many commands one page of `usage_stats` shows.
+const PAGE_LEN: usize = 15;
+
+/// Remembers when the command started, for `record`.
+pub async fn pre_command(ctx: Context<'_>) {
+    ctx.set_invocation_data(Instant::now()).await;
+}
+
+pub async fn post_command(ctx: Context<'_>) {
+    record(ctx, true).await;
+}
+
+/// Stores that the command ran in `command_invocations`. Nobody waits for
+/// this, so failures are only logged.
+pub async fn record(ctx: Context<'_>, success: bool) {
+    let took = match ctx.invocation_data::<Instant>().await {
+        Some(started) => started.elapsed(),
+        None => Default::default(),
+    };
+
+    if let Err(e) = sqlx::query(
+        "INSERT INTO command_invocations (command, guild_id, user_id, duration_ms, success)
+        VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(&ctx.command().qualified_name)
+    .bind(ctx.guild_id().map(i64::from))
+    .bind(i64::from(ctx.author().id))
+    .bind(took.as_millis() as i64)
+    .bind(success)
+    .execute(&ctx.data().pool)
+    .await
+    {
+        error!(
+            "failed to record the use of /{}: {}",
+            ctx.command().qualified_name,
+            e
+        );
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct UsageRow {
+    command: String,
+    last_day: i64,
+    last_week: i64,
+    last_month: i64,
+    failed: i64,
+    average_ms: f64,
+}
+
+/// Shows how often each command was used lately
+#[poise::command(
+    slash_command,
+    owners_only,
+    default_member_permissions = "ADMINISTRATOR"
+)]
+pub async fn usage_stats(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let rows: Vec<UsageRow> = sqlx::query_as(
+        "SELECT command,
+            count(*) FILTER (WHERE created_at > now() - interval '1 day') AS last_day,
+            count(*) FILTER (WHERE created_at > now() - interval '7 days') AS last_week,
+            count(*) AS last_month,
+            count(*) FILTER (WHERE NOT success) AS failed,
+            avg(duration_ms)::float8 AS average_ms
+        FROM command_invocations WHERE created_at > now() - interval '30 days'
+        GROUP BY command ORDER BY last_month DESC",
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+
+    if rows.is_empty() {
+        let content = "No commands were used in the last 30 days.";
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let lines = rows.iter().map(|row| {
+        format!(
+            "`/{}`: {} today, {} this week, {} this month ({} failed, {:.0} ms on average)",
+            row.command, row.last_day, row.last_week, row.last_month, row.failed, row.average_ms
+        )
+    });
+    let pages = audit::pages(lines, PAGE_LEN);
+    audit::paginate(ctx, "Commands used in the last 30 days", &pages).await
+}
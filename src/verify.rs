@@ -0,0 +1,146 @@
+use poise::serenity_prelude::{self as serenity, *};
+use tracing::{error, warn};
+
+use crate::{db, replies::reply, Context, Data, SlimeError};
+
+/// The custom id of every verify button. It has to stay the same across
+/// restarts, since the buttons outlive the bot process.
+const VERIFY_BUTTON: &str = "pond-slime:verify";
+
+const DEFAULT_PROMPT: &str = "Press the button below to get access to the rest of the server.";
+
+/// Answers whoever pressed a verify button, only they see it.
+async fn respond(
+    ctx: &serenity::Context,
+    press: &ComponentInteraction,
+    content: &str,
+) -> Result<(), SlimeError> {
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    press
+        .create_response(ctx, CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+/// Gives whoever pressed a verify button the guild's verified role. Other
+/// component interactions belong to command collectors and are left alone.
+pub async fn interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    interaction: &Interaction,
+) -> Result<(), SlimeError> {
+    let Interaction::Component(press) = interaction else {
+        return Ok(());
+    };
+    if press.data.custom_id != VERIFY_BUTTON {
+        return Ok(());
+    }
+    let (Some(guild_id), Some(member)) = (press.guild_id, &press.member) else {
+        return Ok(());
+    };
+
+    let role_id: Option<i64> =
+        sqlx::query_scalar("SELECT role_id FROM verification_config WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .fetch_optional(&data.pool)
+            .await?;
+    let Some(role_id) = role_id.map(|role_id| RoleId::new(role_id as u64)) else {
+        return respond(ctx, press, "Verification isn't set up here anymore.").await;
+    };
+    if member.roles.contains(&role_id) {
+        return respond(ctx, press, "You're verified already.").await;
+    }
+
+    if let Err(e) = member.add_role(ctx, role_id).await {
+        error!(
+            "failed to verify {} in guild {}: {}",
+            member.user.id, guild_id, e
+        );
+        let content = "I couldn't give you the role, please tell a moderator.";
+        return respond(ctx, press, content).await;
+    }
+    respond(ctx, press, "You're verified, welcome!").await
+}
+
+/// Manages the button members press to get access to the server
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("setup")
+)]
+pub async fn verify(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Posts a verify button that gives members a role
+#[poise::command(slash_command, guild_only)]
+async fn setup(
+    ctx: Context<'_>,
+    #[description = "Channel to post the button in"]
+    #[channel_types("Text")]
+    channel: GuildChannel,
+    #[description = "Role members get for pressing it"] role: Role,
+    #[description = "What the message above the button says"]
+    #[max_length = 2000]
+    prompt: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    if role.id == guild_id.everyone_role() || role.managed {
+        return reply(ctx, format!("Nobody can be given {}.", role.mention())).await;
+    }
+
+    let button = CreateButton::new(VERIFY_BUTTON)
+        .label("Verify")
+        .style(ButtonStyle::Success);
+    let message = CreateMessage::new()
+        .content(prompt.as_deref().unwrap_or(DEFAULT_PROMPT))
+        .components(vec![CreateActionRow::Buttons(vec![button])]);
+    let message = channel.id.send_message(ctx, message).await?;
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let previous: Option<(i64, i64)> = sqlx::query_as(
+        "SELECT channel_id, message_id FROM verification_config WHERE guild_id = $1",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_optional(&data.pool)
+    .await?;
+    sqlx::query(
+        "INSERT INTO verification_config (guild_id, channel_id, message_id, role_id)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (guild_id) DO UPDATE
+        SET channel_id = EXCLUDED.channel_id, message_id = EXCLUDED.message_id,
+        role_id = EXCLUDED.role_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(i64::from(message.id))
+    .bind(i64::from(role.id))
+    .execute(&data.pool)
+    .await?;
+
+    // The old button would still work, but two of them only confuse members.
+    if let Some((channel_id, message_id)) = previous {
+        let channel_id = ChannelId::new(channel_id as u64);
+        let message_id = MessageId::new(message_id as u64);
+        if let Err(e) = channel_id.delete_message(ctx, message_id).await {
+            warn!(
+                "failed to delete the old verify button in guild {}: {}",
+                guild_id, e
+            );
+        }
+    }
+
+    reply(
+        ctx,
+        format!(
+            "Members who press the button in {} get {} now.",
+            channel.mention(),
+            role.mention()
+        ),
+    )
+    .await
+}
@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+
+use crate::{
+    audit,
+    cases::{self, Action, NewCase},
+    escalation, localtime,
+    replies::reply,
+    Context, SlimeError,
+};
+
+/// How many warnings one page of `/warnings list` shows.
+const PAGE_LEN: usize = 10;
+
+#[derive(sqlx::FromRow)]
+struct WarningRow {
+    /// `None` for warnings automod gave.
+    moderator_id: Option<i64>,
+    reason: String,
+    created_at: DateTime<Utc>,
+}
+
+async fn user_warnings(
+    pool: &PgPool,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> sqlx::Result<Vec<WarningRow>> {
+    sqlx::query_as(
+        "SELECT moderator_id, reason, created_at FROM warnings
+        WHERE guild_id = $1 AND user_id = $2 ORDER BY created_at",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user_id))
+    .fetch_all(pool)
+    .await
+}
+
+/// Records that `moderator_id` warned `user_id`, or automod if `None`.
+/// Returns how many warnings the user has now.
+pub async fn add(
+    pool: &PgPool,
+    guild_id: GuildId,
+    user_id: UserId,
+    moderator_id: Option<UserId>,
+    reason: &str,
+) -> sqlx::Result<i64> {
+    sqlx::query(
+        "INSERT INTO warnings (guild_id, user_id, moderator_id, reason) VALUES ($1, $2, $3, $4)",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(user_id))
+    .bind(moderator_id.map(i64::from))
+    .bind(reason)
+    .execute(pool)
+    .await?;
+
+    count(pool, guild_id, user_id).await
+}
+
+/// How many warnings `user_id` has.
+pub async fn count(pool: &PgPool, guild_id: GuildId, user_id: UserId) -> sqlx::Result<i64> {
+    sqlx::query_scalar("SELECT count(*) FROM warnings WHERE guild_id = $1 AND user_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(i64::from(user_id))
+        .fetch_one(pool)
+        .await
+}
+
+/// Warns a member and records why, escalating if that's enough warnings
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn warn(
+    ctx: Context<'_>,
+    #[description = "Member to warn"] user: User,
+    #[description = "Why they are warned"]
+    #[max_length = 1000]
+    reason: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+
+    let count = add(
+        &data.pool,
+        guild_id,
+        user.id,
+        Some(ctx.author().id),
+        &reason,
+    )
+    .await?;
+    let rule = escalation::triggered(&data.pool, guild_id, user.id).await?;
+    let case = NewCase {
+        action: Action::Warn,
+        user_id: Some(user.id),
+        channel_id: None,
+        moderator_id: Some(ctx.author().id),
+        reason: Some(&reason),
+        details: rule
+            .as_ref()
+            .map(|rule| format!("Escalated to {} for {}", rule.action(), rule.trigger())),
+    };
+    let case_id = cases::file(&data.pool, guild_id, case).await?;
+
+    let mut content = format!(
+        "Case {case_id}: {} warned {} ({count} warnings so far): {reason}",
+        ctx.author().mention(),
+        user.mention()
+    );
+    if let Some(rule) = rule {
+        let escalated =
+            escalation::apply(ctx.http(), data, guild_id, user.id, &rule, case_id).await;
+        content.push_str(&match escalated {
+            Ok(escalation_case) => format!(
+                "\nCase {escalation_case}: that's {}, so they get {}.",
+                rule.trigger(),
+                rule.action()
+            ),
+            Err(e) => format!(
+                "\nThat's {}, but giving them {} failed: {e}",
+                rule.trigger(),
+                rule.action()
+            ),
+        });
+    }
+    cases::announce(ctx.http(), data, guild_id, &content).await;
+
+    reply(ctx, content).await
+}
+
+/// Manages the warnings of a member
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Moderation",
+    subcommands("list", "clear")
+)]
+pub async fn warnings(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Lists the warnings a member got
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn list(
+    ctx: Context<'_>,
+    #[description = "Member whose warnings to show"] user: User,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let warnings = user_warnings(&ctx.data().pool, guild_id, user.id).await?;
+    let timezone = ctx.data().settings.get(guild_id).await?.timezone;
+
+    if warnings.is_empty() {
+        return reply(ctx, format!("{} has no warnings.", user.mention())).await;
+    }
+
+    let lines = warnings.iter().map(|warning| {
+        let moderator = match warning.moderator_id {
+            Some(id) => UserId::new(id as u64).mention().to_string(),
+            None => "automod".to_string(),
+        };
+        format!(
+            "- `{}` by {moderator}: {}",
+            localtime::format_datetime(warning.created_at, timezone),
+            warning.reason
+        )
+    });
+    let title = format!("{} has {} warnings", user.name, warnings.len());
+    audit::paginate(ctx, &title, &audit::pages(lines, PAGE_LEN)).await
+}
+
+/// Removes every warning of a member
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+async fn clear(
+    ctx: Context<'_>,
+    #[description = "Member whose warnings to remove"] user: User,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let removed = sqlx::query("DELETE FROM warnings WHERE guild_id = $1 AND user_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(i64::from(user.id))
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = match removed {
+        0 => format!("{} has no warnings.", user.mention()),
+        1 => format!("Removed the warning of {}.", user.mention()),
+        n => format!("Removed {n} warnings of {}.", user.mention()),
+    };
+    reply(ctx, content).await
+}
@@ -0,0 +1,322 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use hyper::{
+    body::HttpBody,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode as HttpStatus,
+};
+use poise::serenity_prelude::*;
+use serde::Deserialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::{db, replies::reply, Context, SlimeError};
+
+/// How many relays a guild can have at once.
+const MAX_RELAYS: i64 = 10;
+
+/// How big a payload can be, in bytes.
+const MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// How many embeds a message can have.
+const MAX_EMBEDS: usize = 10;
+
+/// How long a relay has to wait between two posts.
+const MIN_POST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Signs and checks relay URLs, with the secret and the bot's public
+/// address from `Secrets.toml`.
+pub struct RelayKeys {
+    secret: String,
+    base_url: String,
+}
+
+impl RelayKeys {
+    pub fn new(secret: String, base_url: String) -> Self {
+        Self {
+            secret,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn mac(&self, relay_id: i64) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("HMAC takes any key");
+        mac.update(format!("relay:{relay_id}").as_bytes());
+        mac
+    }
+
+    /// Whether `token` is the signature of relay `relay_id`, compared in
+    /// constant time.
+    fn check(&self, relay_id: i64, token: &str) -> bool {
+        hex::decode(token).is_ok_and(|token| self.mac(relay_id).verify_slice(&token).is_ok())
+    }
+
+    fn url(&self, relay_id: i64) -> String {
+        let token = hex::encode(self.mac(relay_id).finalize().into_bytes());
+        format!("{}/webhooks/{relay_id}/{token}", self.base_url)
+    }
+}
+
+/// What a relay is sent: a message like Discord's own webhooks take.
+#[derive(Deserialize)]
+struct Payload {
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    embeds: Vec<Embed>,
+}
+
+/// Takes the payloads posted to relay URLs and posts them in the relays'
+/// channels.
+pub struct Relay {
+    pool: PgPool,
+    http: Arc<Http>,
+    keys: Arc<RelayKeys>,
+    /// When each relay last posted.
+    last_posts: DashMap<i64, Instant>,
+}
+
+fn respond(status: HttpStatus, text: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(format!("{text}\n")));
+    *response.status_mut() = status;
+    response
+}
+
+async fn read_body(mut body: Body) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk.ok()?);
+        if bytes.len() > MAX_PAYLOAD_BYTES {
+            return None;
+        }
+    }
+    Some(bytes)
+}
+
+impl Relay {
+    pub fn new(pool: PgPool, http: Arc<Http>, keys: Arc<RelayKeys>) -> Self {
+        Self {
+            pool,
+            http,
+            keys,
+            last_posts: DashMap::new(),
+        }
+    }
+
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        let path: Vec<&str> = request.uri().path().trim_matches('/').split('/').collect();
+        let ["webhooks", relay_id, token] = path[..] else {
+            return respond(HttpStatus::NOT_FOUND, "Not found.");
+        };
+        if request.method() != Method::POST {
+            return respond(HttpStatus::METHOD_NOT_ALLOWED, "Relays only take POST.");
+        }
+        let Some(relay_id) = relay_id.parse::<i64>().ok() else {
+            return respond(HttpStatus::NOT_FOUND, "Not found.");
+        };
+        if !self.keys.check(relay_id, token) {
+            return respond(HttpStatus::UNAUTHORIZED, "That's not a relay URL.");
+        }
+
+        let channel_id: Option<i64> =
+            match sqlx::query_scalar("SELECT channel_id FROM webhook_relays WHERE relay_id = $1")
+                .bind(relay_id)
+                .fetch_optional(&self.pool)
+                .await
+            {
+                Ok(channel_id) => channel_id,
+                Err(e) => {
+                    warn!("failed to look up relay {}: {}", relay_id, e);
+                    return respond(HttpStatus::INTERNAL_SERVER_ERROR, "Try again later.");
+                }
+            };
+        let Some(channel_id) = channel_id else {
+            return respond(HttpStatus::NOT_FOUND, "The relay was deleted.");
+        };
+
+        let now = Instant::now();
+        let too_soon = self
+            .last_posts
+            .insert(relay_id, now)
+            .is_some_and(|last| now - last < MIN_POST_INTERVAL);
+        if too_soon {
+            return respond(HttpStatus::TOO_MANY_REQUESTS, "One post a second at most.");
+        }
+
+        let Some(body) = read_body(request.into_body()).await else {
+            let content = format!("Payloads can be {} KB at most.", MAX_PAYLOAD_BYTES / 1024);
+            return respond(HttpStatus::PAYLOAD_TOO_LARGE, &content);
+        };
+        let payload: Payload = match serde_json::from_slice(&body) {
+            Ok(payload) => payload,
+            Err(e) => return respond(HttpStatus::BAD_REQUEST, &format!("Bad JSON: {e}.")),
+        };
+        if payload.content.is_empty() && payload.embeds.is_empty() {
+            return respond(HttpStatus::BAD_REQUEST, "Send a content or embeds.");
+        }
+        if payload.content.chars().count() > 2000 || payload.embeds.len() > MAX_EMBEDS {
+            let content = format!("The content can be 2000 characters and {MAX_EMBEDS} embeds.");
+            return respond(HttpStatus::BAD_REQUEST, &content);
+        }
+
+        let message = CreateMessage::new()
+            .content(payload.content)
+            .embeds(payload.embeds.into_iter().map(CreateEmbed::from).collect())
+            .allowed_mentions(CreateAllowedMentions::new());
+        let channel_id = ChannelId::new(channel_id as u64);
+        if let Err(e) = channel_id.send_message(&self.http, message).await {
+            warn!("failed to relay a message to channel {}: {}", channel_id, e);
+            return respond(
+                HttpStatus::BAD_GATEWAY,
+                &format!("Discord didn't take it: {e}."),
+            );
+        }
+        if let Err(e) =
+            sqlx::query("UPDATE webhook_relays SET last_used_at = now() WHERE relay_id = $1")
+                .bind(relay_id)
+                .execute(&self.pool)
+                .await
+        {
+            warn!("failed to note relay {} was used: {}", relay_id, e);
+        }
+
+        respond(HttpStatus::OK, "Posted.")
+    }
+}
+
+/// Serves relay URLs on `addr` until the server fails.
+pub async fn serve(addr: SocketAddr, relay: Relay) -> Result<(), hyper::Error> {
+    let relay = Arc::new(relay);
+    let make_service = make_service_fn(move |_| {
+        let relay = Arc::clone(&relay);
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let relay = Arc::clone(&relay);
+                async move { Ok::<_, Infallible>(relay.handle(request).await) }
+            }))
+        }
+    });
+    Server::bind(&addr).serve(make_service).await
+}
+
+/// Lets other systems post in channels, through URLs only they know
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("create", "list", "delete")
+)]
+pub async fn webhook(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Makes a URL that posts what's sent to it in a channel
+#[poise::command(slash_command, guild_only)]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "Channel to post in"]
+    #[channel_types("Text", "News")]
+    channel: GuildChannel,
+    #[description = "What posts through it, e.g. CI"]
+    #[max_length = 50]
+    name: String,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(keys) = &data.relay_keys else {
+        let content = "Relays aren't set up for this bot, its owner has to add a relay secret.";
+        return reply(ctx, content.to_string()).await;
+    };
+
+    let relays: i64 = sqlx::query_scalar("SELECT count(*) FROM webhook_relays WHERE guild_id = $1")
+        .bind(i64::from(guild_id))
+        .fetch_one(&data.pool)
+        .await?;
+    if relays >= MAX_RELAYS {
+        let content = format!("This server has {MAX_RELAYS} relays already, delete one first.");
+        return reply(ctx, content).await;
+    }
+
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let relay_id: i64 = sqlx::query_scalar(
+        "INSERT INTO webhook_relays (guild_id, channel_id, name, created_by)
+        VALUES ($1, $2, $3, $4)
+        RETURNING relay_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(channel.id))
+    .bind(name.trim())
+    .bind(i64::from(ctx.author().id))
+    .fetch_one(&data.pool)
+    .await?;
+
+    let content = format!(
+        "Relay `{relay_id}` posts in {}. POST JSON like `{{\"content\": \"Build passed\"}}` or \
+        with `embeds` as Discord's webhooks take to:\n{}\nKeep it secret, anyone with it can \
+        post. `/webhook delete` makes it stop working.",
+        channel.mention(),
+        keys.url(relay_id)
+    );
+    reply(ctx, content).await
+}
+
+/// Lists this server's relays
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let relays: Vec<(i64, i64, String, Option<chrono::DateTime<chrono::Utc>>)> = sqlx::query_as(
+        "SELECT relay_id, channel_id, name, last_used_at FROM webhook_relays
+            WHERE guild_id = $1 ORDER BY relay_id",
+    )
+    .bind(i64::from(ctx.guild_id().unwrap()))
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if relays.is_empty() {
+        let content = "This server has no relays, make one with `/webhook create`.";
+        return reply(ctx, content.to_string()).await;
+    }
+
+    let content = relays
+        .into_iter()
+        .map(|(relay_id, channel_id, name, last_used_at)| {
+            let used = last_used_at.map_or("never used".to_string(), |at| {
+                format!("last used <t:{}:R>", at.timestamp())
+            });
+            format!(
+                "- `{relay_id}` {name} in {}, {used}",
+                ChannelId::new(channel_id as u64).mention()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    reply(ctx, content).await
+}
+
+/// Deletes a relay, its URL stops working
+#[poise::command(slash_command, guild_only)]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "Relay to delete, see /webhook list"] id: i64,
+) -> Result<(), SlimeError> {
+    let removed = sqlx::query("DELETE FROM webhook_relays WHERE guild_id = $1 AND relay_id = $2")
+        .bind(i64::from(ctx.guild_id().unwrap()))
+        .bind(id)
+        .execute(&ctx.data().pool)
+        .await?
+        .rows_affected();
+
+    let content = if removed > 0 {
+        format!("Relay `{id}` is deleted, its URL doesn't work anymore.")
+    } else {
+        format!("There is no relay `{id}`.")
+    };
+    reply(ctx, content).await
+}
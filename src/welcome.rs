@@ -0,0 +1,231 @@
+use poise::serenity_prelude::{self as serenity, *};
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::{db, replies::reply, Context, Data, SlimeError};
+
+/// The placeholders welcome and farewell messages can use, as `/welcome
+/// show` lists them.
+const PLACEHOLDERS: &str = "{user}, {guild} and {member_count}";
+
+/// A guild's row of the `welcome_messages` table.
+#[derive(sqlx::FromRow, Default)]
+struct WelcomeMessages {
+    channel_id: Option<i64>,
+    join_message: Option<String>,
+    leave_message: Option<String>,
+}
+
+async fn load(pool: &PgPool, guild_id: GuildId) -> sqlx::Result<WelcomeMessages> {
+    let messages = sqlx::query_as(
+        "SELECT channel_id, join_message, leave_message FROM welcome_messages
+        WHERE guild_id = $1",
+    )
+    .bind(i64::from(guild_id))
+    .fetch_optional(pool)
+    .await?;
+    Ok(messages.unwrap_or_default())
+}
+
+/// Fills in the placeholders of `template`. The member count is a `?` if
+/// Discord won't say what it is.
+async fn render(
+    http: &Http,
+    guild_id: GuildId,
+    template: &str,
+    user: &str,
+) -> Result<String, SlimeError> {
+    let guild = guild_id.to_partial_guild_with_counts(http).await?;
+    let member_count = guild
+        .approximate_member_count
+        .map_or("?".to_string(), |count| count.to_string());
+    Ok(template
+        .replace("{user}", user)
+        .replace("{guild}", &guild.name)
+        .replace("{member_count}", &member_count))
+}
+
+/// Posts `template` to the welcome channel. The message is whatever an
+/// admin typed, so it may only ping the member it's about.
+async fn post(
+    http: &Http,
+    guild_id: GuildId,
+    channel_id: i64,
+    template: &str,
+    user: &User,
+    mention: bool,
+) -> Result<(), SlimeError> {
+    let name = if mention {
+        user.mention().to_string()
+    } else {
+        user.tag()
+    };
+    let content = render(http, guild_id, template, &name).await?;
+    let message = CreateMessage::new()
+        .content(content)
+        .allowed_mentions(CreateAllowedMentions::new().users([user.id]));
+    let channel_id = ChannelId::new(channel_id as u64);
+    if let Err(e) = channel_id.send_message(http, message).await {
+        warn!(
+            "failed to post to the welcome channel of guild {}: {}",
+            guild_id, e
+        );
+    }
+    Ok(())
+}
+
+/// Welcomes a member who just joined, if the guild set up a welcome.
+pub async fn member_joined(
+    ctx: &serenity::Context,
+    data: &Data,
+    member: &Member,
+) -> Result<(), SlimeError> {
+    if member.user.bot {
+        return Ok(());
+    }
+    let messages = load(&data.pool, member.guild_id).await?;
+    let (Some(channel_id), Some(template)) = (messages.channel_id, messages.join_message) else {
+        return Ok(());
+    };
+    post(
+        &ctx.http,
+        member.guild_id,
+        channel_id,
+        &template,
+        &member.user,
+        true,
+    )
+    .await
+}
+
+/// Says goodbye to a member who left, if the guild set up a farewell. They
+/// are named rather than mentioned, since the mention wouldn't resolve.
+pub async fn member_left(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    user: &User,
+) -> Result<(), SlimeError> {
+    if user.bot {
+        return Ok(());
+    }
+    let messages = load(&data.pool, guild_id).await?;
+    let (Some(channel_id), Some(template)) = (messages.channel_id, messages.leave_message) else {
+        return Ok(());
+    };
+    post(&ctx.http, guild_id, channel_id, &template, user, false).await
+}
+
+/// Manages the messages posted when members join and leave
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("channel", "join", "leave", "show")
+)]
+pub async fn welcome(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Sets the channel welcome and farewell messages are posted in
+#[poise::command(slash_command, guild_only)]
+async fn channel(
+    ctx: Context<'_>,
+    #[description = "Channel for welcomes and farewells, leave out to stop posting them"]
+    #[channel_types("Text", "News")]
+    channel: Option<GuildChannel>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let pool = &ctx.data().pool;
+    db::ensure_guild(pool, guild_id).await?;
+    sqlx::query(
+        "INSERT INTO welcome_messages (guild_id, channel_id) VALUES ($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET channel_id = EXCLUDED.channel_id",
+    )
+    .bind(i64::from(guild_id))
+    .bind(channel.as_ref().map(|channel| i64::from(channel.id)))
+    .execute(pool)
+    .await?;
+
+    let content = match channel {
+        Some(channel) => format!("Welcomes and farewells go to {} now.", channel.mention()),
+        None => "Welcomes and farewells aren't posted anymore.".to_string(),
+    };
+    reply(ctx, content).await
+}
+
+/// Sets the message posted when a member joins
+#[poise::command(slash_command, guild_only)]
+async fn join(
+    ctx: Context<'_>,
+    #[description = "Message with {user}, {guild} or {member_count} in it, leave out to remove"]
+    #[max_length = 2000]
+    message: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let pool = &ctx.data().pool;
+    db::ensure_guild(pool, guild_id).await?;
+    sqlx::query(
+        "INSERT INTO welcome_messages (guild_id, join_message) VALUES ($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET join_message = EXCLUDED.join_message",
+    )
+    .bind(i64::from(guild_id))
+    .bind(&message)
+    .execute(pool)
+    .await?;
+
+    let content = if message.is_some() {
+        "Members who join get this welcome now.".to_string()
+    } else {
+        "Members who join aren't welcomed anymore.".to_string()
+    };
+    reply(ctx, content).await
+}
+
+/// Sets the message posted when a member leaves
+#[poise::command(slash_command, guild_only)]
+async fn leave(
+    ctx: Context<'_>,
+    #[description = "Message with {user}, {guild} or {member_count} in it, leave out to remove"]
+    #[max_length = 2000]
+    message: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let pool = &ctx.data().pool;
+    db::ensure_guild(pool, guild_id).await?;
+    sqlx::query(
+        "INSERT INTO welcome_messages (guild_id, leave_message) VALUES ($1, $2)
+        ON CONFLICT (guild_id) DO UPDATE SET leave_message = EXCLUDED.leave_message",
+    )
+    .bind(i64::from(guild_id))
+    .bind(&message)
+    .execute(pool)
+    .await?;
+
+    let content = if message.is_some() {
+        "Members who leave get this farewell now.".to_string()
+    } else {
+        "Members who leave don't get a farewell anymore.".to_string()
+    };
+    reply(ctx, content).await
+}
+
+/// Shows the welcome and farewell messages and where they go
+#[poise::command(slash_command, guild_only)]
+async fn show(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let messages = load(&ctx.data().pool, guild_id).await?;
+
+    let channel = messages
+        .channel_id
+        .map_or("not set".to_string(), |channel_id| {
+            ChannelId::new(channel_id as u64).mention().to_string()
+        });
+    let join = messages.join_message.as_deref().unwrap_or("not set");
+    let leave = messages.leave_message.as_deref().unwrap_or("not set");
+    let content = format!(
+        "Channel: {channel}\nWelcome: {join}\nFarewell: {leave}\n\
+        Messages can use {PLACEHOLDERS}."
+    );
+    reply(ctx, content).await
+}
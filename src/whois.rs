@@ -0,0 +1,83 @@
+use poise::{serenity_prelude::*, CreateReply};
+
+use crate::{agegate, cases, localtime, msglog, notes, warnings, Context, SlimeError};
+
+/// How many cases `/whois` lists at most.
+const RECENT_CASES: i64 = 5;
+
+/// Shows who a member is and what moderators did about them
+#[poise::command(slash_command, guild_only, category = "Moderation")]
+pub async fn whois(
+    ctx: Context<'_>,
+    #[description = "Member to look up"] user: User,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let member = match guild_id.member(ctx, user.id).await {
+        Ok(member) => Some(member),
+        Err(Error::Http(e)) if e.status_code() == Some(StatusCode::NOT_FOUND) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let timezone = data.settings.get(guild_id).await?.timezone;
+    let warnings = warnings::count(&data.pool, guild_id, user.id).await?;
+    let notes = notes::count(&data.pool, guild_id, user.id).await?;
+    let cases = cases::recent(&data.pool, guild_id, user.id, RECENT_CASES).await?;
+
+    let created = format!(
+        "{} ({} days ago)",
+        localtime::format_datetime(localtime::from_timestamp(user.id.created_at()), timezone),
+        agegate::account_age(user.id).num_days()
+    );
+    let mut embed = CreateEmbed::new()
+        .title(user.tag())
+        .thumbnail(user.face())
+        .field("User", user.mention().to_string(), true)
+        .field("Account created", created, true);
+    embed = match &member {
+        Some(member) => {
+            let joined = member.joined_at.map_or("unknown".to_string(), |joined_at| {
+                localtime::format_datetime(localtime::from_timestamp(joined_at), timezone)
+            });
+            let roles = if member.roles.is_empty() {
+                "none".to_string()
+            } else {
+                let roles = member
+                    .roles
+                    .iter()
+                    .map(|role| role.mention().to_string())
+                    .collect::<Vec<_>>();
+                msglog::field_text(&roles.join(" "))
+            };
+            embed
+                .field("Joined", joined, true)
+                .field("Roles", roles, false)
+        }
+        None => embed.field("Joined", "not a member", true),
+    };
+
+    let cases = if cases.is_empty() {
+        "none".to_string()
+    } else {
+        let lines = cases
+            .iter()
+            .map(|case| {
+                format!(
+                    "`{}` {} {}: {}",
+                    case.case_id,
+                    localtime::format_datetime(case.created_at, timezone),
+                    case.action,
+                    case.reason.as_deref().unwrap_or("no reason given")
+                )
+            })
+            .collect::<Vec<_>>();
+        msglog::field_text(&lines.join("\n"))
+    };
+    embed = embed
+        .field("Warnings", warnings.to_string(), true)
+        .field("Notes", format!("{notes}, see /note list"), true)
+        .field("Recent cases", cases, false);
+
+    ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
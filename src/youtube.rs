@@ -0,0 +1,380 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use poise::serenity_prelude::*;
+use sqlx::PgPool;
+use tracing::{error, warn};
+
+use crate::{
+    db, feeds,
+    replies::reply,
+    syndication::{Feed, Item},
+    Context, Data, SlimeError,
+};
+
+/// How often the background task checks for channels that are due.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often each watched channel's uploads are checked, at most.
+const POLL_MINUTES: f64 = 10.0;
+
+/// How many YouTube channels a guild can watch at once.
+const MAX_WATCHES: i64 = 25;
+
+/// How old a video can be to still be announced. Videos that were private
+/// for a while turn up in the feed with the day they were uploaded.
+const MAX_AGE_HOURS: i64 = 48;
+
+/// What's posted for a new video unless the watch has its own template.
+const DEFAULT_TEMPLATE: &str = "📺 **{channel}** uploaded a new video: {title}\n{url}";
+
+/// The feed of a YouTube channel's latest uploads.
+fn uploads_url(youtube_channel_id: &str) -> String {
+    format!("https://www.youtube.com/feeds/videos.xml?channel_id={youtube_channel_id}")
+}
+
+/// Reads a channel id like `UCxxxxxxxxxxxxxxxxxxxxxx`, or a link to the
+/// channel with it. Links with a handle have no id in them.
+fn parse_channel_id(channel: &str) -> Option<String> {
+    let channel = channel.trim().trim_end_matches('/');
+    let id = channel.rsplit('/').next()?;
+    let valid = id.len() == 24
+        && id.starts_with("UC")
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+    valid.then(|| id.to_string())
+}
+
+/// The id of the video an uploads feed entry is about: YouTube gives its
+/// entries ids like `yt:video:<video id>`.
+fn video_id(item: &Item) -> &str {
+    item.id.strip_prefix("yt:video:").unwrap_or(&item.id)
+}
+
+/// "Announce new videos of `youtube_channel_id` in `channel_id`", from the
+/// `youtube_watches` table. The videos announced are in `youtube_videos`.
+#[derive(sqlx::FromRow)]
+struct Watch {
+    watch_id: i64,
+    guild_id: i64,
+    channel_id: i64,
+    youtube_channel_id: String,
+    channel_name: String,
+    template: Option<String>,
+}
+
+impl Watch {
+    fn announcement(&self, feed: &Feed, item: &Item) -> String {
+        let url = item
+            .link
+            .clone()
+            .unwrap_or_else(|| format!("https://www.youtube.com/watch?v={}", video_id(item)));
+        self.template
+            .as_deref()
+            .unwrap_or(DEFAULT_TEMPLATE)
+            .replace(
+                "{channel}",
+                feed.title.as_deref().unwrap_or(&self.channel_name),
+            )
+            .replace("{title}", item.title.as_deref().unwrap_or("a video"))
+            .replace("{url}", &url)
+    }
+
+    fn line(&self) -> String {
+        let template = if self.template.is_some() {
+            " (own template)"
+        } else {
+            ""
+        };
+        format!(
+            "`{}` {} in {}{template}",
+            self.watch_id,
+            self.channel_name,
+            ChannelId::new(self.channel_id as u64).mention()
+        )
+    }
+}
+
+/// Marks the videos in `feed` as seen by `watch` without announcing them.
+async fn mark_seen(pool: &PgPool, watch: &Watch, feed: &Feed) -> sqlx::Result<()> {
+    let video_ids: Vec<&str> = feed.items.iter().map(video_id).collect();
+    sqlx::query(
+        "INSERT INTO youtube_videos (watch_id, guild_id, video_id)
+        SELECT $1, $2, unnest($3::TEXT[])
+        ON CONFLICT (watch_id, video_id) DO NOTHING",
+    )
+    .bind(watch.watch_id)
+    .bind(watch.guild_id)
+    .bind(&video_ids)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Announces the recent videos in `feed` that `watch` hasn't announced,
+/// oldest first. Each video is claimed first, so it's announced once, and
+/// unclaimed if its announcement fails so the next check tries again.
+async fn announce_new(
+    http: &Http,
+    pool: &PgPool,
+    watch: &Watch,
+    feed: &Feed,
+) -> Result<(), SlimeError> {
+    let now = Utc::now();
+    let recent = feed.items.iter().rev().filter(|item| {
+        item.published
+            .is_some_and(|published| (now - published).num_hours() < MAX_AGE_HOURS)
+    });
+    for item in recent {
+        let claimed = sqlx::query(
+            "INSERT INTO youtube_videos (watch_id, guild_id, video_id) VALUES ($1, $2, $3)
+            ON CONFLICT (watch_id, video_id) DO NOTHING",
+        )
+        .bind(watch.watch_id)
+        .bind(watch.guild_id)
+        .bind(video_id(item))
+        .execute(pool)
+        .await?
+        .rows_affected();
+        if claimed == 0 {
+            continue;
+        }
+
+        let announcement = CreateMessage::new()
+            .content(watch.announcement(feed, item))
+            .allowed_mentions(CreateAllowedMentions::new());
+        let posted = ChannelId::new(watch.channel_id as u64)
+            .send_message(http, announcement)
+            .await;
+        if let Err(e) = posted {
+            sqlx::query("DELETE FROM youtube_videos WHERE watch_id = $1 AND video_id = $2")
+                .bind(watch.watch_id)
+                .bind(video_id(item))
+                .execute(pool)
+                .await?;
+            return Err(e.into());
+        }
+    }
+    // Older videos are marked seen too, so they aren't looked at again.
+    mark_seen(pool, watch, feed).await?;
+
+    Ok(())
+}
+
+/// Fetches the uploads of the watched channels that are due every
+/// `POLL_MINUTES` and announces their new videos. Each channel's feed is
+/// fetched once however many guilds watch it.
+pub async fn poll_uploads(http: Arc<Http>, data: Data) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let due: Vec<Watch> = match sqlx::query_as(
+            "UPDATE youtube_watches SET checked_at = now()
+            WHERE checked_at <= now() - make_interval(mins => $1)
+            RETURNING watch_id, guild_id, channel_id, youtube_channel_id, channel_name, template",
+        )
+        .bind(POLL_MINUTES)
+        .fetch_all(&data.pool)
+        .await
+        {
+            Ok(due) => due,
+            Err(e) => {
+                error!("failed to load due YouTube watches: {}", e);
+                continue;
+            }
+        };
+
+        let mut channels: HashMap<String, Vec<Watch>> = HashMap::new();
+        for watch in due {
+            channels
+                .entry(watch.youtube_channel_id.clone())
+                .or_default()
+                .push(watch);
+        }
+        for (youtube_channel_id, watches) in channels {
+            // It's tried again next time.
+            let feed = match feeds::fetch(&data.web, &uploads_url(&youtube_channel_id)).await {
+                Ok(feed) => feed,
+                Err(e) => {
+                    warn!(
+                        "failed to fetch the uploads of YouTube channel {}: {}",
+                        youtube_channel_id, e
+                    );
+                    continue;
+                }
+            };
+            for watch in watches {
+                if let Err(e) = announce_new(&http, &data.pool, &watch, &feed).await {
+                    error!(
+                        "failed to announce YouTube watch {} in guild {}: {}",
+                        watch.watch_id, watch.guild_id, e
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Announces new videos of YouTube channels
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("watch", "list", "unwatch")
+)]
+pub async fn youtube(_ctx: Context<'_>) -> Result<(), SlimeError> {
+    Ok(())
+}
+
+/// Announces a YouTube channel's new videos, or changes a watch's template
+#[poise::command(slash_command, guild_only)]
+async fn watch(
+    ctx: Context<'_>,
+    #[description = "Id of the YouTube channel, UC…, or a link with it"]
+    #[max_length = 200]
+    channel_id: String,
+    #[description = "Channel to announce in"]
+    #[channel_types("Text", "News")]
+    announce_channel: GuildChannel,
+    #[description = "What to post, with {channel}, {title} and {url} filled in"]
+    #[max_length = 500]
+    template: Option<String>,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let Some(youtube_channel_id) = parse_channel_id(&channel_id) else {
+        let content = format!(
+            "`{channel_id}` is not a YouTube channel id. It starts with UC, find it under \
+            About > Share channel > Copy channel ID."
+        );
+        return reply(ctx, content).await;
+    };
+    let template = template
+        .map(|template| template.trim().to_string())
+        .filter(|template| !template.is_empty());
+    if template
+        .as_deref()
+        .is_some_and(|template| !template.contains("{url}"))
+    {
+        let content = "The template needs `{url}` in it, or nobody can find the video.";
+        return reply(ctx, content.to_string()).await;
+    }
+
+    let watches: i64 =
+        sqlx::query_scalar("SELECT count(*) FROM youtube_watches WHERE guild_id = $1")
+            .bind(i64::from(guild_id))
+            .fetch_one(&data.pool)
+            .await?;
+    let watched: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM youtube_watches
+        WHERE channel_id = $1 AND youtube_channel_id = $2)",
+    )
+    .bind(i64::from(announce_channel.id))
+    .bind(&youtube_channel_id)
+    .fetch_one(&data.pool)
+    .await?;
+    if watches >= MAX_WATCHES && !watched {
+        let content = format!(
+            "This server watches {MAX_WATCHES} YouTube channels already, unwatch one first."
+        );
+        return reply(ctx, content).await;
+    }
+
+    ctx.defer_ephemeral().await?;
+    let feed = match feeds::fetch(&data.web, &uploads_url(&youtube_channel_id)).await {
+        Ok(feed) => feed,
+        Err(e) => {
+            warn!(
+                "failed to fetch the uploads of YouTube channel {}: {}",
+                youtube_channel_id, e
+            );
+            let content = format!(
+                "I couldn't find the uploads of `{youtube_channel_id}`, {}.",
+                e.summary()
+            );
+            return reply(ctx, content).await;
+        }
+    };
+    let channel_name = feed
+        .title
+        .clone()
+        .unwrap_or_else(|| youtube_channel_id.clone());
+
+    // The videos it has already are seen, only the ones after are announced.
+    db::ensure_guild(&data.pool, guild_id).await?;
+    let watch: Watch = sqlx::query_as(
+        "INSERT INTO youtube_watches
+        (guild_id, channel_id, youtube_channel_id, channel_name, template, added_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (channel_id, youtube_channel_id) DO UPDATE SET
+        channel_name = EXCLUDED.channel_name, template = EXCLUDED.template
+        RETURNING watch_id, guild_id, channel_id, youtube_channel_id, channel_name, template",
+    )
+    .bind(i64::from(guild_id))
+    .bind(i64::from(announce_channel.id))
+    .bind(&youtube_channel_id)
+    .bind(&channel_name)
+    .bind(&template)
+    .bind(i64::from(ctx.author().id))
+    .fetch_one(&data.pool)
+    .await?;
+    mark_seen(&data.pool, &watch, &feed).await?;
+
+    let verb = if watched { "Updated" } else { "Watching" };
+    reply(ctx, format!("{verb} {}.", watch.line())).await
+}
+
+/// Lists the YouTube channels this server watches
+#[poise::command(slash_command, guild_only)]
+async fn list(ctx: Context<'_>) -> Result<(), SlimeError> {
+    let watches: Vec<Watch> = sqlx::query_as(
+        "SELECT watch_id, guild_id, channel_id, youtube_channel_id, channel_name, template
+        FROM youtube_watches WHERE guild_id = $1 ORDER BY watch_id",
+    )
+    .bind(i64::from(ctx.guild_id().unwrap()))
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if watches.is_empty() {
+        let content = "This server watches no YouTube channels, add one with `/youtube watch`.";
+        return reply(ctx, content.to_string()).await;
+    }
+
+    let content = watches
+        .iter()
+        .map(|watch| format!("- {}", watch.line()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    reply(ctx, content).await
+}
+
+/// Stops announcing a YouTube channel's videos
+#[poise::command(slash_command, guild_only)]
+async fn unwatch(
+    ctx: Context<'_>,
+    #[description = "Watch to remove, see /youtube list"] id: i64,
+) -> Result<(), SlimeError> {
+    let guild_id = ctx.guild_id().unwrap();
+    let data = ctx.data();
+    let mut transaction = data.pool.begin().await?;
+    let removed = sqlx::query("DELETE FROM youtube_watches WHERE guild_id = $1 AND watch_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(id)
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+    sqlx::query("DELETE FROM youtube_videos WHERE guild_id = $1 AND watch_id = $2")
+        .bind(i64::from(guild_id))
+        .bind(id)
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+
+    let content = if removed > 0 {
+        format!("Watch `{id}` is removed.")
+    } else {
+        format!("There is no watch `{id}`.")
+    };
+    reply(ctx, content).await
+}